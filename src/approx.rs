@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+// Lines longer than this are only matched up to here, to keep typing responsive
+const MAX_LINE_CHARS: usize = 1000;
+
+// Typo-tolerant, case-insensitive matching of a query anywhere in a line, where
+// insertions, deletions, substitutions and swapped neighbours each cost one edit
+pub struct ApproxMatcher {
+    query: Vec<char>,
+    max_distance: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ApproxMatch {
+    pub distance: usize,
+    // Byte range of the closest substring
+    pub range: Range<usize>,
+}
+
+impl ApproxMatcher {
+    pub fn new(query: &str) -> Self {
+        let query: Vec<char> = query.to_lowercase().chars().collect();
+        // One typo per four characters up to two, so short queries stay exact
+        let max_distance = (query.len() / 4).min(2);
+        Self {
+            query,
+            max_distance,
+        }
+    }
+
+    // 1.0 for an exact match, falling towards 0.0 with each edit
+    pub fn similarity(&self, distance: usize) -> f64 {
+        1.0 - distance as f64 / self.query.len().max(1) as f64
+    }
+
+    // The substring closest to the query, if it's within the allowed number of edits
+    pub fn find(&self, line: &str) -> Option<ApproxMatch> {
+        let m = self.query.len();
+        if m == 0 {
+            return None;
+        }
+        // Lowercased chars with the byte range of the original char they came from
+        let chars: Vec<(Range<usize>, char)> = line
+            .char_indices()
+            .take(MAX_LINE_CHARS)
+            .flat_map(|(offset, c)| {
+                let bytes = offset..offset + c.len_utf8();
+                c.to_lowercase().map(move |lower| (bytes.clone(), lower))
+            })
+            .collect();
+
+        // Columns of the edit distance table, each cell paired with where its alignment starts.
+        // Row 0 is free, so a match can begin anywhere in the line.
+        let mut before_previous: Vec<(usize, usize)> = Vec::new();
+        let mut previous: Vec<(usize, usize)> = (0..=m).map(|i| (i, 0)).collect();
+        let mut best: Option<(usize, usize, usize)> = None;
+
+        for j in 1..=chars.len() {
+            let c = chars[j - 1].1;
+            let mut current = vec![(0, j); m + 1];
+            for i in 1..=m {
+                let cost = usize::from(self.query[i - 1] != c);
+                let (substitute, substitute_start) = previous[i - 1];
+                let (delete, delete_start) = current[i - 1];
+                let (insert, insert_start) = previous[i];
+                let mut cell = (substitute + cost, substitute_start);
+                if delete + 1 < cell.0 {
+                    cell = (delete + 1, delete_start);
+                }
+                if insert + 1 < cell.0 {
+                    cell = (insert + 1, insert_start);
+                }
+                // Swapped neighbours, like `recieve` for `receive`
+                if i > 1 && j > 1 && self.query[i - 1] == chars[j - 2].1 && self.query[i - 2] == c {
+                    let (swap, swap_start) = before_previous[i - 2];
+                    if swap + 1 < cell.0 {
+                        cell = (swap + 1, swap_start);
+                    }
+                }
+                current[i] = cell;
+            }
+
+            let (distance, start) = current[m];
+            if distance <= self.max_distance && best.is_none_or(|(d, _, _)| distance < d) {
+                best = Some((distance, start, j));
+            }
+            before_previous = std::mem::replace(&mut previous, current);
+        }
+
+        // Alignments cover chars `start..end`, and a match always covers at least one
+        best.map(|(distance, start, end)| ApproxMatch {
+            distance,
+            range: chars[start.min(end - 1)].0.start..chars[end - 1].0.end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, line: &str) -> Option<(usize, String)> {
+        ApproxMatcher::new(query)
+            .find(line)
+            .map(|m| (m.distance, line[m.range].to_string()))
+    }
+
+    #[test]
+    fn test_exact_match() {
+        assert_eq!(
+            find("receive", "fn receive(msg)"),
+            Some((0, "receive".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_typos() {
+        assert_eq!(
+            find("recieve", "fn receive(msg)"),
+            Some((1, "receive".to_string()))
+        );
+        assert_eq!(
+            find("recive", "let Received = 1;"),
+            Some((1, "Receive".to_string()))
+        );
+        assert_eq!(find("recxvxe", "fn receive(msg)"), None);
+    }
+
+    #[test]
+    fn test_short_queries_are_exact() {
+        assert_eq!(find("fo", "foo"), Some((0, "fo".to_string())));
+        assert_eq!(find("fox", "for"), None);
+    }
+
+    #[test]
+    fn test_similarity() {
+        let matcher = ApproxMatcher::new("receive");
+        assert!(matcher.similarity(0) > matcher.similarity(1));
+        assert_eq!(matcher.similarity(0), 1.0);
+    }
+}
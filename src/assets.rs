@@ -0,0 +1,153 @@
+use bat::assets::HighlightingAssets;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use syntect::dumps::{dump_to_uncompressed_file, from_uncompressed_dump_file};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+// Syntaxes and themes for previews: bat's, plus any from ~/.config/glancr
+pub struct Assets {
+    pub syntaxes: SyntaxSet,
+    pub themes: ThemeSet,
+}
+
+// Loaded on first use and kept for the rest of the session
+pub fn get() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let user_dir = dirs::home_dir().map(|home| home.join(".config").join("glancr"));
+        let cache_dir = dirs::cache_dir().map(|dir| dir.join("glancr"));
+        load(user_dir.as_deref(), cache_dir.as_deref())
+    })
+}
+
+fn load(user_dir: Option<&Path>, cache_dir: Option<&Path>) -> Assets {
+    let base = HighlightingAssets::from_binary()
+        .get_syntax_set()
+        .cloned()
+        .unwrap_or_else(|_| SyntaxSet::load_defaults_newlines());
+    let syntaxes = match user_dir.map(|dir| dir.join("syntaxes")) {
+        Some(dir) => with_user_syntaxes(base, &dir, cache_dir),
+        None => base,
+    };
+
+    let mut themes = ThemeSet::load_defaults();
+    if let Some(dir) = user_dir
+        .map(|dir| dir.join("themes"))
+        .filter(|dir| dir.is_dir())
+    {
+        // A broken theme shouldn't cost the working ones
+        let _ = themes.add_from_folder(dir);
+    }
+    Assets { syntaxes, themes }
+}
+
+// Adding syntaxes means relinking the whole set, which takes long enough to notice, so
+// the result is cached until the files in the directory change
+fn with_user_syntaxes(base: SyntaxSet, dir: &Path, cache_dir: Option<&Path>) -> SyntaxSet {
+    let Some(fingerprint) = fingerprint(dir) else {
+        return base;
+    };
+    let cache =
+        cache_dir.map(|cache_dir| cache_dir.join(format!("syntaxes-{:016x}.bin", fingerprint)));
+    if let Some(syntaxes) = cache
+        .as_ref()
+        .and_then(|cache| from_uncompressed_dump_file(cache).ok())
+    {
+        return syntaxes;
+    }
+
+    let mut builder = base.clone().into_builder();
+    if builder.add_from_folder(dir, true).is_err() {
+        return base;
+    }
+    let syntaxes = builder.build();
+    if let (Some(cache), Some(cache_dir)) = (cache, cache_dir) {
+        let _ = std::fs::create_dir_all(cache_dir);
+        let _ = dump_to_uncompressed_file(&syntaxes, cache);
+    }
+    syntaxes
+}
+
+// Identifies the set of syntax files by name, size and modification time, or None when
+// there aren't any
+fn fingerprint(dir: &Path) -> Option<u64> {
+    let mut files: Vec<(PathBuf, u64, u64)> = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "sublime-syntax")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_secs();
+            Some((entry.path().to_path_buf(), metadata.len(), modified))
+        })
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    files.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SYNTAX: &str = "%YAML 1.2\n---\nname: Glancr Test\nfile_extensions: [glt]\n\
+                          scope: source.glt\ncontexts:\n  main:\n    - match: '\\bkeyword\\b'\n      \
+                          scope: keyword.control.glt\n";
+
+    #[test]
+    fn test_user_syntaxes_are_added_and_cached() {
+        let dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("test.sublime-syntax"), SYNTAX).unwrap();
+
+        // An empty base keeps relinking fast enough for a test
+        let syntaxes = with_user_syntaxes(SyntaxSet::new(), dir.path(), Some(cache_dir.path()));
+        assert!(syntaxes.find_syntax_by_extension("glt").is_some());
+
+        let cached: Vec<_> = std::fs::read_dir(cache_dir.path()).unwrap().collect();
+        assert_eq!(cached.len(), 1);
+        let syntaxes = with_user_syntaxes(SyntaxSet::new(), dir.path(), Some(cache_dir.path()));
+        assert!(syntaxes.find_syntax_by_extension("glt").is_some());
+    }
+
+    #[test]
+    fn test_default_theme_exists() {
+        let dir = TempDir::new().unwrap();
+        let assets = load(Some(dir.path()), None);
+        assert!(assets.themes.themes.contains_key(DEFAULT_THEME));
+    }
+
+    #[test]
+    fn test_fingerprint_needs_syntax_files() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(fingerprint(dir.path()), None);
+        std::fs::write(dir.path().join("a.sublime-syntax"), SYNTAX).unwrap();
+        let first = fingerprint(dir.path());
+        assert!(first.is_some());
+        std::fs::write(dir.path().join("b.sublime-syntax"), SYNTAX).unwrap();
+        assert_ne!(fingerprint(dir.path()), first);
+    }
+}
@@ -0,0 +1,128 @@
+use grep::matcher::{Captures, Matcher};
+use grep::regex::RegexMatcher;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tui_input::Input as TextInput;
+
+// What renaming one file would do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Planned {
+    Rename(PathBuf),
+    // The pattern doesn't match, or the path comes out the same
+    Unchanged,
+    // Something is already there, or another file would get the same path
+    Conflict(PathBuf),
+}
+
+// Renaming the marked files at once with a pattern over their paths and a replacement, shown
+// as a dry run of every rename until it's confirmed
+pub struct BatchRename {
+    pub files: Vec<PathBuf>,
+    pub pattern: TextInput,
+    pub replacement: TextInput,
+    // Typing goes to the replacement rather than the pattern
+    pub editing_replacement: bool,
+}
+
+impl BatchRename {
+    pub fn new(files: Vec<PathBuf>, pattern: &str) -> Self {
+        Self {
+            files,
+            pattern: TextInput::new(pattern.to_string()),
+            replacement: TextInput::default(),
+            editing_replacement: false,
+        }
+    }
+
+    pub fn plan(&self) -> Result<Vec<(PathBuf, Planned)>, String> {
+        plan(&self.files, self.pattern.value(), self.replacement.value())
+    }
+}
+
+// Each file's new path, with the first match of `pattern` in its path replaced: `$1` or
+// `${name}` in the replacement stand for what the pattern's groups captured
+pub fn plan(
+    files: &[PathBuf],
+    pattern: &str,
+    replacement: &str,
+) -> Result<Vec<(PathBuf, Planned)>, String> {
+    if pattern.is_empty() {
+        return Ok(files
+            .iter()
+            .map(|path| (path.clone(), Planned::Unchanged))
+            .collect());
+    }
+    let matcher = RegexMatcher::new(pattern).map_err(|err| err.to_string())?;
+    let mut caps = matcher.new_captures().map_err(|err| err.to_string())?;
+
+    let targets: Vec<Option<PathBuf>> = files
+        .iter()
+        .map(|path| {
+            let haystack = path.to_string_lossy();
+            let haystack = haystack.as_bytes();
+            if !matcher.captures(haystack, &mut caps).ok()? {
+                return None;
+            }
+            let found = caps.get(0)?;
+            let mut renamed = haystack[..found.start()].to_vec();
+            caps.interpolate(
+                |name| matcher.capture_index(name),
+                haystack,
+                replacement.as_bytes(),
+                &mut renamed,
+            );
+            renamed.extend_from_slice(&haystack[found.end()..]);
+            let renamed = PathBuf::from(String::from_utf8_lossy(&renamed).into_owned());
+            (renamed != *path && !renamed.as_os_str().is_empty()).then_some(renamed)
+        })
+        .collect();
+
+    let mut counts: HashMap<&PathBuf, usize> = HashMap::new();
+    for target in targets.iter().flatten() {
+        *counts.entry(target).or_default() += 1;
+    }
+    Ok(files
+        .iter()
+        .zip(&targets)
+        .map(|(path, target)| {
+            let planned = match target {
+                None => Planned::Unchanged,
+                Some(target) if counts[target] > 1 || target.exists() => {
+                    Planned::Conflict(target.clone())
+                }
+                Some(target) => Planned::Rename(target.clone()),
+            };
+            (path.clone(), planned)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_renames_with_capture_groups() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().into_owned();
+        let path = |name: &str| PathBuf::from(format!("{}/{}", root, name));
+        std::fs::write(path("taken.spec.js"), "").unwrap();
+        let files = vec![
+            path("button.test.js"),
+            path("menu.test.js"),
+            path("taken.test.js"),
+            path("README.md"),
+        ];
+
+        let planned = plan(&files, r"(\w+)\.test\.js$", "$1.spec.js").unwrap();
+        assert_eq!(planned[0].1, Planned::Rename(path("button.spec.js")));
+        assert_eq!(planned[1].1, Planned::Rename(path("menu.spec.js")));
+        assert_eq!(planned[2].1, Planned::Conflict(path("taken.spec.js")));
+        assert_eq!(planned[3].1, Planned::Unchanged);
+
+        let planned = plan(&files[..2], r"\w+\.test", "same").unwrap();
+        assert_eq!(planned[0].1, Planned::Conflict(path("same.js")));
+        assert!(plan(&files, "(", "").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::commands::program_exists;
+use anyhow::{anyhow, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Programs that take the clipboard's new contents on stdin, in the order they're tried
+const PROGRAMS: &[(&str, &[&str])] = &[
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+// Puts the text on the clipboard with the first of the usual programs that's installed and
+// then, over ssh or without any of them, with the terminal's OSC 52 escape. Says which.
+pub fn copy(text: &str) -> Result<&'static str> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = std::env::var_os("DISPLAY").is_some();
+    let usable = |program: &str| match program {
+        "wl-copy" => wayland,
+        "xclip" | "xsel" => x11,
+        _ => true,
+    };
+    let Some((program, args)) = PROGRAMS
+        .iter()
+        .find(|(program, _)| usable(program) && program_exists(program))
+    else {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(osc52(text).as_bytes())?;
+        stdout.flush()?;
+        return Ok("the terminal");
+    };
+    let mut child = Command::new(program)
+        .args(*args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Unable to run {}", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("{} exited with {}", program, status));
+    }
+    Ok(program)
+}
+
+// The escape that asks the terminal to set the clipboard, which works through ssh
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(osc52("a.rs\nb.rs\n"), "\x1b]52;c;YS5ycwpiLnJzCg==\x07");
+    }
+}
@@ -0,0 +1,419 @@
+use crate::config::EditorPreset;
+use crate::search::FileMatches;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tui_input::Input as TextInput;
+
+// Whether `program` can be spawned: a path that exists, or a name found on PATH
+pub fn program_exists(program: &str) -> bool {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
+
+// Prompt for an ad-hoc command, with Up/Down cycling through the configured ones
+#[derive(Default)]
+pub struct CommandPrompt {
+    pub input: TextInput,
+    pub choice: Option<usize>,
+}
+
+impl CommandPrompt {
+    pub fn choose(&mut self, commands: &[String], delta: isize) {
+        if commands.is_empty() {
+            return;
+        }
+        let choice = match self.choice {
+            Some(choice) => choice.saturating_add_signed(delta).min(commands.len() - 1),
+            None => 0,
+        };
+        self.choice = Some(choice);
+        self.input = TextInput::new(commands[choice].clone());
+    }
+}
+
+// Captured output of a finished command, shown in a popup
+pub struct CommandOutput {
+    pub title: String,
+    pub text: String,
+    pub scroll: u16,
+    // Every run exited successfully
+    pub succeeded: bool,
+}
+
+// Line and column of the current file's first match, 1:1 without one
+fn position(path: &Path, matches: &HashMap<PathBuf, FileMatches>) -> (u64, u64) {
+    matches
+        .get(path)
+        .map(FileMatches::position)
+        .unwrap_or((1, 1))
+}
+
+// Arguments for opening a file at its first match. `{file}`, `{line}` and `{col}` are
+// replaced in place, like `code --goto {file}:{line}:{col}`; without `{file}` the path
+// is passed last.
+pub fn open_args(
+    args: &[String],
+    path: &Path,
+    matches: &HashMap<PathBuf, FileMatches>,
+) -> Vec<OsString> {
+    let (line, column) = position(path, matches);
+    let mut expanded: Vec<OsString> = args
+        .iter()
+        .map(|arg| match arg.as_str() {
+            // Kept as an OsString so paths that aren't UTF-8 survive
+            "{file}" => path.as_os_str().to_owned(),
+            _ => arg
+                .replace("{file}", &path.to_string_lossy())
+                .replace("{line}", &line.to_string())
+                .replace("{col}", &column.to_string())
+                .into(),
+        })
+        .collect();
+    if !args.iter().any(|arg| arg.contains("{file}")) {
+        expanded.push(path.as_os_str().to_owned());
+    }
+    expanded
+}
+
+// Arguments for opening every file with one command: the configured arguments that don't
+// point at a single file, the preset's own, then each file as the preset writes it
+pub fn open_all_args(
+    args: &[String],
+    preset: &EditorPreset,
+    paths: &[PathBuf],
+    matches: &HashMap<PathBuf, FileMatches>,
+) -> Vec<OsString> {
+    let placeholders = ["{file}", "{line}", "{col}"];
+    let mut expanded: Vec<OsString> = args
+        .iter()
+        .filter(|arg| !placeholders.iter().any(|p| arg.contains(p)))
+        .chain(preset.args.iter().filter(|arg| !args.contains(arg)))
+        .map(OsString::from)
+        .collect();
+    for path in paths {
+        expanded.extend(open_args(std::slice::from_ref(&preset.file), path, matches));
+    }
+    expanded
+}
+
+// Replaces fzf-style placeholders: `{}` (or `{file}`) is the current file, `{+}` every
+// selected file, `{line}` / `{col}` the position of the current file's first match, and
+// `{module}` the current file's Rust module path, like `config::tests` for
+// src/config/tests.rs. Paths are shell-quoted, so templates don't need quotes of their own.
+pub fn expand(
+    template: &str,
+    current: &Path,
+    selected: &[PathBuf],
+    matches: &HashMap<PathBuf, FileMatches>,
+) -> String {
+    let quote = |path: &Path| shell_words::quote(&path.to_string_lossy()).into_owned();
+    let selected = selected
+        .iter()
+        .map(|path| quote(path))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (line, column) = position(current, matches);
+    template
+        .replace("{+}", &selected)
+        .replace("{}", &quote(current))
+        .replace("{file}", &quote(current))
+        .replace("{module}", &shell_words::quote(&module_path(current)))
+        .replace("{line}", &line.to_string())
+        .replace("{col}", &column.to_string())
+}
+
+fn module_path(path: &Path) -> String {
+    let path = path.with_extension("");
+    let names: Vec<String> = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    // Modules start below the crate's src directory, wherever the crate is
+    let start = names
+        .iter()
+        .rposition(|name| name == "src")
+        .map_or(0, |src| src + 1);
+    let mut modules = names[start..].to_vec();
+    // A directory's own module, or the crate root
+    if modules
+        .last()
+        .is_some_and(|name| ["mod", "lib", "main"].contains(&name.as_str()))
+    {
+        modules.pop();
+    }
+    modules.join("::")
+}
+
+// Runs a template once with `{+}` or without `{}`, otherwise once per selected file
+pub fn run_template(
+    template: &str,
+    current: &Path,
+    selected: &[PathBuf],
+    matches: &HashMap<PathBuf, FileMatches>,
+) -> CommandOutput {
+    let per_file = template.contains("{}") || template.contains("{file}");
+    if template.contains("{+}") || !per_file || selected.len() <= 1 {
+        return run(&expand(template, current, selected, matches));
+    }
+
+    let mut text = String::new();
+    let mut failed = 0;
+    for path in selected {
+        let command_line = expand(template, path, selected, matches);
+        let (output, status) = execute(&command_line, &[]);
+        if !status.is_empty() {
+            failed += 1;
+        }
+        text.push_str(&format!("$ {}{}\n{}", command_line, status, output));
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    CommandOutput {
+        title: format!("{}: {} ran, {} failed", template, selected.len(), failed),
+        text,
+        scroll: 0,
+        succeeded: failed == 0,
+    }
+}
+
+pub fn run(command_line: &str) -> CommandOutput {
+    let (text, status) = execute(command_line, &[]);
+    CommandOutput {
+        title: format!("{}{}", command_line, status),
+        text,
+        scroll: 0,
+        succeeded: status.is_empty(),
+    }
+}
+
+// A hook run while the UI is up, with its output returned if it fails
+pub fn run_hook(command_line: &str, env: &[(&str, String)]) -> Result<(), String> {
+    let (text, status) = execute(command_line, env);
+    if status.is_empty() {
+        return Ok(());
+    }
+    Err(format!("{}{}: {}", command_line, status, text.trim()))
+}
+
+// A hook run after the terminal is restored, so its output goes straight to it
+pub fn run_exit_hook(command_line: &str, env: &[(&str, String)]) {
+    let _ = shell(command_line)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .status();
+}
+
+fn shell(command_line: &str) -> Command {
+    let (program, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = Command::new(program);
+    command.arg(flag).arg(command_line);
+    command
+}
+
+// Combined stdout and stderr, and a note about the exit status when it wasn't a success
+fn execute(command_line: &str, env: &[(&str, String)]) -> (String, String) {
+    let output = shell(command_line)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .output();
+
+    match output {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            let status = match output.status.code() {
+                Some(0) => String::new(),
+                Some(code) => format!(" (exit {})", code),
+                None => " (killed)".to_string(),
+            };
+            (text, status)
+        }
+        Err(err) => (
+            format!("Unable to run command: {}", err),
+            " (failed)".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let current = PathBuf::from("./src/main.rs");
+        let selected = vec![current.clone(), PathBuf::from("./my notes.md")];
+        assert_eq!(
+            expand("wc -l {}", &current, &selected, &HashMap::new()),
+            "wc -l ./src/main.rs"
+        );
+        assert_eq!(
+            expand("git add {+}", &current, &selected, &HashMap::new()),
+            "git add ./src/main.rs './my notes.md'"
+        );
+        let current = PathBuf::from("./crates/app/src/config/mod.rs");
+        assert_eq!(
+            expand(
+                "cargo test {module} # {file}",
+                &current,
+                &selected,
+                &HashMap::new()
+            ),
+            "cargo test config # ./crates/app/src/config/mod.rs"
+        );
+        assert_eq!(module_path(Path::new("src/ui/list.rs")), "ui::list");
+        assert_eq!(module_path(Path::new("./src/main.rs")), "");
+    }
+
+    fn matches_at(path: &str, line_number: u64, column: u64) -> HashMap<PathBuf, FileMatches> {
+        let lines = vec![crate::search::LineMatch {
+            line_number,
+            column,
+            text: String::new(),
+            captures: Vec::new(),
+        }];
+        HashMap::from([(
+            PathBuf::from(path),
+            FileMatches {
+                lines,
+                truncated: false,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_expand_position() {
+        let current = PathBuf::from("a.rs");
+        let matches = matches_at("a.rs", 12, 5);
+        assert_eq!(
+            expand("echo {}:{line}:{col}", &current, &[], &matches),
+            "echo a.rs:12:5"
+        );
+        assert_eq!(
+            expand("echo {}:{line}:{col}", &current, &[], &HashMap::new()),
+            "echo a.rs:1:1"
+        );
+    }
+
+    #[test]
+    fn test_open_args() {
+        let path = PathBuf::from("a.rs");
+        let matches = matches_at("a.rs", 12, 5);
+        let args = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            open_args(&args, &path, &matches)
+        };
+        assert_eq!(args(&["--reuse-window"]), vec!["--reuse-window", "a.rs"]);
+        assert_eq!(
+            args(&["--goto", "{file}:{line}:{col}"]),
+            vec!["--goto", "a.rs:12:5"]
+        );
+        assert_eq!(
+            args(&["+call cursor({line}, {col})", "{file}"]),
+            vec!["+call cursor(12, 5)", "a.rs"]
+        );
+    }
+
+    #[test]
+    fn test_open_all_args() {
+        let paths = vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")];
+        let matches = matches_at("a.rs", 12, 5);
+        let preset = EditorPreset {
+            args: vec!["--goto".to_string()],
+            file: "{file}:{line}:{col}".to_string(),
+        };
+        let args: Vec<String> = ["--goto", "{file}:{line}:{col}", "--reuse-window"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            open_all_args(&args, &preset, &paths, &matches),
+            vec!["--goto", "--reuse-window", "a.rs:12:5", "b.rs:1:1"]
+        );
+    }
+
+    #[test]
+    fn test_choose_clamps() {
+        let commands = vec!["wc -l {}".to_string(), "git log -p {}".to_string()];
+        let mut prompt = CommandPrompt::default();
+        prompt.choose(&commands, 1);
+        assert_eq!(prompt.input.value(), "wc -l {}");
+        prompt.choose(&commands, 5);
+        assert_eq!(prompt.input.value(), "git log -p {}");
+        prompt.choose(&commands, -1);
+        assert_eq!(prompt.choice, Some(0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_program_exists() {
+        assert!(program_exists("sh"));
+        assert!(program_exists("/bin/sh"));
+        assert!(!program_exists("glancr-no-such-editor"));
+        assert!(!program_exists("/no/such/editor"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_captures_output_and_status() {
+        let output = run("echo hello; exit 3");
+        assert_eq!(output.text, "hello\n");
+        assert_eq!(output.title, "echo hello; exit 3 (exit 3)");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook_sees_environment() {
+        let env = [("GLANCR_FILE", "src/main.rs".to_string())];
+        assert_eq!(
+            run_hook("test \"$GLANCR_FILE\" = src/main.rs", &env),
+            Ok(())
+        );
+        assert_eq!(
+            run_hook("echo \"no $GLANCR_FILE\" >&2; exit 1", &env),
+            Err("echo \"no $GLANCR_FILE\" >&2; exit 1 (exit 1): no src/main.rs".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_template_per_file() {
+        let selected = vec![PathBuf::from("a"), PathBuf::from("b")];
+        let output = run_template(
+            "echo {}; test {} = a",
+            &selected[0],
+            &selected,
+            &HashMap::new(),
+        );
+        assert_eq!(
+            output.text,
+            "$ echo a; test a = a\na\n$ echo b; test b = a (exit 1)\nb\n"
+        );
+        assert_eq!(output.title, "echo {}; test {} = a: 2 ran, 1 failed");
+
+        let output = run_template("echo {+}", &selected[0], &selected, &HashMap::new());
+        assert_eq!(output.text, "a b\n");
+    }
+}
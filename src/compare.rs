@@ -0,0 +1,110 @@
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span, Text},
+};
+use similar::{ChangeTag, TextDiff};
+use std::path::Path;
+
+const CONTEXT_LINES: usize = 3;
+
+// A unified diff of the anchor file against another file, for comparing near-duplicates
+pub fn diff_preview(anchor: &Path, other: &Path, max_size: u64) -> Text<'static> {
+    let too_large = |path: &Path| {
+        std::fs::metadata(path)
+            .map(|metadata| metadata.len() > max_size)
+            .unwrap_or(false)
+    };
+    if too_large(anchor) || too_large(other) {
+        return Text::raw("Files are too large to diff");
+    }
+
+    let (old, new) = match (
+        std::fs::read_to_string(anchor),
+        std::fs::read_to_string(other),
+    ) {
+        (Ok(old), Ok(new)) => (old, new),
+        _ => return Text::raw("Unable to diff binary or unreadable files"),
+    };
+    if old == new {
+        return Text::raw("Files are identical");
+    }
+
+    let diff = TextDiff::from_lines(&old, &new);
+    let mut lines = Vec::new();
+    for group in diff.grouped_ops(CONTEXT_LINES) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        lines.push(Line::styled(
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_range.start + 1,
+                old_range.len(),
+                new_range.start + 1,
+                new_range.len()
+            ),
+            Style::default().fg(Color::Cyan),
+        ));
+
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let (sign, style) = match change.tag() {
+                    ChangeTag::Delete => ("-", Style::default().fg(Color::Red)),
+                    ChangeTag::Insert => ("+", Style::default().fg(Color::Green)),
+                    ChangeTag::Equal => (" ", Style::default()),
+                };
+                let text = change.value().trim_end_matches(['\r', '\n']);
+                lines.push(Line::from(Span::styled(format!("{}{}", sign, text), style)));
+            }
+        }
+    }
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn line_text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let dir = TempDir::new().unwrap();
+        let anchor = dir.path().join("anchor.txt");
+        let other = dir.path().join("other.txt");
+        std::fs::write(&anchor, old).unwrap();
+        std::fs::write(&other, new).unwrap();
+        diff_preview(&anchor, &other, 1024)
+            .lines
+            .iter()
+            .map(line_text)
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_preview() {
+        let lines = diff("one\ntwo\nthree\n", "one\n2\nthree\n");
+        assert_eq!(
+            lines,
+            vec!["@@ -1,3 +1,3 @@", " one", "-two", "+2", " three"]
+        );
+    }
+
+    #[test]
+    fn test_diff_identical_files() {
+        assert_eq!(diff("same\n", "same\n"), vec!["Files are identical"]);
+    }
+
+    #[test]
+    fn test_diff_too_large() {
+        let lines = diff(&"x".repeat(2048), "x");
+        assert_eq!(lines, vec!["Files are too large to diff"]);
+    }
+}
@@ -0,0 +1,90 @@
+use grep::cli::{DecompressionReader, DecompressionReaderBuilder};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+fn reader_builder() -> &'static DecompressionReaderBuilder {
+    static BUILDER: OnceLock<DecompressionReaderBuilder> = OnceLock::new();
+    BUILDER.get_or_init(DecompressionReaderBuilder::new)
+}
+
+// Going by the extension: .gz, .bz2, .xz, .lz4, .lzma, .br, .zst and .Z
+pub fn is_compressed(path: &Path) -> bool {
+    reader_builder().get_matcher().has_command(path)
+}
+
+// The decompressed contents, streamed from `gzip -d`, `zstd -d` and the like the way `zgrep`
+// does. Without the tool installed the file is read as it is.
+pub fn open(path: &Path) -> io::Result<DecompressionReader> {
+    reader_builder().build(path).map_err(io::Error::other)
+}
+
+// Up to `limit` bytes of the decompressed text, cut back to the last whole line, and whether
+// there was more
+pub fn read_to_string(path: &Path, limit: u64) -> io::Result<(String, bool)> {
+    let mut bytes = Vec::new();
+    open(path)?
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+    let truncated = bytes.len() as u64 > limit;
+    if truncated {
+        let end = bytes
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |i| i + 1);
+        bytes.truncate(end);
+    }
+    let text =
+        String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((text, truncated))
+}
+
+// The name it had before it was compressed, which says what's inside, e.g. `app.log` for
+// `app.log.gz`
+pub fn inner_path(path: &Path) -> PathBuf {
+    path.with_extension("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reads_gzipped_text() {
+        assert!(is_compressed(Path::new("logs/app.log.gz")));
+        assert!(is_compressed(Path::new("dump.sql.zst")));
+        assert!(!is_compressed(Path::new("src/main.rs")));
+        assert_eq!(
+            inner_path(Path::new("logs/app.log.gz")),
+            Path::new("logs/app.log")
+        );
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.log.gz");
+        let Ok(mut gzip) = Command::new("gzip")
+            .arg("-c")
+            .stdin(Stdio::piped())
+            .stdout(std::fs::File::create(&path).unwrap())
+            .spawn()
+        else {
+            // Nothing to decompress with
+            return;
+        };
+        gzip.stdin
+            .take()
+            .unwrap()
+            .write_all(b"first line\nsecond line\n")
+            .unwrap();
+        assert!(gzip.wait().unwrap().success());
+
+        let (text, truncated) = read_to_string(&path, 1024).unwrap();
+        assert_eq!(text, "first line\nsecond line\n");
+        assert!(!truncated);
+        let (text, truncated) = read_to_string(&path, 15).unwrap();
+        assert_eq!(text, "first line\n");
+        assert!(truncated);
+    }
+}
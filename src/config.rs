@@ -1,18 +1,313 @@
-use serde::Deserialize;
-use std::path::PathBuf;
+use crate::excommand::Sort;
+use crate::export::ResultsFormat;
+use crate::generated::GeneratedConfig;
+use crate::keys::KeySpec;
+use crate::performance::PerformanceConfig;
+use crate::preview::{LineNumbers, SyntaxOverride};
+use crate::rowformat::RowTemplate;
+use crate::tiebreak::Tiebreak;
+use crate::{FileFilter, SearchMode};
+use serde::{de, Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
-    #[serde(default = "default_command")]
-    pub open_command: String,
+    #[serde(default = "default_command", deserialize_with = "deserialize_command")]
+    pub open_command: Vec<String>,
+    // Editors that open all the marked files from one command, by program name, on top of the
+    // built-in ones; `null` opens them with a command each
+    #[serde(default)]
+    pub editor_presets: HashMap<String, Option<EditorPreset>>,
+    // Whether open_command runs in the terminal, so glancr steps aside until it exits; guessed
+    // from the program's name when unset
+    pub terminal_editor: Option<bool>,
+    // Come back to the results after opening files instead of quitting
+    #[serde(default)]
+    pub stay_open: bool,
     #[serde(default = "default_ignored_dirs")]
     pub ignored_dirs: Vec<String>,
     #[serde(default = "default_ignored_patterns")]
     pub ignored_patterns: Vec<String>,
+    #[serde(default = "default_recent_commits")]
+    pub recent_commits: usize,
+    pub recent_since: Option<String>,
+    #[serde(default = "default_author_log_depth")]
+    pub author_log_depth: usize,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    pub ripgrep_config: Option<String>,
+    #[serde(
+        default = "default_max_search_file_size",
+        deserialize_with = "deserialize_size"
+    )]
+    pub max_search_file_size: u64,
+    // Search and preview .gz, .zst, .bz2 and other compressed files decompressed, like zgrep
+    #[serde(default)]
+    pub search_compressed: bool,
+    #[serde(
+        default = "default_modified_within",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub modified_within: Duration,
+    #[serde(default = "default_hide_linguist_generated")]
+    pub hide_linguist_generated: bool,
+    #[serde(default = "default_max_matches_per_file")]
+    pub max_matches_per_file: usize,
+    #[serde(default = "default_highlight_filename_query")]
+    pub highlight_filename_query: bool,
+    #[serde(default)]
+    pub line_numbers: LineNumbers,
+    #[serde(default)]
+    pub commands: Vec<String>,
+    // External pager, used instead of the built-in one when set
+    #[serde(default, deserialize_with = "deserialize_optional_command")]
+    pub pager: Option<Vec<String>>,
+    // Delete files outright instead of sending them to the trash
+    #[serde(default)]
+    pub permanent_delete: bool,
+    // Down-ranking or hiding minified bundles and generated code
+    #[serde(default)]
+    pub generated: GeneratedConfig,
+    // Preview colours, a built-in syntect theme or a `.tmTheme` in ~/.config/glancr/themes
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    // File globs mapped to the syntax they're highlighted as, checked in order
+    #[serde(default, deserialize_with = "deserialize_syntax_overrides")]
+    pub syntax_overrides: Vec<SyntaxOverride>,
+    // Languages highlighted with tree-sitter when built with the `tree-sitter` feature
+    #[serde(default)]
+    pub tree_sitter: Vec<String>,
+    // Layout of each result row, with placeholders like `{dir:dim}{name:bold}`
+    #[serde(default, deserialize_with = "deserialize_row_format")]
+    pub row_format: RowTemplate,
+    // Start with each content match's first line shown under its file
+    #[serde(default)]
+    pub two_line_results: bool,
+    // Directory of WebAssembly filters and previewers, used in builds with the `wasm` feature
+    pub wasm_plugins: Option<String>,
+    // Shell commands run at points in a session, like logging every file that's opened
+    #[serde(default)]
+    pub hooks: Hooks,
+    // Keys that run a command template on the selection
+    #[serde(default)]
+    pub key_bindings: Vec<KeyBinding>,
+    // Plain text the screen reader can follow: no borders, scrollbars or colour-only cues
+    #[serde(default)]
+    pub accessible: bool,
+    // Follow file changes with the platform's notifications instead of walking on every search
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    // Wait for typing to pause this long before searching contents, for trees where every
+    // keystroke's search is slow
+    #[serde(default)]
+    pub search_delay_ms: u64,
+    // Debouncing, polling and threads, for tuning to the machine
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    // Keys that quit, besides Ctrl+c which always does
+    #[serde(default = "default_quit_keys", deserialize_with = "deserialize_keys")]
+    pub quit_keys: Vec<KeySpec>,
+    // Keys that suspend to the shell. Ctrl+z undoes query changes unless it's listed here.
+    #[serde(
+        default = "default_suspend_keys",
+        deserialize_with = "deserialize_keys"
+    )]
+    pub suspend_keys: Vec<KeySpec>,
+    // Ask before quitting with files marked
+    #[serde(default)]
+    pub confirm_quit: bool,
+    // Commits listed when the preview shows a file's history
+    #[serde(default = "default_history_commits")]
+    pub history_commits: usize,
+    // What Alt+w copies and `:write` saves: `paths`, one per line, or `quickfix` lines
+    #[serde(default)]
+    pub results_format: ResultsFormat,
+    // How results that rank the same are ordered, instead of the order the walk found them in
+    #[serde(default = "default_tiebreaks")]
+    pub tiebreaks: Vec<Tiebreak>,
 }
 
-fn default_command() -> String {
-    "cursor".to_string()
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyBinding {
+    #[serde(deserialize_with = "deserialize_key")]
+    pub key: KeySpec,
+    pub command: String,
+    // Only report failures, instead of showing the output in a popup
+    #[serde(default)]
+    pub silent: bool,
+    // Search again afterwards, for commands that change files or git state
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    // Run before opening files, with a failure cancelling the open
+    pub before_open: Option<String>,
+    // Run once the editor is launched and glancr has exited
+    pub after_open: Option<String>,
+    // Run whenever glancr exits, after `after_open`
+    pub on_quit: Option<String>,
+}
+
+// How an editor takes several files at once, which it opens as tabs of one window
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EditorPreset {
+    // Arguments before the files, like `--goto`
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Each file's argument, with `{file}`, `{line}` and `{col}` replaced
+    #[serde(default = "default_preset_file")]
+    pub file: String,
+}
+
+fn default_preset_file() -> String {
+    "{file}".to_string()
+}
+
+// Editors known to open every file they're given in one window
+fn builtin_editor_preset(program: &str) -> Option<EditorPreset> {
+    let args = match program {
+        "code" | "code-insiders" | "codium" | "cursor" | "windsurf" => vec!["--goto".to_string()],
+        "subl" | "zed" | "hx" | "helix" => Vec::new(),
+        // One tab per file, though only the first could be put at its match
+        "vim" | "nvim" | "gvim" => {
+            return Some(EditorPreset {
+                args: vec!["-p".to_string()],
+                file: default_preset_file(),
+            })
+        }
+        _ => return None,
+    };
+    Some(EditorPreset {
+        args,
+        file: "{file}:{line}:{col}".to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedSearch {
+    pub name: String,
+    #[serde(default)]
+    pub query: String,
+    #[serde(default = "default_saved_mode")]
+    pub mode: SearchMode,
+    #[serde(default = "default_saved_filter")]
+    pub filter: FileFilter,
+    // Applied after the filter, like `:sort`
+    #[serde(default)]
+    pub sort: Option<Sort>,
+}
+
+fn default_saved_mode() -> SearchMode {
+    SearchMode::Contents
+}
+
+fn default_saved_filter() -> FileFilter {
+    FileFilter::All
+}
+
+fn default_command() -> Vec<String> {
+    editor_command(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok())
+}
+
+// The user's editor, like git picks it, with `cursor` when neither variable is set
+fn editor_command(visual: Option<String>, editor: Option<String>) -> Vec<String> {
+    [visual, editor]
+        .into_iter()
+        .flatten()
+        .filter_map(|command| shell_words::split(&command).ok())
+        .find(|argv| !argv.is_empty())
+        .unwrap_or_else(|| vec!["cursor".to_string()])
+}
+
+// Commands are either a shell-style string like `open -a "Sublime Text"` or a list of arguments
+fn deserialize_command<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Command {
+        Shell(String),
+        Argv(Vec<String>),
+    }
+
+    let argv = match Command::deserialize(deserializer)? {
+        Command::Shell(text) => shell_words::split(&text)
+            .map_err(|err| de::Error::custom(format!("invalid command '{}': {}", text, err)))?,
+        Command::Argv(argv) => argv,
+    };
+    if argv.is_empty() {
+        return Err(de::Error::custom("command is empty"));
+    }
+    Ok(argv)
+}
+
+fn deserialize_optional_command<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    deserialize_command(deserializer).map(Some)
+}
+
+// A map of glob to syntax, kept in the order it's written so earlier entries win
+fn deserialize_syntax_overrides<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<SyntaxOverride>, D::Error> {
+    let mapping = serde_yaml::Mapping::deserialize(deserializer)?;
+    mapping
+        .iter()
+        .map(|(pattern, syntax)| {
+            let (Some(pattern), Some(syntax)) = (pattern.as_str(), syntax.as_str()) else {
+                return Err(de::Error::custom(
+                    "syntax overrides map globs to syntax names",
+                ));
+            };
+            SyntaxOverride::new(pattern, syntax)
+                .map_err(|err| de::Error::custom(format!("invalid glob '{}': {}", pattern, err)))
+        })
+        .collect()
+}
+
+fn deserialize_row_format<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<RowTemplate, D::Error> {
+    let template = String::deserialize(deserializer)?;
+    RowTemplate::parse(&template).map_err(de::Error::custom)
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeySpec, D::Error> {
+    let key = String::deserialize(deserializer)?;
+    KeySpec::parse(&key).ok_or_else(|| de::Error::custom(format!("unknown key '{}'", key)))
+}
+
+fn deserialize_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<KeySpec>, D::Error> {
+    let keys = Vec::<String>::deserialize(deserializer)?;
+    keys.iter()
+        .map(|key| {
+            KeySpec::parse(key).ok_or_else(|| de::Error::custom(format!("unknown key '{}'", key)))
+        })
+        .collect()
+}
+
+fn default_quit_keys() -> Vec<KeySpec> {
+    vec![KeySpec::parse("esc").expect("esc is a key")]
+}
+
+fn default_suspend_keys() -> Vec<KeySpec> {
+    vec![KeySpec::parse("ctrl+alt+z").expect("ctrl+alt+z is a key")]
+}
+
+fn default_theme() -> String {
+    crate::assets::DEFAULT_THEME.to_string()
 }
 
 fn default_ignored_dirs() -> Vec<String> {
@@ -32,41 +327,628 @@ fn default_ignored_dirs() -> Vec<String> {
     ]
 }
 
+// Minified and bundled files are left to `generated`, which spots them by their contents
 fn default_ignored_patterns() -> Vec<String> {
     vec![
         ".lock".to_string(),
         ".log".to_string(),
         ".map".to_string(),
-        ".min.js".to_string(),
-        ".min.css".to_string(),
-        ".bundle.".to_string(),
         ".cache".to_string(),
     ]
 }
 
+fn default_recent_commits() -> usize {
+    5
+}
+
+fn default_author_log_depth() -> usize {
+    1000
+}
+
+fn default_max_search_file_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+// Sizes can be given in bytes or with a unit, like `10mb`
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(bytes),
+        Size::Text(text) => crate::query::parse_size(&text)
+            .ok_or_else(|| de::Error::custom(format!("invalid size '{}'", text))),
+    }
+}
+
+fn default_modified_within() -> Duration {
+    Duration::from_secs(60 * 60 * 24)
+}
+
+// Durations are written like `24h` or `2w`
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    crate::query::parse_duration(&text)
+        .ok_or_else(|| de::Error::custom(format!("invalid duration '{}'", text)))
+}
+
+fn default_hide_linguist_generated() -> bool {
+    true
+}
+
+fn default_max_matches_per_file() -> usize {
+    100
+}
+
+fn default_highlight_filename_query() -> bool {
+    true
+}
+
+fn default_history_commits() -> usize {
+    20
+}
+
+fn default_tiebreaks() -> Vec<Tiebreak> {
+    vec![Tiebreak::Path, Tiebreak::Mtime]
+}
+
+fn default_watch() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             open_command: default_command(),
+            editor_presets: HashMap::new(),
+            terminal_editor: None,
+            stay_open: false,
             ignored_dirs: default_ignored_dirs(),
             ignored_patterns: default_ignored_patterns(),
+            recent_commits: default_recent_commits(),
+            recent_since: None,
+            author_log_depth: default_author_log_depth(),
+            saved_searches: Vec::new(),
+            workspaces: Vec::new(),
+            ripgrep_config: None,
+            max_search_file_size: default_max_search_file_size(),
+            search_compressed: false,
+            modified_within: default_modified_within(),
+            hide_linguist_generated: default_hide_linguist_generated(),
+            max_matches_per_file: default_max_matches_per_file(),
+            highlight_filename_query: default_highlight_filename_query(),
+            line_numbers: LineNumbers::default(),
+            commands: Vec::new(),
+            pager: None,
+            permanent_delete: false,
+            generated: GeneratedConfig::default(),
+            syntax_overrides: Vec::new(),
+            theme: default_theme(),
+            tree_sitter: Vec::new(),
+            row_format: RowTemplate::default(),
+            two_line_results: false,
+            wasm_plugins: None,
+            hooks: Hooks::default(),
+            key_bindings: Vec::new(),
+            accessible: false,
+            watch: default_watch(),
+            search_delay_ms: 0,
+            performance: PerformanceConfig::default(),
+            quit_keys: default_quit_keys(),
+            suspend_keys: default_suspend_keys(),
+            confirm_quit: false,
+            history_commits: default_history_commits(),
+            results_format: ResultsFormat::default(),
+            tiebreaks: default_tiebreaks(),
         }
     }
 }
 
 impl Config {
-    pub fn load() -> Self {
-        let config_path = dirs::home_dir()
-            .map(|mut path| {
-                path.push(".glancr.yml");
-                path
-            })
-            .unwrap_or_else(|| PathBuf::from(".glancr.yml"));
+    // How long typing has to pause before a content search, under either name
+    pub fn search_delay(&self) -> Duration {
+        Duration::from_millis(self.performance.debounce_ms.unwrap_or(self.search_delay_ms))
+    }
+
+    // Whether files open in an editor that takes over the terminal
+    pub fn is_terminal_editor(&self) -> bool {
+        self.terminal_editor.unwrap_or_else(|| {
+            let name = self
+                .open_command
+                .first()
+                .and_then(|program| Path::new(program).file_name())
+                .and_then(|name| name.to_str());
+            matches!(
+                name,
+                Some(
+                    "vi" | "vim"
+                        | "nvim"
+                        | "nano"
+                        | "pico"
+                        | "hx"
+                        | "helix"
+                        | "micro"
+                        | "kak"
+                        | "joe"
+                        | "ne"
+                        | "mg"
+                )
+            )
+        })
+    }
 
-        if let Ok(contents) = std::fs::read_to_string(config_path) {
-            serde_yaml::from_str(&contents).unwrap_or_default()
-        } else {
-            Config::default()
+    // How `program` opens several files with one command, if it can
+    pub fn editor_preset(&self, program: &str) -> Option<EditorPreset> {
+        let name = Path::new(program).file_name()?.to_str()?;
+        match self.editor_presets.get(name) {
+            Some(preset) => preset.clone(),
+            None => builtin_editor_preset(name),
         }
     }
+
+    pub fn saved_search(&self, name: &str) -> Option<&SavedSearch> {
+        self.saved_searches
+            .iter()
+            .find(|search| search.name == name)
+    }
+
+    // Directories searched when none are given on the command line
+    pub fn workspace_roots(&self) -> Vec<PathBuf> {
+        self.workspaces.iter().map(|dir| expand_home(dir)).collect()
+    }
+
+    pub fn ripgrep_config_path(&self) -> Option<PathBuf> {
+        self.ripgrep_config.as_deref().map(expand_home)
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn wasm_plugins_dir(&self) -> Option<PathBuf> {
+        self.wasm_plugins.as_deref().map(expand_home)
+    }
+
+    // The first config file found, with a description of anything wrong with it
+    pub fn load() -> (Self, Vec<String>) {
+        let Some(path) = config_paths().into_iter().find(|path| path.is_file()) else {
+            return (Config::default(), Vec::new());
+        };
+        let format = Format::of(&path);
+        let (config, mut problems) = match std::fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents, format),
+            Err(err) => (Config::default(), vec![err.to_string()]),
+        };
+        if !problems.is_empty() {
+            problems.insert(0, format!("In {}:", path.display()));
+        }
+        (config, problems)
+    }
+
+    // Where settings come from, for messages: the file `load` reads, or every place it looked
+    pub fn location() -> String {
+        let paths = config_paths();
+        match paths.iter().find(|path| path.is_file()) {
+            Some(path) => path.display().to_string(),
+            None => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                format!("any of {}", paths.join(", "))
+            }
+        }
+    }
+
+    fn parse(contents: &str, format: Format) -> (Self, Vec<String>) {
+        if contents.trim().is_empty() {
+            return (Config::default(), Vec::new());
+        }
+        match format.deserialize(contents) {
+            Ok(config) => {
+                let problems = config.validate(contents);
+                (config, problems)
+            }
+            Err((message, line)) => {
+                let problem = describe(contents, line, &shorten_unknown_field(&message));
+                let fallback = "Using the default settings until this is fixed".to_string();
+                (Config::default(), vec![problem, fallback])
+            }
+        }
+    }
+
+    // Settings that parse but can't work
+    fn validate(&self, contents: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut problem = |needle: &str, message: String| {
+            problems.push(describe(contents, line_of(contents, needle), &message));
+        };
+
+        if self.theme != default_theme()
+            && !crate::assets::get().themes.themes.contains_key(&self.theme)
+        {
+            problem(
+                "theme",
+                format!(
+                    "theme '{}' isn't built in or in ~/.config/glancr/themes",
+                    self.theme
+                ),
+            );
+        }
+        for entry in &self.syntax_overrides {
+            if crate::assets::get()
+                .syntaxes
+                .find_syntax_by_token(&entry.syntax)
+                .is_none()
+            {
+                problem(
+                    &entry.syntax,
+                    format!("syntax override names an unknown syntax '{}'", entry.syntax),
+                );
+            }
+        }
+        for workspace in &self.workspaces {
+            if !expand_home(workspace).is_dir() {
+                problem(
+                    workspace,
+                    format!("workspace '{}' isn't a directory", workspace),
+                );
+            }
+        }
+        if let Some(path) = self.ripgrep_config.as_deref() {
+            if !expand_home(path).is_file() {
+                problem(
+                    "ripgrep_config",
+                    format!("ripgrep_config '{}' doesn't exist", path),
+                );
+            }
+        }
+        if let Some(dir) = self.wasm_plugins.as_deref() {
+            if !expand_home(dir).is_dir() {
+                problem(
+                    "wasm_plugins",
+                    format!("wasm_plugins '{}' isn't a directory", dir),
+                );
+            }
+        }
+        let mut names = HashSet::new();
+        for search in &self.saved_searches {
+            if !names.insert(search.name.as_str()) {
+                problem(
+                    &search.name,
+                    format!(
+                        "saved search '{}' is defined twice, the first one is used",
+                        search.name
+                    ),
+                );
+            }
+        }
+        problems
+    }
+}
+
+// Dotfile first, then the XDG-style location, each in any of the supported formats
+fn config_paths() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return vec![PathBuf::from(".glancr.yml")];
+    };
+    let extensions = ["yml", "yaml", "toml", "json"];
+    let dotfiles = extensions
+        .iter()
+        .map(|ext| home.join(format!(".glancr.{}", ext)));
+    let xdg = extensions.iter().map(|ext| {
+        home.join(".config")
+            .join("glancr")
+            .join(format!("config.{}", ext))
+    });
+    dotfiles.chain(xdg).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+
+    // The same serde definitions serve every format; errors come with their 1-based line
+    fn deserialize(self, contents: &str) -> Result<Config, (String, Option<usize>)> {
+        match self {
+            Format::Yaml => serde_yaml::from_str(contents).map_err(|err| {
+                let line = err.location().map(|location| location.line());
+                (err.to_string(), line)
+            }),
+            Format::Toml => toml::from_str(contents).map_err(|err| {
+                let line = err
+                    .span()
+                    .map(|span| contents[..span.start].matches('\n').count() + 1);
+                (err.message().to_string(), line)
+            }),
+            Format::Json => serde_json::from_str(contents)
+                .map_err(|err| (err.to_string(), Some(err.line()).filter(|line| *line > 0))),
+        }
+    }
+}
+
+// A problem with the line it's on, quoted so it can be found
+fn describe(contents: &str, line: Option<usize>, message: &str) -> String {
+    match line.and_then(|line| Some((line, contents.lines().nth(line.checked_sub(1)?)?))) {
+        Some((number, text)) => format!("line {}: {}\n    {}", number, message, text.trim_end()),
+        None => message.to_string(),
+    }
+}
+
+// The first line mentioning `needle`, 1-based like serde_yaml's locations
+fn line_of(contents: &str, needle: &str) -> Option<usize> {
+    contents
+        .lines()
+        .position(|line| line.contains(needle))
+        .map(|index| index + 1)
+}
+
+// serde lists every field it expected after an unknown one, which is more noise than help
+// with this many settings, so only the closest is suggested
+fn shorten_unknown_field(message: &str) -> String {
+    let Some((unknown, expected)) = message.split_once(", expected one of ") else {
+        return strip_location(message);
+    };
+    let field = unknown.rsplit('`').nth(1).unwrap_or_default();
+    let suggestion = expected
+        .split(", ")
+        .map(|candidate| candidate.trim_matches(|c: char| c == '`' || c == ' '))
+        .map(|candidate| candidate.split('`').next().unwrap_or(candidate))
+        .min_by_key(|candidate| edit_distance(field, candidate))
+        .filter(|candidate| edit_distance(field, candidate) <= 3);
+    match suggestion {
+        Some(suggestion) => format!("{}, did you mean `{}`?", unknown, suggestion),
+        None => unknown.to_string(),
+    }
+}
+
+// The line is shown separately, so serde_yaml's "at line 3 column 1" suffix is dropped
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_yaml(contents: &str) -> (Config, Vec<String>) {
+        Config::parse(contents, Format::Yaml)
+    }
+
+    fn open_command(yaml: &str) -> Result<Vec<String>, serde_yaml::Error> {
+        serde_yaml::from_str::<Config>(yaml).map(|config| config.open_command)
+    }
+
+    #[test]
+    fn test_open_command_forms() {
+        assert_eq!(
+            open_command("open_command: open -a \"Sublime Text\"").unwrap(),
+            vec!["open", "-a", "Sublime Text"]
+        );
+        assert_eq!(
+            open_command("open_command: [code, --goto]").unwrap(),
+            vec!["code", "--goto"]
+        );
+        assert_eq!(open_command("{}").unwrap(), default_command());
+    }
+
+    #[test]
+    fn test_editor_presets() {
+        let config: Config =
+            serde_yaml::from_str("editor_presets:\n  code: null\n  gvim:\n    args: [-p]\n")
+                .unwrap();
+        assert_eq!(config.editor_preset("code"), None);
+        assert_eq!(
+            config.editor_preset("/usr/bin/gvim"),
+            Some(EditorPreset {
+                args: vec!["-p".to_string()],
+                file: "{file}".to_string(),
+            })
+        );
+        assert_eq!(
+            config.editor_preset("cursor").map(|preset| preset.file),
+            Some("{file}:{line}:{col}".to_string())
+        );
+        assert_eq!(config.editor_preset("nano"), None);
+    }
+
+    #[test]
+    fn test_terminal_editors() {
+        let config = |yaml: &str| {
+            serde_yaml::from_str::<Config>(yaml)
+                .unwrap()
+                .is_terminal_editor()
+        };
+        assert!(config("open_command: /usr/bin/nvim"));
+        assert!(config("open_command: hx"));
+        assert!(!config("open_command: code --wait"));
+        assert!(config("open_command: my-editor\nterminal_editor: true"));
+    }
+
+    #[test]
+    fn test_editor_fallback() {
+        let some = |command: &str| Some(command.to_string());
+        assert_eq!(
+            editor_command(some("code --wait"), some("vim")),
+            vec!["code", "--wait"]
+        );
+        assert_eq!(editor_command(some(""), some("nvim")), vec!["nvim"]);
+        assert_eq!(editor_command(None, None), vec!["cursor"]);
+    }
+
+    #[test]
+    fn test_syntax_overrides_keep_order() {
+        let config: Config =
+            serde_yaml::from_str("syntax_overrides:\n  '*.vue': html\n  Justfile: makefile\n")
+                .unwrap();
+        let syntaxes: Vec<_> = config
+            .syntax_overrides
+            .iter()
+            .map(|entry| entry.syntax.as_str())
+            .collect();
+        assert_eq!(syntaxes, vec!["html", "makefile"]);
+        assert!(serde_yaml::from_str::<Config>("syntax_overrides:\n  '[': html\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_field_is_reported_with_its_line() {
+        let (config, problems) = parse_yaml("theme: base16-ocean.dark\nopen_comand: vim\n");
+        assert_eq!(config.theme, default_theme());
+        assert_eq!(
+            problems[0],
+            "line 2: unknown field `open_comand`, did you mean `open_command`?\n    open_comand: vim"
+        );
+        assert_eq!(problems.len(), 2);
+
+        let (_, problems) = parse_yaml("saved_searches:\n  - name: a\n    qurey: b\n");
+        assert!(problems[0].starts_with(
+            "line 3: saved_searches[0]: unknown field `qurey`, did you mean `query`?"
+        ));
+    }
+
+    #[test]
+    fn test_validation() {
+        let (config, problems) = parse_yaml("recent_commits: 3\nworkspaces:\n  - /no/such/dir\n");
+        assert_eq!(config.recent_commits, 3);
+        assert_eq!(
+            problems,
+            vec!["line 3: workspace '/no/such/dir' isn't a directory\n      - /no/such/dir"]
+        );
+        assert!(parse_yaml("").1.is_empty());
+        assert!(parse_yaml("max_matches_per_file: 5\n").1.is_empty());
+    }
+
+    #[test]
+    fn test_saved_search_sort() {
+        let (config, problems) =
+            parse_yaml("saved_searches:\n  - name: a\n  - name: b\n    sort: mtime\n");
+        assert!(problems.is_empty());
+        assert_eq!(config.saved_searches[0].sort, None);
+        assert_eq!(config.saved_searches[1].sort, Some(Sort::Mtime));
+    }
+
+    #[test]
+    fn test_toml_and_json() {
+        let (config, problems) = Config::parse(
+            "open_command = [\"code\", \"--goto\"]\n\n[syntax_overrides]\n\"*.vue\" = \"html\"\nJustfile = \"makefile\"\n",
+            Format::Toml,
+        );
+        assert!(problems.is_empty());
+        assert_eq!(config.open_command, vec!["code", "--goto"]);
+        let syntaxes: Vec<_> = config
+            .syntax_overrides
+            .iter()
+            .map(|entry| entry.syntax.as_str())
+            .collect();
+        assert_eq!(syntaxes, vec!["html", "makefile"]);
+
+        let (config, problems) = Config::parse(
+            "{\"recent_commits\": 2, \"line_numbers\": \"off\"}",
+            Format::Json,
+        );
+        assert!(problems.is_empty());
+        assert_eq!(config.recent_commits, 2);
+
+        let (_, problems) = Config::parse("theme = \"x\"\nthem = \"y\"\n", Format::Toml);
+        assert_eq!(
+            problems[0],
+            "line 2: unknown field `them`, did you mean `theme`?\n    them = \"y\""
+        );
+        let (_, problems) = Config::parse("{\n  \"open_comand\": \"vim\"\n}", Format::Json);
+        assert!(problems[0].starts_with("line 2: unknown field `open_comand`"));
+        assert_eq!(Format::of(Path::new("config.toml")), Format::Toml);
+    }
+
+    #[test]
+    fn test_performance() {
+        let (config, problems) = parse_yaml("search_delay_ms: 150\n");
+        assert!(problems.is_empty());
+        assert_eq!(config.search_delay(), Duration::from_millis(150));
+
+        let (config, problems) = parse_yaml(
+            "search_delay_ms: 150\nperformance:\n  debounce_ms: 0\n  search_threads: 2\n",
+        );
+        assert!(problems.is_empty());
+        assert_eq!(config.search_delay(), Duration::ZERO);
+        assert_eq!(config.performance.threads(), 2);
+        assert!(Config::default().performance.threads() >= 1);
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        let (config, problems) = parse_yaml(
+            "key_bindings:\n  - key: f5\n    command: git add {}\n    silent: true\n    refresh: true\n",
+        );
+        assert!(problems.is_empty());
+        let binding = &config.key_bindings[0];
+        assert_eq!(binding.key, KeySpec::parse("f5").unwrap());
+        assert!(binding.silent && binding.refresh);
+
+        let (_, problems) = parse_yaml("key_bindings:\n  - key: hyper+x\n    command: ls\n");
+        assert!(problems[0].contains("unknown key 'hyper+x'"));
+
+        let (config, problems) = parse_yaml("quit_keys: [alt+x, f10]\n");
+        assert!(problems.is_empty());
+        assert_eq!(
+            config.quit_keys,
+            vec![
+                KeySpec::parse("alt+x").unwrap(),
+                KeySpec::parse("f10").unwrap()
+            ]
+        );
+        assert_eq!(
+            Config::default().quit_keys,
+            vec![KeySpec::parse("esc").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("open_comand", "open_command"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("theme", "theme"), 0);
+    }
+
+    #[test]
+    fn test_invalid_open_command() {
+        assert!(open_command("open_command: 'vim \"unterminated'").is_err());
+        assert!(open_command("open_command: []").is_err());
+    }
 }
@@ -0,0 +1,177 @@
+use crate::{FileFilter, SearchMode};
+use anyhow::{bail, Context, Result};
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// One line sent over the control socket
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    // `query <text>` replaces the query
+    Query(String),
+    // `mode contents` or `mode filename`
+    Mode(SearchMode),
+    // `filter <all|dirty|changed_from_default|recent|modified>`
+    Filter(FileFilter),
+    // `selection` replies with the selected and marked files
+    Selection,
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "query" => Ok(Request::Query(argument.to_string())),
+            "mode" => named(argument).map(Request::Mode),
+            "filter" => named(argument).map(Request::Filter),
+            "selection" => Ok(Request::Selection),
+            _ => Err(format!("unknown command '{}'", command)),
+        }
+    }
+}
+
+// A mode or filter by the name the config uses for it
+fn named<'de, T: Deserialize<'de>>(name: &'de str) -> Result<T, String> {
+    T::deserialize(IntoDeserializer::<value::Error>::into_deserializer(name))
+        .map_err(|err| err.to_string())
+}
+
+// Binds a socket, replacing one left behind by a glancr that didn't exit cleanly but not
+// one that's still in use
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("{} is already in use", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Unable to replace {}", path.display()))?;
+    }
+    UnixListener::bind(path).with_context(|| format!("Unable to listen on {}", path.display()))
+}
+
+// A request waiting for the main loop, with where its one-line JSON reply goes
+pub type Pending = (Request, Sender<String>);
+
+// Listens on a Unix socket, handing requests to the main loop between key presses
+pub struct ControlServer {
+    path: PathBuf,
+    requests: Receiver<Pending>,
+}
+
+impl ControlServer {
+    pub fn listen(path: &Path) -> Result<Self> {
+        let listener = bind(path)?;
+        let (sender, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                std::thread::spawn(move || serve(stream, sender));
+            }
+        });
+        Ok(ControlServer {
+            path: path.to_path_buf(),
+            requests,
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<Pending> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(stream: UnixStream, sender: Sender<Pending>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let reply = match Request::parse(&line) {
+            Ok(request) => {
+                let (reply_sender, reply) = mpsc::channel();
+                if sender.send((request, reply_sender)).is_err() {
+                    return;
+                }
+                match reply.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return,
+                }
+            }
+            Err(err) => serde_json::json!({ "error": err }).to_string(),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Request::parse("query fn main\n"),
+            Ok(Request::Query("fn main".to_string()))
+        );
+        assert_eq!(
+            Request::parse("mode filename"),
+            Ok(Request::Mode(SearchMode::Filename))
+        );
+        assert_eq!(
+            Request::parse("filter changed_from_default"),
+            Ok(Request::Filter(FileFilter::ChangedFromDefault))
+        );
+        assert!(Request::parse("filter nope").is_err());
+        assert!(Request::parse("explode").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("glancr.sock");
+        let server = ControlServer::listen(&path).unwrap();
+        assert!(ControlServer::listen(&path).is_err());
+
+        let client = std::thread::spawn({
+            let path = path.clone();
+            move || {
+                let mut stream = UnixStream::connect(&path).unwrap();
+                stream.write_all(b"bogus\nselection\n").unwrap();
+                let mut replies = BufReader::new(stream).lines();
+                let first = replies.next().unwrap().unwrap();
+                let second = replies.next().unwrap().unwrap();
+                (first, second)
+            }
+        });
+        let (request, reply) = loop {
+            if let Some(pending) = server.try_recv() {
+                break pending;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(request, Request::Selection);
+        reply.send("{\"selected\":null}".to_string()).unwrap();
+
+        let (first, second) = client.join().unwrap();
+        assert_eq!(first, "{\"error\":\"unknown command 'bogus'\"}");
+        assert_eq!(second, "{\"selected\":null}");
+
+        drop(server);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::control;
+use crate::rgconfig::SearchDefaults;
+use crate::walk;
+use crate::walkstats::WalkStats;
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsStr;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+// A walk is redone at least this often, and after it's handed out
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// but not more often than this, since every keystroke asks for it
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Where the daemon for a directory listens, keyed by its canonical path
+fn socket_path(root: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    let dir = dirs::runtime_dir().or_else(dirs::cache_dir)?.join("glancr");
+    Some(dir.join(format!("daemon-{:016x}.sock", hasher.finish())))
+}
+
+// The last walk of a served root
+struct Served {
+    files: Vec<PathBuf>,
+    stats: WalkStats,
+    walked_at: Instant,
+}
+
+impl Served {
+    fn walk(root: &Path, defaults: &SearchDefaults) -> Self {
+        let (files, stats) = walk(&[root], defaults);
+        Self {
+            files,
+            stats,
+            walked_at: Instant::now(),
+        }
+    }
+}
+
+// Keeps a walk of each root in memory, serving it to glancr sessions until killed
+pub fn serve(roots: &[PathBuf], defaults: &SearchDefaults) -> Result<()> {
+    let mut served = Vec::new();
+    for root in roots {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Unable to serve {}", root.display()))?;
+        let socket = socket_path(&root).context("No runtime or cache directory for sockets")?;
+        if let Some(dir) = socket.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        served.push((control::bind(&socket)?, root, socket));
+    }
+
+    let key = defaults.walk_key();
+    let served: Vec<_> = served
+        .into_iter()
+        .map(|(listener, root, socket)| {
+            let walked = Served::walk(&root, defaults);
+            println!(
+                "Serving {} files under {} on {}",
+                walked.files.len(),
+                root.display(),
+                socket.display()
+            );
+            (listener, root, RwLock::new(walked))
+        })
+        .collect();
+
+    std::thread::scope(|scope| {
+        for (listener, root, served) in &served {
+            let (refresh, refresh_requests) = mpsc::channel::<()>();
+
+            scope.spawn(move || loop {
+                if let Err(RecvTimeoutError::Disconnected) =
+                    refresh_requests.recv_timeout(REFRESH_INTERVAL)
+                {
+                    return;
+                }
+                refresh_requests.try_iter().for_each(drop);
+                let walked_at = served.read().map(|served| served.walked_at);
+                if walked_at.is_ok_and(|at| at.elapsed() < MIN_REFRESH_INTERVAL) {
+                    continue;
+                }
+                let walked = Served::walk(root, defaults);
+                if let Ok(mut served) = served.write() {
+                    *served = walked;
+                }
+            });
+            scope.spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let _ = answer(stream, key, root, served);
+                    let _ = refresh.send(());
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+// Requests are `files <walk key> <dir>\n`. A daemon walking with those settings answers with
+// NUL-terminated records tagged by their first byte: `+` to say it's serving, then each file
+// below the dir (`f`) followed by the paths left out for linking to it (`l`). The walk's
+// errors (`d` the count denied permission, `e` the others and `m` each message) only go to
+// sessions asking for the whole root, since they can't be told apart by directory.
+// A daemon walking otherwise hangs up without a word.
+fn answer(stream: UnixStream, key: u64, root: &Path, served: &RwLock<Served>) -> io::Result<()> {
+    let mut request = Vec::new();
+    BufReader::new(&stream).read_until(b'\n', &mut request)?;
+    let request = request.strip_suffix(b"\n").unwrap_or(&request);
+    let request = request.strip_prefix(b"files ").unwrap_or_default();
+    let Some(dir) = request.strip_prefix(format!("{:016x} ", key).as_bytes()) else {
+        return Ok(());
+    };
+    let dir = Path::new(OsStr::from_bytes(dir));
+
+    let mut writer = BufWriter::new(&stream);
+    let served = served.read().map_err(|_| io::ErrorKind::Other)?;
+    record(&mut writer, b'+', b"")?;
+    for path in served.files.iter().filter(|path| path.starts_with(dir)) {
+        record(&mut writer, b'f', path.as_os_str().as_bytes())?;
+        for other in served.stats.linked.get(path).into_iter().flatten() {
+            record(&mut writer, b'l', other.as_os_str().as_bytes())?;
+        }
+    }
+    if dir == root {
+        let stats = &served.stats;
+        record(
+            &mut writer,
+            b'd',
+            stats.permission_denied.to_string().as_bytes(),
+        )?;
+        record(&mut writer, b'e', stats.errors.to_string().as_bytes())?;
+        for message in &stats.error_messages {
+            record(&mut writer, b'm', message.as_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+fn record(writer: &mut impl Write, tag: u8, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&[tag])?;
+    writer.write_all(bytes)?;
+    writer.write_all(b"\0")
+}
+
+// Files under `root` from a daemon serving it or a directory above it, starting with
+// `root` like a walk of it would. None when there's no daemon to ask, or it walks with other
+// settings than `defaults`.
+pub fn files(root: &Path, defaults: &SearchDefaults) -> Option<(Vec<PathBuf>, WalkStats)> {
+    let canonical = root.canonicalize().ok()?;
+    let stream = canonical
+        .ancestors()
+        .find_map(|dir| UnixStream::connect(socket_path(dir)?).ok())?;
+    fetch(stream, defaults.walk_key(), root, &canonical)
+}
+
+fn fetch(
+    mut stream: UnixStream,
+    key: u64,
+    root: &Path,
+    canonical: &Path,
+) -> Option<(Vec<PathBuf>, WalkStats)> {
+    let mut request = format!("files {:016x} ", key).into_bytes();
+    request.extend_from_slice(canonical.as_os_str().as_bytes());
+    request.push(b'\n');
+    stream.write_all(&request).ok()?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).ok()?;
+    let mut records = reply
+        .split(|&byte| byte == 0)
+        .filter(|record| !record.is_empty());
+    if records.next()? != b"+" {
+        return None;
+    }
+    // Where the session would have found it, or as served when that's outside the root
+    let local = |path: &[u8]| {
+        let path = Path::new(OsStr::from_bytes(path));
+        path.strip_prefix(canonical)
+            .map_or_else(|_| path.to_path_buf(), |relative| root.join(relative))
+    };
+    let count = |value: &[u8]| std::str::from_utf8(value).ok()?.parse().ok();
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut stats = WalkStats::default();
+    for record in records {
+        let (tag, value) = record.split_first()?;
+        match tag {
+            b'f' => files.push(local(value)),
+            b'l' => stats
+                .linked
+                .entry(files.last()?.clone())
+                .or_default()
+                .push(local(value)),
+            b'd' => stats.permission_denied = count(value)?,
+            b'e' => stats.errors = count(value)?,
+            b'm' => stats
+                .error_messages
+                .push(String::from_utf8_lossy(value).into_owned()),
+            _ => return None,
+        }
+    }
+    stats.walked = files.len() + stats.linked_copies();
+    Some((files, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_serves_files_below_the_requested_dir() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let served = RwLock::new(Served {
+            files: vec![
+                root.join("a.rs"),
+                root.join("src/b.rs"),
+                root.join("src/c/d.rs"),
+            ],
+            stats: WalkStats {
+                linked: [(root.join("src/b.rs"), vec![root.join("src/c/b.rs")])].into(),
+                permission_denied: 2,
+                error_messages: vec!["denied".to_string()],
+                ..WalkStats::default()
+            },
+            walked_at: Instant::now(),
+        });
+        let socket = root.join("daemon.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let key = SearchDefaults::default().walk_key();
+        let ask = |key, local: &str, dir: &Path| {
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    let (stream, _) = listener.accept().unwrap();
+                    answer(stream, SearchDefaults::default().walk_key(), &root, &served).unwrap();
+                });
+                let stream = UnixStream::connect(&socket).unwrap();
+                fetch(stream, key, Path::new(local), dir)
+            })
+        };
+
+        let (files, stats) = ask(key, "./src", &root.join("src")).unwrap();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("./src/b.rs"), PathBuf::from("./src/c/d.rs")]
+        );
+        assert_eq!(stats.walked, 3);
+        assert_eq!(
+            stats.linked[Path::new("./src/b.rs")],
+            vec![PathBuf::from("./src/c/b.rs")]
+        );
+        // Errors can't be told apart by directory, so only the whole root gets them
+        assert_eq!(stats.permission_denied, 0);
+        let (files, stats) = ask(key, ".", &root).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(stats.permission_denied, 2);
+        assert_eq!(stats.error_messages, vec!["denied"]);
+
+        // Hidden files would be missing from this walk
+        let without_hidden = SearchDefaults {
+            hidden: false,
+            ..SearchDefaults::default()
+        };
+        assert_eq!(ask(without_hidden.walk_key(), ".", &root), None);
+    }
+}
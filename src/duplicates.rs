@@ -0,0 +1,160 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Files with identical contents, each group in walk order and the groups wasting the most
+// space first. Only files sharing a size with another are read, and empty files are left out
+// since they're all the same.
+pub fn groups(files: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for path in files {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.is_file() && metadata.len() > 0 {
+                by_size.entry(metadata.len()).or_default().push(path);
+            }
+        }
+    }
+
+    let mut groups: Vec<(u64, Vec<PathBuf>)> = Vec::new();
+    for (size, candidates) in by_size {
+        if candidates.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+        for path in candidates {
+            if let Some(hash) = hash_contents(path) {
+                by_hash.entry(hash).or_default().push(path);
+            }
+        }
+        groups.extend(
+            by_hash
+                .into_values()
+                .filter(|group| group.len() > 1)
+                .flat_map(identical)
+                .filter(|group| group.len() > 1)
+                .map(|group| (size, group)),
+        );
+    }
+
+    let order: HashMap<&PathBuf, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path, i))
+        .collect();
+    for (_, group) in &mut groups {
+        group.sort_by_key(|path| order[path]);
+    }
+    groups.sort_by_key(|(size, group)| {
+        (
+            std::cmp::Reverse(size * (group.len() as u64 - 1)),
+            order[&group[0]],
+        )
+    });
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+// Splits files with the same hash into those whose bytes really are the same, since a 64-bit
+// hash alone could put a file up for deletion that differs from the others
+fn identical(files: Vec<&PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    for path in files {
+        match groups
+            .iter_mut()
+            .find(|group| same_contents(&group[0], path).unwrap_or(false))
+        {
+            Some(group) => group.push(path.clone()),
+            None => groups.push(vec![path.clone()]),
+        }
+    }
+    groups
+}
+
+fn same_contents(a: &Path, b: &Path) -> Option<bool> {
+    let (mut a, mut b) = (File::open(a).ok()?, File::open(b).ok()?);
+    let (mut left, mut right) = ([0; 64 * 1024], [0; 64 * 1024]);
+    loop {
+        let n = read_full(&mut a, &mut left)?;
+        if n != read_full(&mut b, &mut right)? || left[..n] != right[..n] {
+            return Some(false);
+        }
+        if n == 0 {
+            return Some(true);
+        }
+    }
+}
+
+// Fills the buffer unless the file ends first, so two files are compared at the same offsets
+fn read_full(file: &mut File, buffer: &mut [u8]) -> Option<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]).ok()? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Some(filled)
+}
+
+fn hash_contents(path: &Path) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer).ok()?;
+        if n == 0 {
+            return Some(hasher.finish());
+        }
+        hasher.write(&buffer[..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_groups_identical_files() {
+        let dir = TempDir::new().unwrap();
+        let files: Vec<PathBuf> = [
+            ("a.json", "{\"fixture\": 1}"),
+            ("b.txt", "same size, other"),
+            ("c.json", "{\"fixture\": 1}"),
+            ("d.txt", "same size, words"),
+            ("big1", "a much longer duplicated file"),
+            ("big2", "a much longer duplicated file"),
+            ("empty1", ""),
+            ("empty2", ""),
+        ]
+        .iter()
+        .map(|(name, contents)| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        })
+        .collect();
+
+        let groups = groups(&files);
+        assert_eq!(
+            groups,
+            vec![
+                vec![files[4].clone(), files[5].clone()],
+                vec![files[0].clone(), files[2].clone()],
+            ]
+        );
+
+        // Files that only share a hash are kept apart
+        let bucket = vec![&files[1], &files[0], &files[3], &files[2]];
+        assert_eq!(
+            identical(bucket),
+            vec![
+                vec![files[1].clone()],
+                vec![files[0].clone(), files[2].clone()],
+                vec![files[3].clone()],
+            ]
+        );
+    }
+}
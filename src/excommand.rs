@@ -0,0 +1,176 @@
+use crate::{FileFilter, SearchMode};
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+// A line typed after `:` at the start of the search box, for actions without a key of their own
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExCommand {
+    // `:42` selects the 42nd result
+    Jump(usize),
+    // `:q` or `:quit`
+    Quit,
+    // `:reload` walks the tree and searches it again
+    Reload,
+    // `:sort <walk|mtime|recent|tree>`
+    Sort(Sort),
+    // `:mode filename` or `:mode contents`
+    Mode(SearchMode),
+    // `:filter <all|dirty|changed_from_default|recent|modified|duplicates>`
+    Filter(FileFilter),
+    // `:w <file>` or `:write <file>` saves the result list
+    Write(PathBuf),
+    // `:ignore <pattern>` adds a line to the project's .glancrignore
+    Ignore(String),
+    // `:help`
+    Help,
+}
+
+// The orders the results can be shown in, each of which comes with its own filter or view
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    Walk,
+    Mtime,
+    Recent,
+    Tree,
+}
+
+// Every command as it's written, with what it does, for the hints under the search box
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("<n>", "select result n"),
+    ("q", "quit"),
+    ("reload", "walk and search again"),
+    ("sort walk|mtime|recent|tree", "order the results"),
+    ("mode filename|contents", "switch search mode"),
+    (
+        "filter all|dirty|changed_from_default|recent|modified|duplicates",
+        "show only some files",
+    ),
+    ("write <file>", "save the result list"),
+    ("ignore <pattern>", "hide matching paths in this project"),
+    ("help", "list the keys"),
+];
+
+impl ExCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        if let Ok(number) = command.parse::<usize>() {
+            return match number {
+                0 => Err("Results are numbered from 1".to_string()),
+                _ => Ok(ExCommand::Jump(number)),
+            };
+        }
+        match command {
+            "q" | "quit" => Ok(ExCommand::Quit),
+            "reload" => Ok(ExCommand::Reload),
+            "sort" => match argument {
+                "walk" => Ok(ExCommand::Sort(Sort::Walk)),
+                "mtime" => Ok(ExCommand::Sort(Sort::Mtime)),
+                "recent" => Ok(ExCommand::Sort(Sort::Recent)),
+                "tree" => Ok(ExCommand::Sort(Sort::Tree)),
+                _ => Err(format!(
+                    "Unknown sort '{}', expected walk, mtime, recent or tree",
+                    argument
+                )),
+            },
+            "mode" => named(argument).map(ExCommand::Mode),
+            "filter" => named(argument).map(ExCommand::Filter),
+            "w" | "write" => match argument {
+                "" => Err("Write the results where? :write <file>".to_string()),
+                _ => Ok(ExCommand::Write(PathBuf::from(argument))),
+            },
+            "ignore" => match argument {
+                "" => Err("Ignore what? :ignore <pattern>".to_string()),
+                _ => Ok(ExCommand::Ignore(argument.to_string())),
+            },
+            "help" => Ok(ExCommand::Help),
+            "" => Err("Type a command, or Esc".to_string()),
+            _ => Err(format!("Unknown command ':{}'", command)),
+        }
+    }
+}
+
+// The commands that could be what's been typed so far, with what they do
+pub fn hints(line: &str) -> Vec<(&'static str, &'static str)> {
+    let typed = line.trim_start();
+    let word = match typed.split(' ').next().unwrap_or("") {
+        "w" => "write",
+        word => word,
+    };
+    if !word.is_empty() && word.bytes().all(|byte| byte.is_ascii_digit()) {
+        return vec![COMMANDS[0]];
+    }
+    COMMANDS
+        .iter()
+        .copied()
+        .filter(|(usage, _)| {
+            let name = usage.split(' ').next().unwrap_or(usage);
+            if word.is_empty() {
+                true
+            } else if typed.contains(' ') {
+                name == word
+            } else {
+                name.starts_with(word) || (word.starts_with('q') && *usage == "q")
+            }
+        })
+        .collect()
+}
+
+// A mode or filter by the name the config uses for it
+fn named<'de, T: Deserialize<'de>>(name: &'de str) -> Result<T, String> {
+    T::deserialize(IntoDeserializer::<value::Error>::into_deserializer(name))
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_hints() {
+        assert_eq!(ExCommand::parse("42"), Ok(ExCommand::Jump(42)));
+        assert!(ExCommand::parse("0").is_err());
+        assert_eq!(ExCommand::parse("q"), Ok(ExCommand::Quit));
+        assert_eq!(
+            ExCommand::parse("sort mtime "),
+            Ok(ExCommand::Sort(Sort::Mtime))
+        );
+        assert_eq!(
+            ExCommand::parse("mode contents"),
+            Ok(ExCommand::Mode(SearchMode::Contents))
+        );
+        assert_eq!(
+            ExCommand::parse("filter dirty"),
+            Ok(ExCommand::Filter(FileFilter::Dirty))
+        );
+        assert_eq!(
+            ExCommand::parse("w  picked files.txt"),
+            Ok(ExCommand::Write(PathBuf::from("picked files.txt")))
+        );
+        assert!(ExCommand::parse("write").is_err());
+        assert_eq!(
+            ExCommand::parse("ignore /target/"),
+            Ok(ExCommand::Ignore("/target/".to_string()))
+        );
+        assert!(ExCommand::parse("ignore ").is_err());
+        assert!(ExCommand::parse("sort sideways").is_err());
+        assert!(ExCommand::parse("explode").is_err());
+
+        assert_eq!(hints("").len(), COMMANDS.len());
+        let usages = |line| {
+            hints(line)
+                .into_iter()
+                .map(|(usage, _)| usage)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(usages("12"), vec!["<n>"]);
+        assert_eq!(usages("re"), vec!["reload"]);
+        assert_eq!(usages("quit"), vec!["q"]);
+        assert_eq!(usages("sort m"), vec!["sort walk|mtime|recent|tree"]);
+        assert_eq!(usages("w"), vec!["write <file>"]);
+        assert_eq!(usages("w out.txt"), vec!["write <file>"]);
+    }
+}
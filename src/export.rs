@@ -0,0 +1,98 @@
+use crate::search::FileMatches;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+// How Alt+w and `:write` put down the result list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultsFormat {
+    // One path per line, for xargs and friends
+    #[default]
+    Paths,
+    Quickfix,
+}
+
+pub fn results(
+    format: ResultsFormat,
+    files: &[PathBuf],
+    matches: &HashMap<PathBuf, FileMatches>,
+) -> String {
+    match format {
+        ResultsFormat::Paths => files
+            .iter()
+            .map(|path| format!("{}\n", path.to_string_lossy()))
+            .collect(),
+        ResultsFormat::Quickfix => quickfix(files, matches),
+    }
+}
+
+// Results as `path:line:col:text`, the format of `grep -n --column` and Vim's quickfix list.
+// Files found by name have no matching lines, so they point at the top of the file.
+pub fn quickfix(files: &[PathBuf], matches: &HashMap<PathBuf, FileMatches>) -> String {
+    let mut output = String::new();
+    for path in files {
+        let path_str = path.to_string_lossy();
+        match matches.get(path) {
+            Some(file_matches) if !file_matches.lines.is_empty() => {
+                for line in &file_matches.lines {
+                    let _ = writeln!(
+                        output,
+                        "{}:{}:{}:{}",
+                        path_str, line.line_number, line.column, line.text
+                    );
+                }
+            }
+            _ => {
+                let _ = writeln!(output, "{}:1:1:", path_str);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::LineMatch;
+
+    #[test]
+    fn test_quickfix() {
+        let files = vec![PathBuf::from("src/main.rs"), PathBuf::from("README.md")];
+        let mut matches = HashMap::new();
+        matches.insert(
+            PathBuf::from("src/main.rs"),
+            FileMatches {
+                lines: vec![
+                    LineMatch {
+                        line_number: 3,
+                        column: 4,
+                        text: "fn main() {".to_string(),
+                        captures: Vec::new(),
+                    },
+                    LineMatch {
+                        line_number: 10,
+                        column: 1,
+                        text: "main();".to_string(),
+                        captures: Vec::new(),
+                    },
+                ],
+                truncated: false,
+            },
+        );
+
+        assert_eq!(
+            quickfix(&files, &matches),
+            "src/main.rs:3:4:fn main() {\nsrc/main.rs:10:1:main();\nREADME.md:1:1:\n"
+        );
+        assert_eq!(
+            results(ResultsFormat::Paths, &files, &matches),
+            "src/main.rs\nREADME.md\n"
+        );
+        assert_eq!(
+            results(ResultsFormat::Quickfix, &files, &matches),
+            quickfix(&files, &matches)
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Renames or moves a file, through `git mv` when it's tracked so history follows it
+pub fn rename(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        return Err(anyhow!("{} already exists", to.display()));
+    }
+    if let Some(parent) = to.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create {}", parent.display()))?;
+    }
+
+    if is_tracked(from) {
+        let output = git_in(from)
+            .args(["mv", "--"])
+            .arg(absolute(from)?)
+            .arg(absolute(to)?)
+            .output()
+            .context("Unable to run git mv")?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("git mv failed: {}", error.trim()));
+        }
+        return Ok(());
+    }
+
+    std::fs::rename(from, to)
+        .with_context(|| format!("Unable to move {} to {}", from.display(), to.display()))
+}
+
+// Sends a file to the system trash unless deleting permanently
+pub fn delete(path: &Path, permanent: bool) -> Result<()> {
+    if permanent {
+        std::fs::remove_file(path).with_context(|| format!("Unable to delete {}", path.display()))
+    } else {
+        trash::delete(path).with_context(|| format!("Unable to trash {}", path.display()))
+    }
+}
+
+// Runs git from the file's directory, so files in any of the roots' repositories work
+fn git_in(path: &Path) -> Command {
+    let mut command = Command::new("git");
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        command.arg("-C").arg(parent);
+    }
+    command
+}
+
+fn is_tracked(path: &Path) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    git_in(path)
+        .args(["ls-files", "--error-unmatch", "--"])
+        .arg(name)
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_rename_untracked_file_into_new_directory() {
+        let dir = TempDir::new().unwrap();
+        let from = dir.path().join("old.txt");
+        let to = dir.path().join("nested/new.txt");
+        std::fs::write(&from, "contents").unwrap();
+
+        rename(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "contents");
+    }
+
+    #[test]
+    fn test_rename_refuses_to_overwrite() {
+        let dir = TempDir::new().unwrap();
+        let from = dir.path().join("a.txt");
+        let to = dir.path().join("b.txt");
+        std::fs::write(&from, "a").unwrap();
+        std::fs::write(&to, "b").unwrap();
+
+        assert!(rename(&from, &to).is_err());
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "b");
+    }
+
+    #[test]
+    fn test_permanent_delete() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "contents").unwrap();
+
+        delete(&path, true).unwrap();
+        assert!(!path.exists());
+        assert!(delete(&path, true).is_err());
+    }
+
+    #[test]
+    fn test_rename_tracked_file_uses_git_mv() {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        let from = dir.path().join("old.txt");
+        std::fs::write(&from, "contents").unwrap();
+        git(dir.path(), &["add", "old.txt"]);
+
+        rename(&from, &dir.path().join("new.txt")).unwrap();
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args(["diff", "--cached", "--name-status", "-M"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "A\tnew.txt\n");
+    }
+}
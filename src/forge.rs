@@ -0,0 +1,174 @@
+use crate::git;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// The page showing a file at a commit on the forge hosting the remote, with the line
+// highlighted when there is one
+pub fn file_url(remote: &str, commit: &str, path: &str, line: Option<u64>) -> Option<String> {
+    let (host, repo) = parse_remote(remote)?;
+    let path = encode_path(path);
+    let (blob, anchor) = if host.contains("gitlab") {
+        (format!("-/blob/{}/{}", commit, path), "L")
+    } else if host == "bitbucket.org" {
+        (format!("src/{}/{}", commit, path), "lines-")
+    } else if host.contains("gitea") || host == "codeberg.org" {
+        (format!("src/commit/{}/{}", commit, path), "L")
+    } else {
+        // GitHub, and the layout most self-hosted forges copy
+        (format!("blob/{}/{}", commit, path), "L")
+    };
+    let anchor = line
+        .map(|line| format!("#{}{}", anchor, line))
+        .unwrap_or_default();
+    Some(format!("https://{}/{}/{}{}", host, repo, blob, anchor))
+}
+
+// The host and repository path of `git@host:owner/repo.git`, `ssh://git@host:22/owner/repo`
+// or `https://user@host/owner/repo.git`
+fn parse_remote(remote: &str) -> Option<(String, String)> {
+    let remote = remote.trim();
+    let (host, path) = match remote.split_once("://") {
+        Some((scheme, rest)) => {
+            let (host, path) = rest.split_once('/')?;
+            let host = host.rsplit('@').next()?;
+            // An ssh port isn't the web server's
+            let host = match scheme {
+                "http" | "https" => host,
+                _ => host.split(':').next()?,
+            };
+            (host, path)
+        }
+        None => {
+            let (host, path) = remote.split_once(':')?;
+            (host.rsplit('@').next()?, path)
+        }
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    (!host.is_empty() && !path.is_empty()).then(|| (host.to_string(), path.to_string()))
+}
+
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// The URL for a file in the repository it's in, at HEAD
+pub fn url_for(path: &Path, line: Option<u64>) -> Result<String> {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let (top, commit) = git::head(dir).ok_or_else(|| anyhow!("Not in a git repository"))?;
+    let remote = git::remote_url(dir).ok_or_else(|| anyhow!("The repository has no remote"))?;
+    let absolute = path
+        .canonicalize()
+        .with_context(|| format!("Unable to find {}", path.display()))?;
+    let top = top.canonicalize().unwrap_or(top);
+    let relative = absolute
+        .strip_prefix(&top)
+        .map_err(|_| anyhow!("{} isn't in the repository", path.display()))?;
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    file_url(&remote, &commit, &relative, line)
+        .ok_or_else(|| anyhow!("Unable to make a web URL of the remote '{}'", remote))
+}
+
+// Opens the URL with the desktop's default browser, without waiting for it
+pub fn open_in_browser(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/c", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Unable to start a browser")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote() {
+        let parsed = |remote| parse_remote(remote).unwrap();
+        let expected = ("github.com".to_string(), "ignu/glancr-rs".to_string());
+        assert_eq!(parsed("git@github.com:ignu/glancr-rs.git"), expected);
+        assert_eq!(parsed("https://github.com/ignu/glancr-rs"), expected);
+        assert_eq!(
+            parsed("https://token@github.com/ignu/glancr-rs.git\n"),
+            expected
+        );
+        assert_eq!(
+            parsed("ssh://git@gitlab.example.com:2222/group/sub/project.git"),
+            (
+                "gitlab.example.com".to_string(),
+                "group/sub/project".to_string()
+            )
+        );
+        assert_eq!(parse_remote("/srv/git/project.git"), None);
+    }
+
+    #[test]
+    fn test_url_for_a_file_in_another_repository() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        git(&["remote", "add", "origin", "git@github.com:o/r.git"]);
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/a.rs"), "").unwrap();
+        git(&["add", "src/a.rs"]);
+        git(&["commit", "-qm", "Add a"]);
+
+        let url = url_for(&dir.path().join("src/a.rs"), Some(2)).unwrap();
+        assert!(url.starts_with("https://github.com/o/r/blob/"));
+        assert!(url.ends_with("/src/a.rs#L2"));
+    }
+
+    #[test]
+    fn test_file_url() {
+        let commit = "4f2a9c1";
+        assert_eq!(
+            file_url("git@github.com:o/r.git", commit, "src/main.rs", Some(12)).unwrap(),
+            "https://github.com/o/r/blob/4f2a9c1/src/main.rs#L12"
+        );
+        assert_eq!(
+            file_url("https://gitlab.com/o/r.git", commit, "docs/a b.md", None).unwrap(),
+            "https://gitlab.com/o/r/-/blob/4f2a9c1/docs/a%20b.md"
+        );
+        assert_eq!(
+            file_url("git@bitbucket.org:o/r.git", commit, "x.rs", Some(3)).unwrap(),
+            "https://bitbucket.org/o/r/src/4f2a9c1/x.rs#lines-3"
+        );
+        assert_eq!(
+            file_url("https://codeberg.org/o/r", commit, "x.rs", Some(3)).unwrap(),
+            "https://codeberg.org/o/r/src/commit/4f2a9c1/x.rs#L3"
+        );
+    }
+}
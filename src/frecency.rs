@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Opened files are forgotten past this many, least used first
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Entry {
+    count: u64,
+    // Seconds since the epoch
+    last_used: u64,
+}
+
+// How often and how recently files were opened, kept across sessions by absolute path
+#[derive(Debug, Default)]
+pub struct Frecency {
+    path: Option<PathBuf>,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+impl Frecency {
+    pub fn load() -> Self {
+        let Some(path) = dirs::data_dir().map(|dir| dir.join("glancr").join("frecency")) else {
+            return Self::default();
+        };
+        let entries = std::fs::read_to_string(&path)
+            .map(|contents| parse(&contents))
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    // Best effort, a failed save only loses this session's history
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = std::fs::write(path, self.serialize());
+    }
+
+    pub fn record(&mut self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        let entry = self.entries.entry(path).or_insert(Entry {
+            count: 0,
+            last_used: 0,
+        });
+        entry.count += 1;
+        entry.last_used = now();
+
+        if self.entries.len() > MAX_ENTRIES {
+            let now = now();
+            let weakest = self
+                .entries
+                .iter()
+                .min_by(|(_, a), (_, b)| score(a, now).total_cmp(&score(b, now)))
+                .map(|(path, _)| path.clone());
+            if let Some(weakest) = weakest {
+                self.entries.remove(&weakest);
+            }
+        }
+    }
+
+    // Absolute paths, most frecent first
+    pub fn ranked(&self) -> Vec<PathBuf> {
+        let now = now();
+        let mut ranked: Vec<_> = self.entries.iter().collect();
+        ranked.sort_by(|(_, a), (_, b)| score(b, now).total_cmp(&score(a, now)));
+        ranked.into_iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    fn serialize(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(path, entry)| {
+                format!("{}\t{}\t{}\n", entry.count, entry.last_used, path.display())
+            })
+            .collect()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// Open counts weighted by how long ago the file was last opened, like z and zoxide
+fn score(entry: &Entry, now: u64) -> f64 {
+    let age = now.saturating_sub(entry.last_used);
+    let weight = match age {
+        age if age < 60 * 60 => 4.0,
+        age if age < 60 * 60 * 24 => 2.0,
+        age if age < 60 * 60 * 24 * 7 => 1.0,
+        _ => 0.25,
+    };
+    entry.count as f64 * weight
+}
+
+// Lines of `count<TAB>last_used<TAB>path`
+fn parse(contents: &str) -> HashMap<PathBuf, Entry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next()?.parse().ok()?;
+            let last_used = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+            Some((path, Entry { count, last_used }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let contents = "3\t100\t/home/me/a.rs\ngarbage\n1\t200\t/home/me/my file.rs\n";
+        let frecency = Frecency {
+            path: None,
+            entries: parse(contents),
+        };
+        assert_eq!(frecency.entries.len(), 2);
+        assert_eq!(parse(&frecency.serialize()), frecency.entries);
+    }
+
+    #[test]
+    fn test_recent_use_outranks_old_use() {
+        let now = now();
+        let mut frecency = Frecency::default();
+        frecency.entries.insert(
+            PathBuf::from("/old"),
+            Entry {
+                count: 10,
+                last_used: now - 60 * 60 * 24 * 30,
+            },
+        );
+        frecency.entries.insert(
+            PathBuf::from("/new"),
+            Entry {
+                count: 1,
+                last_used: now,
+            },
+        );
+        assert_eq!(
+            frecency.ranked(),
+            vec![PathBuf::from("/new"), PathBuf::from("/old")]
+        );
+    }
+
+    #[test]
+    fn test_record_canonicalizes_and_counts() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "").unwrap();
+
+        let mut frecency = Frecency::default();
+        frecency.record(&path);
+        frecency.record(&dir.path().join(".").join("file.txt"));
+        let canonical = path.canonicalize().unwrap();
+        assert_eq!(frecency.entries[&canonical].count, 2);
+        assert_eq!(frecency.ranked(), vec![canonical]);
+    }
+}
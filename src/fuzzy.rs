@@ -0,0 +1,162 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::path::Path;
+
+// Matches on the file name count for more than matches spread across the directories
+const BASENAME_WEIGHT: i64 = 2;
+const BASENAME_BONUS: i64 = 100;
+// Per query character, when the whole query spells out the name's word starts
+const ACRONYM_BONUS: i64 = 30;
+
+#[derive(Default)]
+pub struct PathMatcher {
+    matcher: SkimMatcherV2,
+}
+
+impl PathMatcher {
+    pub fn score(&self, path: &Path, query: &str) -> Option<i64> {
+        let path_str = path.to_string_lossy();
+        let basename = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_else(|| path_str.clone());
+
+        // `ui/button` matches `button` against the file name and `ui` against its directories
+        if let Some((dir_query, name_query)) = query.rsplit_once('/') {
+            let dir_str = path
+                .parent()
+                .map(|dir| dir.to_string_lossy())
+                .unwrap_or_default();
+            let dir_score = if dir_query.is_empty() {
+                0
+            } else {
+                self.matcher.fuzzy_match(&dir_str, dir_query)?
+            };
+            let name_score = if name_query.is_empty() {
+                0
+            } else {
+                self.matcher.fuzzy_match(&basename, name_query)?
+                    + acronym_bonus(&basename, name_query)
+            };
+            return Some(name_score * BASENAME_WEIGHT + dir_score);
+        }
+
+        if let Some(score) = self.matcher.fuzzy_match(&basename, query) {
+            return Some(
+                (score + acronym_bonus(&basename, query)) * BASENAME_WEIGHT + BASENAME_BONUS,
+            );
+        }
+        self.matcher.fuzzy_match(&path_str, query)
+    }
+}
+
+// The first letter of each word in a name: after a separator, at a capital following a
+// lowercase letter, at the last capital of an acronym (`S` in `HTTPServer`) and at digits
+fn word_starts(name: &str) -> Vec<char> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut starts = Vec::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            continue;
+        }
+        let is_start = match i.checked_sub(1).map(|prev| chars[prev]) {
+            None => true,
+            Some(prev) if !prev.is_alphanumeric() => true,
+            Some(prev) if c.is_uppercase() && prev.is_lowercase() => true,
+            Some(prev) if c.is_ascii_digit() && !prev.is_ascii_digit() => true,
+            Some(prev) => {
+                c.is_uppercase()
+                    && prev.is_uppercase()
+                    && chars.get(i + 1).is_some_and(|next| next.is_lowercase())
+            }
+        };
+        if is_start {
+            starts.push(c);
+        }
+    }
+    starts
+}
+
+// Skim only slightly prefers word boundaries, so `fbq` would rank `fbqueue.ts` over
+// `FooBarQux.ts`; a query made entirely of word starts gets a boost
+fn acronym_bonus(name: &str, query: &str) -> i64 {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.len() < 2 {
+        return 0;
+    }
+    let mut remaining = query.iter().peekable();
+    for start in word_starts(name).into_iter().flat_map(char::to_lowercase) {
+        if remaining.peek() == Some(&&start) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return 0;
+    }
+    query.len() as i64 * ACRONYM_BONUS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basename_match_ranks_above_directory_match() {
+        let matcher = PathMatcher::default();
+        let component = matcher
+            .score(Path::new("./src/components/Button.tsx"), "button")
+            .unwrap();
+        let legacy = matcher
+            .score(Path::new("./src/button-legacy/utils/helpers.ts"), "button")
+            .unwrap();
+        assert!(component > legacy);
+    }
+
+    #[test]
+    fn test_multi_component_query() {
+        let matcher = PathMatcher::default();
+        assert!(matcher
+            .score(Path::new("./src/ui/Button.tsx"), "ui/button")
+            .is_some());
+        assert!(matcher
+            .score(Path::new("./src/api/Button.tsx"), "ui/button")
+            .is_none());
+        assert!(matcher
+            .score(Path::new("./src/ui/helpers.ts"), "ui/button")
+            .is_none());
+    }
+
+    #[test]
+    fn test_word_starts() {
+        assert_eq!(word_starts("FooBarQux.ts"), vec!['F', 'B', 'Q', 't']);
+        assert_eq!(word_starts("HTTPServer2_util"), vec!['H', 'S', '2', 'u']);
+        assert_eq!(word_starts("snake_case-name"), vec!['s', 'c', 'n']);
+    }
+
+    #[test]
+    fn test_acronym_ranks_above_contiguous_match() {
+        let matcher = PathMatcher::default();
+        let acronym = matcher
+            .score(Path::new("./src/FooBarQux.ts"), "fbq")
+            .unwrap();
+        let contiguous = matcher.score(Path::new("./src/fbqueue.ts"), "fbq").unwrap();
+        let scattered = matcher
+            .score(Path::new("./src/feedback_queue.ts"), "fbq")
+            .unwrap();
+        assert!(acronym > contiguous);
+        assert!(acronym > scattered);
+        assert!(matcher
+            .score(Path::new("./http_server.rs"), "ui/hs")
+            .is_none());
+    }
+
+    #[test]
+    fn test_no_match() {
+        let matcher = PathMatcher::default();
+        assert_eq!(matcher.score(Path::new("./src/main.rs"), "xyz"), None);
+    }
+}
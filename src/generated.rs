@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// How much of the start and end of a file is looked at
+const SAMPLE_BYTES: u64 = 8 * 1024;
+const TAIL_BYTES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratedAction {
+    // Listed after everything else
+    Downrank,
+    Exclude,
+    Off,
+}
+
+// Heuristics for spotting bundles, minified files and generated code by their contents
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneratedConfig {
+    pub action: GeneratedAction,
+    // Files whose sampled lines are longer than this on average look minified
+    pub max_average_line_length: usize,
+    // Text near the top of a file that says it was generated
+    pub markers: Vec<String>,
+    // Treat files ending in a `sourceMappingURL` pragma as build output
+    pub sourcemaps: bool,
+}
+
+impl Default for GeneratedConfig {
+    fn default() -> Self {
+        Self {
+            action: GeneratedAction::Downrank,
+            max_average_line_length: 300,
+            markers: vec![
+                "@generated".to_string(),
+                "DO NOT EDIT".to_string(),
+                "Code generated by".to_string(),
+            ],
+            sourcemaps: true,
+        }
+    }
+}
+
+impl GeneratedConfig {
+    pub fn is_generated(&self, path: &Path) -> bool {
+        if self.action == GeneratedAction::Off {
+            return false;
+        }
+        match sample(path) {
+            Some((head, tail)) => self.looks_generated(&head, &tail),
+            None => false,
+        }
+    }
+
+    fn looks_generated(&self, head: &str, tail: &str) -> bool {
+        if self
+            .markers
+            .iter()
+            .any(|marker| head.contains(marker.as_str()))
+        {
+            return true;
+        }
+        if self.sourcemaps
+            && tail.lines().rev().take(3).any(|line| {
+                let line = line.trim_start();
+                (line.starts_with("//#") || line.starts_with("/*#"))
+                    && line.contains("sourceMappingURL=")
+            })
+        {
+            return true;
+        }
+
+        // Minified code packs a whole file into a few very long lines
+        let lines = head.lines().count().max(1);
+        head.len() / lines > self.max_average_line_length
+    }
+}
+
+// The start and end of a file, which are the same text for small files
+fn sample(path: &Path) -> Option<(String, String)> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+
+    let mut head = Vec::new();
+    file.by_ref()
+        .take(SAMPLE_BYTES)
+        .read_to_end(&mut head)
+        .ok()?;
+    let head = String::from_utf8_lossy(&head).into_owned();
+    if len <= SAMPLE_BYTES {
+        return Some((head.clone(), head));
+    }
+
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(len - TAIL_BYTES)).ok()?;
+    file.read_to_end(&mut tail).ok()?;
+    Some((head, String::from_utf8_lossy(&tail).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn is_generated(contents: &str) -> bool {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.js");
+        std::fs::write(&path, contents).unwrap();
+        GeneratedConfig::default().is_generated(&path)
+    }
+
+    #[test]
+    fn test_markers_and_sourcemaps() {
+        assert!(is_generated(
+            "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage pb\n"
+        ));
+        assert!(is_generated("/** @generated */\nexport const a = 1;\n"));
+        assert!(is_generated("var a=1;\n//# sourceMappingURL=app.js.map\n"));
+        assert!(!is_generated("fn main() {\n    println!(\"hello\");\n}\n"));
+    }
+
+    #[test]
+    fn test_long_lines_look_minified() {
+        let minified = format!("{}\n", "var a=function(){return 1};".repeat(40));
+        assert!(is_generated(&minified));
+    }
+
+    #[test]
+    fn test_tail_of_large_file() {
+        let mut contents = "let x = 1;\n".repeat(2000);
+        contents.push_str("//# sourceMappingURL=bundle.js.map\n");
+        assert!(is_generated(&contents));
+    }
+
+    #[test]
+    fn test_off() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.js");
+        std::fs::write(&path, "// @generated\n").unwrap();
+        let config = GeneratedConfig {
+            action: GeneratedAction::Off,
+            ..GeneratedConfig::default()
+        };
+        assert!(!config.is_generated(&path));
+    }
+}
@@ -0,0 +1,695 @@
+use crate::config::Config;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// How a dirty file differs from HEAD, in the order the sections are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DirtyStatus {
+    Staged,
+    Modified,
+    Untracked,
+    Deleted,
+}
+
+impl DirtyStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            DirtyStatus::Staged => "Staged",
+            DirtyStatus::Modified => "Modified",
+            DirtyStatus::Untracked => "Untracked",
+            DirtyStatus::Deleted => "Deleted",
+        }
+    }
+}
+
+pub fn dirty_files(root: &Path) -> Vec<PathBuf> {
+    dirty_statuses(root)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+// Dirty files under `root`, named the way a walk of it names them
+pub fn dirty_statuses(root: &Path) -> Vec<(PathBuf, DirtyStatus)> {
+    // Outside a repository, or without git, nothing is dirty. The short format is the
+    // porcelain one with paths relative to `root` instead of the repository's top.
+    let output = match Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "-c",
+            "color.status=false",
+            "-c",
+            "status.relativePaths=true",
+        ])
+        // Untracked files one by one, as a directory of its own is `./` from inside it
+        .args(["status", "--short", "--untracked-files=all", "--", "."])
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    parse_status(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .map(|(path, status)| (rooted(root, &path), status))
+        .collect()
+}
+
+// A path git printed relative to `root`. Paths under the current directory are left bare,
+// the way git sources always named them.
+fn rooted(root: &Path, path: &Path) -> PathBuf {
+    if root == Path::new(".") {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+// Runs git in each root and merges what it finds in each
+pub fn in_roots<T>(roots: &[PathBuf], run: impl Fn(&Path) -> Vec<T>) -> Vec<T> {
+    roots.iter().flat_map(|root| run(root)).collect()
+}
+
+// `git status --porcelain` lines of `XY path`, where X is the index and Y the work tree
+fn parse_status(output: &str) -> Vec<(PathBuf, DirtyStatus)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let status = line.get(0..2)?;
+            let file_path = line.get(3..)?;
+            // Renames list `old -> new`, and the new path is the one in the work tree
+            let file_path = file_path.rsplit(" -> ").next()?;
+            let mut codes = status.chars();
+            let (index, work_tree) = (codes.next()?, codes.next()?);
+            let status = match (index, work_tree) {
+                (' ', ' ') => return None,
+                ('?', _) => DirtyStatus::Untracked,
+                ('D', _) | (_, 'D') => DirtyStatus::Deleted,
+                (' ', _) => DirtyStatus::Modified,
+                _ => DirtyStatus::Staged,
+            };
+            Some((PathBuf::from(file_path), status))
+        })
+        .collect()
+}
+
+// The top directory and HEAD commit of the repository `dir` is in, or None outside one
+pub fn head(dir: &Path) -> Option<(PathBuf, String)> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-toplevel", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut lines = output.lines();
+    let top = PathBuf::from(lines.next()?);
+    Some((top, lines.next()?.to_string()))
+}
+
+// Where `origin` of the repository `dir` is in is fetched from, or the first remote when
+// there's no origin
+pub fn remote_url(dir: &Path) -> Option<String> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let remotes = git(&["remote"])?;
+    let remote = remotes
+        .lines()
+        .find(|remote| *remote == "origin")
+        .or_else(|| remotes.lines().next())?;
+    let url = git(&["remote", "get-url", remote])?;
+    Some(url.trim().to_string())
+}
+
+// Files under `root` touched by recent commits, most recently changed first
+pub fn recent_files(root: &Path, config: &Config) -> Vec<PathBuf> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(root)
+        .args(["log", "--name-only", "--relative", "--pretty=format:"]);
+    match &config.recent_since {
+        Some(since) => command.arg(format!("--since={}", since)),
+        None => command.arg(format!("-n{}", config.recent_commits)),
+    };
+
+    let output = match command.output() {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    parse_name_only(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .map(|path| rooted(root, &path))
+        // Skip files that have since been deleted or moved
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+// One commit in a file's history
+#[derive(Debug, PartialEq)]
+pub struct FileCommit {
+    pub hash: String,
+    pub date: String,
+    pub author: String,
+    pub subject: String,
+}
+
+// The last `count` commits that touched a file, newest first, following it through renames.
+// git runs from the file's directory, so it's asked in whichever repository the file is in.
+pub fn file_history(path: &Path, count: usize) -> Vec<FileCommit> {
+    let Some(name) = path.file_name() else {
+        return Vec::new();
+    };
+    let mut command = Command::new("git");
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        command.arg("-C").arg(parent);
+    }
+    let output = command
+        .args(["log", "--follow", "--date=short"])
+        .arg(format!("--format=%h{0}%ad{0}%an{0}%s", AUTHOR_MARKER))
+        .arg(format!("-n{}", count))
+        .arg("--")
+        .arg(name)
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_file_history(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn parse_file_history(output: &str) -> Vec<FileCommit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, AUTHOR_MARKER);
+            Some(FileCommit {
+                hash: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                subject: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn recent_description(config: &Config) -> String {
+    match &config.recent_since {
+        Some(since) => format!("changed since {}", since),
+        None => format!("changed in last {} commits", config.recent_commits),
+    }
+}
+
+// Files touched by each recent commit, loaded once and reused by author filters
+#[derive(Debug, Default)]
+pub struct LogCache {
+    commits: Vec<CommitFiles>,
+}
+
+#[derive(Debug)]
+struct CommitFiles {
+    author: String,
+    files: Vec<PathBuf>,
+}
+
+const AUTHOR_MARKER: char = '\u{1f}';
+
+impl LogCache {
+    // The last `max_commits` commits in each root, with the files under it they touched
+    pub fn load(roots: &[PathBuf], max_commits: usize) -> Self {
+        let commits = in_roots(roots, |root| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(root)
+                .args(["log", "--name-only", "--relative"])
+                .arg(format!("--format={}%an <%ae>", AUTHOR_MARKER))
+                .arg(format!("-n{}", max_commits))
+                .stderr(Stdio::null())
+                .output();
+            let mut commits = match output {
+                Ok(output) if output.status.success() => {
+                    Self::parse(&String::from_utf8_lossy(&output.stdout)).commits
+                }
+                _ => Vec::new(),
+            };
+            for commit in &mut commits {
+                for file in &mut commit.files {
+                    *file = rooted(root, file);
+                }
+            }
+            commits
+        });
+        Self { commits }
+    }
+
+    fn parse(output: &str) -> Self {
+        let mut commits: Vec<CommitFiles> = Vec::new();
+        for line in output.lines() {
+            if let Some(author) = line.strip_prefix(AUTHOR_MARKER) {
+                commits.push(CommitFiles {
+                    author: author.to_lowercase(),
+                    files: Vec::new(),
+                });
+            } else if let Some(commit) = commits.last_mut() {
+                if !line.trim().is_empty() {
+                    commit.files.push(PathBuf::from(line.trim()));
+                }
+            }
+        }
+        Self { commits }
+    }
+
+    // Matches a lowercase needle against the author's name or email
+    pub fn files_by_author(&self, author: &str) -> HashSet<&Path> {
+        self.commits
+            .iter()
+            .filter(|commit| commit.author.contains(author))
+            .flat_map(|commit| commit.files.iter().map(PathBuf::as_path))
+            .collect()
+    }
+}
+
+// Paths marked linguist-generated or linguist-vendored in their root's .gitattributes
+pub fn linguist_excluded(roots: &[PathBuf], paths: &[PathBuf]) -> HashSet<PathBuf> {
+    // Each path is asked about in the first root it's under, by its name there
+    let mut by_root: Vec<Vec<(&PathBuf, PathBuf)>> = vec![Vec::new(); roots.len()];
+    for path in paths {
+        let found = roots
+            .iter()
+            .enumerate()
+            .find_map(|(index, root)| Some((index, relative_to(root, path)?)));
+        if let Some((index, name)) = found {
+            by_root[index].push((path, name.to_path_buf()));
+        }
+    }
+
+    let mut excluded = HashSet::new();
+    for (root, named) in roots.iter().zip(by_root) {
+        if named.is_empty() {
+            continue;
+        }
+        let names: Vec<PathBuf> = named.iter().map(|(_, name)| name.clone()).collect();
+        let attributed = check_linguist_attrs(root, &names);
+        excluded.extend(
+            named
+                .into_iter()
+                .filter(|(_, name)| attributed.contains(name))
+                .map(|(path, _)| path.clone()),
+        );
+    }
+    excluded
+}
+
+// The part of a walked path under `root`, if it's under it
+fn relative_to<'a>(root: &Path, path: &'a Path) -> Option<&'a Path> {
+    let root = root.strip_prefix(".").unwrap_or(root);
+    let relative = path.strip_prefix(".").unwrap_or(path);
+    if root.as_os_str().is_empty() {
+        let outside = relative.is_absolute()
+            || relative.components().next() == Some(std::path::Component::ParentDir);
+        (!outside).then_some(relative)
+    } else {
+        relative.strip_prefix(root).ok()
+    }
+}
+
+fn check_linguist_attrs(root: &Path, paths: &[PathBuf]) -> HashSet<PathBuf> {
+    let child = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "check-attr",
+            "-z",
+            "--stdin",
+            "linguist-generated",
+            "linguist-vendored",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return HashSet::new();
+    };
+
+    // Feed stdin from a thread so a full stdout pipe can't deadlock us
+    let mut input = Vec::new();
+    for path in paths {
+        input.extend_from_slice(path.to_string_lossy().as_bytes());
+        input.push(0);
+    }
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output();
+    let _ = writer.join();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_check_attr(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => HashSet::new(),
+    }
+}
+
+// Paths under a directory that git ignores, with ignored directories counted once
+pub fn ignored_count(dir: &Path) -> Option<usize> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "ls-files",
+            "--others",
+            "--ignored",
+            "--exclude-standard",
+            "--directory",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).lines().count())
+}
+
+// Files under a directory as git has them: tracked, and untracked but not ignored, named
+// the way a walk of `dir` names them. Tracked files come with whether the index has them as
+// binary. None outside a repository, with a git older than 2.38 (which added --format) or
+// when git's view could differ from a walk's: nested repositories, submodules, and `.ignore`
+// or `.glancrignore` files, which only the walk reads.
+pub fn ls_files(dir: &Path) -> Option<Vec<(PathBuf, Option<bool>)>> {
+    let ls_files = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["ls-files", "-z"])
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let listed = ls_files(&[
+        "--cached",
+        "--others",
+        "--exclude-standard",
+        "--deduplicate",
+        "--format=%(objectmode)%x09%(eolinfo:index)%x09%(path)",
+    ])?;
+    // Tracked files deleted from the work tree are still in the index
+    let deleted = ls_files(&["--deleted"])?;
+    let deleted: HashSet<&str> = deleted.split('\0').collect();
+    let files = parse_ls_files(&listed)?
+        .into_iter()
+        .filter(|(path, _)| !deleted.contains(path))
+        .map(|(path, binary)| (dir.join(path), binary))
+        // A walk doesn't follow links, and git only marks the tracked ones
+        .filter(|(path, binary)| {
+            binary.is_some() || !path.symlink_metadata().is_ok_and(|m| m.is_symlink())
+        })
+        .collect();
+    Some(files)
+}
+
+// `git ls-files -z --format` prints NUL separated `mode<TAB>eol<TAB>path` entries, where an
+// untracked file has no mode and a binary one an eol of `-text`
+fn parse_ls_files(output: &str) -> Option<Vec<(&str, Option<bool>)>> {
+    let mut files = Vec::new();
+    for entry in output.split('\0').filter(|entry| !entry.is_empty()) {
+        let mut fields = entry.splitn(3, '\t');
+        let (mode, eol, path) = (fields.next()?, fields.next()?, fields.next()?);
+        let name = path.rsplit('/').next().unwrap_or(path);
+        if path.ends_with('/') || mode == "160000" || name == ".ignore" || name == ".glancrignore" {
+            return None;
+        }
+        match mode {
+            "" => files.push((path, None)),
+            "120000" => {}
+            _ => files.push((path, Some(eol == "-text"))),
+        }
+    }
+    Some(files)
+}
+
+pub fn default_branch() -> String {
+    // First try to get the default branch name
+    let output = Command::new("git")
+        .args(["config", "--get", "init.defaultBranch"])
+        .output();
+
+    if let Ok(o) = output {
+        if o.status.success() {
+            let branch = String::from_utf8_lossy(&o.stdout).trim().to_string();
+            if !branch.is_empty() {
+                return branch;
+            }
+        }
+    }
+
+    // Fallback to "main" if we can't get the default branch
+    String::from("main")
+}
+
+fn parse_name_only(output: &str) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && seen.insert(*line))
+        .map(PathBuf::from)
+        .collect()
+}
+
+// `git check-attr -z` prints NUL separated path, attribute, value triples
+fn parse_check_attr(output: &str) -> HashSet<PathBuf> {
+    let fields: Vec<&str> = output.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .filter(|triple| matches!(triple[2], "set" | "true"))
+        .map(|triple| PathBuf::from(triple[0]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_each_root_asks_its_own_repository() {
+        let (first, second) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        for dir in [first.path(), second.path()] {
+            git(dir, &["init", "-q"]);
+            std::fs::create_dir(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("sub/a.rs"), "").unwrap();
+            std::fs::write(dir.join("outside.rs"), "").unwrap();
+        }
+        std::fs::write(
+            second.path().join(".gitattributes"),
+            "*.rs linguist-generated\n",
+        )
+        .unwrap();
+        let roots = vec![first.path().join("sub"), second.path().to_path_buf()];
+
+        // Only what's under each root, named through it
+        let dirty = in_roots(&roots, dirty_files);
+        assert_eq!(
+            dirty,
+            vec![
+                first.path().join("sub/a.rs"),
+                second.path().join(".gitattributes"),
+                second.path().join("outside.rs"),
+                second.path().join("sub/a.rs"),
+            ]
+        );
+
+        let paths = [
+            first.path().join("sub/a.rs"),
+            second.path().join("sub/a.rs"),
+        ];
+        assert_eq!(
+            linguist_excluded(&roots, &paths),
+            HashSet::from([second.path().join("sub/a.rs")])
+        );
+    }
+
+    #[test]
+    fn test_relative_to() {
+        assert_eq!(
+            relative_to(Path::new("."), Path::new("./src/main.rs")),
+            Some(Path::new("src/main.rs"))
+        );
+        assert_eq!(
+            relative_to(Path::new("."), Path::new("src/main.rs")),
+            Some(Path::new("src/main.rs"))
+        );
+        assert_eq!(relative_to(Path::new("."), Path::new("../api/x.rs")), None);
+        assert_eq!(
+            relative_to(Path::new("../api"), Path::new("../api/x.rs")),
+            Some(Path::new("x.rs"))
+        );
+    }
+
+    #[test]
+    fn test_parse_ls_files() {
+        let output = concat!(
+            "100644\tlf\tsrc/main.rs\x00",
+            "100644\t-text\tlogo.png\x00",
+            "120000\tlf\tlink\x00",
+            "\t\tnotes file.txt\x00",
+        );
+        assert_eq!(
+            parse_ls_files(output).unwrap(),
+            vec![
+                ("src/main.rs", Some(false)),
+                ("logo.png", Some(true)),
+                ("notes file.txt", None),
+            ]
+        );
+        assert!(parse_ls_files("\t\tvendor/nested/\0").is_none());
+        assert!(parse_ls_files("160000\t\tsubmodule\0").is_none());
+        assert!(parse_ls_files("\t\tsrc/.glancrignore\0").is_none());
+    }
+
+    #[test]
+    fn test_file_history_asks_the_file_repository() {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/a.rs"), "").unwrap();
+        git(dir.path(), &["add", "sub/a.rs"]);
+        git(
+            dir.path(),
+            &[
+                "-c",
+                "user.name=Ada",
+                "-c",
+                "user.email=ada@example.com",
+                "commit",
+                "-qm",
+                "Add a",
+            ],
+        );
+
+        let history = file_history(&dir.path().join("sub/a.rs"), 5);
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            (history[0].author.as_str(), history[0].subject.as_str()),
+            ("Ada", "Add a")
+        );
+    }
+
+    #[test]
+    fn test_parse_file_history() {
+        let output =
+            "1a2b3c4\u{1f}2026-01-31\u{1f}Ada Lovelace\u{1f}Fix: the \u{1f} case\nnot a commit\n";
+        assert_eq!(
+            parse_file_history(output),
+            vec![FileCommit {
+                hash: "1a2b3c4".to_string(),
+                date: "2026-01-31".to_string(),
+                author: "Ada Lovelace".to_string(),
+                subject: "Fix: the \u{1f} case".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let output = "M  staged.rs\n M modified.rs\nMM both.rs\n?? new.rs\n D gone.rs\n\
+                      R  old.rs -> renamed.rs\n";
+        assert_eq!(
+            parse_status(output),
+            vec![
+                (PathBuf::from("staged.rs"), DirtyStatus::Staged),
+                (PathBuf::from("modified.rs"), DirtyStatus::Modified),
+                (PathBuf::from("both.rs"), DirtyStatus::Staged),
+                (PathBuf::from("new.rs"), DirtyStatus::Untracked),
+                (PathBuf::from("gone.rs"), DirtyStatus::Deleted),
+                (PathBuf::from("renamed.rs"), DirtyStatus::Staged),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_name_only_dedupes_in_order() {
+        let output = "src/main.rs\nsrc/git.rs\n\nsrc/main.rs\nREADME.md\n";
+        assert_eq!(
+            parse_name_only(output),
+            vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/git.rs"),
+                PathBuf::from("README.md"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_cache_files_by_author() {
+        let output = "\u{1f}Alice Smith <alice@example.com>\n\nsrc/main.rs\nsrc/git.rs\n\
+                      \u{1f}Bob <bob@example.com>\n\nREADME.md\n";
+        let cache = LogCache::parse(output);
+
+        let alice = cache.files_by_author("alice");
+        assert_eq!(alice.len(), 2);
+        assert!(alice.contains(Path::new("src/git.rs")));
+
+        let bob = cache.files_by_author("bob@example");
+        assert_eq!(
+            bob.into_iter().collect::<Vec<_>>(),
+            vec![Path::new("README.md")]
+        );
+    }
+
+    #[test]
+    fn test_parse_check_attr() {
+        let output = "dist/app.js\0linguist-generated\0set\0\
+                      dist/app.js\0linguist-vendored\0unspecified\0\
+                      vendor/lib.rb\0linguist-generated\0unspecified\0\
+                      vendor/lib.rb\0linguist-vendored\0true\0\
+                      src/main.rs\0linguist-generated\0false\0\
+                      src/main.rs\0linguist-vendored\0unset\0";
+        let excluded = parse_check_attr(output);
+        assert_eq!(excluded.len(), 2);
+        assert!(excluded.contains(Path::new("dist/app.js")));
+        assert!(excluded.contains(Path::new("vendor/lib.rb")));
+    }
+
+    #[test]
+    fn test_recent_description() {
+        let mut config = Config::default();
+        assert_eq!(recent_description(&config), "changed in last 5 commits");
+
+        config.recent_since = Some("2 days ago".to_string());
+        assert_eq!(recent_description(&config), "changed since 2 days ago");
+    }
+}
@@ -0,0 +1,691 @@
+use crate::config::Config;
+use crate::frecency::Frecency;
+use crate::keys::KeySpec;
+use crate::{draw, handle_key, App, Step};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::style::Style;
+use ratatui::Terminal;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 30;
+
+// An App over a directory of its own, driven by keys the way the terminal would and drawn to
+// a buffer instead of the screen
+pub struct Harness {
+    pub app: App,
+    terminal: Terminal<TestBackend>,
+    _dir: TempDir,
+}
+
+impl Harness {
+    // Files are newest first in the order given, which is how an empty query lists them
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        Self::with_config(files, Config::default())
+    }
+
+    pub fn with_config(files: &[(&str, &str)], config: Config) -> Self {
+        let dir = TempDir::new().unwrap();
+        let now = SystemTime::now();
+        for (age, (path, contents)) in files.iter().enumerate() {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(60 * age as u64))
+                .unwrap();
+        }
+        let config = Config {
+            open_command: vec!["true".to_string()],
+            watch: false,
+            ..config
+        };
+        let mut app = App::with_config(vec![dir.path().to_path_buf()], (config, Vec::new()));
+        app.frecency = Frecency::default();
+        app.filter_files();
+        Self {
+            app,
+            terminal: Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap(),
+            _dir: dir,
+        }
+    }
+
+    // A key written like the config writes them, such as `ctrl+n` or `esc`
+    pub fn press(&mut self, key: &str) -> Step {
+        let key = KeySpec::parse(key).unwrap().to_event();
+        handle_key(&mut self.app, key, false).unwrap()
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            assert_eq!(handle_key(&mut self.app, key, false).unwrap(), Step::Redraw);
+        }
+    }
+
+    // Draws the screen once the preview has loaded
+    pub fn draw(&mut self) -> &Buffer {
+        let mut preview = self.app.get_file_preview();
+        while self.app.previewer.is_loading() {
+            std::thread::sleep(Duration::from_millis(5));
+            self.app.previewer.poll();
+            preview = self.app.get_file_preview();
+        }
+        let app = &mut self.app;
+        self.terminal
+            .draw(|frame| draw(frame, app, preview))
+            .unwrap();
+        self.terminal.backend().buffer()
+    }
+
+    // The screen as text, a line per row with trailing spaces trimmed
+    pub fn screen(&mut self) -> String {
+        let buffer = self.draw();
+        (0..buffer.area.height)
+            .map(|y| {
+                let row: String = (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                row.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // The file list's rows, without borders or hints
+    pub fn results(&mut self) -> Vec<String> {
+        self.screen()
+            .lines()
+            .skip(2)
+            .take_while(|line| !line.starts_with('└'))
+            .filter_map(|line| {
+                let list: String = line.chars().skip(1).take_while(|c| *c != '│').collect();
+                let list = list
+                    .trim_end()
+                    .trim_end_matches(char::is_numeric)
+                    .trim_end();
+                (!list.is_empty()).then(|| list.to_string())
+            })
+            .collect()
+    }
+
+    // The style text is drawn in, at its first appearance right of `column`
+    pub fn style_of(&mut self, text: &str, column: u16) -> Option<Style> {
+        let buffer = self.draw().clone();
+        let (width, height) = (buffer.area.width, buffer.area.height);
+        (0..height).find_map(|y| {
+            let row: Vec<&str> = (0..width)
+                .map(|x| buffer.get(x, y).symbol.as_str())
+                .collect();
+            let start =
+                (column as usize..width as usize).find(|x| row[*x..].concat().starts_with(text))?;
+            Some(buffer.get(start as u16, y).style())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SavedSearch;
+    use crate::excommand::Sort;
+    use crate::{Exit, FileFilter, Overlay, SearchMode};
+    use ratatui::style::{Color, Modifier};
+
+    fn harness() -> Harness {
+        Harness::new(&[
+            ("src/main.rs", "fn main() {\n    run();\n}\n"),
+            (
+                "src/run.rs",
+                "pub fn run() {\n    println!(\"needle\");\n}\n",
+            ),
+            ("README.md", "# Needle\n\nFinds a needle in a haystack.\n"),
+        ])
+    }
+
+    #[test]
+    fn test_results_follow_the_query() {
+        let mut harness = harness();
+        assert_eq!(
+            harness.results(),
+            vec!["src/main.rs", "src/run.rs", "README.md"]
+        );
+
+        harness.type_text("needle");
+        assert_eq!(harness.results(), vec!["README.md (1)", "src/run.rs (1)"]);
+
+        harness.press("ctrl+n");
+        harness.press("ctrl+u");
+        harness.type_text("run");
+        assert_eq!(harness.results()[0], "src/run.rs");
+    }
+
+    #[test]
+    fn test_quote_matches_an_exact_substring() {
+        let mut harness = harness();
+        harness.press("ctrl+n");
+        harness.type_text("rc/r");
+        assert_eq!(harness.results().len(), 2);
+
+        harness.press("ctrl+u");
+        harness.type_text("'RC/R");
+        assert_eq!(harness.results(), vec!["src/run.rs"]);
+    }
+
+    #[test]
+    fn test_max_results_keeps_the_best() {
+        let mut config = Config::default();
+        config.performance.max_results = 2;
+        let mut harness = Harness::with_config(
+            &[
+                ("a.txt", "needle"),
+                ("b.txt", "needle"),
+                ("c.txt", "needle"),
+            ],
+            config,
+        );
+        assert_eq!(harness.results(), vec!["a.txt", "b.txt"]);
+        assert!(harness.screen().contains("(first 2 of 3 results)"));
+
+        harness.type_text("needle");
+        assert_eq!(harness.results(), vec!["a.txt (1)", "b.txt (1)"]);
+        assert!(harness.screen().contains("(first 2 of 3 results)"));
+    }
+
+    #[test]
+    fn test_ignoring_the_selected_directory() {
+        let mut harness = harness();
+        harness.press("alt+n");
+        assert!(harness.screen().contains(":ignore /src/"));
+        harness.press("enter");
+        assert_eq!(harness.results(), vec![".glancrignore", "README.md"]);
+        assert!(harness.screen().contains("Added /src/ to"));
+    }
+
+    #[test]
+    fn test_ignoring_a_result_under_another_root() {
+        let mut harness = Harness::new(&[("one/a.rs", ""), ("two/gen/b.rs", "")]);
+        let top = harness.app.roots[0].clone();
+        harness.app.roots = vec![top.join("one"), top.join("two")];
+        harness.app.filter_files();
+        harness.app.selected_index = harness
+            .app
+            .filtered_files
+            .iter()
+            .position(|path| path.ends_with("b.rs"))
+            .unwrap();
+        harness.press("alt+n");
+        assert_eq!(
+            harness.app.command_line.as_ref().unwrap().value(),
+            "ignore /gen/"
+        );
+        harness.press("enter");
+        let ignored = std::fs::read_to_string(top.join("two/.glancrignore")).unwrap();
+        assert_eq!(ignored, "/gen/\n");
+    }
+
+    #[test]
+    fn test_tab_moves_focus_between_panes() {
+        let mut harness = harness();
+        assert!(harness.screen().contains("Keys: query (Tab)"));
+        harness.press("tab");
+        assert!(harness.screen().contains("Keys: list (Tab)"));
+        harness.press("G");
+        assert_eq!(harness.app.selected_index, 2);
+        harness.press("g");
+        harness.press("space");
+        assert_eq!(harness.app.marked.len(), 1);
+        assert_eq!(harness.app.selected_index, 1);
+
+        harness.press("tab");
+        assert!(harness.screen().contains("Keys: preview (Tab)"));
+        harness.press("j");
+        assert_eq!(harness.app.selected_index, 1);
+        harness.press("shift+tab");
+        // Typing in the list goes to the query instead
+        harness.type_text("run");
+        assert!(harness.screen().contains("Keys: query (Tab)"));
+        assert_eq!(harness.app.input.value(), "run");
+    }
+
+    #[test]
+    fn test_saved_search_with_a_sort() {
+        let config = Config {
+            saved_searches: vec![SavedSearch {
+                name: "rust".to_string(),
+                query: "rs".to_string(),
+                mode: SearchMode::Filename,
+                filter: FileFilter::All,
+                sort: Some(Sort::Tree),
+            }],
+            ..Config::default()
+        };
+        let mut harness = Harness::with_config(&[("src/main.rs", ""), ("notes.md", "")], config);
+        harness.press("ctrl+s");
+        harness.press("enter");
+        assert!(harness.app.tree_view);
+        assert_eq!(harness.app.input.value(), "rs");
+        assert!(harness.results().iter().any(|row| row.contains("main.rs")));
+    }
+
+    #[test]
+    fn test_tree_readme_is_looked_up_again_once_the_directory_changes() {
+        let mut harness = Harness::new(&[("docs/README.md", "# Docs\n"), ("main.rs", "")]);
+        harness.app.toggle_tree_view();
+        let docs = harness.app.roots[0].join("docs");
+        harness.app.tree_cursor = harness
+            .app
+            .tree_rows
+            .iter()
+            .position(|row| row.path == docs)
+            .unwrap();
+        assert!(harness.app.preview_title().starts_with("README: "));
+
+        // Kept while the selection stays, until the watcher says the directory changed
+        let readme = docs.join("README.md");
+        std::fs::remove_file(&readme).unwrap();
+        assert!(harness.app.preview_title().starts_with("README: "));
+        harness.app.forget_file(&readme);
+        assert_eq!(harness.app.preview_title(), "Preview");
+    }
+
+    #[test]
+    fn test_anchor_preview_is_read_again_once_the_anchor_changes() {
+        let mut harness = Harness::new(&[("a.txt", "first\n"), ("b.txt", "other\n")]);
+        harness.press("tab");
+        harness.app.toggle_anchor();
+        harness.press("down");
+        assert!(harness.screen().contains("first"));
+
+        // Kept across frames, until the watcher says the anchor changed
+        let anchor = harness.app.anchor.clone().unwrap();
+        std::fs::write(&anchor, "second\n").unwrap();
+        assert!(harness.screen().contains("first"));
+        harness.app.forget_file(&anchor);
+        let screen = harness.screen();
+        assert!(screen.contains("second") && !screen.contains("first"));
+    }
+
+    #[test]
+    fn test_regex_error_message() {
+        let error = grep::regex::RegexMatcher::new("fn (")
+            .unwrap_err()
+            .to_string();
+        assert!(error.lines().count() > 1);
+        assert_eq!(crate::regex_error_message(&error), "error: unclosed group");
+        assert_eq!(crate::regex_error_message("\n  \n"), "invalid pattern");
+
+        // The last results stay until the pattern compiles again
+        let mut harness = harness();
+        harness.type_text("needle");
+        harness.type_text(" (");
+        assert!(harness.screen().contains("error: unclosed group"));
+        assert_eq!(harness.results(), vec!["README.md (1)", "src/run.rs (1)"]);
+    }
+
+    #[test]
+    fn test_restore_scroll() {
+        let mut harness = harness();
+        // Pages are as tall as the preview was last drawn
+        harness.draw();
+        harness.press("pagedown");
+        let scrolled = harness.app.preview_scroll;
+        assert!(scrolled > 0);
+
+        harness.press("down");
+        assert_eq!(harness.app.preview_scroll, 0);
+        harness.press("up");
+        assert_eq!(harness.app.preview_scroll, scrolled);
+
+        // Restored where the file is now, after the list changed under it
+        harness.app.preview_scroll = 0;
+        harness.app.selected_index = 2;
+        harness.app.filtered_files.rotate_left(1);
+        harness.app.restore_scroll();
+        assert_eq!(harness.app.preview_scroll, scrolled);
+    }
+
+    #[test]
+    fn test_keys_that_change_nothing_leave_the_screen_state() {
+        let mut harness = harness();
+        let unchanged = |harness: &mut Harness, key: &str| {
+            let before = harness.app.screen_state();
+            harness.press(key);
+            !before.overlay && harness.app.screen_state() == before
+        };
+
+        assert!(unchanged(&mut harness, "up"));
+        assert!(unchanged(&mut harness, "backspace"));
+        assert!(!unchanged(&mut harness, "down"));
+        assert!(!unchanged(&mut harness, "ctrl+l"));
+        assert!(!unchanged(&mut harness, "alt+m"));
+        harness.type_text("needle");
+        assert!(unchanged(&mut harness, "end"));
+        assert!(!unchanged(&mut harness, "left"));
+        // Its own keys change what an overlay shows
+        assert!(!unchanged(&mut harness, "f1"));
+        assert!(!unchanged(&mut harness, "down"));
+    }
+
+    #[test]
+    fn test_ctrl_z_undoes_unless_it_suspends() {
+        let mut harness = harness();
+        harness.type_text("run");
+        harness.press("ctrl+u");
+        assert_eq!(harness.press("ctrl+z"), Step::Redraw);
+        assert_eq!(harness.app.input.value(), "run");
+        harness.press("ctrl+y");
+        assert_eq!(harness.app.input.value(), "");
+        assert_eq!(harness.press("ctrl+alt+z"), Step::Suspend);
+
+        let config = Config {
+            suspend_keys: vec![KeySpec::parse("ctrl+z").unwrap()],
+            ..Config::default()
+        };
+        let mut harness = Harness::with_config(&[("a.txt", "")], config);
+        harness.type_text("a");
+        assert_eq!(harness.press("ctrl+z"), Step::Suspend);
+        harness.press("alt+z");
+        assert_eq!(harness.app.input.value(), "");
+    }
+
+    #[test]
+    fn test_help_shows_and_takes_the_configured_keys() {
+        let config = Config {
+            quit_keys: vec![KeySpec::parse("alt+x").unwrap()],
+            suspend_keys: vec![KeySpec::parse("ctrl+z").unwrap()],
+            ..Config::default()
+        };
+        let mut harness = Harness::with_config(&[("a.txt", "")], config);
+        harness.press("f1");
+        let screen = harness.screen();
+        assert!(screen.contains("Ctrl+c/Alt+x Quit"));
+        assert!(screen.contains("Ctrl+z       Suspend to the shell"));
+        assert!(!screen.contains("Ctrl+Alt+z"));
+        assert_eq!(harness.press("alt+x"), Step::Exit(Exit::Quit));
+    }
+
+    #[test]
+    fn test_title_bar() {
+        let mut harness = harness();
+        let title = |harness: &mut Harness| harness.screen().lines().next().unwrap().to_string();
+        let root = harness.app.roots[0].canonicalize().unwrap();
+        assert!(crate::roots_title(&harness.app.roots)
+            .ends_with(root.file_name().unwrap().to_str().unwrap()));
+        let first = title(&mut harness);
+        assert!(first.starts_with(&format!(" {}", crate::roots_title(&harness.app.roots))));
+        assert!(first.contains("filter: all files  │  sort: frecent, then modified"));
+
+        harness.press("ctrl+b");
+        harness.press("ctrl+g");
+        let toggled = title(&mut harness);
+        assert!(toggled.contains("filter: files not on "));
+        assert!(!toggled.contains("generated hidden"));
+
+        harness.app.accessible = true;
+        assert!(title(&mut harness).contains(", sort: "));
+    }
+
+    #[test]
+    fn test_row_sizes_are_read_once_per_search() {
+        let config = Config {
+            row_format: crate::rowformat::RowTemplate::parse("{path} {size}").unwrap(),
+            ..Config::default()
+        };
+        let mut harness = Harness::with_config(&[("a.txt", "abc")], config);
+        assert_eq!(harness.results(), vec!["a.txt 3B"]);
+
+        // Drawing again shows what was read, until the next search
+        let path = harness.app.filtered_files[0].clone();
+        std::fs::write(&path, "abcdef").unwrap();
+        assert_eq!(harness.results(), vec!["a.txt 3B"]);
+        harness.type_text("a");
+        assert_eq!(harness.results(), vec!["a.txt 6B"]);
+    }
+
+    #[test]
+    fn test_row_sizes_are_read_only_for_rows_near_the_selection() {
+        let config = Config {
+            row_format: crate::rowformat::RowTemplate::parse("{path} {size}").unwrap(),
+            ..Config::default()
+        };
+        let names: Vec<String> = (0..200).map(|i| format!("{:03}.txt", i)).collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|name| (name.as_str(), "abc")).collect();
+        let mut harness = Harness::with_config(&files, config);
+        assert!(harness.results()[0].starts_with("000.txt 3B"));
+        assert!(harness.app.row_metadata.len() < 2 * HEIGHT as usize);
+
+        harness.press("tab");
+        harness.press("G");
+        let results = harness.results();
+        assert!(results.iter().any(|row| row.starts_with("199.txt 3B")));
+    }
+
+    #[test]
+    fn test_selected_list_row_counts_headers_and_skips_folded_files() {
+        let mut harness = Harness::new(&[("a/1.txt", ""), ("a/2.txt", ""), ("b/3.txt", "")]);
+        harness.press("alt+s");
+        harness.draw();
+        assert_eq!(harness.app.selected_list_row(), 1);
+        harness.press("alt+o");
+        assert_eq!(harness.app.selected_list_row(), 0);
+        harness.press("down");
+        assert_eq!(harness.app.selected_list_row(), 2);
+    }
+
+    #[test]
+    fn test_highlights_matches_in_the_preview() {
+        let mut harness = harness();
+        harness.type_text("println");
+        let preview = WIDTH * 3 / 10;
+        let style = harness.style_of("println", preview).unwrap();
+        assert_eq!(style.bg, Some(Color::DarkGray));
+        assert!(style.add_modifier.contains(Modifier::BOLD));
+        assert!(harness.style_of("(\"needle\")", preview).unwrap().bg != Some(Color::DarkGray));
+    }
+
+    #[test]
+    fn test_esc_closes_popups_before_quitting() {
+        let mut harness = harness();
+        harness.press("f1");
+        assert!(harness.screen().contains("Keybindings:"));
+        assert_eq!(harness.press("esc"), Step::Redraw);
+        assert!(!harness.screen().contains("Keybindings:"));
+
+        harness.press("alt+x");
+        assert!(harness.screen().contains("Actions"));
+        harness.type_text("tree");
+        harness.press("esc");
+        assert!(!harness.screen().contains("Actions"));
+        assert_eq!(harness.app.input.value(), "");
+
+        assert_eq!(harness.press("esc"), Step::Exit(Exit::Quit));
+    }
+
+    #[test]
+    fn test_only_a_regex_search_of_contents_reads_a_substitution() {
+        let mut harness = Harness::new(&[("s/a/b.txt", "b"), ("other.txt", "say a")]);
+        harness.type_text("s/a/b");
+        assert_eq!(harness.results(), vec!["other.txt (1)"]);
+
+        harness.app.switch_mode(SearchMode::Filename);
+        assert_eq!(harness.results(), vec!["s/a/b.txt"]);
+    }
+
+    #[test]
+    fn test_marking_upward() {
+        let mut harness = Harness::new(&[("a.txt", ""), ("b.txt", ""), ("c.txt", "")]);
+        harness.press("down");
+        harness.press("down");
+        harness.press("alt+M");
+        harness.press("tab");
+        harness.press("shift+space");
+        let names: Vec<_> = harness
+            .app
+            .marked
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["c.txt", "b.txt"]);
+        assert_eq!(harness.app.selected_index, 0);
+    }
+
+    #[test]
+    fn test_a_walk_still_going_only_searches_the_files_found_since() {
+        use crate::progressive::ProgressiveWalk;
+        use crate::walkstats::WalkStats;
+        use std::sync::mpsc;
+
+        let mut harness = Harness::new(&[("a.txt", "needle"), ("b.txt", "needle")]);
+        let root = harness.app.roots[0].clone();
+        let (a, b) = (root.join("a.txt"), root.join("b.txt"));
+        let (step, steps) = mpsc::channel::<()>();
+        let files = vec![a.clone(), b.clone()];
+        harness.app.walking = Some(ProgressiveWalk::start(vec![root], move |found| {
+            for path in &files {
+                steps.recv().unwrap();
+                // Long enough for the file to be handed over on its own
+                std::thread::sleep(Duration::from_millis(60));
+                found(path);
+            }
+            steps.recv().unwrap();
+            (files, WalkStats::default())
+        }));
+        harness.app.input = crate::TextInput::new("needle".to_string());
+        let refresh = |harness: &mut Harness| {
+            step.send(()).unwrap();
+            let walking = harness.app.walking.as_mut().unwrap();
+            while !walking.poll(Duration::from_millis(100)) {}
+            harness.app.filter_files();
+            harness.results()
+        };
+
+        assert_eq!(refresh(&mut harness), vec!["a.txt (1)"]);
+        // a.txt was searched already, so it isn't again until the walk is done
+        std::fs::write(&a, "nothing").unwrap();
+        assert_eq!(refresh(&mut harness), vec!["a.txt (1)", "b.txt (1)"]);
+        assert_eq!(refresh(&mut harness), vec!["b.txt (1)"]);
+        assert!(harness.app.walking.is_none());
+    }
+
+    #[test]
+    fn test_preview_search_counts_the_delivered_preview() {
+        let mut harness = Harness::new(&[("a.txt", "needle\nhay\nneedle\nneedle\n")]);
+        harness.press("tab");
+        harness.press("tab");
+        harness.press("/");
+        harness.type_text("needle");
+        harness.press("enter");
+        assert!(harness.screen().contains("1/3 matches for 'needle'"));
+        harness.press("n");
+        assert!(harness.screen().contains("2/3 matches for 'needle'"));
+    }
+
+    #[test]
+    fn test_a_hint_opens_only_its_own_file() {
+        let mut harness = Harness::new(&[("a.txt", ""), ("b.txt", ""), ("c.txt", "")]);
+        harness.press("alt+m");
+        harness.press("alt+m");
+        harness.draw();
+        assert_eq!(harness.press("alt+3"), Step::Exit(Exit::Open));
+        let opened = harness.app.roots[0].join("c.txt").canonicalize().unwrap();
+        assert_eq!(harness.app.frecency.ranked(), vec![opened]);
+    }
+
+    #[test]
+    fn test_confirm_quit_with_marks_or_a_batch_rename() {
+        let config = Config {
+            confirm_quit: true,
+            ..Config::default()
+        };
+        let files = [("a.txt", "a"), ("b.txt", "b")];
+        let mut harness = Harness::with_config(&files, config);
+        harness.press("alt+m");
+        assert_eq!(harness.press("esc"), Step::Redraw);
+        assert!(harness
+            .screen()
+            .contains("1 marked file(s) will be forgotten"));
+        // Anything but y stays
+        assert_eq!(harness.press("n"), Step::Redraw);
+        assert!(!harness.app.confirm_quit);
+
+        harness.press("f2");
+        assert!(harness.app.batch_rename.is_some());
+        assert_eq!(harness.press("ctrl+c"), Step::Redraw);
+        assert!(harness.screen().contains("1 file(s) won't be renamed"));
+        assert_eq!(harness.press("esc"), Step::Redraw);
+        assert!(harness.app.batch_rename.is_some());
+        // Esc only cancels the rename
+        harness.press("esc");
+        assert!(harness.app.batch_rename.is_none());
+        harness.press("esc");
+        assert_eq!(harness.press("y"), Step::Exit(Exit::Quit));
+
+        // Nothing to lose, nothing to ask
+        let mut harness = Harness::with_config(
+            &files,
+            Config {
+                confirm_quit: true,
+                ..Config::default()
+            },
+        );
+        assert_eq!(harness.press("esc"), Step::Exit(Exit::Quit));
+    }
+
+    #[test]
+    fn test_esc_closes_the_overlay_opened_last() {
+        let mut harness = harness();
+        harness.press("alt+e");
+        harness.type_text("x");
+        assert_eq!(harness.app.input.value(), "");
+        harness.press("esc");
+        assert!(harness.app.language_picker.is_none());
+
+        harness.press("alt+x");
+        // As if the palette had opened the help over itself
+        harness.app.show_help = true;
+        harness.draw();
+        assert_eq!(harness.app.overlays, vec![Overlay::Palette, Overlay::Help]);
+        harness.type_text("tree");
+        assert!(harness.screen().contains("Keybindings:"));
+        assert_eq!(harness.press("esc"), Step::Redraw);
+        assert!(!harness.app.show_help);
+        assert!(harness.screen().contains("Actions"));
+        assert_eq!(harness.press("esc"), Step::Redraw);
+        assert_eq!(harness.app.input.value(), "");
+        assert_eq!(harness.press("esc"), Step::Exit(Exit::Quit));
+    }
+
+    #[test]
+    fn test_duplicates_are_kept_until_a_reload_without_a_watcher() {
+        let mut harness = Harness::new(&[("a.txt", "same"), ("b.txt", "same"), ("c.txt", "else")]);
+        harness.press("alt+u");
+        assert_eq!(harness.app.filtered_files.len(), 2);
+        let description = harness.app.filter_description().unwrap();
+        assert!(description.contains("may be stale"));
+
+        let b = harness.app.roots[0].join("b.txt");
+        std::fs::write(&b, "diff").unwrap();
+        harness.type_text("x");
+        harness.press("backspace");
+        assert_eq!(harness.app.filtered_files.len(), 2);
+        harness.type_text(":reload");
+        harness.press("enter");
+        assert!(harness.app.filtered_files.is_empty());
+    }
+
+    #[test]
+    fn test_command_line_keeps_the_query() {
+        let mut harness = harness();
+        harness.type_text("fn");
+        harness.press("home");
+        harness.type_text(":2");
+        assert!(harness.screen().contains(":2"));
+        harness.press("enter");
+        assert_eq!(harness.app.input.value(), "fn");
+        assert_eq!(harness.app.selected_index, 1);
+    }
+}
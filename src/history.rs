@@ -0,0 +1,76 @@
+// Undo and redo stacks of past states, capped so a long session doesn't grow forever
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    limit: usize,
+}
+
+impl<T> History<T> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    // Records the state before a change, discarding anything that was undone
+    pub fn push(&mut self, state: T) {
+        if self.undo.len() >= self.limit {
+            self.undo.remove(0);
+        }
+        self.undo.push(state);
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let state = self.undo.pop()?;
+        self.redo.push(current);
+        Some(state)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let state = self.redo.pop()?;
+        self.undo.push(current);
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut history = History::new(10);
+        history.push("a");
+        history.push("ab");
+
+        assert_eq!(history.undo("abc"), Some("ab"));
+        assert_eq!(history.undo("ab"), Some("a"));
+        assert_eq!(history.undo("a"), None);
+        assert_eq!(history.redo("a"), Some("ab"));
+        assert_eq!(history.redo("ab"), Some("abc"));
+        assert_eq!(history.redo("abc"), None);
+    }
+
+    #[test]
+    fn test_push_clears_redo() {
+        let mut history = History::new(10);
+        history.push("a");
+        assert_eq!(history.undo("ab"), Some("a"));
+        history.push("a");
+        assert_eq!(history.redo("x"), None);
+    }
+
+    #[test]
+    fn test_limit_drops_oldest() {
+        let mut history = History::new(2);
+        for state in ["a", "b", "c"] {
+            history.push(state);
+        }
+        assert_eq!(history.undo("d"), Some("c"));
+        assert_eq!(history.undo("c"), Some("b"));
+        assert_eq!(history.undo("b"), None);
+    }
+}
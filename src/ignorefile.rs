@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const NAME: &str = ".glancrignore";
+
+// What to ignore to hide a result: its directory, anchored to the root so a directory of the
+// same name elsewhere stays, or the file itself when it's at the top
+pub fn pattern_for(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    match relative.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => format!("/{}/", slashed(dir)),
+        None => format!("/{}", slashed(relative)),
+    }
+}
+
+fn slashed(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Adds the pattern to the root's ignore file unless it's there already, returning the file
+pub fn append(root: &Path, pattern: &str) -> Result<PathBuf> {
+    let file = root.join(NAME);
+    let existing = match std::fs::read_to_string(&file) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("Unable to read {}", file.display())),
+    };
+    if existing.lines().any(|line| line.trim() == pattern) {
+        return Ok(file);
+    }
+    let separator = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut out| writeln!(out, "{}{}", separator, pattern))
+        .with_context(|| format!("Unable to write {}", file.display()))?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pattern_for() {
+        let root = Path::new("./project");
+        assert_eq!(
+            pattern_for(root, Path::new("./project/target/debug/out.rs")),
+            "/target/debug/"
+        );
+        assert_eq!(
+            pattern_for(root, Path::new("./project/notes.md")),
+            "/notes.md"
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(NAME), "/dist/").unwrap();
+        let file = append(dir.path(), "/target/").unwrap();
+        append(dir.path(), "/target/").unwrap();
+        assert_eq!(std::fs::read_to_string(file).unwrap(), "/dist/\n/target/\n");
+    }
+}
@@ -0,0 +1,139 @@
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+
+enum Entry {
+    // Excluded when the text contains this, lowercased
+    Exclude(String),
+    // A `!glob` entry that brings back what an earlier entry excluded
+    Include(GlobMatcher),
+}
+
+// One of `ignored_dirs` or `ignored_patterns`, where the last matching entry wins like in
+// .gitignore, so `!vendor/our-fork/**` after `/vendor/` keeps that one directory
+struct IgnoreList {
+    entries: Vec<Entry>,
+}
+
+impl IgnoreList {
+    fn new(entries: &[String], invalid: &mut Vec<String>) -> Self {
+        let entries = entries
+            .iter()
+            .filter_map(|entry| match entry.strip_prefix('!') {
+                Some(glob) => match include_glob(glob) {
+                    Ok(matcher) => Some(Entry::Include(matcher)),
+                    Err(err) => {
+                        invalid.push(format!("'{}': {}", entry, err));
+                        None
+                    }
+                },
+                None => Some(Entry::Exclude(entry.to_lowercase())),
+            })
+            .collect();
+        IgnoreList { entries }
+    }
+
+    fn is_ignored(&self, text: &str, glob_target: &Path) -> bool {
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|entry| match entry {
+                Entry::Exclude(part) => text.contains(part.as_str()).then_some(true),
+                Entry::Include(matcher) => matcher.is_match(glob_target).then_some(false),
+            })
+            .unwrap_or(false)
+    }
+}
+
+// Globs match anywhere below a root unless they start with `/`, and a trailing `/` means
+// everything in the directory
+fn include_glob(glob: &str) -> Result<GlobMatcher, globset::Error> {
+    let mut pattern = match glob.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None => format!("**/{}", glob),
+    };
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    Ok(GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .case_insensitive(true)
+        .build()?
+        .compile_matcher())
+}
+
+// The config's `ignored_dirs` and `ignored_patterns`, compiled once per session
+pub struct IgnoreRules {
+    dirs: IgnoreList,
+    patterns: IgnoreList,
+    // `!` entries that aren't valid globs, which are left out
+    pub invalid: Vec<String>,
+}
+
+impl IgnoreRules {
+    pub fn new(dirs: &[String], patterns: &[String]) -> Self {
+        let mut invalid = Vec::new();
+        let dirs = IgnoreList::new(dirs, &mut invalid);
+        let patterns = IgnoreList::new(patterns, &mut invalid);
+        IgnoreRules {
+            dirs,
+            patterns,
+            invalid,
+        }
+    }
+
+    // `relative` is the path below its root, which anchored `!/glob` entries match against
+    pub fn is_ignored(&self, path: &Path, relative: &Path) -> bool {
+        let path_str = path.to_string_lossy().to_lowercase();
+        if self.dirs.is_ignored(&path_str, relative) {
+            return true;
+        }
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        self.patterns
+            .is_ignored(&file_name.to_lowercase(), Path::new(file_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(dirs: &[&str], patterns: &[&str]) -> IgnoreRules {
+        let strings = |entries: &[&str]| -> Vec<String> {
+            entries.iter().map(|entry| entry.to_string()).collect()
+        };
+        IgnoreRules::new(&strings(dirs), &strings(patterns))
+    }
+
+    fn ignored(rules: &IgnoreRules, path: &str) -> bool {
+        let path = Path::new(path);
+        rules.is_ignored(path, path.strip_prefix("./").unwrap_or(path))
+    }
+
+    #[test]
+    fn test_negation_reincludes_under_ignored_dir() {
+        let rules = build(&["/vendor/", "!vendor/our-fork/**"], &[]);
+        assert!(ignored(&rules, "./vendor/lib/a.go"));
+        assert!(!ignored(&rules, "./vendor/our-fork/a.go"));
+        assert!(!ignored(&rules, "./src/main.go"));
+    }
+
+    #[test]
+    fn test_last_match_wins() {
+        let rules = build(&["!vendor/our-fork/", "/vendor/"], &[]);
+        assert!(ignored(&rules, "./vendor/our-fork/a.go"));
+
+        let rules = build(&[], &[".lock", "!Cargo.lock"]);
+        assert!(ignored(&rules, "./yarn.lock"));
+        assert!(!ignored(&rules, "./Cargo.lock"));
+    }
+
+    #[test]
+    fn test_anchored_and_invalid_globs() {
+        let rules = build(&["/docs/", "!/docs/keep/", "!a[b"], &[]);
+        assert!(!ignored(&rules, "./docs/keep/x.md"));
+        assert!(ignored(&rules, "./other/docs/keep/x.md"));
+        assert_eq!(rules.invalid.len(), 1);
+    }
+}
@@ -0,0 +1,642 @@
+use crate::git;
+use crate::ignorefile;
+use crate::walkstats::WalkStats;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::hash::{Hash, Hasher};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// First line of the file, changed whenever what's recorded changes meaning
+const FORMAT: &str = "glancr-index 2";
+// What changes a walk's list of files without touching the directory it's in
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ignorefile::NAME];
+// A change this soon after an mtime was read could leave it as it was on a coarse clock
+const RACY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Entry {
+    size: u64,
+    // Nanoseconds since the epoch
+    modified: u64,
+    binary: bool,
+    // The file was as committed at the index's HEAD when it was recorded
+    clean: bool,
+}
+
+// The last complete walk of the roots, and what it went by
+#[derive(Debug, Clone, PartialEq)]
+struct SavedWalk {
+    // `SearchDefaults::walk_key` of the settings it walked with
+    key: u64,
+    // From `seen`, with 0 for an mtime too recent to go by
+    seen: Vec<(PathBuf, u64)>,
+    files: Vec<PathBuf>,
+    linked: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl SavedWalk {
+    // Nothing's been created, removed or ignored since, or every directory would say so
+    fn holds(&self) -> bool {
+        self.seen.iter().all(|(path, modified)| {
+            *modified != 0 && std::fs::metadata(path).is_ok_and(|meta| nanos(&meta) == *modified)
+        })
+    }
+
+    fn parse(&mut self, tag: &str, fields: &str) -> Option<()> {
+        match tag {
+            "seen" => {
+                let (modified, path) = fields.split_once('\t')?;
+                self.seen.push((unescape(path)?, modified.parse().ok()?));
+            }
+            "file" => self.files.push(unescape(fields)?),
+            "link" => {
+                let file = self.files.last()?.clone();
+                self.linked.entry(file).or_default().push(unescape(fields)?);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+// Where the repository is, and what it looked like at startup
+#[derive(Debug)]
+struct Repo {
+    top: PathBuf,
+    cwd: PathBuf,
+    head: String,
+    // Absolute paths `git status` lists, untracked files one by one
+    dirty: HashSet<PathBuf>,
+}
+
+// What's known about each walked file, kept across sessions in ~/.cache/glancr/<project> so
+// a file that hasn't changed since the last session isn't opened again to see if it's binary.
+// Files are checked once per session, by their size and mtime, or not at all when git says
+// they're still as committed. The walk is kept too, and used again while none of the
+// directories it went into have changed.
+#[derive(Debug, Default)]
+pub struct FileIndex {
+    path: Option<PathBuf>,
+    roots: Vec<PathBuf>,
+    walk: Option<SavedWalk>,
+    // The commit entries were recorded at
+    head: Option<String>,
+    entries: HashMap<PathBuf, Entry>,
+    repo: Option<Repo>,
+    // Files checked this session, which are all that's saved
+    checked: HashSet<PathBuf>,
+    // Whether git's index has each tracked file as binary, from `git ls-files`
+    listed: HashMap<PathBuf, bool>,
+    // Files that couldn't be opened to check, which are never recorded
+    unreadable: HashSet<PathBuf>,
+    changed: bool,
+}
+
+impl FileIndex {
+    pub fn load(roots: &[PathBuf]) -> Self {
+        // Paths are kept as walked, relative to where glancr started
+        let cwd = std::env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .ok();
+        let mut hasher = DefaultHasher::new();
+        cwd.hash(&mut hasher);
+        for root in roots {
+            root.canonicalize()
+                .unwrap_or_else(|_| root.clone())
+                .hash(&mut hasher);
+        }
+        let path = dirs::cache_dir().map(|dir| {
+            dir.join("glancr")
+                .join(format!("{:016x}", hasher.finish()))
+                .join("index")
+        });
+        let (head, entries, walk) = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| parse(&contents))
+            .unwrap_or_default();
+        let repo = cwd.and_then(|cwd| {
+            let (top, head) = git::head(&cwd)?;
+            let dirty = git::dirty_statuses(&top)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect();
+            Some(Repo {
+                top,
+                cwd,
+                head,
+                dirty,
+            })
+        });
+        Self {
+            path,
+            roots: roots.to_vec(),
+            walk,
+            head,
+            entries,
+            repo,
+            ..Self::default()
+        }
+    }
+
+    // The last walk of the roots with the settings behind `key`, while it still holds
+    pub fn saved_walk(&self, roots: &[&Path], key: u64) -> Option<(Vec<PathBuf>, WalkStats)> {
+        let walk = self
+            .walk
+            .as_ref()
+            .filter(|walk| walk.key == key && self.is_for(roots))?;
+        if !walk.holds() {
+            return None;
+        }
+        let stats = WalkStats {
+            walked: walk.files.len() + walk.linked.values().map(Vec::len).sum::<usize>(),
+            linked: walk.linked.clone(),
+            ..WalkStats::default()
+        };
+        Some((walk.files.clone(), stats))
+    }
+
+    // Keeps a finished walk of all the roots for next time, taking what it saw along the way.
+    // One that ran into errors isn't kept, so they're walked into and reported again.
+    pub fn record_walk(
+        &mut self,
+        roots: &[&Path],
+        key: u64,
+        files: &[PathBuf],
+        stats: &mut WalkStats,
+    ) {
+        let seen = std::mem::take(&mut stats.seen);
+        if !self.is_for(roots) || stats.errors > 0 || stats.permission_denied > 0 {
+            return;
+        }
+        self.walk = Some(SavedWalk {
+            key,
+            seen,
+            files: files.to_vec(),
+            linked: stats.linked.clone(),
+        });
+        self.changed = true;
+    }
+
+    // For when something a walk can't see changed, like the global gitignore
+    pub fn forget_walk(&mut self) {
+        self.changed |= self.walk.take().is_some();
+    }
+
+    fn is_for(&self, roots: &[&Path]) -> bool {
+        self.roots
+            .iter()
+            .map(PathBuf::as_path)
+            .eq(roots.iter().copied())
+    }
+
+    // Best effort, a failed save only means checking files again next time
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.changed && self.checked.len() == self.entries.len() {
+            return;
+        }
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = std::fs::write(path, self.serialize());
+    }
+
+    // What git's index says about a tracked file, which holds for as long as it's clean
+    pub fn record_listed(&mut self, path: PathBuf, binary: bool) {
+        self.listed.insert(path, binary);
+    }
+
+    pub fn is_binary(&mut self, path: &Path) -> bool {
+        if let Some(&binary) = self.listed.get(path) {
+            if self.is_clean(path) {
+                return binary;
+            }
+        }
+        if let Some(entry) = self.entries.get(path) {
+            if self.checked.contains(path) {
+                return entry.binary;
+            }
+            // Still as committed at the same commit, so it can't have changed
+            let same_head = self
+                .repo
+                .as_ref()
+                .is_some_and(|repo| self.head.as_deref() == Some(repo.head.as_str()));
+            if entry.clean && same_head && self.is_clean(path) {
+                let binary = entry.binary;
+                self.checked.insert(path.to_path_buf());
+                return binary;
+            }
+        }
+
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return self.sniff(path).unwrap_or(false);
+        };
+        let size = metadata.len();
+        let modified = nanos(&metadata);
+        let clean = self.is_clean(path);
+        let previous = self.entries.get(path).copied();
+        let binary = match previous {
+            Some(entry) if entry.size == size && entry.modified == modified => entry.binary,
+            _ => match self.sniff(path) {
+                Some(binary) => binary,
+                None => return false,
+            },
+        };
+        let entry = Entry {
+            size,
+            modified,
+            binary,
+            clean,
+        };
+        if previous != Some(entry) {
+            self.entries.insert(path.to_path_buf(), entry);
+            self.changed = true;
+        }
+        self.checked.insert(path.to_path_buf());
+        binary
+    }
+
+    // Couldn't be opened for lack of permission when it was checked
+    pub fn is_unreadable(&self, path: &Path) -> bool {
+        self.unreadable.contains(path)
+    }
+
+    // Forgets a file that changed, so it's looked at again
+    pub fn invalidate(&mut self, path: &Path) {
+        self.checked.remove(path);
+        self.unreadable.remove(path);
+        if let Some(repo) = &mut self.repo {
+            repo.dirty.insert(repo.cwd.join(path));
+        }
+    }
+
+    // As committed at HEAD, going by git
+    fn is_clean(&self, path: &Path) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let absolute = repo.cwd.join(path);
+        absolute.starts_with(&repo.top)
+            && !absolute
+                .ancestors()
+                .any(|ancestor| repo.dirty.contains(ancestor))
+    }
+
+    // Whether the file is likely binary, or None when it can't be opened to tell
+    fn sniff(&mut self, path: &Path) -> Option<bool> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                self.unreadable.insert(path.to_path_buf());
+                return None;
+            }
+            Err(_) => return Some(false),
+        };
+        self.unreadable.remove(path);
+        let mut buffer = [0; 1024];
+        // Check first 1024 bytes for null bytes or other binary indicators
+        Some(
+            file.read(&mut buffer)
+                .is_ok_and(|n| buffer[..n].contains(&0)),
+        )
+    }
+
+    fn serialize(&self) -> String {
+        let head = self.repo.as_ref().map_or("-", |repo| repo.head.as_str());
+        let mut contents = format!("{}\t{}\n", FORMAT, head);
+        if let Some(walk) = &self.walk {
+            contents.push_str(&format!("walk\t{:016x}\n", walk.key));
+            for (path, modified) in &walk.seen {
+                contents.push_str(&format!("seen\t{}\t{}\n", modified, escape(path)));
+            }
+            for path in &walk.files {
+                contents.push_str(&format!("file\t{}\n", escape(path)));
+                for other in walk.linked.get(path).into_iter().flatten() {
+                    contents.push_str(&format!("link\t{}\n", escape(other)));
+                }
+            }
+        }
+        for (path, entry) in &self.entries {
+            if !self.checked.contains(path) {
+                continue;
+            }
+            contents.push_str(&format!(
+                "entry\t{}\t{}\t{}\t{}\t{}\n",
+                entry.size,
+                entry.modified,
+                u8::from(entry.binary),
+                u8::from(entry.clean),
+                escape(path)
+            ));
+        }
+        contents
+    }
+}
+
+// A walked directory and its ignore files, by when each was last modified
+pub fn seen(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let recent = SystemTime::now() - RACY;
+    let ignore_files = IGNORE_FILES.iter().map(|name| dir.join(name));
+    std::iter::once(dir.to_path_buf())
+        .chain(ignore_files)
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(&path).ok()?;
+            let racy = metadata.modified().is_ok_and(|modified| modified >= recent);
+            let modified = if racy { 0 } else { nanos(&metadata) };
+            Some((path, modified))
+        })
+        .collect()
+}
+
+// Nanoseconds since the epoch
+fn nanos(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+// The commit entries were recorded at, the entries and the walk
+type Parsed = (Option<String>, HashMap<PathBuf, Entry>, Option<SavedWalk>);
+
+// A header line of `FORMAT<TAB>head`, then lines tagged with what they are: `walk<TAB>key`
+// followed by the walk's `seen<TAB>modified<TAB>path`, `file<TAB>path` and `link<TAB>path`
+// (another path to the file before it), and each
+// `entry<TAB>size<TAB>modified<TAB>binary<TAB>clean<TAB>path`
+fn parse(contents: &str) -> Option<Parsed> {
+    let mut lines = contents.lines();
+    let (format, head) = lines.next()?.split_once('\t')?;
+    if format != FORMAT {
+        return None;
+    }
+    let head = (head != "-").then(|| head.to_string());
+    let mut entries = HashMap::new();
+    let mut walk: Option<SavedWalk> = None;
+    // A walk missing any of its lines is worse than none
+    let mut whole = true;
+    for line in lines {
+        let Some((tag, fields)) = line.split_once('\t') else {
+            continue;
+        };
+        match tag {
+            "entry" => entries.extend(parse_entry(fields)),
+            "walk" => {
+                walk = u64::from_str_radix(fields, 16).ok().map(|key| SavedWalk {
+                    key,
+                    seen: Vec::new(),
+                    files: Vec::new(),
+                    linked: HashMap::new(),
+                })
+            }
+            _ => {
+                whole &= walk
+                    .as_mut()
+                    .and_then(|walk| walk.parse(tag, fields))
+                    .is_some()
+            }
+        }
+    }
+    Some((head, entries, walk.filter(|_| whole)))
+}
+
+fn parse_entry(fields: &str) -> Option<(PathBuf, Entry)> {
+    let mut fields = fields.splitn(5, '\t');
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.parse().ok()?;
+    let binary = fields.next()? == "1";
+    let clean = fields.next()? == "1";
+    let path = unescape(fields.next()?)?;
+    let entry = Entry {
+        size,
+        modified,
+        binary,
+        clean,
+    };
+    Some((path, entry))
+}
+
+// Paths are written with backslashes, tabs and line breaks escaped and bytes that aren't
+// UTF-8 as `\xHH`, so any name reads back as it was
+fn escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        for char in chunk.valid().chars() {
+            match char {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                _ => escaped.push(char),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let (&kind, tail) = rest.split_first()?;
+        rest = tail;
+        bytes.push(match kind {
+            b'\\' => b'\\',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b'x' => {
+                let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+                rest = &rest[2..];
+                u8::from_str_radix(hex, 16).ok()?
+            }
+            _ => return None,
+        });
+    }
+    path_of(bytes)
+}
+
+#[cfg(unix)]
+fn path_of(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+// Elsewhere a name that isn't Unicode can't be put back together safely, so it's looked at
+// again instead
+#[cfg(not(unix))]
+fn path_of(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let contents = "glancr-index 2\tabc123\nentry\t10\t5\t0\t1\tsrc/a.rs\ngarbage\n\
+                        entry\t3\t7\t1\t0\tmy\\tfile\\n.bin\n";
+        let (head, entries, walk) = parse(contents).unwrap();
+        assert_eq!(head.as_deref(), Some("abc123"));
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains_key(Path::new("my\tfile\n.bin")));
+        assert_eq!(walk, None);
+
+        let walk = SavedWalk {
+            key: 42,
+            seen: vec![(PathBuf::from("src"), 9)],
+            files: vec![PathBuf::from("src/a.rs"), PathBuf::from("back\\slash")],
+            linked: [(PathBuf::from("src/a.rs"), vec![PathBuf::from("link/a.rs")])].into(),
+        };
+        let index = FileIndex {
+            checked: entries.keys().cloned().collect(),
+            entries,
+            walk: Some(walk),
+            ..FileIndex::default()
+        };
+        let (_, entries, walk) = parse(&index.serialize()).unwrap();
+        assert_eq!(entries, index.entries);
+        assert_eq!(walk, index.walk);
+        assert!(parse("glancr-index 0\t-\n").is_none());
+        // The rest of the index is still worth having without the walk
+        let (_, entries, walk) = parse(
+            "glancr-index 2\t-\nwalk\t2a\nseen\tsoon\tsrc\n\
+                                        entry\t1\t1\t0\t0\ta\n",
+        )
+        .unwrap();
+        assert_eq!((entries.len(), walk), (1, None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_escape_keeps_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9/\xff\\x.rs"));
+        assert_eq!(escape(path), "caf\\xe9/\\xff\\\\x.rs");
+        assert_eq!(unescape(&escape(path)).unwrap(), path);
+        assert_eq!(unescape("dangling\\"), None);
+    }
+
+    #[test]
+    fn test_saved_walk_holds_until_a_directory_changes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log").unwrap();
+        // Older than a moment ago, so the mtimes can be gone by
+        let age = |path: &Path| {
+            let past = SystemTime::now() - Duration::from_secs(60);
+            File::open(path).unwrap().set_modified(past).unwrap();
+        };
+        let walk = |index: &mut FileIndex| {
+            age(&root.join(".gitignore"));
+            age(root);
+            let mut stats = WalkStats {
+                seen: seen(root),
+                ..WalkStats::default()
+            };
+            index.record_walk(&[root], 7, &[root.join("a.txt")], &mut stats);
+            assert!(stats.seen.is_empty());
+        };
+        let mut index = FileIndex {
+            roots: vec![root.to_path_buf()],
+            ..FileIndex::default()
+        };
+
+        walk(&mut index);
+        let (files, stats) = index.saved_walk(&[root], 7).unwrap();
+        assert_eq!((files, stats.walked), (vec![root.join("a.txt")], 1));
+        assert!(index.saved_walk(&[root], 8).is_none());
+        assert!(index.saved_walk(&[Path::new("elsewhere")], 7).is_none());
+
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        assert!(index.saved_walk(&[root], 7).is_none());
+        walk(&mut index);
+        std::fs::write(root.join(".gitignore"), "*.txt").unwrap();
+        assert!(index.saved_walk(&[root], 7).is_none());
+        // What was changed just now can't be told apart from what's changed next
+        let mut stats = WalkStats {
+            seen: seen(root),
+            ..WalkStats::default()
+        };
+        index.record_walk(&[root], 7, &[], &mut stats);
+        assert!(index.saved_walk(&[root], 7).is_none());
+    }
+
+    #[test]
+    fn test_unchanged_files_are_not_opened_again() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "text").unwrap();
+
+        let mut index = FileIndex::default();
+        assert!(!index.is_binary(&path));
+        // Pretend the last session found it binary; it's the same size and mtime, so that stands
+        let mut index = FileIndex {
+            entries: index
+                .entries
+                .into_iter()
+                .map(|(path, entry)| {
+                    (
+                        path,
+                        Entry {
+                            binary: true,
+                            ..entry
+                        },
+                    )
+                })
+                .collect(),
+            ..FileIndex::default()
+        };
+        assert!(index.is_binary(&path));
+
+        std::fs::write(&path, "changed\0").unwrap();
+        index.invalidate(&path);
+        assert!(index.is_binary(&path));
+        std::fs::write(&path, "longer text").unwrap();
+        index.invalidate(&path);
+        assert!(!index.is_binary(&path));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_files_are_not_recorded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("secret");
+        std::fs::write(&path, "text").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        // Root reads anything, so there's nothing to see
+        if File::open(&path).is_ok() {
+            return;
+        }
+
+        let mut index = FileIndex::default();
+        assert!(!index.is_binary(&path));
+        assert!(index.is_unreadable(&path));
+        assert!(index.entries.is_empty());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        index.invalidate(&path);
+        assert!(!index.is_binary(&path));
+        assert!(!index.is_unreadable(&path));
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// A key written like `ctrl+alt+x`, `alt+enter` or `f5`, for bindings in the config and
+// in plugins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySpec {
+    modifiers: KeyModifiers,
+    code: KeyCode,
+}
+
+impl KeySpec {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        let key = parts.pop()?;
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match key.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            // Terminals send Shift+Tab as a key of its own
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "space" => KeyCode::Char(' '),
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            lower => match (lower.strip_prefix('f'), key.chars().count()) {
+                (Some(number), _) if !number.is_empty() => KeyCode::F(number.parse().ok()?),
+                (_, 1) => KeyCode::Char(key.chars().next()?),
+                _ => return None,
+            },
+        };
+        Some(KeySpec { modifiers, code })
+    }
+
+    // The key press itself, for doing whatever the key does
+    pub fn to_event(self) -> KeyEvent {
+        KeyEvent::new(self.code, self.modifiers)
+    }
+
+    // Shift is part of a typed character rather than a modifier of it
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let pressed = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        self.code == key.code && self.modifiers - KeyModifiers::SHIFT == pressed
+    }
+}
+
+// Written the way the help writes keys, like `Ctrl+Alt+z`
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl"),
+            (KeyModifiers::ALT, "Alt"),
+            (KeyModifiers::SHIFT, "Shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            KeyCode::BackTab => write!(f, "Tab"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(modifiers: KeyModifiers, code: KeyCode) -> Option<KeySpec> {
+        Some(KeySpec { modifiers, code })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            KeySpec::parse("Ctrl+Alt+k"),
+            spec(
+                KeyModifiers::CONTROL | KeyModifiers::ALT,
+                KeyCode::Char('k')
+            )
+        );
+        assert_eq!(
+            KeySpec::parse("f5"),
+            spec(KeyModifiers::NONE, KeyCode::F(5))
+        );
+        assert_eq!(
+            KeySpec::parse("alt+F"),
+            spec(KeyModifiers::ALT, KeyCode::Char('F'))
+        );
+        assert_eq!(
+            KeySpec::parse("ctrl+pagedown"),
+            spec(KeyModifiers::CONTROL, KeyCode::PageDown)
+        );
+        assert_eq!(
+            KeySpec::parse("shift+tab"),
+            spec(KeyModifiers::SHIFT, KeyCode::BackTab)
+        );
+        assert_eq!(KeySpec::parse("hyper+x"), None);
+    }
+
+    #[test]
+    fn test_display() {
+        for (spec, shown) in [
+            ("ctrl+alt+z", "Ctrl+Alt+z"),
+            ("esc", "Esc"),
+            ("f10", "F10"),
+            ("shift+tab", "Shift+Tab"),
+            ("alt+space", "Alt+Space"),
+        ] {
+            assert_eq!(KeySpec::parse(spec).unwrap().to_string(), shown);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let bound = KeySpec::parse("alt+F").unwrap();
+        assert!(bound.matches(&KeyEvent::new(
+            KeyCode::Char('F'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT
+        )));
+        assert!(!bound.matches(&KeyEvent::new(KeyCode::Char('F'), KeyModifiers::CONTROL)));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{App, Exit, SearchMode, TextInput};
+use anyhow::Result;
+use std::io::{BufRead, Write};
+
+// Results printed at a time
+const PAGE: usize = 20;
+
+const HELP: &str = "Type a query to search, a result's number to open it, \
+:more for more results, :files or :contents to switch modes, :q to quit";
+
+// One line typed at the prompt
+#[derive(Debug, PartialEq)]
+enum Input {
+    Search(String),
+    Open(usize),
+    Mode(SearchMode),
+    More,
+    Help,
+    Quit,
+}
+
+impl Input {
+    fn parse(line: &str) -> Self {
+        let line = line.trim_end_matches(['\r', '\n']);
+        match line.trim() {
+            ":q" | ":quit" => return Input::Quit,
+            ":more" | ":m" => return Input::More,
+            ":help" | ":h" | "?" => return Input::Help,
+            ":files" => return Input::Mode(SearchMode::Filename),
+            ":contents" => return Input::Mode(SearchMode::Contents),
+            _ => {}
+        }
+        match line.trim().parse::<usize>() {
+            Ok(number) if number > 0 => Input::Open(number),
+            _ => Input::Search(line.to_string()),
+        }
+    }
+}
+
+// A plain question-and-answer loop for screen readers and dumb terminals: each query
+// prints a count and a numbered page of results
+pub fn run(app: &mut App, input: impl BufRead, mut output: impl Write) -> Result<Exit> {
+    writeln!(output, "{}", HELP)?;
+    let mut shown = 0;
+    results(app, &mut output, &mut shown)?;
+    let mut lines = input.lines();
+    loop {
+        write!(output, "{}> ", app.search_mode.name())?;
+        output.flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(Exit::Quit);
+        };
+        match Input::parse(&line) {
+            Input::Quit => return Ok(Exit::Quit),
+            Input::Help => writeln!(output, "{}", HELP)?,
+            Input::More if shown < app.filtered_files.len() => page(app, &mut output, &mut shown)?,
+            Input::More => writeln!(output, "No more results")?,
+            Input::Mode(mode) => {
+                app.search_mode = mode;
+                app.filter_files();
+                results(app, &mut output, &mut shown)?;
+            }
+            Input::Open(number) if number <= app.filtered_files.len() => {
+                app.selected_index = number - 1;
+                if app.execute_command(false)? {
+                    return Ok(Exit::Open);
+                }
+                if let Some(message) = app.message.take() {
+                    writeln!(output, "{}", message)?;
+                }
+            }
+            Input::Open(number) => writeln!(output, "There is no result {}", number)?,
+            Input::Search(query) => {
+                app.input = TextInput::new(query);
+                app.selected_index = 0;
+                app.filter_files();
+                results(app, &mut output, &mut shown)?;
+            }
+        }
+    }
+}
+
+// The result count, then the first page
+fn results(app: &mut App, output: &mut impl Write, shown: &mut usize) -> Result<()> {
+    if let Some(error) = &app.pattern_error {
+        writeln!(output, "Invalid pattern: {}", error)?;
+    }
+    if let Some(message) = app.message.take() {
+        writeln!(output, "{}", message)?;
+    }
+    match app.filtered_files.len() {
+        0 => writeln!(output, "No results")?,
+        1 => writeln!(output, "1 result")?,
+        count => writeln!(output, "{} results", count)?,
+    }
+    *shown = 0;
+    page(app, output, shown)
+}
+
+fn page(app: &App, output: &mut impl Write, shown: &mut usize) -> Result<()> {
+    for (i, path) in app
+        .filtered_files
+        .iter()
+        .enumerate()
+        .skip(*shown)
+        .take(PAGE)
+    {
+        let matches = app
+            .file_matches
+            .get(path)
+            .map(|matches| format!(", {} matches", matches.count_label()))
+            .unwrap_or_default();
+        writeln!(output, "{}. {}{}", i + 1, app.display_path(path), matches)?;
+    }
+    *shown = app.filtered_files.len().min(*shown + PAGE);
+    if *shown < app.filtered_files.len() {
+        writeln!(
+            output,
+            "{} more, type :more to see them",
+            app.filtered_files.len() - *shown
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Input::parse("3\n"), Input::Open(3));
+        assert_eq!(Input::parse("0"), Input::Search("0".to_string()));
+        assert_eq!(Input::parse(" :more "), Input::More);
+        assert_eq!(Input::parse(":contents"), Input::Mode(SearchMode::Contents));
+        assert_eq!(Input::parse(":q"), Input::Quit);
+        assert_eq!(
+            Input::parse("fn main"),
+            Input::Search("fn main".to_string())
+        );
+        assert_eq!(Input::parse(""), Input::Search(String::new()));
+    }
+}
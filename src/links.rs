@@ -0,0 +1,98 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// What makes two paths the same file: its device and inode, or where there aren't any, the
+// path with every link resolved
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<FileId> {
+    std::fs::canonicalize(path).ok()
+}
+
+// A walk that follows links can reach a file by several paths. Each file is kept once, by
+// the path that doesn't go through a link when there is one and otherwise the first one
+// walked, along with the other paths it was reached by.
+pub fn dedupe(files: Vec<PathBuf>) -> (Vec<PathBuf>, HashMap<PathBuf, Vec<PathBuf>>) {
+    let mut seen: HashMap<FileId, usize> = HashMap::new();
+    let mut kept: Vec<PathBuf> = Vec::new();
+    let mut others: HashMap<usize, Vec<PathBuf>> = HashMap::new();
+    for path in files {
+        let Some(id) = file_id(&path) else {
+            kept.push(path);
+            continue;
+        };
+        match seen.entry(id) {
+            Entry::Vacant(slot) => {
+                slot.insert(kept.len());
+                kept.push(path);
+            }
+            Entry::Occupied(slot) => {
+                let first = &mut kept[*slot.get()];
+                let path = if is_direct(&path) && !is_direct(first) {
+                    std::mem::replace(first, path)
+                } else {
+                    path
+                };
+                others.entry(*slot.get()).or_default().push(path);
+            }
+        }
+    }
+    let others = others
+        .into_iter()
+        .map(|(index, paths)| (kept[index].clone(), paths))
+        .collect();
+    (kept, others)
+}
+
+// Whether the path leads to the file without going through a link on the way
+fn is_direct(path: &Path) -> bool {
+    let Ok(canonical) = std::fs::canonicalize(path) else {
+        return false;
+    };
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let absolute: PathBuf = absolute
+        .components()
+        .filter(|component| *component != std::path::Component::CurDir)
+        .collect();
+    canonical == absolute
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_keeps_each_file_once() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), "# readme").unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("alias")).unwrap();
+
+        let (kept, others) = dedupe(vec![
+            root.join("alias/main.rs"),
+            root.join("README.md"),
+            root.join("src/main.rs"),
+        ]);
+        assert_eq!(kept, vec![root.join("src/main.rs"), root.join("README.md")]);
+        assert_eq!(
+            others[&root.join("src/main.rs")],
+            vec![root.join("alias/main.rs")]
+        );
+        assert_eq!(others.len(), 1);
+    }
+}
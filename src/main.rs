@@ -1,517 +1,5370 @@
-use anyhow::{Context, Result};
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
-};
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use grep::{
     regex::RegexMatcher,
-    searcher::{sinks::UTF8, BinaryDetection, SearcherBuilder},
+    searcher::{BinaryDetection, SearcherBuilder},
 };
 use ignore::WalkBuilder;
 use ratatui::{
     prelude::*,
     style::{Color, Style},
     text::Text,
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{
+        block::Title, Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Wrap,
+    },
+};
+use serde::Deserialize;
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    io::stdin,
+    io::stdout,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant, SystemTime},
 };
-use std::{fs::File, io::stdout, io::Read, path::PathBuf, process::Command};
-use tui_input::backend::crossterm::EventHandler;
-use tui_input::Input as TextInput;
+use tui_input::backend::crossterm::to_input_request;
+use tui_input::{Input as TextInput, InputRequest};
 mod preview;
-use preview::get_file_preview;
+mod previewer;
+use preview::{Limit, PreviewOptions};
+use previewer::{Preview, PreviewRequest, Previewer};
+mod approx;
+mod assets;
+mod batchrename;
+mod clipboard;
+mod commands;
+use approx::ApproxMatcher;
+use batchrename::{BatchRename, Planned};
+use commands::{CommandOutput, CommandPrompt};
+mod compare;
+mod compressed;
 mod config;
-use config::Config;
+#[cfg(unix)]
+mod control;
+#[cfg(unix)]
+mod daemon;
+mod duplicates;
+mod excommand;
+mod export;
+use config::{Config, SavedSearch};
+use excommand::{ExCommand, Sort};
+mod fileops;
+mod forge;
+mod frecency;
+use frecency::Frecency;
+mod fuzzy;
+use fuzzy::PathMatcher;
+mod generated;
+use generated::GeneratedAction;
+mod git;
+#[cfg(test)]
+mod harness;
+mod history;
+mod ignorefile;
+mod ignores;
+mod index;
+mod keys;
+mod linemode;
+mod links;
+use history::History;
+use ignores::IgnoreRules;
+use index::FileIndex;
+mod pager;
+mod palette;
+mod performance;
+#[cfg(feature = "lua")]
+mod plugins;
+#[cfg(feature = "profiling")]
+mod profile;
+mod progressive;
+use pager::Pager;
+use palette::{Action, Palette};
+use progressive::ProgressiveWalk;
+mod query;
+use query::Query;
+mod resultcache;
+use resultcache::ResultCache;
+mod rgconfig;
+mod rowformat;
+use rgconfig::SearchDefaults;
+use rowformat::RowValues;
+mod search;
+use search::FileMatches;
+mod stats;
+mod terminal;
+use terminal::{Signals, TerminalGuard};
+mod tiebreak;
+mod tree;
+#[cfg(feature = "tree-sitter")]
+mod treesitter;
+#[cfg(feature = "wasm")]
+mod wasm;
+use tree::TreeRow;
+mod walkstats;
+mod watch;
+use walkstats::WalkStats;
+use watch::{Change, Watcher};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+// How often the loop looks for changes, finished previews and due searches while idle, and
+// how often while something is on its way
+const TICK: Duration = Duration::from_millis(100);
+const BUSY_TICK: Duration = Duration::from_millis(16);
+// How long to wait on the walk before showing whatever it has found so far
+const WALK_PATIENCE: Duration = Duration::from_millis(150);
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+// Milliseconds each spinner frame is shown
+const SPINNER_FRAME: u128 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum FileFilter {
     All,
     Dirty,
     ChangedFromDefault,
+    Recent,
+    Modified,
+    // Walked files with a copy somewhere else in the tree
+    Duplicates,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+impl FileFilter {
+    fn is_walked(self) -> bool {
+        matches!(
+            self,
+            FileFilter::All | FileFilter::Modified | FileFilter::Duplicates
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum SearchMode {
     Filename,
     Contents,
 }
 
+impl SearchMode {
+    // As it's written in the config
+    fn name(self) -> &'static str {
+        match self {
+            SearchMode::Filename => "filename",
+            SearchMode::Contents => "contents",
+        }
+    }
+}
+
+// What the list's sections are made of when it's grouped
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GroupBy {
+    Directory,
+    Extension,
+}
+
+// The pane that takes keys nothing else is bound to, which Tab moves between
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Input,
+    List,
+    Preview,
+}
+
+impl Focus {
+    fn cycle(self, delta: isize) -> Self {
+        const ORDER: [Focus; 3] = [Focus::Input, Focus::List, Focus::Preview];
+        let index = ORDER.iter().position(|focus| *focus == self).unwrap_or(0);
+        ORDER[(index as isize + delta).rem_euclid(ORDER.len() as isize) as usize]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Focus::Input => "query",
+            Focus::List => "list",
+            Focus::Preview => "preview",
+        }
+    }
+}
+
+// What opens over the main screen and takes the keys while it's there
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overlay {
+    Help,
+    SavedPicker,
+    LanguagePicker,
+    CommandPrompt,
+    Palette,
+    Pager,
+    RenamePrompt,
+    BatchRename,
+    ConfirmQuit,
+    ConfirmDelete,
+    CaptureSurvey,
+    StatsPopup,
+    ConfigProblems,
+    CommandOutput,
+    // Drawn in place of the query rather than over the screen
+    CommandLine,
+}
+
+impl Overlay {
+    // Stacked in this order when one key opens several
+    const ALL: [Overlay; 15] = [
+        Overlay::Help,
+        Overlay::SavedPicker,
+        Overlay::LanguagePicker,
+        Overlay::CommandPrompt,
+        Overlay::Palette,
+        Overlay::Pager,
+        Overlay::RenamePrompt,
+        Overlay::BatchRename,
+        Overlay::ConfirmQuit,
+        Overlay::ConfirmDelete,
+        Overlay::CaptureSurvey,
+        Overlay::StatsPopup,
+        Overlay::ConfigProblems,
+        Overlay::CommandOutput,
+        Overlay::CommandLine,
+    ];
+}
+
+// Popup summarising the values a pattern's groups captured across the results
+struct CaptureSurvey {
+    by_value: bool,
+    scroll: u16,
+}
+
+// A search within the previewed file, separate from the query filtering the results
+struct PreviewSearch {
+    path: PathBuf,
+    // The term being typed after `/`
+    input: Option<TextInput>,
+    term: String,
+    // Which matching line the preview is jumped to
+    current: usize,
+    // The matching lines of the preview showing the term, and which preview that was
+    matches: Option<((usize, bool, bool), Vec<usize>)>,
+}
+
+// Languages among the current files, most common first, for picking a `lang:` filter
+struct LanguagePicker {
+    languages: Vec<(&'static str, usize)>,
+    // Row 0 clears the filter, the languages follow
+    selected: usize,
+}
+
+// Everything an undo step restores
+#[derive(PartialEq)]
+struct QueryState {
+    query: String,
+    cursor: usize,
+    search_mode: SearchMode,
+    file_filter: FileFilter,
+}
+
+// What a key press can change on screen, to tell one that changed nothing and needn't be
+// drawn. Overlays keep state of their own, so one being open always counts as a change.
+#[derive(PartialEq)]
+struct ScreenState {
+    overlay: bool,
+    query: QueryState,
+    results: usize,
+    selected: (usize, usize),
+    preview_scroll: usize,
+    focus: Focus,
+    message: Option<String>,
+    views: [bool; 6],
+    line_numbers: preview::LineNumbers,
+    anchor: Option<PathBuf>,
+    group_by: Option<GroupBy>,
+    // Counts, since each key adds or removes one
+    marks: (usize, usize, usize, usize),
+}
+
+// What a search depends on besides the files, which are walked fresh each time
+type SearchKey = (String, SearchMode, FileFilter, bool);
+
+// Everything a search sets, to reuse when the query comes back to one it was a moment ago
+#[derive(Clone)]
+struct CachedSearch {
+    files: Vec<PathBuf>,
+    filtered_files: Vec<PathBuf>,
+    file_matches: HashMap<PathBuf, FileMatches>,
+    dirty_status: HashMap<PathBuf, git::DirtyStatus>,
+    walk_stats: Option<WalkStats>,
+    skipped_large: usize,
+    dropped_results: usize,
+    pattern_error: Option<String>,
+    tree_rows: Vec<TreeRow>,
+}
+
+// A content search of the files a walk has found so far, so each refresh while it goes on only
+// searches the files found since
+struct WalkSearch {
+    // The pattern, with what else decides which files are searched
+    key: (String, String, bool),
+    // How many files were searched, the last of them, and what they matched
+    searched: usize,
+    last: Option<PathBuf>,
+    found: Vec<(PathBuf, FileMatches)>,
+    skipped_large: usize,
+}
+
+// What to do once the terminal has been restored
+#[derive(Debug, PartialEq)]
+enum Exit {
+    Quit,
+    // Files were opened with Enter
+    Open,
+    Export,
+    Quickfix,
+}
+
+// What a key leaves for the main loop to do, for the parts that need the terminal
+#[derive(Debug, PartialEq)]
+enum Step {
+    Redraw,
+    Exit(Exit),
+    // One of `suspend_keys`
+    Suspend,
+    // Hand the terminal to the commands waiting in `foreground`, then come back
+    Foreground,
+}
+
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Subcommands>,
+
+    /// Directories to search (defaults to `workspaces` from the config, then the current directory)
+    #[arg(value_name = "DIR")]
+    roots: Vec<PathBuf>,
+
+    /// Start with a saved search from the config file (~/.glancr.yml or ~/.config/glancr/config.yml, or .toml/.json)
+    #[arg(long, value_name = "NAME")]
+    saved: Option<String>,
+
+    /// Where Ctrl+q writes the results in quickfix format (`-` for stdout, the default)
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Keep the walk of each directory in memory for other glancr sessions to use
+    #[arg(long)]
+    daemon: bool,
+
+    /// Accept commands from other programs on a Unix socket at this path
+    #[arg(long, value_name = "SOCKET")]
+    listen: Option<PathBuf>,
+
+    /// Draw without borders, scrollbars or colour-only cues, for screen readers
+    #[arg(long)]
+    accessible: bool,
+
+    /// Read queries from stdin and print numbered results instead of drawing a screen
+    #[arg(long)]
+    lines: bool,
+}
+
+#[derive(Subcommand)]
+enum Subcommands {
+    /// Run a query without a screen and print how many files and lines matched, by directory
+    /// and by extension. Exits with 1 when nothing matched.
+    Stats {
+        /// The query, as it would be typed, filters like `ext:rs` included
+        query: String,
+
+        /// Directories to search (defaults to `workspaces` from the config, then the current directory)
+        #[arg(value_name = "DIR")]
+        roots: Vec<PathBuf>,
+
+        /// Match file names instead of contents
+        #[arg(long)]
+        filename: bool,
+
+        /// Print JSON instead of tables
+        #[arg(long)]
+        json: bool,
+    },
+}
+
 struct App {
     files: Vec<PathBuf>,
     filtered_files: Vec<PathBuf>,
     selected_index: usize,
     input: TextInput,
     search_mode: SearchMode,
+    // The other mode's query and selection, from when it was last switched away from
+    other_mode: Option<(TextInput, usize)>,
     file_filter: FileFilter,
+    // Content searches tolerate typos instead of treating the query as a regex
+    approximate: bool,
     config: Config,
+    search_defaults: SearchDefaults,
+    preview_options: PreviewOptions,
+    previewer: Previewer,
+    ignore_rules: IgnoreRules,
+    // Whether each walked file is binary, remembered across sessions
+    file_index: FileIndex,
+    // Change notifications for the roots, when `watch` is on and the platform has them
+    watcher: Option<Watcher>,
+    // A content search put off until typing pauses, and when it's due
+    search_due: Option<Instant>,
+    // Frame of the spinner shown while a search or preview is on its way
+    spinner: usize,
+    // The last walk and the roots it was of, reused until the watcher reports a new file
+    walk_cache: Option<(Vec<PathBuf>, Vec<PathBuf>, WalkStats)>,
+    // A walk that took too long to wait for, whose files so far are searched meanwhile
+    walking: Option<ProgressiveWalk>,
+    walk_search: Option<WalkSearch>,
+    // Whether something's ticking to pick up the rest of a walk, which only the TUI does
+    progressive: bool,
+    // Lua hooks and actions from ~/.config/glancr
+    #[cfg(feature = "lua")]
+    plugins: Option<plugins::Plugins>,
+    // Filters and previewers from the `wasm_plugins` directory
+    #[cfg(feature = "wasm")]
+    wasm_plugins: wasm::WasmPlugins,
+    // Timings of the last walk, search, preview and frame
+    #[cfg(feature = "profiling")]
+    profile: profile::Profile,
     show_help: bool,
-    preview_scroll: u16,
-}
-
-// Helper function to check if a file is likely binary
-fn is_binary_file(path: &std::path::Path) -> bool {
-    if let Ok(mut file) = File::open(path) {
-        let mut buffer = [0; 1024];
-        if let Ok(n) = file.read(&mut buffer) {
-            // Check first 1024 bytes for null bytes or other binary indicators
-            return buffer[..n].iter().any(|&byte| byte == 0);
-        }
-    }
-    false
+    preview_scroll: usize,
+    // First visible row of the file list
+    list_offset: usize,
+    // Rows of the preview pane and of the whole screen when last drawn, for paging
+    preview_height: u16,
+    screen_height: u16,
+    // The scroll that brings the end of the loaded preview into view, for `G`
+    preview_bottom: usize,
+    // What Alt+1 to Alt+9 open: the visible files' indexes, or tree rows in the tree view
+    hinted: Vec<usize>,
+    // Manual preview scroll offsets by file, restored when navigating back to one
+    scroll_positions: HashMap<PathBuf, usize>,
+    // Why the current content pattern failed to compile
+    pattern_error: Option<String>,
+    // Shown in place of a pattern error until the next key press
+    message: Option<String>,
+    // Files too large to grep in the last content search
+    skipped_large: usize,
+    // Results past `max_results` that the last search left out
+    dropped_results: usize,
+    // How the last walk went; git sources aren't walked
+    walk_stats: Option<WalkStats>,
+    // Lines of the walk statistics popup
+    stats_popup: Option<Vec<String>>,
+    // Problems found in the config file, shown until a key is pressed
+    config_problems: Option<Vec<String>>,
+    // Matching lines for each file in the last content search
+    file_matches: HashMap<PathBuf, FileMatches>,
+    // Git status of each file while the dirty filter is on, which the list is grouped by
+    dirty_status: HashMap<PathBuf, git::DirtyStatus>,
+    group_by: Option<GroupBy>,
+    // Sections collapsed down to their header
+    folded: HashSet<String>,
+    // The results are shown nested under their directories
+    tree_view: bool,
+    // Content matches show their first matching line under the path
+    two_line: bool,
+    tree_rows: Vec<TreeRow>,
+    tree_cursor: usize,
+    collapsed: HashSet<PathBuf>,
+    // Goes up whenever the results are replaced or laid out again
+    results_version: usize,
+    git_log: Option<git::LogCache>,
+    hide_generated: bool,
+    // Whether each result's preview is cut short, and why, for results seen so far
+    preview_limits: HashMap<PathBuf, Option<Limit>>,
+    // Whether each path checked so far is linguist-generated or vendored
+    linguist_cache: HashMap<PathBuf, bool>,
+    // Whether each path checked so far looks minified or generated
+    generated_cache: HashMap<PathBuf, bool>,
+    // Each result's metadata for `{mtime}` and `{size}` rows, read when it's first drawn
+    row_metadata: HashMap<PathBuf, Option<std::fs::Metadata>>,
+    saved_picker: Option<usize>,
+    language_picker: Option<LanguagePicker>,
+    command_prompt: Option<CommandPrompt>,
+    // A `:` command being typed over the search box, which keeps the query underneath
+    command_line: Option<TextInput>,
+    palette: Option<Palette>,
+    // A key picked from the palette, handled next as if it was pressed
+    replay: Option<KeyEvent>,
+    command_output: Option<CommandOutput>,
+    pager: Option<Pager>,
+    // New path being typed for the selected file
+    rename_prompt: Option<TextInput>,
+    // Pattern and replacement being typed for the marked files' paths
+    batch_rename: Option<BatchRename>,
+    // Files waiting for the delete to be confirmed
+    confirm_delete: Option<Vec<PathBuf>>,
+    // Asking whether to quit and lose the marked files
+    confirm_quit: bool,
+    capture_survey: Option<CaptureSurvey>,
+    // What's open over the screen, topmost last
+    overlays: Vec<Overlay>,
+    // Whether keys edit the query, move through the list or scroll and search the preview
+    focus: Focus,
+    preview_search: Option<PreviewSearch>,
+    // Files picked with Alt+m or Space for bulk actions, in the order they were marked
+    marked: Vec<PathBuf>,
+    roots: Vec<PathBuf>,
+    // The roots as the title bar names them, worked out once since they don't change
+    roots_title: String,
+    // The branch the changed from default filter compares with, asked of git when first shown
+    default_branch: OnceCell<String>,
+    // The README of the directory selected in the tree, looked up once per selection
+    tree_readme: RefCell<Option<(PathBuf, Option<PathBuf>)>>,
+    // Directories the search has been scoped into, innermost last
+    scopes: Vec<PathBuf>,
+    // File marked for comparison against the selected one
+    anchor: Option<PathBuf>,
+    // The anchor's last preview, and what it was read for
+    anchor_preview: Option<(PreviewRequest, Text<'static>)>,
+    // Show a diff against the anchor instead of side-by-side previews
+    show_diff: bool,
+    // Show the selected file's recent commits instead of its contents
+    show_history: bool,
+    // The last history shown, and whose it is
+    file_history: Option<(PathBuf, Text<'static>)>,
+    // A history being read by git on a thread of its own, and whose it is
+    history_loading: Option<(PathBuf, Receiver<Vec<git::FileCommit>>)>,
+    // Files with identical contents, found by the duplicates filter
+    duplicate_groups: Vec<Vec<PathBuf>>,
+    // The files the groups were found among, so they're only looked for again after another
+    // walk or a change the watcher saw
+    duplicates_of: Option<Vec<PathBuf>>,
+    // Terminal editors waiting to be given the terminal
+    foreground: Vec<Command>,
+    history: History<QueryState>,
+    frecency: Frecency,
+    // Whether the last recorded change was typing, so a run of typing undoes in one step
+    typing: bool,
+    // Searches run while editing the query since anything else last changed
+    result_cache: ResultCache<SearchKey, CachedSearch>,
+    // Plain output for screen readers, with the selection announced on the status line
+    accessible: bool,
 }
 
 // Add this helper function to check for directories/files we want to ignore
-fn should_ignore_path(path: &std::path::Path, config: &Config) -> bool {
-    let path_str = path.to_string_lossy().to_lowercase();
-
-    // Check if path contains any of the ignored directory patterns
-    if config.ignored_dirs.iter().any(|dir| path_str.contains(dir)) {
-        return true;
+impl App {
+    fn new(roots: Vec<PathBuf>) -> Self {
+        Self::with_config(roots, Config::load())
     }
 
-    // Check if the file name matches any ignored patterns
-    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-        let file_name_lower = file_name.to_lowercase();
-        if config
-            .ignored_patterns
-            .iter()
-            .any(|pattern| file_name_lower.contains(pattern))
-        {
-            return true;
-        }
-    }
+    // Everything but reading the config file, which tests do without
+    fn with_config(roots: Vec<PathBuf>, (config, config_problems): (Config, Vec<String>)) -> Self {
+        let roots = if !roots.is_empty() {
+            roots
+        } else if !config.workspaces.is_empty() {
+            config.workspace_roots()
+        } else {
+            vec![PathBuf::from(".")]
+        };
 
-    false
-}
+        let search_defaults = SearchDefaults::load(config.ripgrep_config_path());
+        let hide_generated = config.hide_linguist_generated;
+        let two_line = config.two_line_results;
+        let accessible = config.accessible;
+        let preview_options = PreviewOptions {
+            line_numbers: config.line_numbers,
+            syntax_overrides: config.syntax_overrides.clone(),
+            theme: config.theme.clone(),
+            decompress: config.search_compressed,
+            replace: None,
+            #[cfg(feature = "tree-sitter")]
+            tree_sitter: config.tree_sitter.clone(),
+        };
+        let ignore_rules = IgnoreRules::new(&config.ignored_dirs, &config.ignored_patterns);
+        // Problems that would otherwise only show up later, or never
+        let mut warnings = Vec::new();
+        if !cfg!(feature = "tree-sitter") && !config.tree_sitter.is_empty() {
+            warnings.push("tree_sitter is configured but glancr was built without it".to_string());
+        }
+        if !ignore_rules.invalid.is_empty() {
+            warnings.push(format!(
+                "Invalid ignore globs: {}",
+                ignore_rules.invalid.join(", ")
+            ));
+        }
+        if let Some(program) = config.open_command.first() {
+            if !commands::program_exists(program) {
+                warnings.push(format!(
+                    "open_command '{}' isn't on PATH, so Enter can't open files; set it in the config file or $EDITOR",
+                    program
+                ));
+            }
+        }
+        if !cfg!(feature = "wasm") && config.wasm_plugins.is_some() {
+            warnings.push("wasm_plugins is configured but glancr was built without it".to_string());
+        }
+        #[cfg(feature = "wasm")]
+        let wasm_plugins = match config.wasm_plugins_dir() {
+            Some(dir) => {
+                let (wasm_plugins, problems) = wasm::WasmPlugins::load(&dir);
+                warnings.extend(problems);
+                wasm_plugins
+            }
+            None => wasm::WasmPlugins::default(),
+        };
+        #[cfg(feature = "lua")]
+        let plugins = plugins::Plugins::load().unwrap_or_else(|err| {
+            warnings.push(format!("{:#}", err));
+            None
+        });
+        let message = (!warnings.is_empty()).then(|| warnings.join("; "));
 
-impl App {
-    fn new() -> Self {
         App {
             files: Vec::new(),
             filtered_files: Vec::new(),
             selected_index: 0,
             input: TextInput::default(),
             search_mode: SearchMode::Contents,
+            other_mode: None,
             file_filter: FileFilter::All,
-            config: Config::load(),
+            approximate: false,
+            config,
+            search_defaults,
+            previewer: Previewer::new(preview_options.clone()),
+            preview_options,
+            ignore_rules,
+            file_index: FileIndex::load(&roots),
+            watcher: None,
+            walk_cache: None,
+            walking: None,
+            walk_search: None,
+            progressive: false,
+            search_due: None,
+            spinner: 0,
+            #[cfg(feature = "lua")]
+            plugins,
+            #[cfg(feature = "wasm")]
+            wasm_plugins,
+            #[cfg(feature = "profiling")]
+            profile: profile::Profile::default(),
             show_help: false,
             preview_scroll: 0,
+            list_offset: 0,
+            preview_height: 0,
+            preview_bottom: 0,
+            screen_height: 0,
+            hinted: Vec::new(),
+            scroll_positions: HashMap::new(),
+            pattern_error: None,
+            message,
+            skipped_large: 0,
+            dropped_results: 0,
+            file_matches: HashMap::new(),
+            walk_stats: None,
+            stats_popup: None,
+            config_problems: (!config_problems.is_empty()).then_some(config_problems),
+            dirty_status: HashMap::new(),
+            group_by: None,
+            folded: HashSet::new(),
+            tree_view: false,
+            two_line,
+            tree_rows: Vec::new(),
+            tree_cursor: 0,
+            collapsed: HashSet::new(),
+            results_version: 0,
+            git_log: None,
+            hide_generated,
+            linguist_cache: HashMap::new(),
+            generated_cache: HashMap::new(),
+            row_metadata: HashMap::new(),
+            preview_limits: HashMap::new(),
+            saved_picker: None,
+            language_picker: None,
+            command_prompt: None,
+            command_line: None,
+            palette: None,
+            replay: None,
+            command_output: None,
+            pager: None,
+            rename_prompt: None,
+            batch_rename: None,
+            confirm_delete: None,
+            confirm_quit: false,
+            capture_survey: None,
+            overlays: Vec::new(),
+            focus: Focus::Input,
+            preview_search: None,
+            marked: Vec::new(),
+            roots_title: roots_title(&roots),
+            default_branch: OnceCell::new(),
+            tree_readme: RefCell::new(None),
+            roots,
+            scopes: Vec::new(),
+            anchor: None,
+            show_diff: false,
+            show_history: false,
+            file_history: None,
+            anchor_preview: None,
+            history_loading: None,
+            duplicate_groups: Vec::new(),
+            duplicates_of: None,
+            foreground: Vec::new(),
+            history: History::new(100),
+            frecency: Frecency::load(),
+            typing: false,
+            result_cache: ResultCache::new(64),
+            accessible,
         }
     }
 
-    // Add this new method to get dirty files from git
-    fn get_dirty_files() -> Vec<PathBuf> {
-        let output = Command::new("git")
-            .args(["status", "--porcelain"])
-            .output()
-            .unwrap_or_else(|_| panic!("Failed to execute git command"));
+    // Searches from scratch, since whatever asked may have changed what a search would find
+    fn filter_files(&mut self) {
+        self.result_cache.clear();
+        self.refilter();
+    }
 
-        String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .filter_map(|line| {
-                let status = &line[0..2];
-                let file_path = &line[3..];
-                // Include both modified and untracked files
-                if status.trim().is_empty() {
-                    None
-                } else {
-                    Some(PathBuf::from(file_path))
-                }
-            })
-            .collect()
+    // The query as typed, read as a substitution only by a regex search of contents since
+    // `s/a/b` is a file name or approximate text anywhere else
+    fn query(&self) -> Query {
+        let query = Query::parse(self.input.value());
+        if self.search_mode == SearchMode::Contents && !self.approximate {
+            query.with_substitution()
+        } else {
+            query
+        }
     }
 
-    // Modify the existing filter_files method
-    fn filter_files(&mut self) {
+    // Searches after the query was edited, reusing the results of a query seen since the last
+    // full search
+    fn refilter(&mut self) {
+        self.results_version += 1;
+        let key = (
+            self.input.value().to_string(),
+            self.search_mode,
+            self.file_filter,
+            self.approximate,
+        );
+        let Some(cached) = self.result_cache.get(&key).cloned() else {
+            self.search();
+            // Results from part of the tree would hide the rest when the query came back
+            if self.walking.is_some() {
+                self.check_preview_limits();
+                return;
+            }
+            let cached = CachedSearch {
+                files: self.files.clone(),
+                filtered_files: self.filtered_files.clone(),
+                file_matches: self.file_matches.clone(),
+                dirty_status: self.dirty_status.clone(),
+                walk_stats: self.walk_stats.clone(),
+                skipped_large: self.skipped_large,
+                dropped_results: self.dropped_results,
+                pattern_error: self.pattern_error.clone(),
+                tree_rows: self.tree_rows.clone(),
+            };
+            self.result_cache.insert(key, cached);
+            self.check_preview_limits();
+            return;
+        };
+        self.files = cached.files;
+        self.filtered_files = cached.filtered_files;
+        self.file_matches = cached.file_matches;
+        self.dirty_status = cached.dirty_status;
+        self.walk_stats = cached.walk_stats;
+        self.skipped_large = cached.skipped_large;
+        self.dropped_results = cached.dropped_results;
+        self.pattern_error = cached.pattern_error;
+        self.tree_rows = cached.tree_rows;
+        if self.tree_view {
+            self.select_tree_row(self.tree_cursor);
+        } else {
+            self.selected_index = self
+                .selected_index
+                .min(self.filtered_files.len().saturating_sub(1));
+        }
+        self.check_preview_limits();
+    }
+
+    // Looks at results not seen before to badge the ones whose preview will be cut short
+    fn check_preview_limits(&mut self) {
+        for path in &self.filtered_files {
+            if !self.preview_limits.contains_key(path) {
+                let binary = self.file_index.is_binary(path);
+                let limit = Limit::of(path, binary, self.preview_options.decompress);
+                self.preview_limits.insert(path.clone(), limit);
+            }
+        }
+    }
+
+    fn search(&mut self) {
+        #[cfg(feature = "profiling")]
+        let started = Instant::now();
+        let query = self.query();
+        self.skipped_large = 0;
+        self.row_metadata.clear();
+        self.pattern_error = None;
+
         // First, update the base files according to the file filter
+        self.walk_stats = None;
         self.files = match self.file_filter {
             FileFilter::All => {
-                // Use the original file collection logic
-                let mut files = Vec::new();
-                for entry in WalkBuilder::new(".")
-                    .hidden(false)
-                    .git_ignore(true)
-                    .build()
-                    .filter_map(|e| e.ok())
-                    .filter(|e| {
-                        let path = e.path();
-                        if !e.file_type().map_or(false, |ft| ft.is_file()) {
-                            return false;
-                        }
-                        if should_ignore_path(path, &self.config) {
-                            return false;
-                        }
-                        !is_binary_file(path)
-                    })
-                {
-                    files.push(entry.path().to_path_buf());
-                }
+                let (files, stats) = self.walk_files(&query);
+                self.walk_stats = Some(stats);
                 files
             }
-            FileFilter::Dirty => Self::get_dirty_files(),
-            FileFilter::ChangedFromDefault => Self::get_dirty_files(),
+            FileFilter::Modified => {
+                let (files, stats) = self.recently_modified_files(&query);
+                self.walk_stats = Some(stats);
+                files
+            }
+            FileFilter::Dirty => {
+                let statuses = git::in_roots(&self.roots, git::dirty_statuses);
+                self.dirty_status = statuses.iter().cloned().collect();
+                statuses.into_iter().map(|(path, _)| path).collect()
+            }
+            FileFilter::ChangedFromDefault => {
+                let files = git::in_roots(&self.roots, git::dirty_files);
+                tiebreak::sort(files, &self.config.tiebreaks)
+            }
+            FileFilter::Recent => {
+                git::in_roots(&self.roots, |root| git::recent_files(root, &self.config))
+            }
+            FileFilter::Duplicates => {
+                let (files, stats) = self.walk_files(&query);
+                self.walk_stats = Some(stats);
+                if self.duplicates_of.as_ref() != Some(&files) {
+                    self.duplicate_groups = duplicates::groups(&files);
+                    self.duplicates_of = Some(files);
+                }
+                self.duplicate_groups.concat()
+            }
+        };
+        #[cfg(feature = "profiling")]
+        let started = {
+            self.profile.walked(started, self.files.len());
+            Instant::now()
         };
 
+        // Git sources aren't walked, so scopes and structured filters are applied here
+        if !self.file_filter.is_walked() && !self.scopes.is_empty() {
+            let root = self.search_roots()[0];
+            let root = root.strip_prefix("./").unwrap_or(root).to_path_buf();
+            self.files
+                .retain(|path| path.strip_prefix("./").unwrap_or(path).starts_with(&root));
+        }
+        if !self.file_filter.is_walked() && query.has_path_filters() {
+            self.files.retain(|path| {
+                let metadata = if query.needs_metadata() {
+                    std::fs::metadata(path).ok()
+                } else {
+                    None
+                };
+                query.matches_path(path, metadata.as_ref())
+            });
+        }
+        if !self.file_filter.is_walked() && !query.langs.is_empty() {
+            let languages = self.query_languages(&query);
+            let mut files = std::mem::take(&mut self.files);
+            files.retain(|path| self.matches_languages(path, languages.as_deref()));
+            self.files = files;
+        }
+        if self.hide_generated {
+            self.filter_linguist_generated();
+        }
+        if !query.authors.is_empty() {
+            self.filter_by_authors(&query.authors);
+        }
+        if self.config.generated.action == GeneratedAction::Exclude {
+            let before = self.files.len();
+            let files = std::mem::take(&mut self.files);
+            self.files = files
+                .into_iter()
+                .filter(|path| !self.is_generated(path))
+                .collect();
+            if let Some(stats) = &mut self.walk_stats {
+                stats.generated = before - self.files.len();
+            }
+        }
+
+        #[cfg(feature = "wasm")]
+        if !self.wasm_plugins.is_empty() {
+            let files = std::mem::take(&mut self.files);
+            self.files = match self.wasm_plugins.filter(files.clone()) {
+                Ok(kept) => kept,
+                Err(err) => {
+                    self.message = Some(format!("{:#}", err));
+                    files
+                }
+            };
+        }
+
+        #[cfg(feature = "lua")]
+        if let Some(plugins) = &self.plugins {
+            let files = std::mem::take(&mut self.files);
+            self.files = match plugins.filter(files.clone()) {
+                Ok(kept) => kept,
+                Err(err) => {
+                    self.message = Some(format!("Lua: {:#}", err));
+                    files
+                }
+            };
+        }
+
         // Then apply the search filter
-        let query = self.input.value();
-        if query.is_empty() {
-            self.filtered_files = self.files.clone();
-            return;
+        if query.text.is_empty() || self.search_mode != SearchMode::Contents {
+            self.file_matches.clear();
         }
-        match self.search_mode {
-            SearchMode::Filename => self.filter_by_filename(query.to_string()),
-            SearchMode::Contents => self.filter_by_contents(query.to_string()),
+        if query.text.is_empty() {
+            // Other filters already come in a meaningful order
+            self.filtered_files = match self.file_filter {
+                FileFilter::All => self.recent_first(&self.files),
+                _ => self.files.clone(),
+            };
+        } else {
+            match self.search_mode {
+                SearchMode::Filename => self.filter_by_filename(query.text),
+                SearchMode::Contents if self.approximate => self.filter_by_approximate(&query.text),
+                SearchMode::Contents => {
+                    self.filter_by_contents(self.search_defaults.pattern(&query.text))
+                }
+            }
+        }
+
+        #[cfg(feature = "lua")]
+        if let Some(plugins) = &self.plugins {
+            match plugins.results(self.filtered_files.clone()) {
+                Ok(files) => self.filtered_files = files,
+                Err(err) => self.message = Some(format!("Lua: {:#}", err)),
+            }
+        }
+
+        // Stable, so generated files keep their relative order at the bottom
+        if self.config.generated.action == GeneratedAction::Downrank {
+            let files = std::mem::take(&mut self.filtered_files);
+            let (generated, mut files): (Vec<_>, Vec<_>) =
+                files.into_iter().partition(|path| self.is_generated(path));
+            files.extend(generated);
+            self.filtered_files = files;
+        }
+
+        // The best results only, so huge trees stay quick to rank, group and draw
+        let max_results = self.config.performance.max_results;
+        self.dropped_results = 0;
+        if max_results > 0 && self.filtered_files.len() > max_results {
+            self.dropped_results = self.filtered_files.len() - max_results;
+            self.filtered_files.truncate(max_results);
+        }
+
+        // Stable, so each section keeps the search's ranking
+        if self.tree_view {
+            self.rebuild_tree();
+        } else if self.group_by.is_some() {
+            // Sections are in the order of their best result
+            let mut order = HashMap::new();
+            for path in &self.filtered_files {
+                let next = order.len();
+                order.entry(self.group_label(path)).or_insert(next);
+            }
+            let files = std::mem::take(&mut self.filtered_files);
+            let mut files: Vec<_> = files
+                .into_iter()
+                .map(|path| (order[&self.group_label(&path)], path))
+                .collect();
+            files.sort_by_key(|(order, _)| *order);
+            self.filtered_files = files.into_iter().map(|(_, path)| path).collect();
+        } else if self.file_filter == FileFilter::Dirty {
+            let statuses = &self.dirty_status;
+            self.filtered_files
+                .sort_by_key(|path| statuses.get(path).copied());
         }
 
         self.selected_index = self
             .selected_index
             .min(self.filtered_files.len().saturating_sub(1));
+        #[cfg(feature = "profiling")]
+        self.profile.searched(started, self.filtered_files.len());
     }
 
-    fn filter_by_filename(&mut self, query: String) {
-        let matcher = SkimMatcherV2::default();
-        self.filtered_files = self
-            .files
-            .iter()
-            .filter(|path| {
-                let path_str = path.to_string_lossy();
-                matcher.fuzzy_match(&path_str, &query).is_some()
-            })
-            .cloned()
-            .collect();
+    // Lays the results out as a tree, keeping the cursor on the same row where it can
+    fn rebuild_tree(&mut self) {
+        self.results_version += 1;
+        let selected = self
+            .tree_rows
+            .get(self.tree_cursor)
+            .map(|row| row.path.clone());
+        let (files, rows) =
+            tree::build(&self.filtered_files, &self.search_roots(), &self.collapsed);
+        self.filtered_files = files;
+        self.tree_rows = rows;
+        self.tree_cursor = selected
+            .and_then(|selected| self.tree_rows.iter().position(|row| row.path == selected))
+            .unwrap_or(0);
+        self.select_tree_row(self.tree_cursor);
     }
 
-    fn filter_by_contents(&mut self, query: String) {
-        if let Some(regex_matcher) = RegexMatcher::new(&query).ok() {
-            let mut searcher = SearcherBuilder::new()
-                .binary_detection(BinaryDetection::quit(0))
-                .build();
-
-            self.filtered_files = self
-                .files
-                .iter()
-                .filter(|path| {
-                    let mut found = false;
-                    let sink = UTF8(|_line_num, _line| {
-                        found = true;
-                        Ok(false) // Stop searching after first match
-                    });
-
-                    searcher
-                        .search_path(&regex_matcher, path, sink)
-                        .unwrap_or_else(|_| {
-                            found = false;
-                        });
-                    found
-                })
-                .cloned()
-                .collect();
-        } else {
-            self.filtered_files.clear();
+    fn select_tree_row(&mut self, row: usize) {
+        self.tree_cursor = row.min(self.tree_rows.len().saturating_sub(1));
+        if let Some(row) = self.tree_rows.get(self.tree_cursor) {
+            self.selected_index = row.file_index;
         }
+        self.restore_scroll();
     }
 
-    fn get_file_preview(&self) -> (Text<'static>, Option<u16>) {
-        if self.filtered_files.is_empty() {
-            return (Text::raw(""), None);
+    // Selects the file shown with hint `number`, returning whether there is one
+    fn select_hinted(&mut self, number: usize) -> bool {
+        let Some(&target) = number
+            .checked_sub(1)
+            .and_then(|index| self.hinted.get(index))
+        else {
+            return false;
+        };
+        if self.tree_view {
+            self.select_tree_row(target);
+        } else {
+            self.selected_index = target;
+            self.restore_scroll();
         }
-
-        let path = &self.filtered_files[self.selected_index];
-        get_file_preview(path, self.input.value(), self.search_mode)
+        true
     }
 
-    fn execute_command(&self) -> Result<()> {
-        if self.filtered_files.is_empty() {
-            return Ok(());
+    // Highlights the query in the snippets under each result, when there are snippets to show
+    fn snippet_matcher(&self) -> Option<RegexMatcher> {
+        if !self.two_line || self.search_mode != SearchMode::Contents || self.approximate {
+            return None;
         }
+        let query = self.query();
+        if query.text.is_empty() {
+            return None;
+        }
+        preview::match_highlighter(
+            &self.search_defaults.pattern(&query.text),
+            SearchMode::Contents,
+        )
+    }
 
-        let path = &self.filtered_files[self.selected_index];
-        let path_str = path.to_string_lossy();
-
-        // Split the command string into program and arguments
-        let mut parts = self.config.open_command.split_whitespace();
-        let program = parts.next().unwrap_or("edit");
-        let mut command = Command::new(program);
+    fn toggle_tree_view(&mut self) {
+        self.tree_view = !self.tree_view;
+        self.tree_rows.clear();
+        self.tree_cursor = 0;
+        self.selected_index = 0;
+        self.filter_files();
+        self.reset_scroll();
+    }
 
-        // Add any additional arguments from the config
-        command.args(parts);
+    // The directory under the cursor in the tree view
+    fn selected_tree_dir(&self) -> Option<&TreeRow> {
+        self.tree_rows
+            .get(self.tree_cursor)
+            .filter(|row| self.tree_view && row.is_dir)
+    }
 
-        // Add the file path as the final argument
-        command.arg(path_str.as_ref());
+    // The selected directory's README, read from the disk only when the selection moves
+    fn selected_readme(&self) -> Option<PathBuf> {
+        let dir = &self.selected_tree_dir()?.path;
+        let mut cached = self.tree_readme.borrow_mut();
+        match &*cached {
+            Some((looked_in, readme)) if looked_in == dir => readme.clone(),
+            _ => {
+                let readme = tree::readme(dir);
+                *cached = Some((dir.clone(), readme.clone()));
+                readme
+            }
+        }
+    }
 
-        command.spawn()?;
-        Ok(())
+    // Left collapses a directory, or moves up to the parent of a file or collapsed one
+    fn tree_left(&mut self) {
+        let Some(row) = self.tree_rows.get(self.tree_cursor) else {
+            return;
+        };
+        if row.is_dir && row.expanded {
+            self.collapsed.insert(row.path.clone());
+            self.rebuild_tree();
+        } else if let Some(parent) = self.tree_rows[..self.tree_cursor]
+            .iter()
+            .rposition(|parent| parent.depth < row.depth)
+        {
+            self.select_tree_row(parent);
+        }
     }
 
-    fn toggle_help(&mut self) {
-        self.show_help = !self.show_help;
+    // Right expands a directory, or steps into one that's already open
+    fn tree_right(&mut self) {
+        let Some(row) = self.tree_rows.get(self.tree_cursor) else {
+            return;
+        };
+        if !row.is_dir {
+            return;
+        }
+        if row.expanded {
+            self.select_tree_row(self.tree_cursor + 1);
+        } else {
+            self.collapsed.remove(&row.path.clone());
+            self.rebuild_tree();
+        }
     }
 
-    fn get_git_branch() -> String {
-        // First try to get the default branch name
-        let output = Command::new("git")
-            .args(["config", "--get", "init.defaultBranch"])
-            .output();
+    // Directory rows preview the results beneath them
+    fn directory_preview(&self, row: &TreeRow) -> Text<'static> {
+        let lines: Vec<Line> = self
+            .filtered_files
+            .iter()
+            .filter(|path| path.starts_with(&row.path))
+            .map(|path| Line::raw(self.display_path(path)))
+            .collect();
+        Text::from(lines)
+    }
 
-        if let Ok(o) = output {
-            if o.status.success() {
-                let branch = String::from_utf8_lossy(&o.stdout).trim().to_string();
-                if !branch.is_empty() {
-                    return branch;
+    // The section a file is listed under, if the list is split into sections
+    fn group_label(&self, path: &Path) -> Option<String> {
+        if self.tree_view {
+            return None;
+        }
+        match self.group_by {
+            Some(GroupBy::Directory) => Some(match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => {
+                    format!("{}/", self.display_path(dir))
                 }
-            }
+                _ => "./".to_string(),
+            }),
+            Some(GroupBy::Extension) => Some(match path.extension() {
+                Some(extension) => format!(".{}", extension.to_string_lossy()),
+                None => "(no extension)".to_string(),
+            }),
+            None if self.file_filter == FileFilter::Dirty => self
+                .dirty_status
+                .get(path)
+                .map(|status| status.label().to_string()),
+            None => None,
         }
+    }
 
-        // Fallback to "main" if we can't get the default branch
-        String::from("main")
+    fn cycle_group_by(&mut self) {
+        self.group_by = match self.group_by {
+            None => Some(GroupBy::Directory),
+            Some(GroupBy::Directory) => Some(GroupBy::Extension),
+            Some(GroupBy::Extension) => None,
+        };
+        self.folded.clear();
+        self.filter_files();
+        self.reset_scroll();
     }
 
-    fn scroll_preview(&mut self, frame_height: u16, delta: i16) {
-        // Calculate scroll amount as ~90% of visible area (excluding borders)
-        let scroll_amount = ((frame_height.saturating_sub(2)) as f32 * 0.9) as u16;
+    // Files in a folded section are hidden, except the first which stands in for its header
+    fn is_hidden(&self, index: usize) -> bool {
+        let Some(label) = self.group_label(&self.filtered_files[index]) else {
+            return false;
+        };
+        index > 0
+            && self.folded.contains(&label)
+            && self.group_label(&self.filtered_files[index - 1]).as_ref() == Some(&label)
+    }
 
-        if delta < 0 {
-            self.preview_scroll = self.preview_scroll.saturating_sub(scroll_amount);
-        } else {
-            self.preview_scroll = self.preview_scroll.saturating_add(scroll_amount);
+    // The list row the selection is drawn on, below the section headers and past the files
+    // folded away above it. A folded section's header stands in for its files.
+    fn selected_list_row(&self) -> usize {
+        let mut row: usize = 0;
+        let mut previous = None;
+        for path in self.filtered_files.iter().take(self.selected_index + 1) {
+            let group = self.group_label(path);
+            let folded = group
+                .as_ref()
+                .is_some_and(|group| self.folded.contains(group));
+            if group.is_some() && group != previous {
+                row += 1;
+            }
+            if !folded {
+                row += 1;
+            }
+            previous = group;
         }
+        row.saturating_sub(1)
     }
 
-    fn reset_scroll(&mut self) {
-        self.preview_scroll = 0;
+    fn toggle_fold(&mut self) {
+        let Some(label) = self
+            .filtered_files
+            .get(self.selected_index)
+            .and_then(|path| self.group_label(path))
+        else {
+            return;
+        };
+        if !self.folded.remove(&label) {
+            self.folded.insert(label);
+            // Land on the header of the folded section
+            while self.is_hidden(self.selected_index) {
+                self.selected_index -= 1;
+            }
+        }
     }
-}
 
-fn run_app() -> Result<()> {
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
-
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    let mut app = App::new();
-    app.filter_files();
-    let mut frame_height = 0;
+    // Moves the selection past any files hidden in folded sections
+    fn move_selection(&mut self, delta: isize) {
+        if self.tree_view {
+            self.select_tree_row(self.tree_cursor.saturating_add_signed(delta));
+            return;
+        }
+        let last = self.filtered_files.len().saturating_sub(1);
+        for _ in 0..delta.unsigned_abs() {
+            let mut index = self.selected_index;
+            loop {
+                let next = index.saturating_add_signed(delta.signum()).min(last);
+                if next == index {
+                    break;
+                }
+                index = next;
+                if !self.is_hidden(index) {
+                    self.selected_index = index;
+                    break;
+                }
+            }
+        }
+        self.restore_scroll();
+    }
 
-    loop {
-        terminal.draw(|frame| {
-            let layout = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-                .split(frame.size());
-
-            let right_layout = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(3),
-                    Constraint::Length(3),
-                    Constraint::Length(1),
-                ])
-                .split(layout[1]);
-            frame_height = right_layout[0].height;
-
-            let file_list = List::new(
-                app.filtered_files
-                    .iter()
-                    .enumerate()
-                    .map(|(i, path)| {
-                        let style = if i == app.selected_index {
-                            Style::default().bg(Color::DarkGray)
-                        } else {
-                            Style::default()
-                        };
-                        ListItem::new(path.to_string_lossy().into_owned()).style(style)
-                    })
-                    .collect::<Vec<_>>(),
-            )
-            .block(Block::default().borders(Borders::ALL).title("Files"));
+    // The syntax names a query's `lang:` tokens allow, or None without any
+    fn query_languages(&self, query: &Query) -> Option<Vec<&'static str>> {
+        (!query.langs.is_empty()).then(|| {
+            query
+                .langs
+                .iter()
+                .flat_map(|lang| preview::resolve_language(lang))
+                .collect()
+        })
+    }
 
-            let (preview_text, scroll_to) = app.get_file_preview();
-            let preview = Paragraph::new(preview_text.clone())
-                .block(Block::default().borders(Borders::ALL).title("Preview"))
-                .wrap(Wrap { trim: true });
+    fn matches_languages(&self, path: &Path, languages: Option<&[&str]>) -> bool {
+        let Some(languages) = languages else {
+            return true;
+        };
+        preview::detect_language(path, &self.preview_options.syntax_overrides)
+            .is_some_and(|language| languages.contains(&language))
+    }
 
-            // Calculate available height for preview (accounting for borders)
-            let available_height = right_layout[0].height.saturating_sub(2);
+    fn walk_files(&mut self, query: &Query) -> (Vec<PathBuf>, WalkStats) {
+        let (walked, mut stats) = self.cached_walk();
+        let mut index = std::mem::take(&mut self.file_index);
+        let roots = self.search_roots();
 
-            // Apply scrolling rules
-            let preview = if let Some(scroll_pos) = scroll_to {
-                if scroll_pos < 15 {
-                    preview
+        let languages = self.query_languages(query);
+        let mut files = Vec::new();
+        for path in walked {
+            let relative = roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .unwrap_or(&path);
+            if self.ignore_rules.is_ignored(&path, relative) {
+                stats.excluded += 1;
+                continue;
+            }
+            if query.has_path_filters() {
+                let metadata = if query.needs_metadata() {
+                    std::fs::metadata(&path).ok()
                 } else {
-                    let total_lines = preview_text.height() as u16;
-                    if total_lines <= available_height {
-                        preview
-                    } else {
-                        let adjusted_scroll = scroll_pos.saturating_sub(10);
-                        preview.scroll((adjusted_scroll + app.preview_scroll, 0))
+                    None
+                };
+                if !query.matches_path(&path, metadata.as_ref()) {
+                    stats.filtered += 1;
+                    continue;
+                }
+            }
+            let decompressed = self.config.search_compressed && compressed::is_compressed(&path);
+            if !decompressed && index.is_binary(&path) {
+                stats.binary += 1;
+                continue;
+            }
+            if index.is_unreadable(&path) {
+                stats.unreadable += 1;
+            }
+            if !self.matches_languages(&path, languages.as_deref()) {
+                stats.filtered += 1;
+                continue;
+            }
+            files.push(path);
+        }
+        self.file_index = index;
+        (files, stats)
+    }
+
+    // The last walk while the watcher says nothing new has shown up, otherwise a new one
+    fn cached_walk(&mut self) -> (Vec<PathBuf>, WalkStats) {
+        let roots: Vec<PathBuf> = self
+            .search_roots()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+        if let Some((walked_roots, files, stats)) = &self.walk_cache {
+            if *walked_roots == roots {
+                return (files.clone(), stats.clone());
+            }
+        }
+        if let Some(mut walking) = self.walking.take().filter(|walking| walking.roots == roots) {
+            let (files, mut stats) = walking.found();
+            // Files found so far stay in the order they were found until the walk is done,
+            // rather than being sorted again, and stat'ed for an mtime tiebreak, every refresh.
+            // That also keeps what was already searched at the front.
+            if !walking.is_done() {
+                self.walking = Some(walking);
+                return (files, stats);
+            }
+            let walked: Vec<&Path> = roots.iter().map(PathBuf::as_path).collect();
+            let key = self.search_defaults.walk_key();
+            self.file_index
+                .record_walk(&walked, key, &files, &mut stats);
+            let files = tiebreak::sort(files, &self.config.tiebreaks);
+            if self.watcher.as_ref().is_some_and(Watcher::is_ready) {
+                self.walk_cache = Some((roots, files.clone(), stats.clone()));
+            }
+            return (files, stats);
+        }
+        let (files, stats) =
+            self.walked_paths(&roots.iter().map(PathBuf::as_path).collect::<Vec<_>>());
+        // A walk that is still going is sorted and cached when it finishes, above
+        if self.walking.is_some() {
+            return (files, stats);
+        }
+        let files = tiebreak::sort(files, &self.config.tiebreaks);
+        if self.watcher.as_ref().is_some_and(Watcher::is_ready) {
+            self.walk_cache = Some((roots, files.clone(), stats.clone()));
+        }
+        (files, stats)
+    }
+
+    // Refreshes the results for whatever the watcher saw change, returning whether anything did
+    fn apply_changes(&mut self) -> bool {
+        let Some(watcher) = &mut self.watcher else {
+            return false;
+        };
+        let changes = match watcher.changes() {
+            Ok(changes) => changes,
+            Err(err) => {
+                self.watcher = None;
+                self.walk_cache = None;
+                self.message = Some(format!("Not watching for changes: {}", err));
+                return true;
+            }
+        };
+        if changes.is_empty() {
+            return false;
+        }
+
+        let mut refresh = !self.file_filter.is_walked();
+        // The preview on screen is of a file that changed
+        let mut showing = false;
+        let mut created = Vec::new();
+        for change in changes {
+            // What's generated or vendored can change with the attributes, for any file
+            let path = match &change {
+                Change::Modified(path) | Change::Removed(path) | Change::Created(path) => {
+                    Some(path)
+                }
+                Change::Rescan => None,
+            };
+            if path.is_some_and(|path| path.ends_with(".gitattributes")) {
+                self.forget_generated();
+                refresh = true;
+            }
+            match change {
+                Change::Modified(path) => {
+                    showing |= self.forget_file(&path);
+                    refresh = refresh
+                        || self
+                            .walk_cache
+                            .as_ref()
+                            .is_none_or(|(_, files, _)| files.contains(&path));
+                }
+                Change::Removed(path) => {
+                    showing |= self.forget_file(&path);
+                    if let Some((_, files, _)) = &mut self.walk_cache {
+                        let before = files.len();
+                        files.retain(|file| !file.starts_with(&path));
+                        refresh = refresh || files.len() != before;
+                    }
+                }
+                Change::Created(path) => created.push(path),
+                Change::Rescan => {
+                    self.walk_cache = None;
+                    self.walking = None;
+                    self.file_index.forget_walk();
+                    self.preview_limits.clear();
+                    self.forget_generated();
+                    self.tree_readme.take();
+                    self.duplicates_of = None;
+                    self.anchor_preview = None;
+                    self.previewer.clear();
+                    refresh = true;
+                }
+            }
+        }
+        // New files only need a walk in directories it found files in, not build output and
+        // other ignored places
+        if let Some((roots, files, _)) = &self.walk_cache {
+            let walked_dirs: HashSet<&Path> = files
+                .iter()
+                .filter_map(|file| file.parent())
+                .chain(roots.iter().map(PathBuf::as_path))
+                .collect();
+            if created
+                .iter()
+                .any(|path| path.parent().is_some_and(|dir| walked_dirs.contains(dir)))
+            {
+                self.walk_cache = None;
+                refresh = true;
+            }
+        }
+        if refresh {
+            self.filter_files();
+        }
+        refresh || showing
+    }
+
+    // Every file is checked again for being generated, by its contents and attributes
+    fn forget_generated(&mut self) {
+        self.generated_cache.clear();
+        self.linguist_cache.clear();
+    }
+
+    // Drops what's known about a file's contents after it changed, returning whether its
+    // preview is the one showing
+    fn forget_file(&mut self, path: &Path) -> bool {
+        self.file_index.invalidate(path);
+        self.preview_limits.remove(path);
+        self.generated_cache.remove(path);
+        self.row_metadata.remove(path);
+        self.duplicates_of = None;
+        self.walk_search = None;
+        if self.anchor.as_deref() == Some(path) {
+            self.anchor_preview = None;
+        }
+        // A README may have come or gone
+        let mut readme = self.tree_readme.borrow_mut();
+        if readme
+            .as_ref()
+            .is_some_and(|(dir, _)| path.parent() == Some(dir.as_path()))
+        {
+            *readme = None;
+        }
+        self.previewer.invalidate(path)
+    }
+
+    // Every file under the roots, from a `glancr --daemon` serving them with the same settings
+    // when there is one
+    fn walked_paths(&mut self, roots: &[&Path]) -> (Vec<PathBuf>, WalkStats) {
+        #[cfg(unix)]
+        {
+            let served: Option<Vec<_>> = roots
+                .iter()
+                .map(|root| daemon::files(root, &self.search_defaults))
+                .collect();
+            if let Some(served) = served {
+                let mut files = Vec::new();
+                let mut stats = WalkStats::default();
+                for (served_files, served_stats) in served {
+                    files.extend(served_files);
+                    stats.absorb(served_stats);
+                }
+                return (files, stats);
+            }
+        }
+        if let Some(files) = self.listed_paths(roots) {
+            let stats = WalkStats {
+                walked: files.len(),
+                ..WalkStats::default()
+            };
+            return (files, stats);
+        }
+        let key = self.search_defaults.walk_key();
+        if let Some(walked) = self.file_index.saved_walk(roots, key) {
+            return walked;
+        }
+        if !self.progressive {
+            let (files, mut stats) = walk(roots, &self.search_defaults);
+            self.file_index.record_walk(roots, key, &files, &mut stats);
+            return (files, stats);
+        }
+        let defaults = self.search_defaults.clone();
+        let owned: Vec<PathBuf> = roots.iter().map(|root| root.to_path_buf()).collect();
+        let mut walking = ProgressiveWalk::start(owned.clone(), move |found| {
+            let roots: Vec<&Path> = owned.iter().map(PathBuf::as_path).collect();
+            walk_with(&roots, &defaults, found)
+        });
+        // Small trees are done before anything's drawn, so the list doesn't flicker
+        walking.poll(WALK_PATIENCE);
+        let (files, mut stats) = walking.found();
+        if walking.is_done() {
+            self.file_index.record_walk(roots, key, &files, &mut stats);
+        } else {
+            self.walking = Some(walking);
+            self.walk_search = None;
+        }
+        (files, stats)
+    }
+
+    // Every file under the roots from git's index, when they're all in repositories and git
+    // would list what a walk finds, which saves walking and opening tracked files to see if
+    // they're binary
+    fn listed_paths(&mut self, roots: &[&Path]) -> Option<Vec<PathBuf>> {
+        let defaults = &self.search_defaults;
+        let plain = defaults.globs.is_empty()
+            && defaults.types.is_empty()
+            && defaults.types_not.is_empty()
+            && !defaults.no_ignore
+            && !defaults.follow;
+        if self.file_filter != FileFilter::All || !plain {
+            return None;
+        }
+        let listed: Vec<Vec<(PathBuf, Option<bool>)>> = roots
+            .iter()
+            .map(|root| git::ls_files(root))
+            .collect::<Option<_>>()?;
+        let mut files = Vec::new();
+        for (root, listed) in roots.iter().zip(listed) {
+            for (path, binary) in listed {
+                let hidden = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .iter()
+                    .any(|part| part.to_string_lossy().starts_with('.'));
+                if hidden && !self.search_defaults.hidden {
+                    continue;
+                }
+                if let Some(binary) = binary {
+                    self.file_index.record_listed(path.clone(), binary);
+                }
+                files.push(path);
+            }
+        }
+        Some(files)
+    }
+
+    // Walked files modified within the configured window, newest first
+    fn recently_modified_files(&mut self, query: &Query) -> (Vec<PathBuf>, WalkStats) {
+        let cutoff = SystemTime::now()
+            .checked_sub(self.config.modified_within)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let (files, stats) = self.walk_files(query);
+        let mut files: Vec<_> = files
+            .into_iter()
+            .filter_map(|path| {
+                let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                (modified >= cutoff).then_some((modified, path))
+            })
+            .collect();
+        files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        (files.into_iter().map(|(_, path)| path).collect(), stats)
+    }
+
+    // The start screen: frequently opened files, then the rest by most recently modified
+    fn recent_first(&self, files: &[PathBuf]) -> Vec<PathBuf> {
+        let roots: Vec<_> = self
+            .search_roots()
+            .into_iter()
+            .filter_map(|root| Some((root, root.canonicalize().ok()?)))
+            .collect();
+        let available: HashSet<&PathBuf> = files.iter().collect();
+
+        // History is kept by absolute path, so map it back onto the walked paths
+        let mut ordered: Vec<PathBuf> = self
+            .frecency
+            .ranked()
+            .into_iter()
+            .filter_map(|absolute| {
+                roots.iter().find_map(|(root, canonical)| {
+                    let path = root.join(absolute.strip_prefix(canonical).ok()?);
+                    available.contains(&path).then_some(path)
+                })
+            })
+            .collect();
+
+        let frecent: HashSet<PathBuf> = ordered.iter().cloned().collect();
+        let mut rest: Vec<_> = files
+            .iter()
+            .filter(|path| !frecent.contains(*path))
+            .map(|path| {
+                let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+                (modified.unwrap_or(SystemTime::UNIX_EPOCH), path.clone())
+            })
+            .collect();
+        rest.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        ordered.extend(rest.into_iter().map(|(_, path)| path));
+        ordered
+    }
+
+    fn is_generated(&mut self, path: &Path) -> bool {
+        if let Some(&generated) = self.generated_cache.get(path) {
+            return generated;
+        }
+        let generated = self.config.generated.is_generated(path);
+        self.generated_cache.insert(path.to_path_buf(), generated);
+        generated
+    }
+
+    fn filter_linguist_generated(&mut self) {
+        let unchecked: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|path| !self.linguist_cache.contains_key(*path))
+            .cloned()
+            .collect();
+        if !unchecked.is_empty() {
+            let excluded = git::linguist_excluded(&self.roots, &unchecked);
+            for path in unchecked {
+                let is_excluded = excluded.contains(&path);
+                self.linguist_cache.insert(path, is_excluded);
+            }
+        }
+
+        let cache = &self.linguist_cache;
+        self.files
+            .retain(|path| !cache.get(path).copied().unwrap_or(false));
+    }
+
+    fn filter_by_authors(&mut self, authors: &[String]) {
+        let log = self
+            .git_log
+            .get_or_insert_with(|| git::LogCache::load(&self.roots, self.config.author_log_depth));
+        let authored: Vec<_> = authors
+            .iter()
+            .map(|author| log.files_by_author(author))
+            .collect();
+
+        // Git reports paths without the leading ./ that the walker produces
+        self.files.retain(|path| {
+            let path = path.strip_prefix("./").unwrap_or(path);
+            authored.iter().any(|files| files.contains(path))
+        });
+    }
+
+    fn filter_by_filename(&mut self, query: String) {
+        // A leading ' asks for a contiguous substring instead of a fuzzy match, like fzf
+        if let Some(needle) = query.strip_prefix('\'') {
+            let needle = needle.to_lowercase();
+            self.filtered_files = self
+                .files
+                .iter()
+                .filter(|path| self.display_path(path).to_lowercase().contains(&needle))
+                .cloned()
+                .collect();
+            return;
+        }
+
+        let matcher = PathMatcher::default();
+        let mut scored: Vec<_> = self
+            .files
+            .iter()
+            .filter_map(|path| {
+                let display = self.display_path(path);
+                matcher
+                    .score(Path::new(&display), &query)
+                    .map(|score| (score, path))
+            })
+            .collect();
+
+        // Stable, so equally scored paths stay in tiebreak order
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.filtered_files = scored.into_iter().map(|(_, path)| path.clone()).collect();
+    }
+
+    fn filter_by_contents(&mut self, query: String) {
+        let regex_matcher = match RegexMatcher::new(&query) {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                // Keep showing the last results until the pattern compiles again
+                self.pattern_error = Some(regex_error_message(&err.to_string()));
+                return;
+            }
+        };
+
+        // The files a walk still going had found by the last refresh are at the front, in the
+        // same order, for as long as nothing else about the search changed
+        let key = (
+            query.clone(),
+            self.input.value().to_string(),
+            self.hide_generated,
+        );
+        let growing = self.walking.is_some() && self.file_filter == FileFilter::All;
+        let files = &self.files;
+        let resumed = self.walk_search.take().filter(|partial| {
+            growing
+                && partial.key == key
+                && partial.searched <= files.len()
+                && partial.last.as_ref() == partial.searched.checked_sub(1).map(|i| &files[i])
+        });
+        let start = resumed.as_ref().map_or(0, |partial| partial.searched);
+
+        let config = &self.config;
+        let max_size = config.max_search_file_size;
+        let limit = config.max_matches_per_file.max(1);
+        let skipped = AtomicUsize::new(0);
+        let found = performance::filter_map(
+            &self.files[start..],
+            config.performance.threads(),
+            || {
+                SearcherBuilder::new()
+                    .binary_detection(BinaryDetection::quit(0))
+                    .build()
+            },
+            |searcher, path| {
+                if std::fs::metadata(path).is_ok_and(|meta| meta.len() > max_size) {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                let matches = if config.search_compressed && compressed::is_compressed(path) {
+                    search::search_compressed(searcher, &regex_matcher, path, limit)
+                } else {
+                    search::search_file(searcher, &regex_matcher, path, limit)
+                };
+                matches.is_match().then(|| (path.to_path_buf(), matches))
+            },
+        );
+        let mut skipped = skipped.into_inner();
+        let found = if growing {
+            let mut partial = resumed.unwrap_or(WalkSearch {
+                key,
+                searched: 0,
+                last: None,
+                found: Vec::new(),
+                skipped_large: 0,
+            });
+            partial.found.extend(found);
+            partial.skipped_large += skipped;
+            partial.searched = self.files.len();
+            partial.last = self.files.last().cloned();
+            skipped = partial.skipped_large;
+            let found = partial.found.clone();
+            self.walk_search = Some(partial);
+            found
+        } else {
+            found
+        };
+        self.skipped_large += skipped;
+        self.file_matches.clear();
+        let mut filtered_files = Vec::with_capacity(found.len());
+        for (path, matches) in found {
+            filtered_files.push(path.clone());
+            self.file_matches.insert(path, matches);
+        }
+        self.filtered_files = filtered_files;
+    }
+
+    // Files with a line close to the query, most similar first
+    fn filter_by_approximate(&mut self, query: &str) {
+        let matcher = ApproxMatcher::new(query);
+        let max_size = self.config.max_search_file_size;
+        let limit = self.config.max_matches_per_file.max(1);
+        self.file_matches.clear();
+
+        let mut scored = Vec::new();
+        for path in &self.files {
+            if std::fs::metadata(path).is_ok_and(|meta| meta.len() > max_size) {
+                self.skipped_large += 1;
+                continue;
+            }
+            // Binary and unreadable files aren't valid UTF-8, so they're skipped here
+            let contents = if self.config.search_compressed && compressed::is_compressed(path) {
+                compressed::read_to_string(path, u64::MAX).map(|(contents, _)| contents)
+            } else {
+                std::fs::read_to_string(path)
+            };
+            let Ok(contents) = contents else {
+                continue;
+            };
+
+            let mut matches = FileMatches::default();
+            let mut best = usize::MAX;
+            for (index, line) in contents.lines().enumerate() {
+                let Some(found) = matcher.find(line) else {
+                    continue;
+                };
+                if matches.lines.len() >= limit {
+                    matches.truncated = true;
+                    break;
+                }
+                best = best.min(found.distance);
+                matches.lines.push(search::LineMatch {
+                    line_number: index as u64 + 1,
+                    column: found.range.start as u64 + 1,
+                    text: line.trim_end().to_string(),
+                    captures: Vec::new(),
+                });
+            }
+            if matches.is_match() {
+                scored.push((matcher.similarity(best), path.clone()));
+                self.file_matches.insert(path.clone(), matches);
+            }
+        }
+
+        // Stable, so equally similar files stay in tiebreak order
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        self.filtered_files = scored.into_iter().map(|(_, path)| path).collect();
+    }
+
+    // The exact text approximate matches found in a file, as a pattern for highlighting them
+    fn approximate_pattern(&self, path: &Path, query: &str) -> String {
+        let matcher = ApproxMatcher::new(query);
+        let mut snippets: Vec<String> = self
+            .file_matches
+            .get(path)
+            .into_iter()
+            .flat_map(|matches| &matches.lines)
+            .filter_map(|line| {
+                let found = matcher.find(&line.text)?;
+                Some(search::escape_regex(&line.text[found.range]))
+            })
+            .collect();
+        snippets.sort();
+        snippets.dedup();
+        snippets.join("|")
+    }
+
+    // How the current query is being interpreted, shown next to the input
+    fn query_status(&self) -> Line<'static> {
+        let query = self.query();
+        let dim = Style::default().fg(Color::DarkGray);
+        if query.text.is_empty() {
+            return Line::default();
+        }
+        if self.search_due.is_some() && !self.accessible {
+            return Line::styled(format!(" {} ", SPINNER[self.spinner]), dim);
+        }
+
+        let (kind, ignores_case) = match self.search_mode {
+            SearchMode::Filename if query.text.starts_with('\'') => ("exact", true),
+            // The fuzzy matcher is smart case
+            SearchMode::Filename => ("fuzzy", !query.text.chars().any(char::is_uppercase)),
+            SearchMode::Contents if self.approximate => ("approx", true),
+            SearchMode::Contents if query.replace.is_some() => (
+                "replace",
+                self.search_defaults.ignores_case(&query.text) || query.text.starts_with("(?i)"),
+            ),
+            SearchMode::Contents => {
+                let kind = if query.text.contains(search::is_regex_meta) {
+                    "regex"
+                } else {
+                    "literal"
+                };
+                let ignores_case = self.search_defaults.ignores_case(&query.text)
+                    || query.text.starts_with("(?i)");
+                (kind, ignores_case)
+            }
+        };
+
+        let mut spans = vec![
+            Span::styled(format!(" {} ", kind), dim),
+            Span::styled(if ignores_case { "aa " } else { "Aa " }, dim),
+        ];
+        if self.pattern_error.is_some() {
+            spans.push(Span::styled("✗ ", Style::default().fg(Color::Red)));
+        } else {
+            spans.push(Span::styled("✓ ", Style::default().fg(Color::Green)));
+        }
+        Line::from(spans)
+    }
+
+    // The preview, where to scroll it, and whether that's exact because only part of a large
+    // file was loaded
+    fn get_file_preview(&mut self) -> Preview {
+        if self.filtered_files.is_empty() {
+            return (Text::raw(""), None, false);
+        }
+
+        if let Some(row) = self.selected_tree_dir() {
+            let Some(readme) = self.selected_readme() else {
+                return (self.directory_preview(row), None, false);
+            };
+            let request = self.preview_request(readme);
+            return self.previewer.get(request);
+        }
+
+        let path = self.filtered_files[self.selected_index].clone();
+        if self.show_history {
+            return (self.history_preview(path), None, false);
+        }
+        match &self.anchor {
+            Some(anchor) if self.show_diff => (
+                compare::diff_preview(anchor, &path, self.config.max_search_file_size),
+                None,
+                false,
+            ),
+            _ => {
+                if let Some(preview) = self.plugin_preview(&path) {
+                    return preview;
+                }
+                let group = self.duplicate_group(&path).map(<[PathBuf]>::to_vec);
+                let request = self.preview_request(path.clone());
+                let preview = self.previewer.get(request);
+                match group {
+                    Some(group) => self.with_duplicates(preview, &path, &group),
+                    None => preview,
+                }
+            }
+        }
+    }
+
+    // Dims the row of a file that can't be read, behind a lock
+    fn mark_unreadable(&self, line: &mut Line<'static>, path: &Path) {
+        if !self.file_index.is_unreadable(path) {
+            return;
+        }
+        let lock = if self.accessible {
+            "unreadable: "
+        } else {
+            "🔒 "
+        };
+        line.spans.insert(0, Span::raw(lock));
+        line.patch_style(Style::default().fg(Color::DarkGray));
+    }
+
+    // Counts the other paths a file was reached by through links, after its name
+    fn mark_linked(&self, line: &mut Line<'static>, path: &Path) {
+        let Some(others) = self
+            .walk_stats
+            .as_ref()
+            .and_then(|stats| stats.linked.get(path))
+        else {
+            return;
+        };
+        let label = match others.len() {
+            1 => " (+1 link)".to_string(),
+            count => format!(" (+{} links)", count),
+        };
+        line.spans
+            .push(Span::styled(label, Style::default().fg(Color::DarkGray)));
+    }
+
+    // Says after a file's name why its preview is cut short
+    fn mark_limited(&self, line: &mut Line<'static>, path: &Path) {
+        let Some(limit) = self.preview_limits.get(path).copied().flatten() else {
+            return;
+        };
+        let badge = if self.accessible {
+            format!(" ({})", limit.badge())
+        } else {
+            format!(" ⚠ {}", limit.badge())
+        };
+        line.spans
+            .push(Span::styled(badge, Style::default().fg(Color::Yellow)));
+    }
+
+    // The copies of a file, itself included, while the duplicates filter is on
+    fn duplicate_group(&self, path: &Path) -> Option<&[PathBuf]> {
+        if self.file_filter != FileFilter::Duplicates {
+            return None;
+        }
+        self.duplicate_groups
+            .iter()
+            .find(|group| group.iter().any(|member| member == path))
+            .map(Vec::as_slice)
+    }
+
+    // Lists the copies above the file's contents, or leaves a windowed preview of a large file
+    // alone, where the scroll position has to stay exact
+    fn with_duplicates(&self, preview: Preview, path: &Path, group: &[PathBuf]) -> Preview {
+        let (mut text, scroll_to, windowed) = preview;
+        if windowed {
+            return (text, scroll_to, windowed);
+        }
+        let mut header = vec![Line::styled(
+            format!("{} identical files:", group.len()),
+            Style::default().fg(Color::Yellow),
+        )];
+        for member in group {
+            let style = if member == path {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            header.push(Line::styled(self.display_path(member), style));
+        }
+        header.push(Line::from(""));
+        let offset = header.len();
+        text.lines.splice(0..0, header);
+        (text, scroll_to.map(|line| line + offset), windowed)
+    }
+
+    // The anchor's preview, read again only once something it depends on changes
+    fn anchor_preview(&mut self) -> Option<Text<'static>> {
+        let anchor = self.anchor.clone()?;
+        let request = self.preview_request(anchor.clone());
+        match &self.anchor_preview {
+            Some((shown, text)) if *shown == request => Some(text.clone()),
+            _ => {
+                let (text, _, _) = self.preview_path(&anchor);
+                self.anchor_preview = Some((request, text.clone()));
+                Some(text)
+            }
+        }
+    }
+
+    // A file's preview, read right away rather than on the preview thread
+    fn preview_path(&self, path: &Path) -> Preview {
+        self.plugin_preview(path).unwrap_or_else(|| {
+            self.preview_request(path.to_path_buf())
+                .load(&self.preview_options)
+        })
+    }
+
+    #[cfg(feature = "wasm")]
+    fn plugin_preview(&self, path: &Path) -> Option<Preview> {
+        match self.wasm_plugins.preview(path)? {
+            Ok(text) => Some((Text::raw(text), None, false)),
+            Err(err) => Some((Text::raw(format!("{:#}", err)), None, false)),
+        }
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn plugin_preview(&self, _path: &Path) -> Option<Preview> {
+        None
+    }
+
+    fn preview_request(&self, path: PathBuf) -> PreviewRequest {
+        let line_numbers = self.preview_options.line_numbers;
+        if let Some(search) = self.active_preview_search(&path) {
+            return PreviewRequest {
+                pattern: search::literal_pattern(&search.term),
+                path,
+                mode: SearchMode::Contents,
+                scroll: 0,
+                line_numbers,
+                replace: None,
+            };
+        }
+
+        let query = self.query();
+        // Only a regex search of contents reads the query as a substitution
+        let replace = query.replace.clone();
+        let pattern = match self.search_mode {
+            SearchMode::Contents if self.approximate && !query.text.is_empty() => {
+                self.approximate_pattern(&path, &query.text)
+            }
+            SearchMode::Contents if !query.text.is_empty() => {
+                self.search_defaults.pattern(&query.text)
+            }
+            SearchMode::Filename if !self.config.highlight_filename_query => String::new(),
+            _ => query.text,
+        };
+        PreviewRequest {
+            path,
+            pattern,
+            mode: self.search_mode,
+            scroll: self.preview_scroll,
+            line_numbers,
+            replace,
+        }
+    }
+
+    // Opens the marked files, or the selected one when nothing is marked or `with_marked` is
+    // false because a particular result was picked. Returns whether the files were opened,
+    // which a failing `before_open` hook prevents.
+    fn execute_command(&mut self, with_marked: bool) -> Result<bool> {
+        self.run_due_search();
+        if self.filtered_files.is_empty() {
+            return Ok(false);
+        }
+        let paths = if with_marked {
+            self.selected_paths()
+        } else {
+            vec![self.filtered_files[self.selected_index].clone()]
+        };
+        if let Some(hook) = &self.config.hooks.before_open {
+            if let Err(err) = commands::run_hook(hook, &self.hook_env(&paths)) {
+                self.message = Some(format!("before_open hook failed: {}", err));
+                return Ok(false);
+            }
+        }
+
+        // The config guarantees a program, followed by any additional arguments
+        let (program, args) = self
+            .config
+            .open_command
+            .split_first()
+            .ok_or_else(|| anyhow!("open_command is empty"))?;
+
+        // Editors with a preset get every marked file in one command, others one command each
+        let preset = match paths.len() {
+            1 => None,
+            _ => self.config.editor_preset(program),
+        };
+        let mut commands = Vec::new();
+        if let Some(preset) = &preset {
+            let mut command = Command::new(program);
+            command.args(commands::open_all_args(
+                args,
+                preset,
+                &paths,
+                &self.file_matches,
+            ));
+            commands.push(command);
+        } else {
+            for path in &paths {
+                let mut command = Command::new(program);
+                command.args(commands::open_args(args, path, &self.file_matches));
+                commands.push(command);
+            }
+        }
+        // A terminal editor waits until glancr has let go of the terminal
+        if self.config.is_terminal_editor() {
+            self.foreground = commands;
+        } else {
+            for mut command in commands {
+                command.spawn()?;
+            }
+        }
+
+        for path in paths {
+            self.frecency.record(&path);
+            #[cfg(feature = "lua")]
+            if let Some(plugins) = &self.plugins {
+                let line = self
+                    .file_matches
+                    .get(&path)
+                    .map_or(1, |matches| matches.position().0);
+                // The editor is already running, so a failing hook only gets a mention
+                if let Err(err) = plugins.opened(&path, line) {
+                    self.message = Some(format!("Lua: {:#}", err));
+                }
+            }
+        }
+        self.frecency.save();
+        Ok(true)
+    }
+
+    // Applies a request from the control socket, returning its one-line JSON reply
+    #[cfg(unix)]
+    fn handle_control(&mut self, request: control::Request) -> String {
+        let before = self.query_state();
+        match request {
+            control::Request::Query(query) => {
+                self.input = TextInput::new(query);
+                self.selected_index = 0;
+            }
+            control::Request::Mode(mode) => self.search_mode = mode,
+            control::Request::Filter(filter) => self.file_filter = filter,
+            control::Request::Selection => {
+                // Absolute, since the other end doesn't know where glancr was started
+                let absolute = |path: &PathBuf| {
+                    std::fs::canonicalize(path)
+                        .unwrap_or_else(|_| path.clone())
+                        .to_string_lossy()
+                        .into_owned()
+                };
+                let selected = self.filtered_files.get(self.selected_index);
+                let (line, column) = selected
+                    .and_then(|path| self.file_matches.get(path))
+                    .map_or((1, 1), FileMatches::position);
+                return serde_json::json!({
+                    "selected": selected.map(absolute),
+                    "line": line,
+                    "column": column,
+                    "marked": self.marked.iter().map(absolute).collect::<Vec<_>>(),
+                    "query": self.input.value(),
+                    "mode": self.search_mode.name(),
+                })
+                .to_string();
+            }
+        }
+        self.filter_files();
+        self.reset_scroll();
+        self.record_change(before, false);
+        serde_json::json!({ "ok": true }).to_string()
+    }
+
+    // Variables describing the selection, for hook scripts
+    fn hook_env(&self, paths: &[PathBuf]) -> Vec<(&'static str, String)> {
+        let file = paths.first();
+        let (line, column) = file
+            .and_then(|path| self.file_matches.get(path))
+            .map_or((1, 1), FileMatches::position);
+        let files: Vec<String> = paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        vec![
+            (
+                "GLANCR_FILE",
+                file.map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            ("GLANCR_FILES", files.join("\n")),
+            ("GLANCR_LINE", line.to_string()),
+            ("GLANCR_COLUMN", column.to_string()),
+            ("GLANCR_QUERY", self.input.value().to_string()),
+            ("GLANCR_MODE", self.search_mode.name().to_string()),
+        ]
+    }
+
+    // Writes the results as `path:line:col:text` for an editor's quickfix list
+    fn export_results(&self, output: Option<&Path>) -> Result<()> {
+        let results = export::quickfix(&self.filtered_files, &self.file_matches);
+        match output {
+            Some(path) if path != Path::new("-") => std::fs::write(path, results)
+                .with_context(|| format!("Unable to write results to {}", path.display())),
+            _ => Ok(stdout().write_all(results.as_bytes())?),
+        }
+    }
+
+    // The result list in the configured format, one entry per line
+    fn results_text(&self) -> String {
+        export::results(
+            self.config.results_format,
+            &self.filtered_files,
+            &self.file_matches,
+        )
+    }
+
+    fn copy_results(&mut self) {
+        if self.filtered_files.is_empty() {
+            self.message = Some("No results to copy".to_string());
+            return;
+        }
+        self.message = Some(match clipboard::copy(&self.results_text()) {
+            Ok(via) => format!("Copied {} results with {}", self.filtered_files.len(), via),
+            Err(err) => format!("Unable to copy the results: {:#}", err),
+        });
+    }
+
+    // Opens the selected file on the forge the repository is hosted on, at its first match, or
+    // copies the link instead
+    fn share_url(&mut self, copy: bool) {
+        let Some(path) = self.filtered_files.get(self.selected_index) else {
+            return;
+        };
+        let line = self
+            .file_matches
+            .get(path)
+            .map(|matches| matches.position().0);
+        let shared = forge::url_for(path, line).and_then(|url| {
+            if copy {
+                let via = clipboard::copy(&url)?;
+                Ok(format!("Copied {} with {}", url, via))
+            } else {
+                forge::open_in_browser(&url)?;
+                Ok(format!("Opened {}", url))
+            }
+        });
+        self.message = Some(match shared {
+            Ok(message) => message,
+            Err(err) => format!("{:#}", err),
+        });
+    }
+
+    fn write_results(&mut self, path: &Path) {
+        self.message = Some(match std::fs::write(path, self.results_text()) {
+            Ok(()) => format!(
+                "Wrote {} results to {}",
+                self.filtered_files.len(),
+                path.display()
+            ),
+            Err(err) => format!("Unable to write {}: {}", path.display(), err),
+        });
+    }
+
+    // Opens the command line with the pattern that hides the selected result's directory, to
+    // run as it is or edit first
+    fn start_ignore(&mut self) {
+        let pattern = match self.filtered_files.get(self.selected_index) {
+            Some(path) => ignorefile::pattern_for(self.ignore_root(), path),
+            None => String::new(),
+        };
+        self.command_line = Some(TextInput::new(format!("ignore {}", pattern)));
+    }
+
+    // The root whose .glancrignore hides the selected result, which is the root it's under
+    fn ignore_root(&self) -> &Path {
+        let selected = self.filtered_files.get(self.selected_index);
+        self.roots
+            .iter()
+            .find(|root| selected.is_some_and(|path| path.starts_with(root)))
+            .unwrap_or(&self.roots[0])
+    }
+
+    // Adds the pattern to the .glancrignore of the selected result's root and walks again
+    // without what it matches
+    fn ignore(&mut self, pattern: &str) {
+        match ignorefile::append(self.ignore_root(), pattern) {
+            Ok(file) => {
+                self.walk_cache = None;
+                self.walking = None;
+                self.filter_files();
+                self.reset_scroll();
+                self.message = Some(format!(
+                    "Added {} to {}, {} results",
+                    pattern,
+                    file.display(),
+                    self.filtered_files.len()
+                ));
+            }
+            Err(err) => self.message = Some(format!("{:#}", err)),
+        }
+    }
+
+    // The configured editor's program and arguments, if it can load a quickfix errorfile
+    fn quickfix_editor(&self) -> Option<&[String]> {
+        let parts = &self.config.open_command;
+        let program = Path::new(parts.first()?).file_name()?.to_str()?;
+        matches!(program, "vi" | "vim" | "nvim" | "gvim" | "mvim").then_some(parts)
+    }
+
+    // Opens every result in the editor's quickfix list, waiting for it to exit
+    fn open_quickfix(&self) -> Result<()> {
+        let Some(parts) = self.quickfix_editor() else {
+            return Ok(());
+        };
+        let errorfile = std::env::temp_dir().join("glancr.qf");
+        self.export_results(Some(&errorfile))?;
+
+        // Placeholders point at a single file, which the quickfix list replaces
+        let placeholders = ["{file}", "{line}", "{col}"];
+        Command::new(&parts[0])
+            .args(
+                parts[1..]
+                    .iter()
+                    .filter(|arg| !placeholders.iter().any(|p| arg.contains(p))),
+            )
+            .arg("-q")
+            .arg(&errorfile)
+            .status()
+            .with_context(|| format!("Unable to run {}", parts[0]))?;
+        Ok(())
+    }
+
+    fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+    }
+
+    // Keys go to the help while it's open rather than the query. F1 and Ctrl+h, which open
+    // it, close it again, as do Esc and q. Returns whether to quit, for Ctrl+c or a quit key
+    // other than Esc.
+    fn handle_help_key(&mut self, key: KeyEvent) -> bool {
+        if key.code != KeyCode::Esc && self.config.quit_keys.iter().any(|quit| quit.matches(&key)) {
+            return self.quit_now();
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return true,
+            KeyCode::Char('h') if key.modifiers == KeyModifiers::CONTROL => self.show_help = false,
+            KeyCode::Esc | KeyCode::F(1) | KeyCode::Char('q') => self.show_help = false,
+            _ => {}
+        }
+        false
+    }
+
+    fn scroll_preview(&mut self, frame_height: u16, delta: i16) {
+        // Calculate scroll amount as ~90% of visible area (excluding borders)
+        let scroll_amount = ((frame_height.saturating_sub(2)) as f32 * 0.9) as usize;
+
+        if delta < 0 {
+            self.preview_scroll = self.preview_scroll.saturating_sub(scroll_amount);
+        } else {
+            self.preview_scroll = self.preview_scroll.saturating_add(scroll_amount);
+        }
+
+        if let Some(path) = self.filtered_files.get(self.selected_index) {
+            self.scroll_positions
+                .insert(path.clone(), self.preview_scroll);
+        }
+    }
+
+    // Readline-style editing of the search box, refiltering whenever the text changes
+    fn edit_input(&mut self, key: KeyEvent) {
+        let request = match (key.code, key.modifiers) {
+            (KeyCode::Char('b'), KeyModifiers::ALT) => Some(InputRequest::GoToPrevWord),
+            (KeyCode::Char('f'), KeyModifiers::ALT) => Some(InputRequest::GoToNextWord),
+            _ => to_input_request(&Event::Key(key)),
+        };
+        let Some(request) = request else {
+            return;
+        };
+        if self
+            .input
+            .handle(request)
+            .is_some_and(|changed| changed.value)
+        {
+            let delay = self.config.search_delay();
+            if self.search_mode == SearchMode::Contents && !delay.is_zero() {
+                self.search_due = Some(Instant::now() + delay);
+            } else {
+                self.refilter();
+            }
+            self.reset_scroll();
+        }
+    }
+
+    // Runs a search that was put off, before anything acts on the results
+    fn run_due_search(&mut self) {
+        if self.search_due.take().is_some() {
+            self.refilter();
+        }
+    }
+
+    // Catches up on whatever happened while waiting for input, returning whether it changed
+    // what's on screen
+    fn tick(&mut self) -> bool {
+        let mut changed =
+            self.apply_changes() | self.previewer.poll() | self.poll_history(Duration::ZERO);
+        if let Some(walking) = &mut self.walking {
+            walking.poll(Duration::ZERO);
+            if walking.is_due() {
+                self.filter_files();
+                changed = true;
+            }
+        }
+        self.previewer
+            .set_watched(self.watcher.as_ref().is_some_and(Watcher::is_ready));
+        if self.search_due.is_some_and(|due| Instant::now() >= due) {
+            self.run_due_search();
+            changed = true;
+        }
+        if self.is_busy() && !self.accessible {
+            let since_epoch = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            let frame = (since_epoch.as_millis() / SPINNER_FRAME) as usize % SPINNER.len();
+            changed |= frame != self.spinner;
+            self.spinner = frame;
+        }
+        changed
+    }
+
+    fn is_busy(&self) -> bool {
+        self.search_due.is_some()
+            || self.previewer.is_loading()
+            || self.walking.is_some()
+            || self.history_loading.is_some()
+    }
+
+    // How long to wait for input before the next tick, shorter while something's on its way
+    fn tick_interval(&self) -> Duration {
+        if self.is_busy() {
+            BUSY_TICK
+        } else {
+            self.config.performance.tick(TICK)
+        }
+    }
+
+    fn is_open(&self, overlay: Overlay) -> bool {
+        match overlay {
+            Overlay::Help => self.show_help,
+            Overlay::SavedPicker => self.saved_picker.is_some(),
+            Overlay::LanguagePicker => self.language_picker.is_some(),
+            Overlay::CommandPrompt => self.command_prompt.is_some(),
+            Overlay::Palette => self.palette.is_some(),
+            Overlay::Pager => self.pager.is_some(),
+            Overlay::RenamePrompt => self.rename_prompt.is_some(),
+            Overlay::BatchRename => self.batch_rename.is_some(),
+            Overlay::ConfirmQuit => self.confirm_quit,
+            Overlay::ConfirmDelete => self.confirm_delete.is_some(),
+            Overlay::CaptureSurvey => self.capture_survey.is_some(),
+            Overlay::StatsPopup => self.stats_popup.is_some(),
+            Overlay::ConfigProblems => self.config_problems.is_some(),
+            Overlay::CommandOutput => self.command_output.is_some(),
+            Overlay::CommandLine => self.command_line.is_some(),
+        }
+    }
+
+    // Brings the stack up to date with what's open, wherever it was opened from: closed
+    // overlays drop out and new ones go on top
+    fn sync_overlays(&mut self) {
+        let mut overlays = std::mem::take(&mut self.overlays);
+        overlays.retain(|overlay| self.is_open(*overlay));
+        for overlay in Overlay::ALL {
+            if self.is_open(overlay) && !overlays.contains(&overlay) {
+                overlays.push(overlay);
+            }
+        }
+        self.overlays = overlays;
+    }
+
+    fn screen_state(&self) -> ScreenState {
+        let overlay = Overlay::ALL.iter().any(|overlay| self.is_open(*overlay))
+            || self.preview_search.is_some();
+        ScreenState {
+            overlay,
+            query: self.query_state(),
+            results: self.results_version,
+            selected: (self.selected_index, self.tree_cursor),
+            preview_scroll: self.preview_scroll,
+            focus: self.focus,
+            message: self.message.clone(),
+            views: [
+                self.approximate,
+                self.hide_generated,
+                self.tree_view,
+                self.two_line,
+                self.show_history,
+                self.show_diff,
+            ],
+            line_numbers: self.preview_options.line_numbers,
+            anchor: self.anchor.clone(),
+            group_by: self.group_by,
+            marks: (
+                self.marked.len(),
+                self.scopes.len(),
+                self.folded.len(),
+                self.collapsed.len(),
+            ),
+        }
+    }
+
+    fn query_state(&self) -> QueryState {
+        QueryState {
+            query: self.input.value().to_string(),
+            cursor: self.input.cursor(),
+            search_mode: self.search_mode,
+            file_filter: self.file_filter,
+        }
+    }
+
+    // Pushes the state from before a key press if the key changed the search
+    fn record_change(&mut self, before: QueryState, typing: bool) {
+        let current = self.query_state();
+        if current.query == before.query
+            && current.search_mode == before.search_mode
+            && current.file_filter == before.file_filter
+        {
+            return;
+        }
+        if !(typing && self.typing) {
+            self.history.push(before);
+        }
+        self.typing = typing;
+    }
+
+    fn restore_query_state(&mut self, state: QueryState) {
+        self.input = TextInput::new(state.query).with_cursor(state.cursor);
+        self.search_mode = state.search_mode;
+        self.file_filter = state.file_filter;
+        self.typing = false;
+        self.refilter();
+        self.reset_scroll();
+    }
+
+    fn undo(&mut self) {
+        let current = self.query_state();
+        if let Some(state) = self.history.undo(current) {
+            self.restore_query_state(state);
+        }
+    }
+
+    fn redo(&mut self) {
+        let current = self.query_state();
+        if let Some(state) = self.history.redo(current) {
+            self.restore_query_state(state);
+        }
+    }
+
+    // Switches modes, bringing back the query the other mode had so flipping between them
+    // doesn't mean typing it again. The first switch takes the current query along.
+    fn switch_mode(&mut self, mode: SearchMode) {
+        if mode == self.search_mode {
+            self.filter_files();
+            self.reset_scroll();
+            return;
+        }
+        let current = (self.input.clone(), self.selected_index);
+        let (input, selected) = self
+            .other_mode
+            .replace(current)
+            .unwrap_or((self.input.clone(), self.selected_index));
+        self.input = input;
+        self.selected_index = selected;
+        self.search_mode = mode;
+        self.refilter();
+        self.reset_scroll();
+    }
+
+    fn reset_scroll(&mut self) {
+        self.preview_scroll = 0;
+    }
+
+    // Returns to where the selected file was last scrolled to this session
+    fn restore_scroll(&mut self) {
+        self.preview_scroll = self
+            .filtered_files
+            .get(self.selected_index)
+            .and_then(|path| self.scroll_positions.get(path))
+            .copied()
+            .unwrap_or(0);
+    }
+
+    fn search_roots(&self) -> Vec<&Path> {
+        match self.scopes.last() {
+            Some(scope) => vec![scope.as_path()],
+            None => self.roots.iter().map(PathBuf::as_path).collect(),
+        }
+    }
+
+    // Paths are shown relative to their root, prefixed with the root's name in a workspace
+    fn display_path(&self, path: &Path) -> String {
+        if self.roots.len() == 1 && self.roots[0] == Path::new(".") {
+            return path.to_string_lossy().into_owned();
+        }
+
+        for root in &self.roots {
+            if let Ok(relative) = path.strip_prefix(root) {
+                if self.roots.len() == 1 {
+                    return relative.to_string_lossy().into_owned();
+                }
+                let name = root
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_else(|| root.to_string_lossy());
+                return Path::new(name.as_ref())
+                    .join(relative)
+                    .to_string_lossy()
+                    .into_owned();
+            }
+        }
+        path.to_string_lossy().into_owned()
+    }
+
+    // Re-roots the search at the directory containing the selected file
+    fn push_scope(&mut self) {
+        let Some(dir) = self
+            .filtered_files
+            .get(self.selected_index)
+            .and_then(|path| path.parent())
+            .filter(|dir| !dir.as_os_str().is_empty() && !self.search_roots().contains(dir))
+        else {
+            return;
+        };
+        self.scopes.push(dir.to_path_buf());
+        self.selected_index = 0;
+        self.filter_files();
+        self.reset_scroll();
+    }
+
+    fn pop_scope(&mut self) {
+        if self.scopes.pop().is_some() {
+            self.selected_index = 0;
+            self.filter_files();
+            self.reset_scroll();
+        }
+    }
+
+    // Names the current search mode
+    fn search_label(&self) -> &'static str {
+        match self.search_mode {
+            SearchMode::Filename if self.query().text.starts_with('\'') => "Exact Filename Search",
+            SearchMode::Filename => "Filename Search",
+            SearchMode::Contents if self.approximate => "Approximate Content Search",
+            SearchMode::Contents => "Content Search",
+        }
+    }
+
+    // The selection, where it is in the results and the mode, on the one line an
+    // accessible session reads out
+    fn announcement(&self) -> String {
+        let mode = self.search_label();
+        let Some(path) = self.filtered_files.get(self.selected_index) else {
+            return format!("No results. {}. F1 for help", mode);
+        };
+        let matches = self
+            .file_matches
+            .get(path)
+            .map(|matches| format!(", {} matches", matches.count_label()))
+            .unwrap_or_default();
+        let marked = match self.marked.len() {
+            0 => String::new(),
+            count => format!(", {} marked", count),
+        };
+        format!(
+            "{} of {}: {}{}{}. {}. F1 for help",
+            self.selected_index + 1,
+            self.filtered_files.len(),
+            self.display_path(path),
+            matches,
+            marked,
+            mode
+        )
+    }
+
+    fn preview_title(&self) -> String {
+        match (&self.anchor, self.filtered_files.get(self.selected_index)) {
+            (Some(anchor), Some(path)) if self.show_diff => format!(
+                "Diff: {} → {}",
+                self.display_path(anchor),
+                self.display_path(path)
+            ),
+            _ if self.selected_tree_dir().is_some() => match self.selected_readme() {
+                Some(readme) => format!("README: {}", self.display_path(&readme)),
+                None => "Preview".to_string(),
+            },
+            (_, Some(path)) if self.show_history => format!("History: {}", self.display_path(path)),
+            (_, path) => {
+                let mut title = "Preview".to_string();
+                if let Some(target) = path.and_then(|path| preview::link_target(path)) {
+                    title = format!("{} → {}", title, target.display());
+                }
+                if let Some(others) =
+                    path.and_then(|path| self.walk_stats.as_ref()?.linked.get(path))
+                {
+                    let others: Vec<String> = others
+                        .iter()
+                        .map(|other| self.display_path(other))
+                        .collect();
+                    title = format!("{} (also {})", title, others.join(", "));
+                }
+                if let Some(limit) = path
+                    .and_then(|path| self.preview_limits.get(path))
+                    .copied()
+                    .flatten()
+                {
+                    title = format!("{} ({})", title, limit.explanation());
+                }
+                if self.previewer.is_loading() && !self.accessible {
+                    title = format!("{} {}", title, SPINNER[self.spinner]);
+                }
+                title
+            }
+        }
+    }
+
+    // Marks the selected file as the anchor, or clears it if it already is
+    fn toggle_anchor(&mut self) {
+        let Some(path) = self.filtered_files.get(self.selected_index) else {
+            return;
+        };
+        if self.anchor.as_ref() == Some(path) {
+            self.anchor = None;
+            self.show_diff = false;
+        } else {
+            self.anchor = Some(path.clone());
+        }
+        self.reset_scroll();
+    }
+
+    fn toggle_history(&mut self) {
+        self.show_history = !self.show_history;
+        // Commits may have been made since it was last shown
+        self.file_history = None;
+        self.history_loading = None;
+        self.reset_scroll();
+    }
+
+    // The selected file's history, asking git for it on a thread so a slow `git log` doesn't
+    // hold up moving through the results
+    fn history_preview(&mut self, path: PathBuf) -> Text<'static> {
+        if let Some((shown, text)) = &self.file_history {
+            if *shown == path {
+                return text.clone();
+            }
+        }
+        if self.history_loading.as_ref().map(|(loading, _)| loading) != Some(&path) {
+            let (sender, receiver) = mpsc::channel();
+            let (file, count) = (path.clone(), self.config.history_commits);
+            std::thread::spawn(move || {
+                let _ = sender.send(git::file_history(&file, count));
+            });
+            self.history_loading = Some((path.clone(), receiver));
+        }
+        // A short wait, so a quick answer never flickers through "Loading…"
+        if self.poll_history(Duration::from_millis(15)) {
+            self.history_preview(path)
+        } else {
+            Text::raw("Loading…")
+        }
+    }
+
+    // Takes in the history being loaded once git is done, returning whether it arrived
+    fn poll_history(&mut self, patience: Duration) -> bool {
+        let Some((path, receiver)) = &self.history_loading else {
+            return false;
+        };
+        let commits = match receiver.recv_timeout(patience) {
+            Ok(commits) => commits,
+            Err(mpsc::RecvTimeoutError::Timeout) => return false,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Vec::new(),
+        };
+        self.file_history = Some((path.clone(), history_text(commits)));
+        self.history_loading = None;
+        true
+    }
+
+    fn toggle_diff(&mut self) {
+        if self.anchor.is_some() {
+            self.show_diff = !self.show_diff;
+            self.reset_scroll();
+        }
+    }
+
+    fn filter_description(&self) -> Option<String> {
+        match self.file_filter {
+            FileFilter::All => None,
+            FileFilter::Dirty => Some("dirty files".to_string()),
+            FileFilter::ChangedFromDefault => Some(format!(
+                "files not on {}",
+                self.default_branch.get_or_init(git::default_branch)
+            )),
+            FileFilter::Recent => Some(git::recent_description(&self.config)),
+            FileFilter::Modified => Some(format!(
+                "modified in last {}",
+                query::format_duration(self.config.modified_within)
+            )),
+            // Without a watcher, a file could have changed since they were found
+            FileFilter::Duplicates if !self.watcher.as_ref().is_some_and(Watcher::is_ready) => {
+                Some(format!(
+                    "duplicates in {} group(s), may be stale, :reload to refresh",
+                    self.duplicate_groups.len()
+                ))
+            }
+            FileFilter::Duplicates => Some(format!(
+                "duplicates in {} group(s)",
+                self.duplicate_groups.len()
+            )),
+        }
+    }
+
+    // How the results are ordered, which depends on the query as much as the filter
+    fn sort_description(&self) -> &'static str {
+        if self.tree_view {
+            return "tree";
+        }
+        let query = self.query();
+        match (self.search_mode, self.file_filter) {
+            (SearchMode::Filename, _) if !query.text.is_empty() => "best match",
+            (SearchMode::Contents, _) if self.approximate && !query.text.is_empty() => {
+                "most similar"
+            }
+            (_, FileFilter::All) if query.text.is_empty() => "frecent, then modified",
+            (_, FileFilter::Modified) => "newest first",
+            (_, FileFilter::Recent) => "most recently committed",
+            (_, FileFilter::Dirty) => "git status",
+            (_, FileFilter::Duplicates) => "most space wasted",
+            _ => tiebreak::describe(&self.config.tiebreaks),
+        }
+    }
+
+    // One line across the top saying what's being searched and how, after a few toggles
+    fn title_bar(&self) -> Line<'static> {
+        let mut root = self.roots_title.clone();
+        if let Some(scope) = self.scopes.last() {
+            root = format!("{} › {}", root, self.display_path(scope));
+        }
+
+        let mut parts = vec![
+            format!(
+                "filter: {}",
+                self.filter_description().unwrap_or("all files".to_string())
+            ),
+            format!("sort: {}", self.sort_description()),
+        ];
+        if let Some(group_by) = self.group_by.filter(|_| !self.tree_view) {
+            parts.push(format!("grouped by {:?}", group_by).to_lowercase());
+        }
+        if self.search_defaults.hidden {
+            parts.push("hidden files".to_string());
+        }
+        if self.search_defaults.no_ignore {
+            parts.push("no ignore files".to_string());
+        }
+        if self.hide_generated {
+            parts.push("generated hidden".to_string());
+        }
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut spans = vec![Span::styled(
+            format!(" {}", root),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        let separator = if self.accessible { ", " } else { "  │  " };
+        for part in parts {
+            spans.push(Span::styled(separator, dim));
+            spans.push(Span::raw(part));
+        }
+        Line::from(spans)
+    }
+
+    fn files_title(&self) -> String {
+        let title = match self.scopes.last() {
+            Some(root) => {
+                let display = self.display_path(root);
+                let root = Path::new(&display);
+                let root = root.strip_prefix("./").unwrap_or(root);
+                let crumbs: Vec<_> = root
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                format!("Files: {}", crumbs.join(" › "))
+            }
+            None => "Files".to_string(),
+        };
+        match self.marked.len() {
+            0 => title,
+            count => format!("{} ({} marked)", title, count),
+        }
+    }
+
+    fn apply_saved_search(&mut self, search: &SavedSearch) {
+        self.input = TextInput::new(search.query.clone());
+        self.search_mode = search.mode;
+        self.file_filter = search.filter;
+        self.selected_index = 0;
+        match search.sort {
+            Some(sort) => self.sort_by(sort),
+            None => {
+                self.filter_files();
+                self.reset_scroll();
+            }
+        }
+    }
+
+    // Each order comes with the filter or view that makes it
+    fn sort_by(&mut self, sort: Sort) {
+        let (filter, tree_view) = match sort {
+            Sort::Walk => (FileFilter::All, false),
+            Sort::Mtime => (FileFilter::Modified, false),
+            Sort::Recent => (FileFilter::Recent, false),
+            Sort::Tree => (self.file_filter, true),
+        };
+        self.file_filter = filter;
+        if tree_view != self.tree_view {
+            self.toggle_tree_view();
+        } else {
+            self.filter_files();
+            self.reset_scroll();
+        }
+    }
+
+    fn toggle_saved_picker(&mut self) {
+        self.saved_picker = match self.saved_picker {
+            Some(_) => None,
+            None if !self.config.saved_searches.is_empty() => Some(0),
+            None => None,
+        };
+    }
+
+    // Keys go to the saved search picker while it is open
+    fn handle_saved_picker_key(&mut self, code: KeyCode) {
+        let Some(selected) = self.saved_picker else {
+            return;
+        };
+        let count = self.config.saved_searches.len();
+        match code {
+            KeyCode::Esc => self.saved_picker = None,
+            KeyCode::Up => self.saved_picker = Some(selected.saturating_sub(1)),
+            KeyCode::Down => self.saved_picker = Some((selected + 1).min(count - 1)),
+            KeyCode::Enter => {
+                let search = self.config.saved_searches[selected].clone();
+                self.saved_picker = None;
+                self.apply_saved_search(&search);
+            }
+            _ => {}
+        }
+    }
+
+    fn toggle_language_picker(&mut self) {
+        if self.language_picker.take().is_some() {
+            return;
+        }
+        let mut counts: HashMap<&'static str, usize> = HashMap::new();
+        for path in &self.files {
+            if let Some(language) =
+                preview::detect_language(path, &self.preview_options.syntax_overrides)
+            {
+                *counts.entry(language).or_default() += 1;
+            }
+        }
+        let mut languages: Vec<_> = counts.into_iter().collect();
+        languages.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        self.language_picker = Some(LanguagePicker {
+            languages,
+            selected: 0,
+        });
+    }
+
+    fn handle_language_picker_key(&mut self, code: KeyCode) {
+        let Some(picker) = &mut self.language_picker else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.language_picker = None,
+            KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Down => picker.selected = (picker.selected + 1).min(picker.languages.len()),
+            KeyCode::Enter => {
+                let language = picker
+                    .selected
+                    .checked_sub(1)
+                    .map(|row| picker.languages[row].0);
+                self.language_picker = None;
+                self.set_language_filter(language);
+            }
+            _ => {}
+        }
+    }
+
+    // Replaces any `lang:` tokens in the query with one for `language`
+    fn set_language_filter(&mut self, language: Option<&str>) {
+        let mut query: String = self
+            .input
+            .value()
+            .split_inclusive(char::is_whitespace)
+            .filter(|piece| !piece.trim_end().starts_with("lang:"))
+            .collect();
+        query.truncate(query.trim_end().len());
+        if let Some(language) = language {
+            if !query.is_empty() {
+                query.push(' ');
+            }
+            query.push_str("lang:");
+            query.push_str(&preview::language_token(language));
+        }
+        self.input = TextInput::new(query);
+        self.selected_index = 0;
+        self.filter_files();
+        self.reset_scroll();
+    }
+
+    // Runs the Lua action bound to `key`, if there is one
+    #[cfg(feature = "lua")]
+    fn run_plugin_binding(&mut self, key: &KeyEvent) -> bool {
+        let Some(plugins) = &self.plugins else {
+            return false;
+        };
+        let Some(action) = plugins.binding(key) else {
+            return false;
+        };
+        let path = self.filtered_files.get(self.selected_index);
+        self.message = match plugins.run_action(&action, path.map(PathBuf::as_path)) {
+            Ok(message) => message,
+            Err(err) => Some(format!("Lua: {:#}", err)),
+        };
+        true
+    }
+
+    #[cfg(not(feature = "lua"))]
+    fn run_plugin_binding(&mut self, _key: &KeyEvent) -> bool {
+        false
+    }
+
+    // Files that actions like `{+}` apply to, the marked ones or else the current one
+    fn selected_paths(&self) -> Vec<PathBuf> {
+        if !self.marked.is_empty() {
+            return self.marked.clone();
+        }
+        self.filtered_files
+            .get(self.selected_index)
+            .cloned()
+            .into_iter()
+            .collect()
+    }
+
+    // Marks or unmarks the current file, then moves past it
+    fn toggle_mark(&mut self, delta: isize) {
+        let Some(path) = self.filtered_files.get(self.selected_index) else {
+            return;
+        };
+        match self.marked.iter().position(|marked| marked == path) {
+            Some(index) => {
+                self.marked.remove(index);
+            }
+            None => self.marked.push(path.clone()),
+        }
+        self.move_selection(delta);
+    }
+
+    fn open_command_prompt(&mut self) {
+        if !self.filtered_files.is_empty() {
+            self.command_prompt = Some(CommandPrompt::default());
+        }
+    }
+
+    fn handle_command_prompt_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = &mut self.command_prompt else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.command_prompt = None,
+            KeyCode::Up => prompt.choose(&self.config.commands, -1),
+            KeyCode::Down => prompt.choose(&self.config.commands, 1),
+            KeyCode::Enter => {
+                let template = prompt.input.value().to_string();
+                self.command_prompt = None;
+                let Some(current) = self.filtered_files.get(self.selected_index) else {
+                    return;
+                };
+                if !template.trim().is_empty() {
+                    self.command_output = Some(commands::run_template(
+                        &template,
+                        current,
+                        &self.selected_paths(),
+                        &self.file_matches,
+                    ));
+                }
+            }
+            _ => {
+                if let Some(request) = to_input_request(&Event::Key(key)) {
+                    prompt.input.handle(request);
+                }
+            }
+        }
+    }
+
+    // Edits the `:` command line, returning whether the command was to quit
+    fn handle_command_line_key(&mut self, key: KeyEvent) -> bool {
+        let Some(line) = &mut self.command_line else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Esc => self.command_line = None,
+            KeyCode::Backspace if line.value().is_empty() => self.command_line = None,
+            // `::` searches for a colon after all
+            KeyCode::Char(':') if line.value().is_empty() => {
+                self.command_line = None;
+                self.edit_input(key);
+            }
+            KeyCode::Enter => {
+                let typed = line.value().to_string();
+                self.command_line = None;
+                match ExCommand::parse(&typed) {
+                    Ok(command) => return self.run_ex_command(command),
+                    Err(err) => self.message = Some(err),
+                }
+            }
+            _ => {
+                if let Some(request) = to_input_request(&Event::Key(key)) {
+                    line.handle(request);
+                }
+            }
+        }
+        false
+    }
+
+    fn run_ex_command(&mut self, command: ExCommand) -> bool {
+        match command {
+            ExCommand::Jump(number) => self.jump_to(number),
+            ExCommand::Quit => return self.quit_now(),
+            ExCommand::Reload => {
+                self.walk_cache = None;
+                self.walking = None;
+                self.file_index.forget_walk();
+                self.forget_generated();
+                self.tree_readme.take();
+                self.duplicates_of = None;
+                self.anchor_preview = None;
+                self.previewer.clear();
+                self.filter_files();
+                self.message = Some(format!("Reloaded, {} results", self.filtered_files.len()));
+            }
+            ExCommand::Sort(sort) => self.sort_by(sort),
+            ExCommand::Mode(mode) => self.switch_mode(mode),
+            ExCommand::Filter(filter) => {
+                self.file_filter = filter;
+                self.filter_files();
+                self.reset_scroll();
+            }
+            ExCommand::Write(path) => self.write_results(&path),
+            ExCommand::Ignore(pattern) => self.ignore(&pattern),
+            ExCommand::Help => self.show_help = true,
+        }
+        false
+    }
+
+    fn handle_palette_key(&mut self, key: KeyEvent) {
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.palette = None,
+            KeyCode::Up => palette.choose(-1),
+            KeyCode::Down => palette.choose(1),
+            KeyCode::Enter => {
+                let chosen = palette.chosen();
+                self.palette = None;
+                match chosen {
+                    Some(Action::Key(key)) => self.replay = palette::key_event(key),
+                    Some(Action::Ex(command)) => {
+                        let quit = self.run_ex_command(command);
+                        if quit {
+                            self.replay = palette::key_event("Ctrl+c");
+                        }
+                    }
+                    Some(Action::ToggleHidden) => self.toggle_hidden(),
+                    Some(Action::NextTheme) => self.next_theme(),
+                    Some(Action::CommandLine(typed)) => {
+                        self.command_line = Some(TextInput::new(typed.to_string()))
+                    }
+                    None => {}
+                }
+            }
+            _ => {
+                if let Some(request) = to_input_request(&Event::Key(key)) {
+                    if palette
+                        .input
+                        .handle(request)
+                        .is_some_and(|changed| changed.value)
+                    {
+                        palette.selected = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    fn toggle_hidden(&mut self) {
+        self.search_defaults.hidden = !self.search_defaults.hidden;
+        self.walk_cache = None;
+        self.filter_files();
+        self.reset_scroll();
+    }
+
+    // Previews in the next of the built-in and installed themes, by name
+    fn next_theme(&mut self) {
+        let themes = &assets::get().themes.themes;
+        let next = themes
+            .range::<str, _>((
+                std::ops::Bound::Excluded(self.preview_options.theme.as_str()),
+                std::ops::Bound::Unbounded,
+            ))
+            .next()
+            .or_else(|| themes.iter().next());
+        let Some((name, _)) = next else {
+            return;
+        };
+        self.preview_options.theme = name.clone();
+        self.previewer = Previewer::new(self.preview_options.clone());
+        self.message = Some(format!("Theme: {}", name));
+    }
+
+    // Selects the result numbered `number` from 1 in the order they're listed, opening up
+    // whatever it's folded or collapsed into
+    fn jump_to(&mut self, number: usize) {
+        let index = number - 1;
+        if index >= self.filtered_files.len() {
+            self.message = Some(format!(
+                "There are only {} results",
+                self.filtered_files.len()
+            ));
+            return;
+        }
+        if self.tree_view {
+            match self
+                .tree_rows
+                .iter()
+                .position(|row| !row.is_dir && row.file_index == index)
+            {
+                Some(row) => self.select_tree_row(row),
+                None => {
+                    self.message = Some(format!("Result {} is in a collapsed directory", number))
+                }
+            }
+            return;
+        }
+        if self.is_hidden(index) {
+            if let Some(label) = self.group_label(&self.filtered_files[index]) {
+                self.folded.remove(&label);
+            }
+        }
+        self.selected_index = index;
+        self.restore_scroll();
+    }
+
+    // Runs the command a `key_bindings` entry binds to `key`, if there is one
+    fn run_key_binding(&mut self, key: &KeyEvent) -> bool {
+        let Some(binding) = self
+            .config
+            .key_bindings
+            .iter()
+            .find(|binding| binding.key.matches(key))
+            .cloned()
+        else {
+            return false;
+        };
+        let Some(current) = self.filtered_files.get(self.selected_index) else {
+            self.message = Some("No file selected".to_string());
+            return true;
+        };
+        let output = commands::run_template(
+            &binding.command,
+            current,
+            &self.selected_paths(),
+            &self.file_matches,
+        );
+        if binding.refresh {
+            // The command may have rewritten them
+            for path in self.selected_paths() {
+                self.forget_file(&path);
+            }
+            self.filter_files();
+        }
+        if !binding.silent {
+            self.command_output = Some(output);
+        } else if !output.succeeded {
+            self.message = Some(format!("{}: {}", output.title, output.text.trim()));
+        }
+        true
+    }
+
+    fn open_rename_prompt(&mut self) {
+        // Marked files are renamed together, starting from a filename query as the pattern
+        if !self.marked.is_empty() {
+            let pattern = match self.search_mode {
+                SearchMode::Filename => self.input.value(),
+                _ => "",
+            };
+            self.batch_rename = Some(BatchRename::new(self.marked.clone(), pattern));
+            return;
+        }
+        if let Some(path) = self.filtered_files.get(self.selected_index) {
+            self.rename_prompt = Some(TextInput::new(path.to_string_lossy().into_owned()));
+        }
+    }
+
+    fn handle_rename_prompt_key(&mut self, key: KeyEvent) {
+        let Some(input) = &mut self.rename_prompt else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.rename_prompt = None,
+            KeyCode::Enter => {
+                let to = PathBuf::from(input.value().trim());
+                self.rename_prompt = None;
+                if let Some(from) = self.filtered_files.get(self.selected_index).cloned() {
+                    if !to.as_os_str().is_empty() && to != from {
+                        self.rename_selected(&from, to);
+                    }
+                }
+            }
+            _ => {
+                if let Some(request) = to_input_request(&Event::Key(key)) {
+                    input.handle(request);
+                }
+            }
+        }
+    }
+
+    fn rename_selected(&mut self, from: &Path, to: PathBuf) {
+        if let Err(err) = fileops::rename(from, &to) {
+            self.message = Some(format!("{:#}", err));
+            return;
+        }
+        self.follow_rename(from, &to);
+
+        self.filter_files();
+        if let Some(index) = self.filtered_files.iter().position(|path| *path == to) {
+            self.selected_index = index;
+        }
+    }
+
+    // Keep references to the file pointing at its new name
+    fn follow_rename(&mut self, from: &Path, to: &Path) {
+        for path in self.marked.iter_mut().chain(self.anchor.as_mut()) {
+            if path == from {
+                *path = to.to_path_buf();
+            }
+        }
+        if let Some(scroll) = self.scroll_positions.remove(from) {
+            self.scroll_positions.insert(to.to_path_buf(), scroll);
+        }
+    }
+
+    // Returns whether to quit, for Ctrl+c or a quit key other than Esc, which only cancels
+    fn handle_batch_rename_key(&mut self, key: KeyEvent) -> bool {
+        let quitting = (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL)
+            || self.config.quit_keys.iter().any(|quit| quit.matches(&key));
+        if key.code != KeyCode::Esc && quitting {
+            return self.quit_now();
+        }
+        let Some(batch) = &mut self.batch_rename else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Esc => self.batch_rename = None,
+            KeyCode::Tab | KeyCode::BackTab => {
+                batch.editing_replacement = !batch.editing_replacement;
+            }
+            KeyCode::Enter => match batch.plan() {
+                Err(err) => self.message = Some(regex_error_message(&err)),
+                Ok(plan)
+                    if plan
+                        .iter()
+                        .any(|(_, planned)| matches!(planned, Planned::Conflict(_))) =>
+                {
+                    self.message =
+                        Some("Some files would be overwritten, nothing renamed".to_string());
+                }
+                Ok(plan) => {
+                    self.batch_rename = None;
+                    self.apply_batch_rename(plan);
+                }
+            },
+            _ => {
+                let input = if batch.editing_replacement {
+                    &mut batch.replacement
+                } else {
+                    &mut batch.pattern
+                };
+                if let Some(request) = to_input_request(&Event::Key(key)) {
+                    input.handle(request);
+                }
+            }
+        }
+        false
+    }
+
+    // Renames one file at a time, stopping at the first that fails
+    fn apply_batch_rename(&mut self, plan: Vec<(PathBuf, Planned)>) {
+        let mut renamed = 0;
+        let mut failure = None;
+        for (from, planned) in plan {
+            let Planned::Rename(to) = planned else {
+                continue;
+            };
+            if let Err(err) = fileops::rename(&from, &to) {
+                failure = Some(err);
+                break;
+            }
+            self.follow_rename(&from, &to);
+            renamed += 1;
+        }
+        self.message = Some(match failure {
+            Some(err) => format!("Renamed {} file(s), then: {:#}", renamed, err),
+            None => format!("Renamed {} file(s)", renamed),
+        });
+        self.filter_files();
+    }
+
+    // Whether a quit key should quit now, or ask first because files are marked or about to be
+    // renamed
+    fn quit_now(&mut self) -> bool {
+        if self.config.confirm_quit && (!self.marked.is_empty() || self.batch_rename.is_some()) {
+            self.confirm_quit = true;
+            return false;
+        }
+        true
+    }
+
+    // Whether the answer was to quit
+    fn handle_confirm_quit_key(&mut self, code: KeyCode) -> bool {
+        self.confirm_quit = false;
+        matches!(code, KeyCode::Char('y') | KeyCode::Char('Y'))
+    }
+
+    fn handle_confirm_delete_key(&mut self, code: KeyCode) {
+        let Some(paths) = self.confirm_delete.take() else {
+            return;
+        };
+        if !matches!(code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            return;
+        }
+
+        let permanent = self.config.permanent_delete;
+        let mut errors = Vec::new();
+        for path in &paths {
+            match fileops::delete(path, permanent) {
+                Ok(()) => {
+                    self.marked.retain(|marked| marked != path);
+                    if self.anchor.as_ref() == Some(path) {
+                        self.anchor = None;
+                        self.show_diff = false;
+                    }
+                }
+                Err(err) => errors.push(format!("{:#}", err)),
+            }
+        }
+        if !errors.is_empty() {
+            self.message = Some(errors.join("; "));
+        }
+
+        self.filter_files();
+        self.selected_index = self
+            .selected_index
+            .min(self.filtered_files.len().saturating_sub(1));
+        self.restore_scroll();
+    }
+
+    // Distinct captured values with their counts, most common or alphabetical first
+    fn capture_survey_lines(&self, by_value: bool) -> Vec<String> {
+        let mut counts = search::capture_counts(self.file_matches.values());
+        if by_value {
+            counts.sort();
+        } else {
+            counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        }
+        counts
+            .into_iter()
+            .map(|(value, count)| format!("{:>6}  {}", count, value))
+            .collect()
+    }
+
+    fn open_walk_stats(&mut self) {
+        let lines = match &self.walk_stats {
+            Some(stats) => {
+                // Asking git is slower than the walk, so it's only done for the popup
+                let gitignored = (!self.search_defaults.no_ignore).then(|| {
+                    self.search_roots()
+                        .into_iter()
+                        .filter_map(git::ignored_count)
+                        .sum()
+                });
+                stats.lines(self.skipped_large, gitignored)
+            }
+            None => vec![
+                "Files come from git with this filter, so nothing was walked".to_string(),
+                format!(
+                    "{:>8}  too large to search (max_search_file_size)",
+                    self.skipped_large
+                ),
+            ],
+        };
+        self.stats_popup = Some(lines);
+    }
+
+    fn handle_capture_survey_key(&mut self, code: KeyCode) {
+        let Some(survey) = &mut self.capture_survey else {
+            return;
+        };
+        match code {
+            KeyCode::Char('s') => {
+                survey.by_value = !survey.by_value;
+                survey.scroll = 0;
+            }
+            KeyCode::Up => survey.scroll = survey.scroll.saturating_sub(1),
+            KeyCode::Down => survey.scroll = survey.scroll.saturating_add(1),
+            KeyCode::PageUp => survey.scroll = survey.scroll.saturating_sub(10),
+            KeyCode::PageDown => survey.scroll = survey.scroll.saturating_add(10),
+            _ => self.capture_survey = None,
+        }
+    }
+
+    // The preview search, if one has been run on this file
+    fn active_preview_search(&self, path: &Path) -> Option<&PreviewSearch> {
+        self.preview_search
+            .as_ref()
+            .filter(|search| search.path == path && !search.term.is_empty())
+    }
+
+    // Finds the preview search's matches in the preview once it shows the term, rather than on
+    // every frame
+    fn count_preview_matches(&mut self, text: &Text) {
+        let shown = (
+            self.previewer.shown(),
+            self.show_history,
+            self.show_diff && self.anchor.is_some(),
+        );
+        let loading = self.previewer.is_loading();
+        let Some(path) = self.filtered_files.get(self.selected_index) else {
+            return;
+        };
+        let Some(search) = self
+            .preview_search
+            .as_mut()
+            .filter(|search| search.path == *path && !search.term.is_empty())
+        else {
+            return;
+        };
+        // Until the preview with the term arrives, the last one is still showing
+        if loading
+            || search
+                .matches
+                .as_ref()
+                .is_some_and(|(seen, _)| *seen == shown)
+        {
+            return;
+        }
+        search.matches = Some((shown, preview::match_lines(text)));
+    }
+
+    fn preview_matches(&self) -> &[usize] {
+        self.filtered_files
+            .get(self.selected_index)
+            .and_then(|path| self.active_preview_search(path)?.matches.as_ref())
+            .map_or(&[], |(_, matches)| matches.as_slice())
+    }
+
+    // Line of the preview search match the preview is jumped to
+    fn preview_search_line(&self) -> Option<usize> {
+        let path = self.filtered_files.get(self.selected_index)?;
+        let search = self.active_preview_search(path)?;
+        let matches = self.preview_matches();
+        matches
+            .get(search.current.min(matches.len().checked_sub(1)?))
+            .copied()
+    }
+
+    // Shown in the preview's title while it has focus
+    fn preview_search_status(&self) -> String {
+        let Some(search) = &self.preview_search else {
+            return "/ search  n/N next/previous  g/G top/bottom  Esc back to query".to_string();
+        };
+        if let Some(input) = &search.input {
+            return format!("/{}", input_with_cursor(input));
+        }
+        if search.matches.is_none() {
+            return format!("Searching for '{}'…", search.term);
+        }
+        let count = self.preview_matches().len();
+        if count == 0 {
+            return format!("No matches for '{}'", search.term);
+        }
+        format!(
+            "{}/{} matches for '{}'",
+            search.current + 1,
+            count,
+            search.term
+        )
+    }
+
+    // Jumps to the next or previous match, wrapping around at either end
+    fn next_preview_match(&mut self, delta: isize) {
+        let count = self.preview_matches().len();
+        let Some(search) = &mut self.preview_search else {
+            return;
+        };
+        if count > 0 {
+            search.current = (search.current as isize + delta).rem_euclid(count as isize) as usize;
+            self.preview_scroll = 0;
+        }
+    }
+
+    fn handle_preview_key(&mut self, key: KeyEvent, frame_height: u16) {
+        if let Some(search) = &mut self.preview_search {
+            if let Some(input) = &mut search.input {
+                match key.code {
+                    KeyCode::Esc => search.input = None,
+                    KeyCode::Enter => {
+                        search.term = input.value().to_string();
+                        search.input = None;
+                        search.current = 0;
+                        search.matches = None;
+                        self.preview_scroll = 0;
+                    }
+                    _ => {
+                        if let Some(request) = to_input_request(&Event::Key(key)) {
+                            input.handle(request);
+                        }
+                    }
+                }
+                return;
+            }
+        }
+
+        match key.code {
+            KeyCode::Char('/') => {
+                let Some(path) = self.filtered_files.get(self.selected_index) else {
+                    return;
+                };
+                self.preview_search = Some(PreviewSearch {
+                    path: path.clone(),
+                    input: Some(TextInput::default()),
+                    term: String::new(),
+                    current: 0,
+                    matches: None,
+                });
+            }
+            KeyCode::Char('n') => self.next_preview_match(1),
+            KeyCode::Char('N') => self.next_preview_match(-1),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.preview_scroll = self.preview_scroll.saturating_sub(1)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.preview_scroll = self.preview_scroll.saturating_add(1)
+            }
+            KeyCode::PageUp => self.scroll_preview(frame_height, -1),
+            KeyCode::PageDown => self.scroll_preview(frame_height, 1),
+            // Esc clears the search first, then hands the keys back to the query
+            KeyCode::Esc if self.preview_search.is_some() => {
+                self.preview_search = None;
+                self.preview_scroll = 0;
+            }
+            KeyCode::Char('g') => self.preview_scroll = 0,
+            KeyCode::Char('G') => self.preview_scroll = self.preview_bottom,
+            KeyCode::Tab => self.focus = self.focus.cycle(1),
+            KeyCode::BackTab => self.focus = self.focus.cycle(-1),
+            KeyCode::Esc => self.focus = Focus::Input,
+            KeyCode::Left if key.modifiers == KeyModifiers::ALT => self.focus = Focus::Input,
+            _ => {}
+        }
+    }
+
+    // The list's own keys while it has focus, returning false for the ones it leaves to the
+    // rest of the app. Typing goes back to the query.
+    fn handle_list_key(&mut self, key: KeyEvent) -> bool {
+        if !key.modifiers.difference(KeyModifiers::SHIFT).is_empty() {
+            return false;
+        }
+        let count = self.filtered_files.len().max(self.tree_rows.len()) as isize;
+        match key.code {
+            KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Char('g') => self.move_selection(-count),
+            KeyCode::Char('G') => self.move_selection(count),
+            KeyCode::Char(' ') if key.modifiers == KeyModifiers::SHIFT => self.toggle_mark(-1),
+            KeyCode::Char(' ') => self.toggle_mark(1),
+            KeyCode::Char('/') | KeyCode::Esc => self.focus = Focus::Input,
+            KeyCode::Char(_) => {
+                self.focus = Focus::Input;
+                return false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_command_output_key(&mut self, code: KeyCode) {
+        let Some(output) = &mut self.command_output else {
+            return;
+        };
+        match code {
+            KeyCode::Up => output.scroll = output.scroll.saturating_sub(1),
+            KeyCode::Down => output.scroll = output.scroll.saturating_add(1),
+            KeyCode::PageUp => output.scroll = output.scroll.saturating_sub(10),
+            KeyCode::PageDown => output.scroll = output.scroll.saturating_add(10),
+            _ => self.command_output = None,
+        }
+    }
+}
+
+// Each root absolute, with the home directory as ~
+fn roots_title(roots: &[PathBuf]) -> String {
+    let home = dirs::home_dir();
+    let roots: Vec<String> = roots
+        .iter()
+        .map(|root| {
+            let absolute = root.canonicalize().unwrap_or_else(|_| root.clone());
+            match home
+                .as_ref()
+                .and_then(|home| absolute.strip_prefix(home).ok())
+            {
+                Some(relative) => Path::new("~").join(relative).to_string_lossy().into_owned(),
+                None => absolute.to_string_lossy().into_owned(),
+            }
+        })
+        .collect();
+    roots.join(", ")
+}
+
+// Regex errors span several lines with a caret diagram; the last line says what's wrong
+fn regex_error_message(error: &str) -> String {
+    error
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("invalid pattern")
+        .to_string()
+}
+
+// Draws a scrollbar over the right border of a bordered pane
+fn render_scrollbar(
+    frame: &mut Frame,
+    area: Rect,
+    borders: Borders,
+    content_length: usize,
+    position: usize,
+) {
+    if !borders.contains(Borders::RIGHT) || content_length <= area.height.saturating_sub(2) as usize
+    {
+        return;
+    }
+    let mut state = ScrollbarState::new(content_length).position(position);
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None),
+        area.inner(&Margin {
+            vertical: 1,
+            horizontal: 0,
+        }),
+        &mut state,
+    );
+}
+
+// The `:` commands that could be what's typed, described once there's only one
+fn command_hints(typed: &str) -> Line<'static> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let hints = excommand::hints(typed);
+    let text = match hints.as_slice() {
+        [] => "no such command".to_string(),
+        [(usage, description)] => format!("{} — {}", usage, description),
+        _ => hints
+            .iter()
+            .map(|(usage, _)| usage.split(' ').next().unwrap_or(usage))
+            .collect::<Vec<_>>()
+            .join(" · "),
+    };
+    Line::styled(format!(" {} ", text), dim)
+}
+
+// The border of a pane, picked out while it has focus
+fn focused_border(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    }
+}
+
+// The input's text with a `|` drawn at the cursor
+fn input_with_cursor(input: &TextInput) -> String {
+    // The cursor is a char index, so find its byte offset before inserting
+    let mut value = input.value().to_string();
+    let cursor_position = value
+        .char_indices()
+        .nth(input.cursor())
+        .map_or(value.len(), |(index, _)| index);
+    value.insert(cursor_position, '|');
+    value
+}
+
+// A rect of the given size centered in `area`, clipped to fit
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    Rect::new(
+        (area.width as i32 / 2 - width as i32 / 2).max(0) as u16,
+        (area.height as i32 / 2 - height as i32 / 2).max(0) as u16,
+        width,
+        height,
+    )
+    .intersection(area)
+}
+
+// The files the walker yields under the roots, before glancr's own filtering
+fn walk(roots: &[&Path], defaults: &SearchDefaults) -> (Vec<PathBuf>, WalkStats) {
+    walk_with(roots, defaults, &mut |_| true)
+}
+
+// Like walk, calling back with each file as it's found and stopping when the callback returns
+// false because nothing wants the rest
+fn walk_with(
+    roots: &[&Path],
+    defaults: &SearchDefaults,
+    found: &mut dyn FnMut(&Path) -> bool,
+) -> (Vec<PathBuf>, WalkStats) {
+    let mut walker = WalkBuilder::new(roots[0]);
+    for root in &roots[1..] {
+        walker.add(root);
+    }
+    if !defaults.no_ignore {
+        // gitignore syntax in any directory, for what only matters to glancr
+        walker.add_custom_ignore_filename(ignorefile::NAME);
+    }
+    if let Some(overrides) = defaults.overrides(roots[0]) {
+        walker.overrides(overrides);
+    }
+    if let Some(types) = defaults.file_types() {
+        walker.types(types);
+    }
+
+    let mut files = Vec::new();
+    let mut stats = WalkStats::default();
+    for entry in walker
+        .hidden(!defaults.hidden)
+        .git_ignore(!defaults.no_ignore)
+        .ignore(!defaults.no_ignore)
+        .follow_links(defaults.follow)
+        .build()
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                stats.record_error(&err);
+                continue;
+            }
+        };
+        if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+            stats.seen.extend(index::seen(entry.path()));
+        }
+        if entry.file_type().is_some_and(|ft| ft.is_file()) {
+            stats.walked += 1;
+            let wanted = found(entry.path());
+            files.push(entry.into_path());
+            if !wanted {
+                break;
+            }
+        }
+    }
+    if defaults.follow {
+        let (kept, linked) = links::dedupe(files);
+        files = kept;
+        stats.linked = linked;
+    }
+    (files, stats)
+}
+
+// One line per commit: hash, date, author and subject
+fn history_text(commits: Vec<git::FileCommit>) -> Text<'static> {
+    let author_width = commits
+        .iter()
+        .map(|commit| commit.author.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line<'static>> = commits
+        .into_iter()
+        .map(|commit| {
+            Line::from(vec![
+                Span::styled(commit.hash, Style::default().fg(Color::Yellow)),
+                Span::raw(" "),
+                Span::styled(commit.date, Style::default().fg(Color::Blue)),
+                Span::raw(" "),
+                Span::styled(
+                    format!("{:width$}", commit.author, width = author_width),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::raw("  "),
+                Span::raw(commit.subject),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        Text::raw("No commits touch this file")
+    } else {
+        Text::from(lines)
+    }
+}
+
+// `glancr stats`: the query's results summed up, with every match counted
+fn run_stats(query: String, roots: Vec<PathBuf>, filename: bool, json: bool) -> Result<()> {
+    let mut app = App::new(roots);
+    app.config.max_matches_per_file = usize::MAX;
+    app.config.performance.max_results = 0;
+    app.search_mode = if filename {
+        SearchMode::Filename
+    } else {
+        SearchMode::Contents
+    };
+    app.input = TextInput::new(query);
+    app.filter_files();
+    if let Some(error) = &app.pattern_error {
+        return Err(anyhow!("Invalid pattern: {}", error));
+    }
+
+    // Every match in a line is counted, except by approximate queries which match a line once
+    let matcher = (app.search_mode == SearchMode::Contents && !app.approximate)
+        .then(|| {
+            let query = app.query();
+            RegexMatcher::new(&app.search_defaults.pattern(&query.text)).ok()
+        })
+        .flatten();
+    // Directories are named from where glancr was run
+    let mut stats = stats::Stats::collect(app.filtered_files.iter().map(|path| {
+        let lines = app
+            .file_matches
+            .get(path)
+            .map_or(&[][..], |matches| &matches.lines[..]);
+        let matches = match &matcher {
+            Some(matcher) => lines
+                .iter()
+                .map(|line| search::count_matches(matcher, &line.text))
+                .sum(),
+            None => lines.len(),
+        };
+        (path.strip_prefix(".").unwrap_or(path), lines.len(), matches)
+    }));
+    stats.skipped_large = app.skipped_large;
+    if json {
+        println!("{}", stats.to_json());
+    } else {
+        print!("{}", stats.render(app.search_mode == SearchMode::Contents));
+    }
+    if stats.total.files == 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Handles a key press, leaving whatever needs the terminal to the caller
+fn handle_key(app: &mut App, key: KeyEvent, replayed: bool) -> Result<Step> {
+    app.sync_overlays();
+    let step = route_key(app, key, replayed);
+    // Whatever the key opened goes on top
+    app.sync_overlays();
+    step
+}
+
+fn route_key(app: &mut App, key: KeyEvent, replayed: bool) -> Result<Step> {
+    app.message = None;
+    let before = app.query_state();
+    // Typing a word is a single undo step, spaces start the next one
+    let typing = matches!(key.code, KeyCode::Char(c) if !c.is_whitespace())
+        && !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+    let overlay = app
+        .overlays
+        .last()
+        .copied()
+        .filter(|_| key.kind == KeyEventKind::Press);
+    if let Some(overlay) = overlay {
+        // Only the overlay on top sees the key, Esc included, so nothing leaks into the query
+        match overlay {
+            Overlay::Pager => {
+                let page = app.screen_height.saturating_sub(3);
+                if let Some(pager) = &mut app.pager {
+                    if !pager.handle_key(key, page, &app.preview_options) {
+                        app.pager = None;
+                    }
+                }
+            }
+            Overlay::CaptureSurvey => app.handle_capture_survey_key(key.code),
+            Overlay::ConfigProblems => app.config_problems = None,
+            Overlay::StatsPopup => app.stats_popup = None,
+            Overlay::ConfirmQuit => {
+                if app.handle_confirm_quit_key(key.code) {
+                    return Ok(Step::Exit(Exit::Quit));
+                }
+            }
+            Overlay::ConfirmDelete => app.handle_confirm_delete_key(key.code),
+            Overlay::RenamePrompt => app.handle_rename_prompt_key(key),
+            Overlay::BatchRename => {
+                if app.handle_batch_rename_key(key) {
+                    return Ok(Step::Exit(Exit::Quit));
+                }
+            }
+            Overlay::CommandOutput => app.handle_command_output_key(key.code),
+            Overlay::CommandPrompt => app.handle_command_prompt_key(key),
+            Overlay::Palette => {
+                app.handle_palette_key(key);
+                app.record_change(before, false);
+            }
+            Overlay::CommandLine => {
+                if app.handle_command_line_key(key) {
+                    return Ok(Step::Exit(Exit::Quit));
+                }
+                app.record_change(before, false);
+            }
+            Overlay::SavedPicker => {
+                app.handle_saved_picker_key(key.code);
+                app.record_change(before, false);
+            }
+            Overlay::LanguagePicker => {
+                app.handle_language_picker_key(key.code);
+                app.record_change(before, false);
+            }
+            Overlay::Help => {
+                if app.handle_help_key(key) {
+                    return Ok(Step::Exit(Exit::Quit));
+                }
+            }
+        }
+    } else if key.kind == KeyEventKind::Press && app.focus == Focus::Preview {
+        app.handle_preview_key(key, app.preview_height);
+    } else if key.kind == KeyEventKind::Press
+        && app.focus == Focus::List
+        && app.handle_list_key(key)
+    {
+        // Moved through the list
+    } else if key.kind == KeyEventKind::Press && !replayed && app.run_key_binding(&key) {
+        // Bound in the config
+    } else if key.kind == KeyEventKind::Press && !replayed && app.run_plugin_binding(&key) {
+        // Bound by a Lua script
+    } else if key.kind == KeyEventKind::Press {
+        let mut open = false;
+        let mut with_marked = true;
+        match key.code {
+            _ if app
+                .config
+                .suspend_keys
+                .iter()
+                .any(|suspend| suspend.matches(&key)) =>
+            {
+                return Ok(Step::Suspend);
+            }
+            // Alt too, for when Ctrl+z is one of the suspend keys
+            KeyCode::Char('z')
+                if key.modifiers == KeyModifiers::CONTROL || key.modifiers == KeyModifiers::ALT =>
+            {
+                app.undo();
+                return Ok(Step::Redraw);
+            }
+            KeyCode::Char('y')
+                if key.modifiers == KeyModifiers::CONTROL || key.modifiers == KeyModifiers::ALT =>
+            {
+                app.redo();
+                return Ok(Step::Redraw);
+            }
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                return Ok(Step::Exit(Exit::Quit))
+            }
+            _ if app.config.quit_keys.iter().any(|quit| quit.matches(&key)) => {
+                if app.quit_now() {
+                    return Ok(Step::Exit(Exit::Quit));
+                }
+            }
+            KeyCode::Char('q') if key.modifiers == KeyModifiers::CONTROL => {
+                return Ok(Step::Exit(Exit::Export));
+            }
+            KeyCode::Char('q') if key.modifiers == KeyModifiers::ALT => {
+                if app.quickfix_editor().is_some() {
+                    return Ok(Step::Exit(Exit::Quickfix));
+                }
+                app.message = Some(format!(
+                    "The quickfix list needs vim or nvim as open_command, not '{}'",
+                    shell_words::join(&app.config.open_command)
+                ));
+            }
+            KeyCode::Char('w') if key.modifiers == KeyModifiers::ALT => app.copy_results(),
+            KeyCode::Char('n') if key.modifiers == KeyModifiers::ALT => app.start_ignore(),
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::ALT => app.share_url(false),
+            KeyCode::Char('R') if key.modifiers.contains(KeyModifiers::ALT) => app.share_url(true),
+            KeyCode::Char('n') if key.modifiers == KeyModifiers::CONTROL => {
+                app.switch_mode(SearchMode::Filename);
+            }
+            KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => {
+                app.switch_mode(SearchMode::Contents);
+            }
+            KeyCode::Char('a') if key.modifiers == KeyModifiers::ALT => {
+                app.approximate = !app.approximate;
+                if app.search_mode == SearchMode::Contents {
+                    app.filter_files();
+                    app.reset_scroll();
+                }
+            }
+            KeyCode::Char('b') if key.modifiers == KeyModifiers::CONTROL => {
+                app.file_filter = match app.file_filter {
+                    FileFilter::ChangedFromDefault => FileFilter::All,
+                    _ => FileFilter::ChangedFromDefault,
+                };
+                app.filter_files();
+                app.reset_scroll();
+            }
+            KeyCode::Char('o') if key.modifiers == KeyModifiers::CONTROL => {
+                app.push_scope();
+            }
+            KeyCode::Char('p') if key.modifiers == KeyModifiers::CONTROL => {
+                app.pop_scope();
+            }
+            KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
+                app.toggle_saved_picker();
+            }
+            KeyCode::Char('p') if key.modifiers == KeyModifiers::ALT => {
+                if let Some(path) = app.filtered_files.get(app.selected_index) {
+                    match &app.config.pager {
+                        Some(pager) => {
+                            let mut command = Command::new(&pager[0]);
+                            command.args(&pager[1..]).arg(path);
+                            app.foreground.push(command);
+                            return Ok(Step::Foreground);
+                        }
+                        None => app.pager = Some(Pager::open(path.clone(), &app.preview_options)),
                     }
                 }
+            }
+            KeyCode::F(2) => app.open_rename_prompt(),
+            #[cfg(feature = "profiling")]
+            KeyCode::F(12) => app.profile.shown = !app.profile.shown,
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => {
+                app.open_walk_stats();
+            }
+            KeyCode::Char('g') if key.modifiers == KeyModifiers::ALT => {
+                app.capture_survey = Some(CaptureSurvey {
+                    by_value: false,
+                    scroll: 0,
+                });
+            }
+            KeyCode::Char('d') if key.modifiers == KeyModifiers::ALT => {
+                let paths = app.selected_paths();
+                if !paths.is_empty() {
+                    app.confirm_delete = Some(paths);
+                }
+            }
+            KeyCode::Right if key.modifiers == KeyModifiers::ALT => {
+                app.focus = Focus::Preview;
+            }
+            KeyCode::Char('s') if key.modifiers == KeyModifiers::ALT => {
+                app.cycle_group_by();
+            }
+            KeyCode::Char('o') if key.modifiers == KeyModifiers::ALT => app.toggle_fold(),
+            KeyCode::Tab => app.focus = app.focus.cycle(1),
+            KeyCode::BackTab => app.focus = app.focus.cycle(-1),
+            KeyCode::Char('m') if key.modifiers == KeyModifiers::ALT => app.toggle_mark(1),
+            // Terminals send Alt+Shift+m with or without the shift
+            KeyCode::Char('M') if key.modifiers.contains(KeyModifiers::ALT) => app.toggle_mark(-1),
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::ALT => {
+                app.open_command_prompt();
+            }
+            KeyCode::Char('x') if key.modifiers == KeyModifiers::CONTROL => {
+                app.toggle_anchor();
+            }
+            // Ctrl+Shift+p only where the terminal tells it apart from Ctrl+p
+            KeyCode::Char('x') if key.modifiers == KeyModifiers::ALT => {
+                app.palette = Some(Palette::default());
+            }
+            KeyCode::Char('P') if key.modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                app.palette = Some(Palette::default());
+            }
+            KeyCode::Char('v') if key.modifiers == KeyModifiers::CONTROL => {
+                app.toggle_diff();
+            }
+            KeyCode::Char('l') if key.modifiers == KeyModifiers::CONTROL => {
+                app.preview_options.line_numbers = app.preview_options.line_numbers.next();
+            }
+            KeyCode::Char('g') if key.modifiers == KeyModifiers::CONTROL => {
+                app.hide_generated = !app.hide_generated;
+                app.filter_files();
+                app.reset_scroll();
+            }
+            KeyCode::Char('t') if key.modifiers == KeyModifiers::CONTROL => {
+                app.file_filter = match app.file_filter {
+                    FileFilter::Modified => FileFilter::All,
+                    _ => FileFilter::Modified,
+                };
+                app.filter_files();
+                app.reset_scroll();
+            }
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                app.file_filter = match app.file_filter {
+                    FileFilter::Recent => FileFilter::All,
+                    _ => FileFilter::Recent,
+                };
+                app.filter_files();
+                app.reset_scroll();
+            }
+            KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => {
+                app.file_filter = match app.file_filter {
+                    FileFilter::Dirty => FileFilter::All,
+                    _ => FileFilter::Dirty,
+                };
+                app.filter_files();
+                app.reset_scroll();
+            }
+            KeyCode::Char('u') if key.modifiers == KeyModifiers::ALT => {
+                app.file_filter = match app.file_filter {
+                    FileFilter::Duplicates => FileFilter::All,
+                    _ => FileFilter::Duplicates,
+                };
+                app.filter_files();
+                app.reset_scroll();
+            }
+
+            KeyCode::Up => app.move_selection(-1),
+            KeyCode::Down => app.move_selection(1),
+            KeyCode::Char('t') if key.modifiers == KeyModifiers::ALT => {
+                app.toggle_tree_view();
+            }
+            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => {
+                app.two_line = !app.two_line;
+            }
+            KeyCode::Char('h') if key.modifiers == KeyModifiers::ALT => {
+                app.toggle_history();
+            }
+            KeyCode::Char('e') if key.modifiers == KeyModifiers::ALT => {
+                app.toggle_language_picker();
+            }
+            KeyCode::Left if app.tree_view && key.modifiers.is_empty() => app.tree_left(),
+            KeyCode::Right if app.tree_view && key.modifiers.is_empty() => app.tree_right(),
+            KeyCode::Enter if app.selected_tree_dir().is_some() => {
+                let expanded = app.selected_tree_dir().is_some_and(|row| row.expanded);
+                if expanded {
+                    app.tree_left();
+                } else {
+                    app.tree_right();
+                }
+            }
+            KeyCode::Char(digit @ '1'..='9') if key.modifiers == KeyModifiers::ALT => {
+                open = app.select_hinted(digit as usize - '0' as usize);
+                with_marked = false;
+            }
+            KeyCode::Enter if !app.filtered_files.is_empty() => open = true,
+            KeyCode::Char(':')
+                if app.input.cursor() == 0
+                    && !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                app.command_line = Some(TextInput::default());
+            }
+            KeyCode::F(1) => {
+                app.toggle_help();
+            }
+            KeyCode::Char('h') if key.modifiers == KeyModifiers::CONTROL => {
+                app.toggle_help();
+            }
+            // vim-ish bindings too
+            KeyCode::PageUp => {
+                app.scroll_preview(app.preview_height, -1);
+            }
+            KeyCode::PageDown => {
+                app.scroll_preview(app.preview_height, 1);
+            }
+            _ => app.edit_input(key),
+        }
+        app.record_change(before, typing);
+        if open && app.execute_command(with_marked)? {
+            if !app.config.stay_open {
+                return Ok(Step::Exit(Exit::Open));
+            }
+            return Ok(Step::Foreground);
+        }
+    }
+    Ok(Step::Redraw)
+}
+
+// A help line for configurable keys, after any that always do the same
+fn help_line(keys: &[keys::KeySpec], always: Option<&str>, what: &str) -> String {
+    let names: Vec<String> = always
+        .map(str::to_string)
+        .into_iter()
+        .chain(keys.iter().map(keys::KeySpec::to_string))
+        .collect();
+    format!("{:12} {}", names.join("/"), what)
+}
+
+// Draws the whole screen, with the preview the selected file already has loaded
+fn draw(frame: &mut Frame, app: &mut App, (preview_text, scroll_to, windowed): Preview) {
+    app.screen_height = frame.size().height;
+    app.count_preview_matches(&preview_text);
+    let anchor_text = app.anchor_preview().filter(|_| !app.show_diff);
+    // Screen readers read box drawing out character by character
+    let borders = if app.accessible {
+        Borders::NONE
+    } else {
+        Borders::ALL
+    };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(frame.size());
+    frame.render_widget(Paragraph::new(app.title_bar()), rows[0]);
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(rows[1]);
+
+    let error = app.message.as_ref().or(app.pattern_error.as_ref());
+    let error_height = if error.is_some() { 1 } else { 0 };
+    let right_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(error_height),
+            Constraint::Length(1),
+        ])
+        .split(layout[1]);
+    app.preview_height = right_layout[0].height;
+
+    // Section headers go above the first file in each group, shifting the selection's row
+    let mut list_items = Vec::new();
+    // The file or tree row each item opens with Alt+N, if any
+    let mut item_targets = Vec::new();
+    let selected_row;
+    if app.tree_view {
+        for (i, row) in app.tree_rows.iter().enumerate() {
+            let indent = "  ".repeat(row.depth);
+            let line = if row.is_dir {
+                let marker = match (row.expanded, app.accessible) {
+                    (true, false) => "▾",
+                    (false, false) => "▸",
+                    (true, true) => "-",
+                    (false, true) => "+",
+                };
+                Line::styled(
+                    format!("{}{} {}/", indent, marker, row.name),
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::BOLD),
+                )
+            } else {
+                let mark = if app.marked.contains(&row.path) {
+                    "● "
+                } else {
+                    ""
+                };
+                let count = app
+                    .file_matches
+                    .get(&row.path)
+                    .map(|matches| format!(" ({})", matches.count_label()))
+                    .unwrap_or_default();
+                let mut line = Line::raw(format!("{}  {}{}{}", indent, mark, row.name, count));
+                app.mark_unreadable(&mut line, &row.path);
+                app.mark_limited(&mut line, &row.path);
+                app.mark_linked(&mut line, &row.path);
+                line
+            };
+            let style = if i == app.tree_cursor {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            list_items.push(ListItem::new(line).style(style));
+            item_targets.push((!row.is_dir).then_some(i));
+        }
+        selected_row = app.tree_cursor;
+    } else {
+        let snippet_matcher = app.snippet_matcher();
+        selected_row = app.selected_list_row();
+        for (i, path) in app.filtered_files.iter().enumerate() {
+            let group = app.group_label(path);
+            let folded = group
+                .as_ref()
+                .is_some_and(|group| app.folded.contains(group));
+            if group.is_some() && (i == 0 || app.group_label(&app.filtered_files[i - 1]) != group) {
+                let count = app.filtered_files[i..]
+                    .iter()
+                    .take_while(|path| app.group_label(path) == group)
+                    .count();
+                let marker = match (folded, app.accessible) {
+                    (true, false) => "▸",
+                    (false, false) => "▾",
+                    (true, true) => "+",
+                    (false, true) => "-",
+                };
+                let mut style = Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD);
+                // A folded section's header is selected in place of its files
+                if folded && (i..i + count).contains(&app.selected_index) {
+                    style = style.bg(Color::DarkGray);
+                }
+                list_items.push(ListItem::new(Line::styled(
+                    format!("{} {} ({})", marker, group.unwrap_or_default(), count),
+                    style,
+                )));
+                item_targets.push(None);
+            }
+            if folded {
+                continue;
+            }
+            // The list keeps the selection in view, so only rows within a screen of it can show
+            if app.config.row_format.needs_metadata()
+                && list_items.len().abs_diff(selected_row) < layout[0].height as usize
+                && !app.row_metadata.contains_key(path)
+            {
+                app.row_metadata
+                    .insert(path.clone(), std::fs::metadata(path).ok());
+            }
+            let style = if i == app.selected_index {
+                Style::default().bg(Color::DarkGray)
             } else {
-                preview.scroll((app.preview_scroll, 0))
+                Style::default()
+            };
+            // Captured values go in their own column
+            let mut captures: Vec<&str> = app
+                .file_matches
+                .get(path)
+                .into_iter()
+                .flat_map(|matches| &matches.lines)
+                .flat_map(|line| line.captures.iter().map(String::as_str))
+                .collect();
+            captures.sort();
+            captures.dedup();
+            let template = &app.config.row_format;
+            let display_path = app.display_path(path);
+            let mut line = template.render(&RowValues {
+                path,
+                display_path: &display_path,
+                marked: app.marked.contains(path),
+                matches: app.file_matches.get(path).map(FileMatches::count_label),
+                captures,
+                metadata: app.row_metadata.get(path).cloned().flatten(),
+            });
+            app.mark_unreadable(&mut line, path);
+            app.mark_limited(&mut line, path);
+            app.mark_linked(&mut line, path);
+            let first_match = app
+                .file_matches
+                .get(path)
+                .and_then(|matches| matches.lines.first());
+            let item = match first_match {
+                Some(first) if app.two_line && app.search_mode == SearchMode::Contents => {
+                    let snippet = preview::snippet(&first.text, snippet_matcher.as_ref());
+                    ListItem::new(Text::from(vec![line, snippet]))
+                }
+                _ => ListItem::new(line),
             };
+            list_items.push(item.style(style));
+            item_targets.push(Some(i));
+        }
+    }
+    let item_heights: Vec<usize> = list_items.iter().map(ListItem::height).collect();
+    // Without colours the selection needs a marker of its own
+    let file_list = List::new(list_items)
+        .highlight_symbol(if app.accessible { "> " } else { "" })
+        .block(
+            Block::default()
+                .borders(borders)
+                .title(app.files_title())
+                .border_style(focused_border(app.focus == Focus::List)),
+        );
 
-            // Calculate cursor position
-            let cursor_position = app.input.cursor();
-            let mut input_value = app.input.value().to_string();
-            input_value.insert(cursor_position, '|'); // Insert cursor character
+    // With an anchor the preview is split to show both files side by side
+    let (anchor_area, preview_area) = match app.anchor {
+        Some(_) if !app.show_diff => {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(right_layout[0]);
+            (Some(panes[0]), panes[1])
+        }
+        _ => (None, right_layout[0]),
+    };
 
-            // Determine the label based on the current search mode
-            let search_label = match app.search_mode {
-                SearchMode::Filename => "Filename Search",
-                SearchMode::Contents => "Content Search",
-            };
+    let mut preview_block = Block::default().borders(borders).title(app.preview_title());
+    if app.focus == Focus::Preview {
+        preview_block = preview_block
+            .border_style(focused_border(true))
+            .title(Title::from(app.preview_search_status()).alignment(Alignment::Right));
+    }
+    let preview = Paragraph::new(preview_text.clone())
+        .block(preview_block)
+        .wrap(Wrap { trim: true });
 
-            let input = Paragraph::new(input_value)
-                .block(Block::default().borders(Borders::ALL).title(search_label));
+    // Calculate available size for preview (accounting for borders)
+    let available_height = preview_area.height.saturating_sub(2);
+    let available_width = preview_area.width.saturating_sub(2);
 
-            let status = Paragraph::new({
-                let filter_info = match app.file_filter {
-                    FileFilter::All => "",
-                    FileFilter::Dirty => " (dirty files)",
-                    FileFilter::ChangedFromDefault => {
-                        let default_branch = App::get_git_branch();
-                        &format!(" (files not on {})", default_branch)[..]
-                    }
-                };
+    // Apply scrolling rules, counting wrapped lines as the rows they take up
+    let total_rows = preview::visual_row(&preview_text, preview_text.lines.len(), available_width);
+    let search_line = app.preview_search_line();
+    let match_scroll = match search_line {
+        // Searching the preview always brings the match into view, a few lines down
+        Some(line) => preview::visual_row(&preview_text, line, available_width).saturating_sub(3),
+        None => scroll_to
+            .map(|line| preview::visual_row(&preview_text, line, available_width))
+            .filter(|row| *row >= 15 && total_rows > available_height as usize)
+            .map(|row| row.saturating_sub(10))
+            .unwrap_or(0),
+    };
+    let preview_position = match scroll_to {
+        // Large files are loaded around the scroll position, which is already applied
+        Some(position) if windowed && search_line.is_none() => position,
+        _ => match_scroll.saturating_add(app.preview_scroll),
+    };
+    // As far as the widget goes; only a small file with that many short lines gets there
+    let widget_scroll = preview_position.min(u16::MAX as usize) as u16;
+    let preview = preview.scroll((widget_scroll, 0));
+    let last_top = total_rows.saturating_sub(available_height as usize);
+    app.preview_bottom = (app.preview_scroll + last_top).saturating_sub(preview_position);
 
-                match app.search_mode {
-                    SearchMode::Filename => {
-                        format!("Mode: Filename Search{} (F1 for Help)", filter_info)
-                    }
-                    SearchMode::Contents => {
-                        format!("Mode: Grep{} (F1 for Help)", filter_info)
-                    }
-                }
-            })
-            .style(Style::default().fg(Color::Rgb(155, 155, 155)));
+    let input = match &app.command_line {
+        Some(line) => Paragraph::new(format!(":{}", input_with_cursor(line))).block(
+            Block::default()
+                .borders(borders)
+                .title("Command")
+                .title(Title::from(command_hints(line.value())).alignment(Alignment::Right)),
+        ),
+        None => Paragraph::new(input_with_cursor(&app.input)).block(
+            Block::default()
+                .borders(borders)
+                .title(app.search_label())
+                .title(Title::from(app.query_status()).alignment(Alignment::Right)),
+        ),
+    };
+
+    let status = Paragraph::new(if app.accessible {
+        app.announcement()
+    } else {
+        let filter_info = app
+            .filter_description()
+            .map(|description| format!(" ({})", description))
+            .unwrap_or_default();
+
+        let grep = if app.approximate {
+            "Approximate Grep"
+        } else {
+            "Grep"
+        };
+        // Files the walk dropped, so it's clear when one is missing on purpose
+        let filter_info = match &app.walk_stats {
+            Some(stats) if stats.skipped() > 0 => format!(
+                "{} ({} of {} walked files skipped, Alt+i)",
+                filter_info,
+                stats.skipped(),
+                stats.walked
+            ),
+            _ => filter_info,
+        };
+        let filter_info = match &app.walking {
+            Some(_) => format!(
+                "{} (still walking, {} files so far)",
+                filter_info,
+                app.files.len()
+            ),
+            None => filter_info,
+        };
+        let filter_info = if app.dropped_results > 0 {
+            format!(
+                "{} (first {} of {} results)",
+                filter_info,
+                app.filtered_files.len(),
+                app.filtered_files.len() + app.dropped_results
+            )
+        } else {
+            filter_info
+        };
+        let mode = match app.search_mode {
+            SearchMode::Filename => format!("Filename Search{}", filter_info),
+            SearchMode::Contents if app.skipped_large > 0 => format!(
+                "{}{} ({} large files skipped)",
+                grep, filter_info, app.skipped_large
+            ),
+            SearchMode::Contents => format!("{}{}", grep, filter_info),
+        };
+        format!(
+            "Mode: {} · Keys: {} (Tab) · F1 for Help",
+            mode,
+            app.focus.name()
+        )
+    })
+    .style(Style::default().fg(Color::Rgb(155, 155, 155)));
+
+    // Keep the list's scroll offset between frames so it only moves to follow the selection
+    let mut list_state = ListState::default()
+        .with_offset(app.list_offset)
+        .with_selected(Some(selected_row));
+    frame.render_stateful_widget(file_list, layout[0], &mut list_state);
+    app.list_offset = list_state.offset();
+
+    // Numbers at the right edge of the first visible files, for Alt+1 to Alt+9
+    let list_inner = if app.accessible {
+        layout[0]
+    } else {
+        layout[0].inner(&Margin::new(1, 1))
+    };
+    app.hinted.clear();
+    let mut row = 0;
+    for (target, height) in item_targets.iter().zip(&item_heights).skip(app.list_offset) {
+        if row >= list_inner.height as usize || app.hinted.len() == 9 {
+            break;
+        }
+        if let Some(target) = target {
+            app.hinted.push(*target);
+            let hint_area = Rect::new(
+                list_inner.right().saturating_sub(1),
+                list_inner.y + row as u16,
+                1,
+                1,
+            );
+            frame.render_widget(
+                Paragraph::new(app.hinted.len().to_string())
+                    .style(Style::default().fg(Color::DarkGray)),
+                hint_area,
+            );
+        }
+        row += height;
+    }
+    render_scrollbar(
+        frame,
+        layout[0],
+        borders,
+        app.filtered_files.len(),
+        app.selected_index,
+    );
+
+    frame.render_widget(preview, preview_area);
+    render_scrollbar(frame, preview_area, borders, total_rows, preview_position);
 
-            frame.render_widget(file_list, layout[0]);
-            frame.render_widget(preview, right_layout[0]);
-            frame.render_widget(input, right_layout[1]);
-            frame.render_widget(status, right_layout[2]);
+    // The anchor scrolls along with the selected file to keep them lined up
+    if let (Some(area), Some(anchor), Some(anchor_text)) = (anchor_area, &app.anchor, anchor_text) {
+        let anchor_rows =
+            preview::visual_row(&anchor_text, anchor_text.lines.len(), available_width);
+        let anchor_preview = Paragraph::new(anchor_text)
+            .block(
+                Block::default()
+                    .borders(borders)
+                    .title(format!("Anchor: {}", app.display_path(anchor))),
+            )
+            .wrap(Wrap { trim: true })
+            .scroll((widget_scroll, 0));
+        frame.render_widget(anchor_preview, area);
+        render_scrollbar(frame, area, borders, anchor_rows, preview_position);
+    }
+    frame.render_widget(input, right_layout[1]);
+    if let Some(error) = error {
+        let error = Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red));
+        frame.render_widget(error, right_layout[2]);
+    }
+    frame.render_widget(status, right_layout[3]);
 
-            if app.show_help {
-                let help_text = vec![
+    // In the order they were opened, so the one taking keys is drawn on top
+    app.sync_overlays();
+    for overlay in app.overlays.clone() {
+        match overlay {
+            Overlay::Help => {
+                let quit = help_line(
+                    &app.config.quit_keys,
+                    Some("Ctrl+c"),
+                    if app
+                        .config
+                        .quit_keys
+                        .iter()
+                        .any(|key| key.to_event().code == KeyCode::Esc)
+                    {
+                        "Quit (Esc closes whatever is open first)"
+                    } else {
+                        "Quit"
+                    },
+                );
+                let suspend = help_line(&app.config.suspend_keys, None, "Suspend to the shell");
+                let mut help_text = vec![
                     "Keybindings:",
                     "─────────────",
                     "F1/Ctrl+h    Show/hide this help",
-                    "Ctrl+c/Esc   Quit",
+                    &quit,
+                    "Ctrl+q       Quit and write results in quickfix format",
+                    "Alt+q        Quit and open results in vim's quickfix list",
+                    "Alt+w        Copy the results to the clipboard",
+                    "Alt+n        Ignore the selected file's directory in this project",
+                    "Alt+r/R      Open/copy the selected file's GitHub or GitLab link",
                     "Ctrl+n       Switch to filename search",
                     "Ctrl+f       Switch to content search",
+                    "Alt+a        Toggle typo-tolerant content search",
                     "Ctrl+d       Toggle dirty files filter",
                     "Ctrl+b       Toggle changed from default filter",
+                    "Ctrl+r       Toggle recently committed filter",
+                    "Ctrl+t       Toggle recently modified filter",
+                    "Alt+u        Toggle duplicate files filter",
+                    "Ctrl+g       Show/hide linguist generated and vendored files",
+                    "Ctrl+s       Pick a saved search",
+                    "Alt+e        Pick a language to filter by",
+                    "Ctrl+o       Scope search to selected file's directory",
+                    "Ctrl+p       Pop back out of the scoped directory",
                     "↑/↓          Navigate files",
                     "Enter        Open selected file",
+                    "Alt+1..9     Open the file numbered at the right of the list",
                     "PgUp/PgDn    Scroll preview",
+                    "Tab/S-Tab    Move focus between the query, list and preview",
+                    "             List: j/k move, g/G first/last, Space marks, typing goes to the query",
+                    "             Preview: j/k scroll, g/G top/bottom, / searches, n/N next/previous",
+                    "Alt+→/←      Focus the preview/query",
+                    "Ctrl+l       Cycle absolute/relative/no line numbers",
+                    "Ctrl+a/e     Move to start/end of the query",
+                    "Alt+b/f      Move back/forward a word",
+                    "Ctrl+w/u     Delete previous word/whole query",
+                    "Ctrl+z/y     Undo/redo query changes (Alt+z/y too)",
+                    "Ctrl+x       Mark/unmark selected file as anchor for comparison",
+                    "Ctrl+v       Toggle diff against the anchor",
+                    "Alt+h        Toggle the selected file's recent commits in the preview",
+                    "Alt+m        Mark/unmark file and move down (Space in the list)",
+                    "Alt+M        Mark/unmark file and move up (Shift+Space in the list)",
+                    "Alt+s        Group files by directory, extension or not at all",
+                    "Alt+o        Fold/unfold the selected file's group",
+                    "Alt+t        Toggle the tree view (←/→ collapse/expand directories)",
+                    "Alt+l        Toggle showing each file's first matching line under it",
+                    "Alt+p        View the selected file in a pager",
+                    "F2           Rename or move the selected file, or the marked ones by pattern",
+                    "Alt+i        Show what the walk found and skipped",
+                    "Alt+g        Summarize values captured by the pattern's groups",
+                    "Alt+d        Delete the selected or marked files",
+                    "Alt+c        Run a command on the selected or marked files",
+                    ":            Start a command at the start of the query, :42 selects result 42",
+                    "Alt+x        Pick any action by name",
                 ];
+                if !app.config.suspend_keys.is_empty() {
+                    help_text.insert(4, &suspend);
+                }
+                #[cfg(feature = "profiling")]
+                help_text.push("F12          Show timings of the last walk, search and frame");
+                if app.accessible {
+                    help_text.retain(|line| !line.starts_with('─'));
+                }
 
                 let block = Block::default()
                     .title("Help")
-                    .borders(Borders::ALL)
+                    .borders(borders)
                     .style(Style::default().bg(Color::Black));
 
                 let help_paragraph = Paragraph::new(Text::from(help_text.join("\n")))
                     .block(block)
                     .wrap(Wrap { trim: true });
 
-                // Create a centered rect for the modal
-                let area = frame.size();
-                let help_rect = Rect::new(
-                    (area.width as i32 / 2 - 30).max(0) as u16,
-                    (area.height as i32 / 2 - 7).max(0) as u16,
-                    60,
-                    14,
-                );
+                // Size the modal to fit the keybindings
+                let help_rect = centered_rect(frame.size(), 60, help_text.len() as u16 + 2);
 
                 frame.render_widget(Clear, help_rect); // Clear the background
                 frame.render_widget(help_paragraph, help_rect);
             }
-        })?;
+            Overlay::SavedPicker => {
+                if let Some(selected) = app.saved_picker {
+                    let items: Vec<ListItem> = app
+                        .config
+                        .saved_searches
+                        .iter()
+                        .enumerate()
+                        .map(|(i, search)| {
+                            let style = if i == selected {
+                                Style::default().bg(Color::DarkGray)
+                            } else {
+                                Style::default()
+                            };
+                            ListItem::new(format!("{:20} {}", search.name, search.query))
+                                .style(style)
+                        })
+                        .collect();
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => break,
-                    KeyCode::Esc => break,
-                    KeyCode::Char('n') if key.modifiers == KeyModifiers::CONTROL => {
-                        app.search_mode = SearchMode::Filename;
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
-                    KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => {
-                        app.search_mode = SearchMode::Contents;
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
-                    KeyCode::Char('b') if key.modifiers == KeyModifiers::CONTROL => {
-                        app.file_filter = match app.file_filter {
-                            FileFilter::ChangedFromDefault => FileFilter::All,
-                            _ => FileFilter::ChangedFromDefault,
-                        };
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
-                    KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => {
-                        app.file_filter = match app.file_filter {
-                            FileFilter::All | FileFilter::ChangedFromDefault => FileFilter::Dirty,
-                            FileFilter::Dirty => FileFilter::All,
+                    let picker_rect = centered_rect(frame.size(), 60, items.len() as u16 + 2);
+                    let picker = List::new(items).block(
+                        Block::default()
+                            .title("Saved Searches")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, picker_rect);
+                    frame.render_widget(picker, picker_rect);
+                }
+            }
+            Overlay::LanguagePicker => {
+                if let Some(picker) = &app.language_picker {
+                    let rows = std::iter::once(("Any language".to_string(), None)).chain(
+                        picker
+                            .languages
+                            .iter()
+                            .map(|(name, count)| (name.to_string(), Some(*count))),
+                    );
+                    let items: Vec<ListItem> = rows
+                        .enumerate()
+                        .map(|(i, (name, count))| {
+                            let style = if i == picker.selected {
+                                Style::default().bg(Color::DarkGray)
+                            } else {
+                                Style::default()
+                            };
+                            let count = count.map(|count| count.to_string()).unwrap_or_default();
+                            ListItem::new(format!("{:40} {:>6}", name, count)).style(style)
+                        })
+                        .collect();
+
+                    let picker_rect =
+                        centered_rect(frame.size(), 60, (items.len() as u16 + 2).min(20));
+                    let mut state = ListState::default().with_selected(Some(picker.selected));
+                    let list = List::new(items).block(
+                        Block::default()
+                            .title("Languages")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, picker_rect);
+                    frame.render_stateful_widget(list, picker_rect, &mut state);
+                }
+            }
+            Overlay::CommandPrompt => {
+                if let Some(prompt) = &app.command_prompt {
+                    let mut lines = vec![Line::from(format!(
+                        "> {}",
+                        input_with_cursor(&prompt.input)
+                    ))];
+                    lines.extend(app.config.commands.iter().enumerate().map(|(i, command)| {
+                        let style = if Some(i) == prompt.choice {
+                            Style::default().bg(Color::DarkGray)
+                        } else {
+                            Style::default().fg(Color::Gray)
                         };
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
+                        Line::styled(format!("  {}", command), style)
+                    }));
 
-                    KeyCode::Backspace => {
-                        app.input.handle_event(&Event::Key(key));
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
-                    KeyCode::Up => {
-                        app.selected_index = app.selected_index.saturating_sub(1);
-                        app.reset_scroll();
-                    }
-                    KeyCode::Down => {
-                        if !app.filtered_files.is_empty() {
-                            app.selected_index =
-                                (app.selected_index + 1).min(app.filtered_files.len() - 1);
-                            app.reset_scroll();
-                        }
+                    let prompt_rect = centered_rect(frame.size(), 70, lines.len() as u16 + 2);
+                    let prompt = Paragraph::new(lines).block(
+                        Block::default()
+                            .title("Run Command ({} file, {+} selected files)")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, prompt_rect);
+                    frame.render_widget(prompt, prompt_rect);
+                }
+            }
+            Overlay::Palette => {
+                if let Some(palette) = &app.palette {
+                    // Enough of the list to scroll through, keeping the chosen entry in view
+                    const SHOWN: usize = 15;
+                    let matches = palette.matches();
+                    let start = palette.selected.saturating_sub(SHOWN - 1);
+                    let mut lines = vec![Line::from(format!(
+                        "> {}",
+                        input_with_cursor(&palette.input)
+                    ))];
+                    lines.extend(matches.iter().enumerate().skip(start).take(SHOWN).map(
+                        |(i, entry)| {
+                            let style = if i == palette.selected {
+                                Style::default().bg(Color::DarkGray)
+                            } else {
+                                Style::default().fg(Color::Gray)
+                            };
+                            Line::from(vec![
+                                Span::styled(format!("  {:<46}", entry.name), style),
+                                Span::styled(
+                                    format!("{:>10}", entry.key().unwrap_or("")),
+                                    style.fg(Color::DarkGray),
+                                ),
+                            ])
+                        },
+                    ));
+                    if matches.is_empty() {
+                        lines.push(Line::styled(
+                            "  No matching actions",
+                            Style::default().fg(Color::Gray),
+                        ));
                     }
-                    KeyCode::Enter => {
-                        if !app.filtered_files.is_empty() {
-                            app.execute_command()?;
-                            break;
+
+                    let palette_rect = centered_rect(frame.size(), 62, lines.len() as u16 + 2);
+                    let palette = Paragraph::new(lines).block(
+                        Block::default()
+                            .title("Actions")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+                    frame.render_widget(Clear, palette_rect);
+                    frame.render_widget(palette, palette_rect);
+                }
+            }
+            Overlay::Pager => {
+                if let Some(pager) = &app.pager {
+                    let area = frame.size();
+                    let pager_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(1)])
+                        .split(area);
+                    let content = Paragraph::new(pager.text.clone())
+                        .block(
+                            Block::default()
+                                .borders(borders)
+                                .title(app.display_path(&pager.path)),
+                        )
+                        .scroll((pager.scroll, pager.column));
+
+                    frame.render_widget(Clear, area);
+                    frame.render_widget(content, pager_layout[0]);
+                    render_scrollbar(
+                        frame,
+                        pager_layout[0],
+                        borders,
+                        pager.text.lines.len(),
+                        pager.scroll as usize,
+                    );
+                    frame.render_widget(Paragraph::new(pager.status()), pager_layout[1]);
+                }
+            }
+            Overlay::RenamePrompt => {
+                if let Some(input) = &app.rename_prompt {
+                    let prompt_rect = centered_rect(frame.size(), 70, 3);
+                    let prompt = Paragraph::new(input_with_cursor(input)).block(
+                        Block::default()
+                            .title("Rename or Move")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, prompt_rect);
+                    frame.render_widget(prompt, prompt_rect);
+                }
+            }
+            Overlay::BatchRename => {
+                if let Some(batch) = &app.batch_rename {
+                    let field = |label: &str, input: &TextInput, editing: bool| {
+                        let value = if editing {
+                            input_with_cursor(input)
+                        } else {
+                            input.value().to_string()
+                        };
+                        Line::from(vec![
+                            Span::styled(label.to_string(), Style::default().fg(Color::Cyan)),
+                            Span::raw(value),
+                        ])
+                    };
+                    let mut lines = vec![
+                        field("Pattern:     ", &batch.pattern, !batch.editing_replacement),
+                        field(
+                            "Replacement: ",
+                            &batch.replacement,
+                            batch.editing_replacement,
+                        ),
+                        Line::from(""),
+                    ];
+                    let mut renames = 0;
+                    match batch.plan() {
+                        Err(err) => lines.push(Line::styled(
+                            regex_error_message(&err),
+                            Style::default().fg(Color::Red),
+                        )),
+                        Ok(plan) => {
+                            for (from, planned) in plan {
+                                let from = from.display();
+                                lines.push(match planned {
+                                    Planned::Rename(to) => {
+                                        renames += 1;
+                                        Line::from(format!("{} → {}", from, to.display()))
+                                    }
+                                    Planned::Unchanged => Line::styled(
+                                        format!("{} (unchanged)", from),
+                                        Style::default().fg(Color::DarkGray),
+                                    ),
+                                    Planned::Conflict(to) => Line::styled(
+                                        format!("{} → {} (already taken)", from, to.display()),
+                                        Style::default().fg(Color::Red),
+                                    ),
+                                });
+                            }
                         }
                     }
-                    KeyCode::F(1) => {
-                        app.toggle_help();
-                    }
-                    KeyCode::Char('h') if key.modifiers == KeyModifiers::CONTROL => {
-                        app.toggle_help();
-                    }
-                    KeyCode::Char(_) => {
-                        app.input.handle_event(&Event::Key(key));
-                        app.filter_files();
-                        app.reset_scroll();
-                    }
-                    // vim-ish bindings too
-                    KeyCode::PageUp => {
-                        app.scroll_preview(frame_height, -1);
+                    lines.push(Line::from(""));
+                    lines.push(Line::styled(
+                        format!(
+                            "Tab to switch fields, Enter to rename {} file(s), Esc to cancel",
+                            renames
+                        ),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                    let rename_rect = centered_rect(
+                        frame.size(),
+                        frame.size().width.saturating_sub(10),
+                        lines.len() as u16 + 2,
+                    );
+                    let rename = Paragraph::new(lines).block(
+                        Block::default()
+                            .title(format!("Rename {} Files", batch.files.len()))
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, rename_rect);
+                    frame.render_widget(rename, rename_rect);
+                }
+            }
+            Overlay::ConfirmQuit => {
+                let lost = match &app.batch_rename {
+                    Some(batch) => format!("  {} file(s) won't be renamed", batch.files.len()),
+                    None => format!("  {} marked file(s) will be forgotten", app.marked.len()),
+                };
+                let lines = vec![
+                    Line::from(lost),
+                    Line::from(""),
+                    Line::styled(
+                        "y to quit, any other key to stay",
+                        Style::default().fg(Color::Yellow),
+                    ),
+                ];
+                let confirm_rect = centered_rect(frame.size(), 50, lines.len() as u16 + 2);
+                let confirm = Paragraph::new(lines).block(
+                    Block::default()
+                        .title("Quit?")
+                        .borders(borders)
+                        .style(Style::default().bg(Color::Black)),
+                );
+
+                frame.render_widget(Clear, confirm_rect);
+                frame.render_widget(confirm, confirm_rect);
+            }
+            Overlay::ConfirmDelete => {
+                if let Some(paths) = &app.confirm_delete {
+                    let action = if app.config.permanent_delete {
+                        "Permanently delete"
+                    } else {
+                        "Move to trash"
+                    };
+                    let mut lines: Vec<Line> = paths
+                        .iter()
+                        .map(|path| Line::from(format!("  {}", app.display_path(path))))
+                        .collect();
+                    lines.push(Line::from(""));
+                    lines.push(Line::styled(
+                        "y to confirm, any other key to cancel",
+                        Style::default().fg(Color::Yellow),
+                    ));
+
+                    let confirm_rect = centered_rect(frame.size(), 70, lines.len() as u16 + 2);
+                    let confirm = Paragraph::new(lines).block(
+                        Block::default()
+                            .title(format!("{} {} file(s)?", action, paths.len()))
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+
+                    frame.render_widget(Clear, confirm_rect);
+                    frame.render_widget(confirm, confirm_rect);
+                }
+            }
+            Overlay::CaptureSurvey => {
+                if let Some(survey) = &app.capture_survey {
+                    let mut lines = app.capture_survey_lines(survey.by_value);
+                    if lines.is_empty() {
+                        lines.push(
+                            "No captured values, add a group like `use (\\w+)::`".to_string(),
+                        );
                     }
-                    KeyCode::PageDown => {
-                        app.scroll_preview(frame_height, 1);
+                    let area = frame.size();
+                    let survey_rect =
+                        centered_rect(area, 60, (lines.len() as u16 + 2).min(area.height));
+                    let order = if survey.by_value { "value" } else { "count" };
+                    let survey_text = Paragraph::new(lines.join("\n"))
+                        .block(
+                            Block::default()
+                                .title(format!("Captures by {} (s to sort by the other)", order))
+                                .borders(borders)
+                                .style(Style::default().bg(Color::Black)),
+                        )
+                        .scroll((survey.scroll, 0));
+
+                    frame.render_widget(Clear, survey_rect);
+                    frame.render_widget(survey_text, survey_rect);
+                }
+            }
+            Overlay::StatsPopup => {
+                if let Some(lines) = &app.stats_popup {
+                    let area = frame.size();
+                    let stats_rect =
+                        centered_rect(area, 72, (lines.len() as u16 + 2).min(area.height));
+                    let stats_text = Paragraph::new(lines.join("\n")).block(
+                        Block::default()
+                            .title("Walk Statistics")
+                            .borders(borders)
+                            .style(Style::default().bg(Color::Black)),
+                    );
+                    frame.render_widget(Clear, stats_rect);
+                    frame.render_widget(stats_text, stats_rect);
+                }
+            }
+            Overlay::ConfigProblems => {
+                if let Some(problems) = &app.config_problems {
+                    let area = frame.size();
+                    let mut lines: Vec<Line> = problems
+                        .iter()
+                        .flat_map(|problem| problem.lines().chain(std::iter::once("")))
+                        .map(|line| Line::raw(line.to_string()))
+                        .collect();
+                    lines.push(Line::styled(
+                        "Press any key to continue",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                    let width = area.width * 4 / 5;
+                    let rect =
+                        centered_rect(area, width, (lines.len() as u16 + 2).min(area.height));
+                    let text = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                        Block::default()
+                            .title("Config Problems")
+                            .borders(borders)
+                            .border_style(Style::default().fg(Color::Red))
+                            .style(Style::default().bg(Color::Black)),
+                    );
+                    frame.render_widget(Clear, rect);
+                    frame.render_widget(text, rect);
+                }
+            }
+            Overlay::CommandOutput => {
+                if let Some(output) = &app.command_output {
+                    let area = frame.size();
+                    let output_rect = centered_rect(area, area.width * 4 / 5, area.height * 4 / 5);
+                    let output_text = Paragraph::new(output.text.as_str())
+                        .block(
+                            Block::default()
+                                .title(output.title.as_str())
+                                .borders(borders)
+                                .style(Style::default().bg(Color::Black)),
+                        )
+                        .scroll((output.scroll, 0));
+
+                    frame.render_widget(Clear, output_rect);
+                    frame.render_widget(output_text, output_rect);
+                }
+            }
+            Overlay::CommandLine => {}
+        }
+    }
+
+    // Over everything else, in the top right corner
+    #[cfg(feature = "profiling")]
+    if app.profile.shown {
+        let area = frame.size();
+        let lines = app.profile.lines();
+        let longest = lines.iter().map(|line| line.chars().count()).max();
+        let width = (longest.unwrap_or(0) as u16 + 2).min(area.width / 2);
+        let rect = Rect {
+            x: area.width - width,
+            y: 1,
+            width,
+            height: (lines.len() as u16 + 2).min(area.height.saturating_sub(1)),
+        };
+        let text = Paragraph::new(lines.join("\n")).block(
+            Block::default()
+                .title("Profile")
+                .borders(borders)
+                .style(Style::default().bg(Color::Black)),
+        );
+        frame.render_widget(Clear, rect);
+        frame.render_widget(text, rect);
+    }
+}
+
+fn run_app(cli: Cli) -> Result<()> {
+    if let Some(Subcommands::Stats {
+        query,
+        roots,
+        filename,
+        json,
+    }) = cli.command
+    {
+        return run_stats(query, roots, filename, json);
+    }
+    let mut app = App::new(cli.roots);
+    app.progressive = !cli.lines;
+    if cli.daemon {
+        #[cfg(unix)]
+        return daemon::serve(&app.roots, &app.search_defaults);
+        #[cfg(not(unix))]
+        return Err(anyhow!(
+            "--daemon needs Unix sockets, which this platform doesn't have"
+        ));
+    }
+    #[cfg(feature = "lua")]
+    if let Some(Err(err)) = app.plugins.as_ref().map(plugins::Plugins::start) {
+        app.message = Some(format!("Lua: {:#}", err));
+    }
+    if let Some(name) = &cli.saved {
+        let search =
+            app.config.saved_search(name).cloned().ok_or_else(|| {
+                anyhow!("No saved search named '{}' in {}", name, Config::location())
+            })?;
+        app.apply_saved_search(&search);
+    } else {
+        app.filter_files();
+    }
+
+    #[cfg(unix)]
+    let control = match &cli.listen {
+        Some(path) => {
+            let server = control::ControlServer::listen(path)?;
+            // So editors glancr opens, and commands it runs, can talk back to it
+            std::env::set_var("GLANCR_SOCKET", path);
+            Some(server)
+        }
+        None => None,
+    };
+    #[cfg(not(unix))]
+    if cli.listen.is_some() {
+        return Err(anyhow!(
+            "--listen needs Unix sockets, which this platform doesn't have"
+        ));
+    }
+
+    app.accessible |= cli.accessible;
+    if cli.lines {
+        let exit = linemode::run(&mut app, stdin().lock(), stdout())?;
+        return finish(&mut app, exit, cli.output.as_deref());
+    }
+
+    if app.config.watch {
+        match Watcher::new(&app.roots) {
+            Ok(watcher) => app.watcher = Some(watcher),
+            Err(err) => app.message = Some(format!("Not watching for changes: {:#}", err)),
+        }
+    }
+
+    let signals = Signals::register()?;
+    let guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut exit = Exit::Quit;
+
+    // Set by a key press that left the screen as it was, which has nothing new to draw
+    let mut unchanged = false;
+    'app: loop {
+        if !std::mem::take(&mut unchanged) {
+            #[cfg(feature = "profiling")]
+            let frame_started = Instant::now();
+            let preview = app.get_file_preview();
+            #[cfg(feature = "profiling")]
+            if let Some((path, took)) = &app.previewer.load_time {
+                app.profile.highlighted(*took, path);
+            }
+            terminal.draw(|frame| draw(frame, &mut app, preview))?;
+            #[cfg(feature = "profiling")]
+            app.profile.drew(frame_started);
+        }
+
+        // Wait for input, checking for signals in between
+        let mut replayed = false;
+        let event = loop {
+            if let Some(key) = app.replay.take() {
+                replayed = true;
+                break Some(Event::Key(key));
+            }
+            if signals.terminated() {
+                break 'app;
+            }
+            if signals.take_suspend() {
+                break None;
+            }
+            #[cfg(unix)]
+            if let Some((request, reply)) = control.as_ref().and_then(|c| c.try_recv()) {
+                let _ = reply.send(app.handle_control(request));
+                continue 'app;
+            }
+            if app.tick() {
+                continue 'app;
+            }
+            // Anything that can't change what's on screen is dropped here, without a redraw
+            if event::poll(app.tick_interval())? {
+                let event = event::read()?;
+                if changes_screen(&event) {
+                    break Some(event);
+                }
+            }
+        };
+        let Some(event) = event else {
+            guard.suspend()?;
+            terminal.clear()?;
+            continue;
+        };
+
+        let Event::Key(key) = event else {
+            continue;
+        };
+        let before = app.screen_state();
+        match handle_key(&mut app, key, replayed)? {
+            Step::Redraw => unchanged = !before.overlay && app.screen_state() == before,
+            Step::Exit(how) => {
+                exit = how;
+                break;
+            }
+            Step::Suspend => {
+                guard.suspend()?;
+                terminal.clear()?;
+            }
+            Step::Foreground => {
+                for mut command in std::mem::take(&mut app.foreground) {
+                    if let Err(err) = guard.run_foreground(&mut command) {
+                        app.message = Some(format!(
+                            "Unable to run {}: {:#}",
+                            command.get_program().to_string_lossy(),
+                            err
+                        ));
                     }
-                    _ => {}
                 }
+                terminal.clear()?;
             }
         }
     }
 
-    stdout().execute(LeaveAlternateScreen)?;
-    disable_raw_mode()?;
-    Ok(())
+    drop(guard);
+    finish(&mut app, exit, cli.output.as_deref())
+}
+
+// Only key presses are handled, and a resize needs everything laid out again. Releases,
+// focus changes and the rest are ignored.
+fn changes_screen(event: &Event) -> bool {
+    match event {
+        Event::Key(key) => key.kind == KeyEventKind::Press,
+        Event::Resize(..) => true,
+        _ => false,
+    }
+}
+
+// Runs a terminal editor, the exit hooks, then whatever the session ended by asking for
+fn finish(app: &mut App, exit: Exit, output: Option<&Path>) -> Result<()> {
+    app.file_index.save();
+    for mut command in std::mem::take(&mut app.foreground) {
+        command.status().with_context(|| {
+            format!("Unable to run {}", command.get_program().to_string_lossy())
+        })?;
+    }
+    let env = app.hook_env(&app.selected_paths());
+    let hooks = &app.config.hooks;
+    if let (Exit::Open, Some(hook)) = (&exit, &hooks.after_open) {
+        commands::run_exit_hook(hook, &env);
+    }
+    if let Some(hook) = &hooks.on_quit {
+        commands::run_exit_hook(hook, &env);
+    }
+
+    match exit {
+        Exit::Quit | Exit::Open => Ok(()),
+        Exit::Export => app.export_results(output),
+        Exit::Quickfix => app.open_quickfix(),
+    }
 }
 
 fn main() -> Result<()> {
-    run_app().context("Error running application")
+    let cli = Cli::parse();
+    terminal::install_panic_hook();
+    run_app(cli).context("Error running application")
 }
@@ -0,0 +1,184 @@
+use crate::preview::{self, get_file_preview, PreviewOptions};
+use crate::{search, SearchMode};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::text::Text;
+use std::path::PathBuf;
+use tui_input::backend::crossterm::to_input_request;
+use tui_input::Input as TextInput;
+
+// Full-screen view of a single file with less-style keys and search
+pub struct Pager {
+    pub path: PathBuf,
+    pub text: Text<'static>,
+    pub scroll: u16,
+    pub column: u16,
+    // The search being typed after `/`
+    pub input: Option<TextInput>,
+    term: String,
+    matches: Vec<usize>,
+}
+
+impl Pager {
+    pub fn open(path: PathBuf, options: &PreviewOptions) -> Self {
+        let (text, _) = get_file_preview(&path, "", SearchMode::Contents, options);
+        Pager {
+            path,
+            text,
+            scroll: 0,
+            column: 0,
+            input: None,
+            term: String::new(),
+            matches: Vec::new(),
+        }
+    }
+
+    // Highlights a literal, smart-cased term and jumps to its next match
+    fn search(&mut self, term: String, options: &PreviewOptions) {
+        let pattern = search::literal_pattern(&term);
+        let (text, _) = get_file_preview(&self.path, &pattern, SearchMode::Contents, options);
+        self.matches = preview::match_lines(&text);
+        self.text = text;
+        self.term = term;
+        self.scroll = self.scroll.saturating_sub(1);
+        self.next_match(true);
+    }
+
+    fn next_match(&mut self, forward: bool) {
+        let current = self.scroll as usize;
+        let target = if forward {
+            self.matches.iter().find(|&&line| line > current)
+        } else {
+            self.matches.iter().rev().find(|&&line| line < current)
+        };
+        // Wrap around like less does
+        let target = target.or(if forward {
+            self.matches.first()
+        } else {
+            self.matches.last()
+        });
+        if let Some(&line) = target {
+            self.scroll = line as u16;
+        }
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        let last = self.text.lines.len().saturating_sub(1);
+        self.scroll = (self.scroll as usize)
+            .saturating_add_signed(delta)
+            .min(last) as u16;
+    }
+
+    // Shown under the file, like the bottom line of less
+    pub fn status(&self) -> String {
+        if let Some(input) = &self.input {
+            return format!("/{}|", input.value());
+        }
+        if self.term.is_empty() {
+            return "/ search  n/N next/previous  q quit".to_string();
+        }
+        let position = self
+            .matches
+            .iter()
+            .position(|&line| line == self.scroll as usize)
+            .map(|index| format!("{}/", index + 1))
+            .unwrap_or_default();
+        format!(
+            "{}{} matches for '{}'",
+            position,
+            self.matches.len(),
+            self.term
+        )
+    }
+
+    // Returns false once the pager should close
+    pub fn handle_key(&mut self, key: KeyEvent, page: u16, options: &PreviewOptions) -> bool {
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Esc => self.input = None,
+                KeyCode::Enter => {
+                    let term = input.value().to_string();
+                    self.input = None;
+                    self.search(term, options);
+                }
+                _ => {
+                    if let Some(request) = to_input_request(&Event::Key(key)) {
+                        input.handle(request);
+                    }
+                }
+            }
+            return true;
+        }
+
+        let page = page.max(1) as isize;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => self.scroll_by(1),
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_by(-page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll_by(isize::MAX),
+            KeyCode::Left => self.column = self.column.saturating_sub(8),
+            KeyCode::Right => self.column = self.column.saturating_add(8),
+            KeyCode::Char('/') => self.input = Some(TextInput::default()),
+            KeyCode::Char('n') => self.next_match(true),
+            KeyCode::Char('N') => self.next_match(false),
+            _ => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_keys(pager: &mut Pager, keys: &str) {
+        for c in keys.chars() {
+            pager.handle_key(key(KeyCode::Char(c)), 10, &PreviewOptions::default());
+        }
+    }
+
+    #[test]
+    fn test_search_jumps_between_matches() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "one\nneedle\ntwo\nthree\nNeedle\n").unwrap();
+        let options = PreviewOptions::default();
+        let mut pager = Pager::open(path, &options);
+
+        type_keys(&mut pager, "/needle");
+        assert_eq!(pager.status(), "/needle|");
+        pager.handle_key(key(KeyCode::Enter), 10, &options);
+        assert_eq!(pager.scroll, 1);
+        assert_eq!(pager.status(), "1/2 matches for 'needle'");
+
+        type_keys(&mut pager, "n");
+        assert_eq!(pager.scroll, 4);
+        type_keys(&mut pager, "n");
+        assert_eq!(pager.scroll, 1);
+        type_keys(&mut pager, "N");
+        assert_eq!(pager.scroll, 4);
+    }
+
+    #[test]
+    fn test_quit_and_scroll_limits() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "a\nb\nc\n").unwrap();
+        let options = PreviewOptions::default();
+        let mut pager = Pager::open(path, &options);
+
+        type_keys(&mut pager, "G");
+        assert_eq!(pager.scroll, 2);
+        type_keys(&mut pager, "g");
+        assert_eq!(pager.scroll, 0);
+        assert!(!pager.handle_key(key(KeyCode::Char('q')), 10, &options));
+    }
+}
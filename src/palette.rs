@@ -0,0 +1,184 @@
+use crate::excommand::{ExCommand, Sort};
+use crate::keys::KeySpec;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use tui_input::Input as TextInput;
+
+// What picking an entry does
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    // Whatever the key does, written like `ctrl+d`
+    Key(&'static str),
+    Ex(ExCommand),
+    // Actions without a key of their own
+    ToggleHidden,
+    NextTheme,
+    // The : command line, with the start of a command already typed
+    CommandLine(&'static str),
+}
+
+pub struct Entry {
+    pub name: &'static str,
+    pub action: Action,
+}
+
+impl Entry {
+    // The key that does the same, for learning it from the palette
+    pub fn key(&self) -> Option<&'static str> {
+        match self.action {
+            Action::Key(key) => Some(key),
+            Action::CommandLine(_) => Some(":"),
+            _ => None,
+        }
+    }
+}
+
+const fn entry(name: &'static str, action: Action) -> Entry {
+    Entry { name, action }
+}
+
+// Every action, in the order they're listed before anything is typed
+pub const ENTRIES: &[Entry] = &[
+    entry("Search file names", Action::Key("Ctrl+n")),
+    entry("Search file contents", Action::Key("Ctrl+f")),
+    entry("Toggle approximate content search", Action::Key("Alt+a")),
+    entry("Toggle dirty files filter", Action::Key("Ctrl+d")),
+    entry(
+        "Toggle changed from default branch filter",
+        Action::Key("Ctrl+b"),
+    ),
+    entry("Toggle recently committed filter", Action::Key("Ctrl+r")),
+    entry("Toggle recently modified filter", Action::Key("Ctrl+t")),
+    entry("Toggle duplicate files filter", Action::Key("Alt+u")),
+    entry("Toggle generated and vendored files", Action::Key("Ctrl+g")),
+    entry("Toggle hidden files", Action::ToggleHidden),
+    entry(
+        "Sort in walk order",
+        Action::Ex(ExCommand::Sort(Sort::Walk)),
+    ),
+    entry(
+        "Sort newest first",
+        Action::Ex(ExCommand::Sort(Sort::Mtime)),
+    ),
+    entry(
+        "Sort most recently committed first",
+        Action::Ex(ExCommand::Sort(Sort::Recent)),
+    ),
+    entry("Toggle tree view", Action::Key("Alt+t")),
+    entry(
+        "Group by directory, extension or nothing",
+        Action::Key("Alt+s"),
+    ),
+    entry("Fold or unfold the selected group", Action::Key("Alt+o")),
+    entry("Pick a saved search", Action::Key("Ctrl+s")),
+    entry("Pick a language", Action::Key("Alt+e")),
+    entry(
+        "Scope to the selected file's directory",
+        Action::Key("Ctrl+o"),
+    ),
+    entry("Pop out of the scoped directory", Action::Key("Ctrl+p")),
+    entry("Reload", Action::Ex(ExCommand::Reload)),
+    entry("Undo query change", Action::Key("Alt+z")),
+    entry("Redo query change", Action::Key("Alt+y")),
+    entry("Next theme", Action::NextTheme),
+    entry("Cycle line numbers", Action::Key("Ctrl+l")),
+    entry("Toggle matching line under each file", Action::Key("Alt+l")),
+    entry("Toggle recent commits in the preview", Action::Key("Alt+h")),
+    entry("Mark as anchor for comparison", Action::Key("Ctrl+x")),
+    entry("Toggle diff against the anchor", Action::Key("Ctrl+v")),
+    entry("Focus the preview", Action::Key("Alt+Right")),
+    entry("Move focus to the next pane", Action::Key("Tab")),
+    entry("Mark or unmark the selected file", Action::Key("Alt+m")),
+    entry("View in the pager", Action::Key("Alt+p")),
+    entry("Rename or move", Action::Key("F2")),
+    entry("Delete", Action::Key("Alt+d")),
+    entry("Run a command", Action::Key("Alt+c")),
+    entry("Show walk stats", Action::Key("Alt+i")),
+    entry("Summarize captured values", Action::Key("Alt+g")),
+    entry("Type a : command", Action::CommandLine("")),
+    entry("Copy results to the clipboard", Action::Key("Alt+w")),
+    entry("Write results to a file", Action::CommandLine("write ")),
+    entry("Ignore the selected directory", Action::Key("Alt+n")),
+    entry(
+        "Open the selected file on GitHub or GitLab",
+        Action::Key("Alt+r"),
+    ),
+    entry(
+        "Copy the selected file's GitHub or GitLab link",
+        Action::Key("Alt+R"),
+    ),
+    entry(
+        "Export results in quickfix format and quit",
+        Action::Key("Ctrl+q"),
+    ),
+    entry("Open results in vim's quickfix list", Action::Key("Alt+q")),
+    entry("Suspend to the shell", Action::Key("Ctrl+z")),
+    entry("Show help", Action::Key("F1")),
+    entry("Quit", Action::Key("Ctrl+c")),
+];
+
+// Every action by name, narrowed down by fuzzy matching what's typed
+#[derive(Default)]
+pub struct Palette {
+    pub input: TextInput,
+    pub selected: usize,
+}
+
+impl Palette {
+    // The best matches first, or everything in order before anything is typed
+    pub fn matches(&self) -> Vec<&'static Entry> {
+        let typed = self.input.value();
+        if typed.is_empty() {
+            return ENTRIES.iter().collect();
+        }
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, &'static Entry)> = ENTRIES
+            .iter()
+            .filter_map(|entry| {
+                let haystack = format!("{} {}", entry.name, entry.key().unwrap_or(""));
+                Some((matcher.fuzzy_match(&haystack, typed)?, entry))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn choose(&mut self, delta: isize) {
+        let last = self.matches().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn chosen(&self) -> Option<Action> {
+        self.matches()
+            .get(self.selected)
+            .map(|entry| entry.action.clone())
+    }
+}
+
+// The key press an action stands for, if it's one
+pub fn key_event(key: &str) -> Option<crossterm::event::KeyEvent> {
+    KeySpec::parse(key).map(KeySpec::to_event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_by_name_and_key() {
+        for entry in ENTRIES {
+            if let Action::Key(key) = entry.action {
+                assert!(key_event(key).is_some(), "{}", key);
+            }
+        }
+
+        let mut palette = Palette::default();
+        assert_eq!(palette.matches().len(), ENTRIES.len());
+        palette.input = TextInput::new("dirty".to_string());
+        assert_eq!(palette.chosen(), Some(Action::Key("Ctrl+d")));
+        palette.input = TextInput::new("hidden".to_string());
+        assert_eq!(palette.chosen(), Some(Action::ToggleHidden));
+        palette.input = TextInput::new("zzzz".to_string());
+        assert_eq!(palette.chosen(), None);
+    }
+}
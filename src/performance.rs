@@ -0,0 +1,112 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// Below this many files a thread costs more to start than it saves
+const FILES_PER_THREAD: usize = 256;
+// A shorter tick would keep a core busy polling for nothing
+const MIN_TICK_MS: u64 = 10;
+
+// Knobs for the machine glancr runs on: a laptop on battery, a workstation with cores to spare
+// or a shell over a slow link
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerformanceConfig {
+    // Wait for typing to pause this long before searching contents, instead of
+    // `search_delay_ms`
+    pub debounce_ms: Option<u64>,
+    // How often to check on the watcher and previews while nothing is happening, 100 if unset
+    // and never less than 10
+    pub tick_ms: Option<u64>,
+    // Threads reading files for content searches, one per core if unset or 0
+    pub search_threads: usize,
+    // Keep only this many of the best results, all of them if unset or 0
+    pub max_results: usize,
+}
+
+impl PerformanceConfig {
+    pub fn threads(&self) -> usize {
+        if self.search_threads > 0 {
+            self.search_threads
+        } else {
+            thread::available_parallelism().map_or(1, |threads| threads.get())
+        }
+    }
+
+    pub fn tick(&self, default: Duration) -> Duration {
+        self.tick_ms
+            .map_or(default, |ms| Duration::from_millis(ms.max(MIN_TICK_MS)))
+    }
+}
+
+// Runs `search` over the files on up to `threads` threads, each with its own state from
+// `init`, and keeps what it returns in the files' order
+pub fn filter_map<S, T, I, F>(files: &[PathBuf], threads: usize, init: I, search: F) -> Vec<T>
+where
+    T: Send,
+    I: Fn() -> S + Sync,
+    F: Fn(&mut S, &Path) -> Option<T> + Sync,
+{
+    let threads = threads.min(files.len() / FILES_PER_THREAD).max(1);
+    let run = |chunk: &[PathBuf]| -> Vec<T> {
+        let mut state = init();
+        chunk
+            .iter()
+            .filter_map(|path| search(&mut state, path))
+            .collect()
+    };
+    if threads == 1 {
+        return run(files);
+    }
+    let chunk_size = files.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || run(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_map_keeps_order() {
+        let files: Vec<PathBuf> = (0..1000).map(|i| PathBuf::from(i.to_string())).collect();
+        let even = |threads| {
+            filter_map(
+                &files,
+                threads,
+                || (),
+                |_: &mut (), path| {
+                    let number: usize = path.to_str()?.parse().ok()?;
+                    number.is_multiple_of(2).then_some(number)
+                },
+            )
+        };
+        let expected: Vec<usize> = (0..1000).step_by(2).collect();
+        assert_eq!(even(1), expected);
+        assert_eq!(even(4), expected);
+        assert_eq!(even(64), expected);
+    }
+
+    #[test]
+    fn test_tick_has_a_minimum() {
+        let tick = |tick_ms| {
+            PerformanceConfig {
+                tick_ms,
+                ..PerformanceConfig::default()
+            }
+            .tick(Duration::from_millis(100))
+        };
+        assert_eq!(tick(None), Duration::from_millis(100));
+        assert_eq!(tick(Some(250)), Duration::from_millis(250));
+        assert_eq!(tick(Some(0)), Duration::from_millis(MIN_TICK_MS));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::keys::KeySpec;
+use anyhow::{Context, Result};
+use crossterm::event::KeyEvent;
+use mlua::{Function, Lua, Table, Value};
+use std::path::{Path, PathBuf};
+
+// The `glancr` table scripts register their hooks with
+const PRELUDE: &str = r#"
+glancr = { _on_start = {}, _on_results = {}, _filters = {}, _on_open = {}, _actions = {}, _bindings = {} }
+function glancr.on_start(f) table.insert(glancr._on_start, f) end
+function glancr.on_results(f) table.insert(glancr._on_results, f) end
+function glancr.filter_result(f) table.insert(glancr._filters, f) end
+function glancr.on_open(f) table.insert(glancr._on_open, f) end
+function glancr.action(name, f) glancr._actions[name] = f end
+function glancr.bind(key, name) glancr._bindings[key] = name end
+"#;
+
+// Lua scripts from ~/.config/glancr: init.lua, then plugins/*.lua in name order
+pub struct Plugins {
+    lua: Lua,
+}
+
+impl Plugins {
+    // None when there are no scripts to run
+    pub fn load() -> Result<Option<Self>> {
+        let Some(dir) = dirs::home_dir().map(|home| home.join(".config").join("glancr")) else {
+            return Ok(None);
+        };
+        Plugins::load_from(&dir)
+    }
+
+    fn load_from(dir: &Path) -> Result<Option<Self>> {
+        let mut scripts: Vec<PathBuf> = std::fs::read_dir(dir.join("plugins"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .collect();
+        scripts.sort();
+        let init = dir.join("init.lua");
+        if init.is_file() {
+            scripts.insert(0, init);
+        }
+        if scripts.is_empty() {
+            return Ok(None);
+        }
+
+        let lua = Lua::new();
+        lua.load(PRELUDE).exec()?;
+        for script in &scripts {
+            let source = std::fs::read_to_string(script)
+                .with_context(|| format!("Couldn't read {}", script.display()))?;
+            lua.load(source)
+                .set_name(script.display().to_string())
+                .exec()
+                .with_context(|| format!("Error in {}", script.display()))?;
+        }
+        Ok(Some(Plugins { lua }))
+    }
+
+    fn table(&self, name: &str) -> Result<Table> {
+        let glancr: Table = self.lua.globals().get("glancr")?;
+        Ok(glancr.get(name)?)
+    }
+
+    fn hooks(&self, name: &str) -> Result<Vec<Function>> {
+        Ok(self
+            .table(name)?
+            .sequence_values::<Function>()
+            .collect::<mlua::Result<_>>()?)
+    }
+
+    pub fn start(&self) -> Result<()> {
+        for hook in self.hooks("_on_start")? {
+            hook.call::<()>(())?;
+        }
+        Ok(())
+    }
+
+    // `filter_result` hooks drop a file by returning false
+    pub fn filter(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let filters = self.hooks("_filters")?;
+        if filters.is_empty() {
+            return Ok(paths);
+        }
+        let mut kept = Vec::with_capacity(paths.len());
+        'paths: for path in paths {
+            let name = path.to_string_lossy().into_owned();
+            for filter in &filters {
+                if let Value::Boolean(false) = filter.call::<Value>(name.as_str())? {
+                    continue 'paths;
+                }
+            }
+            kept.push(path);
+        }
+        Ok(kept)
+    }
+
+    // `on_results` hooks see the ranked results and can return a replacement list
+    pub fn results(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let mut paths = paths;
+        for hook in self.hooks("_on_results")? {
+            let names: Vec<String> = paths
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            if let Some(names) = hook.call::<Option<Vec<String>>>(names)? {
+                paths = names.into_iter().map(PathBuf::from).collect();
+            }
+        }
+        Ok(paths)
+    }
+
+    pub fn opened(&self, path: &Path, line: u64) -> Result<()> {
+        for hook in self.hooks("_on_open")? {
+            hook.call::<()>((path.to_string_lossy().into_owned(), line))?;
+        }
+        Ok(())
+    }
+
+    // The action bound to a key with `glancr.bind("alt+x", "name")`
+    pub fn binding(&self, key: &KeyEvent) -> Option<String> {
+        let bindings = self.table("_bindings").ok()?;
+        bindings
+            .pairs::<String, String>()
+            .filter_map(|pair| pair.ok())
+            .find(|(spec, _)| KeySpec::parse(spec).is_some_and(|bound| bound.matches(key)))
+            .map(|(_, action)| action)
+    }
+
+    // Runs an action on the selected file; whatever text it returns is shown as a message
+    pub fn run_action(&self, name: &str, path: Option<&Path>) -> Result<Option<String>> {
+        let action: Option<Function> = self.table("_actions")?.get(name)?;
+        let action = action.with_context(|| format!("No Lua action named '{}'", name))?;
+        let path = path.map(|path| path.to_string_lossy().into_owned());
+        Ok(action.call::<Option<String>>(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use tempfile::TempDir;
+
+    fn plugins(script: &str) -> Plugins {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("init.lua"), script).unwrap();
+        Plugins::load_from(dir.path()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_no_scripts() {
+        let dir = TempDir::new().unwrap();
+        assert!(Plugins::load_from(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_filter_and_results_hooks() {
+        let plugins = plugins(
+            r#"
+            glancr.filter_result(function(path) return not path:find("%.snap$") end)
+            glancr.on_results(function(paths) table.sort(paths) return paths end)
+            "#,
+        );
+        let paths = vec![
+            PathBuf::from("b.rs"),
+            PathBuf::from("a.snap"),
+            PathBuf::from("a.rs"),
+        ];
+        let filtered = plugins.filter(paths).unwrap();
+        assert_eq!(filtered, vec![PathBuf::from("b.rs"), PathBuf::from("a.rs")]);
+        let ordered = plugins.results(filtered).unwrap();
+        assert_eq!(ordered, vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")]);
+    }
+
+    #[test]
+    fn test_actions_and_bindings() {
+        let plugins = plugins(
+            r#"
+            opened = {}
+            glancr.on_open(function(path, line) opened[#opened + 1] = path .. ":" .. line end)
+            glancr.action("shout", function(path) return "opened " .. #opened .. ", at " .. path end)
+            glancr.bind("alt+x", "shout")
+            "#,
+        );
+        plugins.opened(Path::new("a.rs"), 3).unwrap();
+
+        let key = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::ALT);
+        let action = plugins.binding(&key).unwrap();
+        assert_eq!(
+            plugins
+                .run_action(&action, Some(Path::new("a.rs")))
+                .unwrap(),
+            Some("opened 1, at a.rs".to_string())
+        );
+        let other = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert_eq!(plugins.binding(&other), None);
+        assert!(plugins.run_action("missing", None).is_err());
+    }
+
+    #[test]
+    fn test_script_errors_name_the_file() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("init.lua"), "this is not lua").unwrap();
+        let err = Plugins::load_from(dir.path()).err().unwrap();
+        assert!(err.to_string().contains("init.lua"));
+    }
+}
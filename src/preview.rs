@@ -1,7 +1,11 @@
+use crate::assets;
+use crate::compressed;
+use crate::query::Replace;
+use crate::rowformat::format_size;
 use crate::SearchMode;
-use bat::assets::HighlightingAssets;
+use globset::{Glob, GlobMatcher};
 use grep::{
-    matcher::Matcher,
+    matcher::{Captures, Matcher},
     regex::RegexMatcher,
     searcher::{sinks::UTF8, BinaryDetection, SearcherBuilder},
 };
@@ -9,40 +13,228 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
 };
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
-use syntect::{easy::HighlightLines, highlighting::ThemeSet};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use syntect::{
+    easy::HighlightLines,
+    parsing::{SyntaxReference, SyntaxSet},
+};
 
 const MAX_FILE_SIZE: u64 = 1024 * 512; // 512KB threshold
 const MAX_LINES_TO_FORMAT: usize = 1000; // Reasonable number of lines to syntax highlight
+const CHUNK_LINES: usize = 1000; // Lines of a large file loaded past the one scrolled to
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineNumbers {
+    #[default]
+    Absolute,
+    // Distance from the first match, like vim's relativenumber
+    Relative,
+    Off,
+}
+
+impl LineNumbers {
+    pub fn next(self) -> Self {
+        match self {
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
+            LineNumbers::Off => LineNumbers::Absolute,
+        }
+    }
+}
+
+// Highlights files matching a glob as the named syntax, like `*.vue` as `html`
+#[derive(Debug, Clone)]
+pub struct SyntaxOverride {
+    matcher: GlobMatcher,
+    // Globs without a slash only look at the file name
+    whole_path: bool,
+    pub syntax: String,
+}
+
+impl SyntaxOverride {
+    pub fn new(pattern: &str, syntax: &str) -> Result<Self, globset::Error> {
+        Ok(Self {
+            matcher: Glob::new(pattern)?.compile_matcher(),
+            whole_path: pattern.contains('/'),
+            syntax: syntax.to_string(),
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        if self.whole_path {
+            self.matcher
+                .is_match(path.strip_prefix("./").unwrap_or(path))
+        } else {
+            path.file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PreviewOptions {
+    pub line_numbers: LineNumbers,
+    pub syntax_overrides: Vec<SyntaxOverride>,
+    // Name of a built-in theme or one from ~/.config/glancr/themes
+    pub theme: String,
+    // Show compressed files decompressed
+    pub decompress: bool,
+    // Shows each match crossed out, followed by what replacing it would leave
+    pub replace: Option<Replace>,
+    // Languages highlighted by tree-sitter instead of syntect
+    #[cfg(feature = "tree-sitter")]
+    pub tree_sitter: Vec<String>,
+}
+
+// Renders the line number column, sized to fit the last line number
+struct Gutter {
+    mode: LineNumbers,
+    width: usize,
+    first_match: Option<usize>,
+}
+
+impl Gutter {
+    fn new(mode: LineNumbers, line_count: usize, first_match: Option<usize>) -> Self {
+        let width = line_count.to_string().len().max(4);
+        Self {
+            mode,
+            width,
+            first_match,
+        }
+    }
+
+    fn span(&self, line_number: usize) -> Option<Span<'static>> {
+        let number = match (self.mode, self.first_match) {
+            (LineNumbers::Off, _) => return None,
+            (LineNumbers::Relative, Some(first)) if first != line_number => {
+                first.abs_diff(line_number)
+            }
+            _ => line_number,
+        };
+        Some(Span::styled(
+            format!("{:>width$} ", number, width = self.width),
+            Style::default().fg(Color::DarkGray),
+        ))
+    }
+}
+
+// Why a file's preview leaves some or all of it out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    // Over MAX_FILE_SIZE, so it's loaded a window at a time and not highlighted
+    Large(u64),
+    // Not shown at all
+    Binary,
+}
+
+impl Limit {
+    // Going by the file's size, and whether the walk found it to be binary. Compressed files
+    // are cut off after MAX_FILE_SIZE of their text instead, when they're decompressed.
+    pub fn of(path: &Path, binary: bool, decompress: bool) -> Option<Limit> {
+        if decompress && compressed::is_compressed(path) {
+            return None;
+        }
+        if binary {
+            return Some(Limit::Binary);
+        }
+        let size = std::fs::metadata(path).ok()?.len();
+        (size > MAX_FILE_SIZE).then_some(Limit::Large(size))
+    }
+
+    // Next to the file in the list
+    pub fn badge(self) -> String {
+        match self {
+            Limit::Large(size) => format!("large {}", format_size(size)),
+            Limit::Binary => "binary".to_string(),
+        }
+    }
+
+    // In the preview's title
+    pub fn explanation(self) -> String {
+        match self {
+            Limit::Large(size) => format!(
+                "{}, over the {} limit: not highlighted",
+                format_size(size),
+                format_size(MAX_FILE_SIZE)
+            ),
+            Limit::Binary => "binary, not previewed".to_string(),
+        }
+    }
+}
 
 pub fn get_file_preview(
     path: &PathBuf,
     query: &str,
     search_mode: SearchMode,
-) -> (Text<'static>, Option<u16>) {
-    // Check file size first
-    let metadata = match std::fs::metadata(path) {
-        Ok(meta) => meta,
-        Err(_) => return (Text::raw("Unable to read file metadata"), None),
-    };
+    options: &PreviewOptions,
+) -> (Text<'static>, Option<usize>) {
+    let (text, scroll_to, _) = get_file_preview_at(path, query, search_mode, options, 0);
+    (text, scroll_to)
+}
 
-    if metadata.len() > MAX_FILE_SIZE {
-        return get_large_file_preview(path, query, search_mode);
-    }
+// Like get_file_preview, for a preview scrolled `scroll` lines past where it starts. Large
+// files only load the lines around that, and say so by returning true, in which case the
+// scroll position returned is the exact one to use.
+pub fn get_file_preview_at(
+    path: &PathBuf,
+    query: &str,
+    search_mode: SearchMode,
+    options: &PreviewOptions,
+    scroll: usize,
+) -> (Text<'static>, Option<usize>, bool) {
+    // Compressed files show as much of their decompressed text as a small file would, and are
+    // highlighted by the name they had before they were compressed
+    let (content, syntax_path) = if options.decompress && compressed::is_compressed(path) {
+        match compressed::read_to_string(path, MAX_FILE_SIZE) {
+            Ok((content, false)) => (content, compressed::inner_path(path)),
+            // Said at the end, so line numbers still line up with the content
+            Ok((mut content, true)) => {
+                content.push_str(&format!(
+                    "\n⚠️  Only the first {} of the decompressed text is shown\n",
+                    format_size(MAX_FILE_SIZE)
+                ));
+                (content, compressed::inner_path(path))
+            }
+            Err(_) => return (Text::raw("Unable to read file"), None, false),
+        }
+    } else {
+        // Check file size first
+        let metadata = match std::fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(err) => return (Text::raw(unreadable_message(path, &err)), None, false),
+        };
+
+        if metadata.len() > MAX_FILE_SIZE {
+            let (text, scroll_to) =
+                get_large_file_preview(path, query, search_mode, options, scroll);
+            return (text, scroll_to, true);
+        }
 
-    // Read the file content
-    let content = match std::fs::read_to_string(path) {
-        Ok(content) => content,
-        Err(_) => return (Text::raw("Unable to read file"), None),
+        // Read the file content
+        match std::fs::read_to_string(path) {
+            Ok(content) if !content.contains('\0') => (content, highlighted_as(path)),
+            Ok(_) => return (binary_message(metadata.len()), None, false),
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
+                return (binary_message(metadata.len()), None, false)
+            }
+            Err(err) => return (Text::raw(unreadable_message(path, &err)), None, false),
+        }
     };
+    let path = &syntax_path;
 
     let lines: Vec<&str> = content.lines().collect();
 
     // Find the first matching line index
     let first_match_index = if !query.is_empty() && search_mode == SearchMode::Contents {
-        if let Some(regex_matcher) = RegexMatcher::new(query).ok() {
+        if let Ok(regex_matcher) = RegexMatcher::new(query) {
             let mut searcher = SearcherBuilder::new()
                 .binary_detection(BinaryDetection::quit(0))
                 .build();
@@ -53,7 +245,9 @@ pub fn get_file_preview(
                 Ok(false) // Stop after first match
             });
 
-            searcher.search_path(&regex_matcher, path, sink).ok();
+            searcher
+                .search_slice(&regex_matcher, content.as_bytes(), sink)
+                .ok();
             match_line
         } else {
             None
@@ -63,18 +257,139 @@ pub fn get_file_preview(
     };
 
     // Calculate scroll position
-    let scroll_to = first_match_index.map(|line_num| line_num as u16);
-    //let ps = SyntaxSet::load_defaults_newlines();
-    let ps = HighlightingAssets::from_binary();
-    let ps = ps.get_syntax_set().unwrap();
-    let ts = ThemeSet::load_defaults();
-
-    // Try multiple methods to detect the correct syntax
-    let syntax = ps
-        // First try by file extension
-        .find_syntax_for_file(path)
+    let scroll_to = first_match_index.map(|line_num| line_num as usize);
+    let assets = assets::get();
+    let ps = &assets.syntaxes;
+    let theme = assets
+        .themes
+        .themes
+        .get(&options.theme)
+        .unwrap_or(&assets.themes.themes[assets::DEFAULT_THEME]);
+
+    let syntax = find_syntax(ps, path, lines.first().copied(), &options.syntax_overrides);
+
+    let mut text_lines = Vec::new();
+    let highlight_matcher = match_highlighter(query, search_mode);
+    let gutter = Gutter::new(
+        options.line_numbers,
+        lines.len(),
+        first_match_index.map(|line_num| line_num as usize),
+    );
+
+    #[cfg(feature = "tree-sitter")]
+    let tree_sitter_lines = crate::treesitter::language_for(path, &options.tree_sitter)
+        .and_then(|language| crate::treesitter::highlight(language, &content));
+    #[cfg(not(feature = "tree-sitter"))]
+    let tree_sitter_lines: Option<Vec<Vec<(Style, String)>>> = None;
+
+    let mut h = HighlightLines::new(syntax, theme);
+    for (idx, line) in lines.iter().enumerate() {
+        let mut line_spans = Vec::new();
+        line_spans.extend(gutter.span(idx + 1));
+
+        let tokens = match &tree_sitter_lines {
+            // Past the highlighting limit the rest is plain, with matches still marked
+            None if idx >= MAX_LINES_TO_FORMAT => Ok(vec![(Style::default(), line.to_string())]),
+            Some(tree_sitter_lines) => Ok(tree_sitter_lines.get(idx).cloned().unwrap_or_default()),
+            None => h.highlight_line(line, ps).map(|ranges| {
+                ranges
+                    .into_iter()
+                    .map(|(style, text)| {
+                        let fg = style.foreground;
+                        (
+                            Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)),
+                            text.to_string(),
+                        )
+                    })
+                    .collect()
+            }),
+        };
+        match tokens {
+            Ok(tokens) => {
+                // Matches are found on the whole line so they can span syntax tokens
+                let matches = highlight_matcher
+                    .as_ref()
+                    .map(|matcher| match_ranges(matcher, line))
+                    .unwrap_or_default();
+                let replacements =
+                    replacements(highlight_matcher.as_ref(), line, options.replace.as_ref());
+
+                let mut offset = 0;
+                for (style, text) in tokens.iter() {
+                    push_marked(
+                        &mut line_spans,
+                        text,
+                        offset,
+                        &matches,
+                        &replacements,
+                        *style,
+                    );
+                    offset += text.len();
+                }
+                text_lines.push(Line::from(line_spans));
+            }
+            Err(_) => text_lines.push(Line::from("Error reading file".to_string())),
+        }
+    }
+
+    (Text::from(text_lines), scroll_to, false)
+}
+
+fn binary_message(size: u64) -> Text<'static> {
+    Text::styled(
+        format!(
+            "Binary file ({}), not previewed. Open it with something that reads its format",
+            format_size(size)
+        ),
+        Style::default().fg(Color::Yellow),
+    )
+}
+
+// Where a symlink points, as written in the link
+pub fn link_target(path: &Path) -> Option<PathBuf> {
+    path.symlink_metadata()
         .ok()
-        .flatten()
+        .filter(|metadata| metadata.is_symlink())?;
+    std::fs::read_link(path).ok()
+}
+
+// Why a file can't be shown, which for a link is usually about what it points to
+fn unreadable_message(path: &Path, err: &std::io::Error) -> String {
+    match link_target(path) {
+        Some(target) if err.kind() == std::io::ErrorKind::NotFound => {
+            format!("Broken link: {} doesn't exist", target.display())
+        }
+        // Links pointing back at themselves end up here
+        Some(target) => format!("Unable to follow link to {}: {}", target.display(), err),
+        None if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            format!("Permission denied: {}", path.display())
+        }
+        None => "Unable to read file".to_string(),
+    }
+}
+
+// A link named without an extension, like `config` pointing at `config.yml`, is highlighted
+// by its target's name
+fn highlighted_as(path: &Path) -> PathBuf {
+    match link_target(path) {
+        Some(target) if path.extension().is_none() => target,
+        _ => path.to_path_buf(),
+    }
+}
+
+fn find_syntax<'a>(
+    ps: &'a SyntaxSet,
+    path: &Path,
+    first_line: Option<&str>,
+    overrides: &[SyntaxOverride],
+) -> &'a SyntaxReference {
+    // Configured overrides win over any detection, by syntax name or extension
+    overrides
+        .iter()
+        .find(|entry| entry.matches(path))
+        .and_then(|entry| ps.find_syntax_by_token(&entry.syntax))
+        // Then try by file extension
+        .or_else(|| ps.find_syntax_for_file(path).ok().flatten())
         // Then try by extension directly for common web files
         .or_else(|| {
             let ext = path
@@ -89,151 +404,411 @@ pub fn get_file_preview(
             }
         })
         // Then try by first line of content
-        .or_else(|| {
-            lines
-                .first()
-                .and_then(|first_line| ps.find_syntax_by_first_line(first_line))
-        })
+        .or_else(|| first_line.and_then(|first_line| ps.find_syntax_by_first_line(first_line)))
         // Finally fallback to plain text
-        .unwrap_or_else(|| ps.find_syntax_by_extension("txt").unwrap());
+        .unwrap_or_else(|| ps.find_syntax_by_extension("txt").unwrap())
+}
 
-    let mut text_lines = Vec::new();
+// The language a file is highlighted as, for `lang:` filters; None for plain text
+pub fn detect_language(path: &Path, overrides: &[SyntaxOverride]) -> Option<&'static str> {
+    let mut first_line = String::new();
+    if let Ok(file) = File::open(path) {
+        let _ = BufReader::new(file).take(1024).read_line(&mut first_line);
+    }
+    let ps = &assets::get().syntaxes;
+    let syntax = find_syntax(ps, path, Some(first_line.as_str()), overrides);
+    (syntax.name != "Plain Text").then_some(syntax.name.as_str())
+}
 
-    let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-    for (idx, line) in lines.iter().take(MAX_LINES_TO_FORMAT).enumerate() {
-        let mut line_spans = Vec::new();
-        let line_number = idx + 1;
-        line_spans.push(Span::styled(
-            format!("{:4} ", line_number),
-            Style::default().fg(Color::DarkGray),
+// The syntaxes a `lang:` value names, by extension (`js`, `sh`) or by name (`rust`), with
+// variants like "JavaScript (Babel)" counting as the language they're named after
+pub fn resolve_language(token: &str) -> Vec<&'static str> {
+    let key = language_key(token);
+    assets::get()
+        .syntaxes
+        .syntaxes()
+        .iter()
+        .filter(|syntax| {
+            let name = language_key(&syntax.name);
+            syntax
+                .file_extensions
+                .iter()
+                .any(|ext| ext.eq_ignore_ascii_case(token))
+                || name == key
+                || name
+                    .strip_prefix(&key)
+                    .is_some_and(|rest| rest.starts_with('('))
+        })
+        .map(|syntax| syntax.name.as_str())
+        .collect()
+}
+
+// How a language is written in a `lang:` token, preferring one of its extensions
+pub fn language_token(name: &str) -> String {
+    let ps = &assets::get().syntaxes;
+    ps.find_syntax_by_name(name)
+        .and_then(|syntax| syntax.file_extensions.first())
+        .filter(|ext| ps.find_syntax_by_extension(ext).map(|s| s.name.as_str()) == Some(name))
+        .cloned()
+        .unwrap_or_else(|| language_key(name))
+}
+
+fn language_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// The row `line_index` starts on once the lines before it wrap to `width`
+pub fn visual_row(text: &Text, line_index: usize, width: u16) -> usize {
+    let width = width.max(1) as usize;
+    text.lines
+        .iter()
+        .take(line_index)
+        .map(|line| line.width().max(1).div_ceil(width))
+        .sum()
+}
+
+// Indexes of the lines with a highlighted match in them
+pub fn match_lines(text: &Text) -> Vec<usize> {
+    text.lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            line.spans.iter().any(|span| {
+                span.style.bg == Some(Color::DarkGray)
+                    && span.style.add_modifier.contains(Modifier::BOLD)
+            })
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Content searches highlight the regex, filename searches the literal query terms
+pub fn match_highlighter(query: &str, search_mode: SearchMode) -> Option<RegexMatcher> {
+    if query.is_empty() {
+        return None;
+    }
+    match search_mode {
+        SearchMode::Contents => RegexMatcher::new(query).ok(),
+        SearchMode::Filename => {
+            RegexMatcher::new(&crate::search::literal_terms_pattern(query)?).ok()
+        }
+    }
+}
+
+// Byte ranges of every non-empty match in the line
+fn match_ranges(matcher: &RegexMatcher, line: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let _ = matcher.find_iter(line.as_bytes(), |m| {
+        if !m.is_empty() {
+            ranges.push(m.start()..m.end());
+        }
+        true
+    });
+    ranges
+}
+
+// What each of the line's matches would be replaced with, in order: only the first unless
+// the replacement is global
+fn replacements(
+    matcher: Option<&RegexMatcher>,
+    line: &str,
+    replace: Option<&Replace>,
+) -> Vec<String> {
+    let (Some(matcher), Some(replace)) = (matcher, replace) else {
+        return Vec::new();
+    };
+    let Ok(mut caps) = matcher.new_captures() else {
+        return Vec::new();
+    };
+    let haystack = line.as_bytes();
+    let mut replacements = Vec::new();
+    let _ = matcher.captures_iter(haystack, &mut caps, |caps| {
+        if caps.get(0).is_some_and(|m| !m.is_empty()) {
+            let mut replaced = Vec::new();
+            caps.interpolate(
+                |name| matcher.capture_index(name),
+                haystack,
+                replace.replacement.as_bytes(),
+                &mut replaced,
+            );
+            replacements.push(String::from_utf8_lossy(&replaced).into_owned());
+        }
+        replace.global || replacements.is_empty()
+    });
+    replacements
+}
+
+// A matching line shown under its file in the result list, dimmed apart from the matches
+pub fn snippet(text: &str, matcher: Option<&RegexMatcher>) -> Line<'static> {
+    let trimmed = text.trim_start();
+    let offset = text.len() - trimmed.len();
+    let trimmed = trimmed.trim_end();
+    let matches = matcher
+        .map(|matcher| match_ranges(matcher, text))
+        .unwrap_or_default();
+    let mut spans = vec![Span::raw("    ")];
+    push_highlighted(
+        &mut spans,
+        trimmed,
+        offset,
+        &matches,
+        Style::default().fg(Color::Gray).add_modifier(Modifier::DIM),
+    );
+    Line::from(spans)
+}
+
+fn match_style(base: Style) -> Style {
+    base.bg(Color::DarkGray).add_modifier(Modifier::BOLD)
+}
+
+// Pushes `text`, which starts at byte `offset` of its line, split into plain and
+// highlighted spans wherever it overlaps one of the line's match ranges
+fn push_highlighted(
+    spans: &mut Vec<Span<'static>>,
+    text: &str,
+    offset: usize,
+    matches: &[Range<usize>],
+    style: Style,
+) {
+    push_marked(spans, text, offset, matches, &[], style);
+}
+
+// Like push_highlighted, with the matches that have a replacement crossed out and the
+// replacement after them
+fn push_marked(
+    spans: &mut Vec<Span<'static>>,
+    text: &str,
+    offset: usize,
+    matches: &[Range<usize>],
+    replacements: &[String],
+    style: Style,
+) {
+    let end = offset + text.len();
+    let mut cursor = offset;
+    for (i, range) in matches.iter().enumerate() {
+        if range.end <= cursor || range.start >= end {
+            continue;
+        }
+        let start = range.start.max(cursor);
+        let stop = range.end.min(end);
+        if start > cursor {
+            spans.push(Span::styled(
+                text[cursor - offset..start - offset].to_string(),
+                style,
+            ));
+        }
+        let replacement = replacements.get(i);
+        spans.push(Span::styled(
+            text[start - offset..stop - offset].to_string(),
+            match replacement {
+                Some(_) => style.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT),
+                None => match_style(style),
+            },
         ));
+        if let Some(replacement) = replacement.filter(|_| stop == range.end) {
+            spans.push(Span::styled(
+                replacement.clone(),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        cursor = stop;
+    }
+    if cursor < end {
+        spans.push(Span::styled(text[cursor - offset..].to_string(), style));
+    }
+}
 
-        match h.highlight_line(line, &ps) {
-            Ok(ranges) => {
-                for (style, text) in ranges.iter() {
-                    let fg_color =
-                        Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b);
-
-                    if !query.is_empty() && search_mode == SearchMode::Contents {
-                        if let Some(regex_matcher) = RegexMatcher::new(query).ok() {
-                            if let Ok(Some(match_result)) = regex_matcher.find(text.as_bytes()) {
-                                let match_start = match_result.start();
-                                let mut last_idx = 0;
-
-                                for idx in match_start..match_result.end() {
-                                    if idx > last_idx {
-                                        line_spans.push(Span::styled(
-                                            text[last_idx..idx].to_string(),
-                                            Style::default().fg(fg_color),
-                                        ));
-                                    }
-
-                                    line_spans.push(Span::styled(
-                                        text[idx..idx + 1].to_string(),
-                                        Style::default()
-                                            .fg(fg_color)
-                                            .bg(Color::DarkGray)
-                                            .add_modifier(Modifier::BOLD),
-                                    ));
-
-                                    last_idx = idx + 1;
-                                }
-
-                                if last_idx < text.len() {
-                                    line_spans.push(Span::styled(
-                                        text[last_idx..].to_string(),
-                                        Style::default().fg(fg_color),
-                                    ));
-                                }
-                                continue;
-                            }
+// Where every CHUNK_LINES-th line of the last large file previewed starts, indexed only as
+// far as anything has scrolled so a huge file is never read in full just to show its top
+struct LineIndex {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+    // Byte offset of line `i * CHUNK_LINES`
+    checkpoints: Vec<u64>,
+    // Lines in the file, once the index has reached its end
+    line_count: Option<usize>,
+    // 1-based line of each pattern's first match
+    first_matches: HashMap<String, Option<usize>>,
+}
+
+static LINE_INDEX: Mutex<Option<LineIndex>> = Mutex::new(None);
+
+impl LineIndex {
+    fn new(path: &Path, metadata: &Metadata) -> Self {
+        LineIndex {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            checkpoints: vec![0],
+            line_count: None,
+            first_matches: HashMap::new(),
+        }
+    }
+
+    fn is_for(&self, path: &Path, metadata: &Metadata) -> bool {
+        self.path == path && self.len == metadata.len() && self.modified == metadata.modified().ok()
+    }
+
+    // The nearest indexed line at or before `line`, with its byte offset
+    fn checkpoint(&mut self, file: &mut File, line: usize) -> std::io::Result<(usize, u64)> {
+        while self.line_count.is_none() && self.checkpoints.len() <= line / CHUNK_LINES {
+            let start = self.checkpoints[self.checkpoints.len() - 1];
+            file.seek(SeekFrom::Start(start))?;
+            let mut reader = BufReader::with_capacity(1 << 16, &mut *file);
+            let (mut offset, mut newlines, mut unterminated) = (start, 0, false);
+            while newlines < CHUNK_LINES {
+                let buffer = reader.fill_buf()?;
+                if buffer.is_empty() {
+                    let indexed = (self.checkpoints.len() - 1) * CHUNK_LINES;
+                    self.line_count = Some(indexed + newlines + usize::from(unterminated));
+                    break;
+                }
+                let mut consumed = buffer.len();
+                for (i, byte) in buffer.iter().enumerate() {
+                    if *byte == b'\n' {
+                        newlines += 1;
+                        if newlines == CHUNK_LINES {
+                            consumed = i + 1;
+                            break;
                         }
                     }
-
-                    line_spans.push(Span::styled(
-                        text.to_string(),
-                        Style::default().fg(fg_color),
-                    ));
                 }
-                text_lines.push(Line::from(line_spans));
+                unterminated = buffer[consumed - 1] != b'\n';
+                offset += consumed as u64;
+                reader.consume(consumed);
+            }
+            if newlines == CHUNK_LINES {
+                self.checkpoints.push(offset);
             }
-            Err(_) => text_lines.push(Line::from("Error reading file".to_string())),
         }
+        let chunk = (line / CHUNK_LINES).min(self.checkpoints.len() - 1);
+        Ok((chunk * CHUNK_LINES, self.checkpoints[chunk]))
     }
 
-    // If we hit the limit, add a notice
-    if lines.len() > MAX_LINES_TO_FORMAT {
-        text_lines.push(Line::from(vec![Span::styled(
-            "⚠️  File truncated - showing first 1000 lines only",
-            Style::default().fg(Color::Yellow),
-        )]));
+    fn first_match(&mut self, path: &Path, matcher: &RegexMatcher, pattern: &str) -> Option<usize> {
+        if let Some(line) = self.first_matches.get(pattern) {
+            return *line;
+        }
+        let mut first = None;
+        let sink = UTF8(|line_num, _line| {
+            first = Some(line_num as usize);
+            Ok(false)
+        });
+        SearcherBuilder::new()
+            .binary_detection(BinaryDetection::quit(0))
+            .build()
+            .search_path(matcher, path, sink)
+            .ok();
+        self.first_matches.insert(pattern.to_string(), first);
+        first
     }
-
-    (Text::from(text_lines), scroll_to)
 }
 
-// New function to handle large files
+// Files over MAX_FILE_SIZE are shown as plain text, a window of lines at a time around where
+// the preview is scrolled to. The scroll position returned is exact rather than a match to
+// bring into view, since the window already starts near it.
 fn get_large_file_preview(
     path: &PathBuf,
     query: &str,
     search_mode: SearchMode,
-) -> (Text<'static>, Option<u16>) {
-    let file = match File::open(path) {
-        Ok(file) => file,
-        Err(_) => return (Text::raw("Unable to read file"), None),
+    options: &PreviewOptions,
+    scroll: usize,
+) -> (Text<'static>, Option<usize>) {
+    let (mut file, metadata) = match File::open(path).and_then(|file| {
+        let metadata = file.metadata()?;
+        Ok((file, metadata))
+    }) {
+        Ok(opened) => opened,
+        Err(err) => return (Text::raw(unreadable_message(path, &err)), None),
+    };
+    let mut index = LINE_INDEX
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !index
+        .as_ref()
+        .is_some_and(|index| index.is_for(path, &metadata))
+    {
+        *index = Some(LineIndex::new(path, &metadata));
+    }
+    let Some(index) = index.as_mut() else {
+        return (Text::raw("Unable to read file"), None);
     };
 
-    let reader = BufReader::new(file);
-    let mut text_lines = Vec::new();
-    let mut first_match_line = None;
+    let highlight_matcher = match_highlighter(query, search_mode);
+    let first_match_line = match (&highlight_matcher, search_mode) {
+        (Some(matcher), SearchMode::Contents) => index.first_match(path, matcher, query),
+        _ => None,
+    };
 
-    // Add a warning header
-    text_lines.push(Line::from(vec![Span::styled(
-        "⚠️  Large file detected - showing plain text without syntax highlighting",
-        Style::default().fg(Color::Yellow),
-    )]));
+    // The first line in view, with the match a few lines down like smaller files
+    let mut top = first_match_line
+        .filter(|line| *line >= 15)
+        .map_or(0, |line| line - 10)
+        + scroll;
+    let window = (|| {
+        index.checkpoint(&mut file, top)?;
+        if let Some(line_count) = index.line_count {
+            top = top.min(line_count.saturating_sub(1));
+        }
+        let start = top.saturating_sub(CHUNK_LINES / 2);
+        let (indexed, offset) = index.checkpoint(&mut file, start)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let lines: Vec<String> = BufReader::new(&mut file)
+            .split(b'\n')
+            .skip(start - indexed)
+            .take(top - start + CHUNK_LINES)
+            .map(|line| {
+                line.map(|line| {
+                    let line = line.strip_suffix(b"\r").unwrap_or(&line);
+                    String::from_utf8_lossy(line).into_owned()
+                })
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok::<_, std::io::Error>((start, lines))
+    })();
+    let Ok((start, lines)) = window else {
+        return (Text::raw("Unable to read file"), None);
+    };
 
-    for (idx, line_result) in reader.lines().take(MAX_LINES_TO_FORMAT).enumerate() {
-        let line = match line_result {
-            Ok(line) => line,
-            Err(_) => continue,
-        };
+    let mut text_lines = vec![Line::from(vec![Span::styled(
+        format!(
+            "⚠️  Large file ({}) - showing lines {}-{} as plain text, scroll to load more",
+            format_size(metadata.len()),
+            start + 1,
+            start + lines.len()
+        ),
+        Style::default().fg(Color::Yellow),
+    )])];
 
+    let gutter = Gutter::new(options.line_numbers, start + lines.len(), first_match_line);
+    for (idx, line) in lines.iter().enumerate() {
+        let matches = highlight_matcher
+            .as_ref()
+            .map(|matcher| match_ranges(matcher, line))
+            .unwrap_or_default();
+        let replacements = replacements(highlight_matcher.as_ref(), line, options.replace.as_ref());
         let mut line_spans = Vec::new();
-        let line_number = idx + 1;
-
-        // Add line number
-        line_spans.push(Span::styled(
-            format!("{:4} ", line_number),
-            Style::default().fg(Color::DarkGray),
-        ));
-
-        // Check for matches if we're searching
-        if !query.is_empty() && search_mode == SearchMode::Contents {
-            if let Some(regex_matcher) = RegexMatcher::new(query).ok() {
-                if let Ok(is_match) = regex_matcher.is_match(line.as_bytes()) {
-                    if is_match {
-                        first_match_line = first_match_line.or(Some(line_number as u16));
-                        // Highlight the matching line
-                        line_spans.push(Span::styled(
-                            line,
-                            Style::default()
-                                .bg(Color::DarkGray)
-                                .add_modifier(Modifier::BOLD),
-                        ));
-                        text_lines.push(Line::from(line_spans));
-                        continue;
-                    }
-                }
-            }
-        }
-
-        // Add regular line
-        line_spans.push(Span::raw(line));
+        line_spans.extend(gutter.span(start + idx + 1));
+        push_marked(
+            &mut line_spans,
+            line,
+            0,
+            &matches,
+            &replacements,
+            Style::default(),
+        );
         text_lines.push(Line::from(line_spans));
     }
 
-    (Text::from(text_lines), first_match_line)
+    // Past the header to the first line in view, unless that's the top of the file
+    let position = if top == 0 { 0 } else { top - start + 1 };
+    (Text::from(text_lines), Some(position))
 }
 
 #[cfg(test)]
@@ -243,6 +818,26 @@ mod tests {
     use std::io::Write;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_syntax_overrides_win() {
+        let ps = &assets::get().syntaxes;
+        let overrides = vec![
+            SyntaxOverride::new("*.vue", "html").unwrap(),
+            SyntaxOverride::new("Justfile", "makefile").unwrap(),
+            SyntaxOverride::new("templates/*.txt", "html").unwrap(),
+        ];
+        let name = |path: &str| {
+            find_syntax(ps, Path::new(path), None, &overrides)
+                .name
+                .clone()
+        };
+        assert_eq!(name("./src/App.vue"), "HTML");
+        assert_eq!(name("./Justfile"), "Makefile");
+        assert_eq!(name("./templates/page.txt"), "HTML");
+        assert_eq!(name("./notes/page.txt"), "Plain Text");
+        assert_eq!(name("./src/main.rs"), "Rust");
+    }
+
     fn create_test_file(content: &str) -> (TempDir, PathBuf) {
         let dir = TempDir::new().unwrap();
         let file_path = dir.path().join("test.rs");
@@ -256,18 +851,42 @@ mod tests {
         let content = "fn main() {\n    println!(\"Hello\");\n}";
         let (_dir, path) = create_test_file(content);
 
-        let (preview, scroll) = get_file_preview(&path, "", SearchMode::Contents);
-        assert!(preview.lines.len() > 0);
+        let (preview, scroll) =
+            get_file_preview(&path, "", SearchMode::Contents, &PreviewOptions::default());
+        assert!(!preview.lines.is_empty());
         assert_eq!(scroll, None);
     }
 
+    #[test]
+    fn test_binary_and_large_files_say_why_they_are_cut_short() {
+        let (_dir, path) = create_test_file("\0\x01\x02 not text");
+        let (preview, _) =
+            get_file_preview(&path, "", SearchMode::Contents, &PreviewOptions::default());
+        assert_eq!(
+            preview.lines[0].spans[0].content,
+            "Binary file (12B), not previewed. Open it with something that reads its format"
+        );
+        assert_eq!(Limit::of(&path, true, false), Some(Limit::Binary));
+        assert_eq!(Limit::of(&path, false, false), None);
+
+        let (_dir, path) = create_test_file(&"x".repeat(MAX_FILE_SIZE as usize + 1));
+        let limit = Limit::of(&path, false, false);
+        assert_eq!(limit, Some(Limit::Large(MAX_FILE_SIZE + 1)));
+        assert_eq!(limit.unwrap().badge(), "large 512K");
+    }
+
     #[test]
     fn test_file_preview_with_query() {
         let content = "line one\nline two\nline three with match\nline four";
         let (_dir, path) = create_test_file(content);
 
-        let (preview, scroll) = get_file_preview(&path, "match", SearchMode::Contents);
-        assert!(preview.lines.len() > 0);
+        let (preview, scroll) = get_file_preview(
+            &path,
+            "match",
+            SearchMode::Contents,
+            &PreviewOptions::default(),
+        );
+        assert!(!preview.lines.is_empty());
         println!("{:?}", scroll);
 
         assert_eq!(scroll, Some(3));
@@ -276,33 +895,286 @@ mod tests {
     #[test]
     fn test_file_preview_nonexistent_file() {
         let path = PathBuf::from("nonexistent_file.txt");
-        let (preview, scroll) = get_file_preview(&path, "", SearchMode::Contents);
+        let (preview, scroll) =
+            get_file_preview(&path, "", SearchMode::Contents, &PreviewOptions::default());
 
         assert_eq!(preview.lines.len(), 1);
         assert_eq!(preview.lines[0].spans[0].content, "Unable to read file");
         assert_eq!(scroll, None);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_file_preview_follows_links() {
+        let (dir, path) = create_test_file("linked contents");
+        let preview_of = |name: &str| {
+            let (preview, _) = get_file_preview(
+                &dir.path().join(name),
+                "",
+                SearchMode::Contents,
+                &PreviewOptions::default(),
+            );
+            preview.lines[0]
+                .spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        };
+        std::os::unix::fs::symlink(&path, dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", dir.path().join("broken")).unwrap();
+        std::os::unix::fs::symlink("loop", dir.path().join("loop")).unwrap();
+
+        assert!(preview_of("link").ends_with("linked contents"));
+        assert_eq!(link_target(&dir.path().join("link")), Some(path));
+        assert_eq!(
+            preview_of("broken"),
+            "Broken link: missing.txt doesn't exist"
+        );
+        assert!(preview_of("loop").starts_with("Unable to follow link to loop: "));
+    }
+
     #[test]
     fn test_file_preview_line_numbers() {
         let content = "line1\nline2\nline3";
         let (_dir, path) = create_test_file(content);
 
-        let (preview, _) = get_file_preview(&path, "", SearchMode::Contents);
+        let (preview, _) =
+            get_file_preview(&path, "", SearchMode::Contents, &PreviewOptions::default());
 
         // Check if first line starts with line number
         let first_line_number = preview.lines[0].spans[0].content.trim();
         assert_eq!(first_line_number, "1");
     }
 
+    #[test]
+    fn test_file_preview_relative_and_hidden_line_numbers() {
+        let content = "one\ntwo\nthree match\nfour";
+        let (_dir, path) = create_test_file(content);
+
+        let options = PreviewOptions {
+            line_numbers: LineNumbers::Relative,
+            ..PreviewOptions::default()
+        };
+        let (preview, _) = get_file_preview(&path, "match", SearchMode::Contents, &options);
+        let numbers: Vec<_> = preview
+            .lines
+            .iter()
+            .map(|line| line.spans[0].content.trim().to_string())
+            .collect();
+        assert_eq!(numbers, vec!["2", "1", "3", "1"]);
+
+        let options = PreviewOptions {
+            line_numbers: LineNumbers::Off,
+            ..PreviewOptions::default()
+        };
+        let (preview, _) = get_file_preview(&path, "", SearchMode::Contents, &options);
+        assert_eq!(preview.lines[0].spans[0].content, "one");
+    }
+
+    #[test]
+    fn test_visual_row_counts_wrapped_lines() {
+        let text = Text::from(vec![
+            Line::from("x".repeat(25)),
+            Line::from(""),
+            Line::from("short"),
+            Line::from("after"),
+        ]);
+        assert_eq!(visual_row(&text, 0, 10), 0);
+        assert_eq!(visual_row(&text, 1, 10), 3);
+        assert_eq!(visual_row(&text, 3, 10), 5);
+        assert_eq!(visual_row(&text, 3, 100), 3);
+    }
+
+    #[test]
+    fn test_gutter_grows_for_long_files() {
+        let gutter = Gutter::new(LineNumbers::Absolute, 123_456, None);
+        assert_eq!(gutter.span(7).unwrap().content, "     7 ");
+    }
+
+    #[test]
+    fn test_large_file_preview_loads_lines_around_the_scroll_position() {
+        let content: String = (0..100_000).map(|i| format!("line {}\n", i)).collect();
+        let (_dir, path) = create_test_file(&content);
+        let options = PreviewOptions {
+            line_numbers: LineNumbers::Off,
+            ..PreviewOptions::default()
+        };
+        let line_at = |query: &str, scroll: usize| {
+            let (text, position, windowed) =
+                get_file_preview_at(&path, query, SearchMode::Contents, &options, scroll);
+            assert!(windowed);
+            assert!(text.lines.len() <= CHUNK_LINES * 2);
+            let line = &text.lines[position.unwrap()];
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        };
+
+        assert_eq!(
+            line_at("", 0),
+            "⚠️  Large file (1.0M) - showing lines 1-1000 as plain text, scroll to load more"
+        );
+        assert_eq!(line_at("", 2000), "line 2000");
+        assert_eq!(line_at("", 70_000), "line 70000");
+        // The match sits ten lines down, like in smaller files
+        assert_eq!(line_at("line 50000", 0), "line 49991");
+        assert_eq!(line_at("line 50000", 5), "line 49996");
+        // Scrolling past the end stops at the last line
+        assert_eq!(line_at("line 99990", 200_000), "line 99999");
+    }
+
     #[test]
     fn test_file_preview_syntax_highlighting() {
         let content = "fn main() {\n    let x = 42;\n}";
         let (_dir, path) = create_test_file(content);
 
-        let (preview, _) = get_file_preview(&path, "", SearchMode::Contents);
+        let (preview, _) =
+            get_file_preview(&path, "", SearchMode::Contents, &PreviewOptions::default());
 
         println!("{:?}", preview.lines[0].spans[1].content);
         assert!(preview.lines[0].spans.len() > 1);
     }
+
+    #[test]
+    fn test_file_preview_highlights_every_match() {
+        let content = "let foo = foo_bar(foo);";
+        let (_dir, path) = create_test_file(content);
+
+        let (preview, _) = get_file_preview(
+            &path,
+            "foo",
+            SearchMode::Contents,
+            &PreviewOptions::default(),
+        );
+
+        let highlighted: String = preview.lines[0]
+            .spans
+            .iter()
+            .filter(|span| span.style.bg == Some(Color::DarkGray))
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(highlighted, "foofoofoo");
+    }
+
+    #[test]
+    fn test_file_preview_shows_replacements() {
+        let content = "let foo_1 = foo_2;";
+        let (_dir, path) = create_test_file(content);
+        let preview_line = |global: bool| {
+            let options = PreviewOptions {
+                line_numbers: LineNumbers::Off,
+                replace: Some(Replace {
+                    replacement: "bar_$1".to_string(),
+                    global,
+                }),
+                ..PreviewOptions::default()
+            };
+            let (preview, _) = get_file_preview(&path, r"foo_(\d)", SearchMode::Contents, &options);
+            let marked = |span: &Span| {
+                if span.style.add_modifier.contains(Modifier::CROSSED_OUT) {
+                    format!("[-{}]", span.content)
+                } else if span.style.fg == Some(Color::Green) {
+                    format!("[+{}]", span.content)
+                } else {
+                    span.content.to_string()
+                }
+            };
+            preview.lines[0]
+                .spans
+                .iter()
+                .map(marked)
+                .collect::<String>()
+        };
+
+        assert_eq!(
+            preview_line(true),
+            "let [-foo_1][+bar_1] = [-foo_2][+bar_2];"
+        );
+        assert_eq!(preview_line(false), "let [-foo_1][+bar_1] = foo_2;");
+    }
+
+    #[test]
+    fn test_match_lines() {
+        let content = "foo\nbar\nfoo bar\n";
+        let (_dir, path) = create_test_file(content);
+
+        let (preview, _) = get_file_preview(
+            &path,
+            "foo",
+            SearchMode::Contents,
+            &PreviewOptions::default(),
+        );
+        assert_eq!(match_lines(&preview), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_file_preview_highlights_filename_terms() {
+        let content = "struct Button;\nfn main() {}";
+        let (_dir, path) = create_test_file(content);
+
+        let (preview, scroll) = get_file_preview(
+            &path,
+            "button",
+            SearchMode::Filename,
+            &PreviewOptions::default(),
+        );
+
+        let highlighted: Vec<_> = preview.lines[0]
+            .spans
+            .iter()
+            .filter(|span| span.style.bg == Some(Color::DarkGray))
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(highlighted, vec!["Button"]);
+        assert_eq!(scroll, None);
+    }
+
+    #[test]
+    fn test_detect_and_resolve_language() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("deploy");
+        std::fs::write(&script, "#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, "hello\n").unwrap();
+
+        let python = detect_language(&script, &[]).unwrap();
+        assert!(resolve_language("py").contains(&python));
+        assert_eq!(detect_language(&notes, &[]), None);
+        assert_eq!(resolve_language("RUST"), vec!["Rust"]);
+        assert_eq!(language_token("Rust"), "rs");
+        assert!(resolve_language("nonsense").is_empty());
+
+        let js = detect_language(Path::new("app.js"), &[]).unwrap();
+        assert!(resolve_language("js").contains(&js));
+        assert!(resolve_language("javascript").contains(&js));
+    }
+
+    #[test]
+    fn test_snippet_trims_and_highlights() {
+        let matcher = RegexMatcher::new("todo").unwrap();
+        let line = snippet("    // todo: fix\n", Some(&matcher));
+        let contents: Vec<_> = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(contents, vec!["    ", "// ", "todo", ": fix"]);
+        assert!(line.spans[2].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_push_highlighted_splits_across_spans() {
+        let mut spans = Vec::new();
+        let matches = [2..6, 7..8];
+        push_highlighted(&mut spans, "abcd", 0, &matches, Style::default());
+        push_highlighted(&mut spans, "efgh", 4, &matches, Style::default());
+
+        let contents: Vec<_> = spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(contents, vec!["ab", "cd", "ef", "g", "h"]);
+        assert_eq!(spans[1].style.bg, Some(Color::DarkGray));
+        assert_eq!(spans[2].style.bg, Some(Color::DarkGray));
+        assert_eq!(spans[3].style.bg, None);
+        assert_eq!(spans[4].style.bg, Some(Color::DarkGray));
+    }
 }
@@ -0,0 +1,259 @@
+use crate::preview::{get_file_preview_at, LineNumbers, PreviewOptions};
+use crate::query::Replace;
+use crate::resultcache::ResultCache;
+use crate::SearchMode;
+use ratatui::text::Text;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+// How long drawing waits for a preview before showing a placeholder, so small files never
+// flicker through "Loading…" on their way in
+const WAIT: Duration = Duration::from_millis(15);
+
+// Previews kept for going back to while the watcher would say when they go stale
+const CACHED: usize = 32;
+
+// The preview, where to scroll it, and whether only a window of a large file was loaded
+pub type Preview = (Text<'static>, Option<usize>, bool);
+
+// Everything a file's preview depends on
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewRequest {
+    pub path: PathBuf,
+    pub pattern: String,
+    pub mode: SearchMode,
+    pub scroll: usize,
+    pub line_numbers: LineNumbers,
+    pub replace: Option<Replace>,
+}
+
+impl PreviewRequest {
+    pub fn load(&self, options: &PreviewOptions) -> Preview {
+        let options = PreviewOptions {
+            line_numbers: self.line_numbers,
+            replace: self.replace.clone(),
+            ..options.clone()
+        };
+        get_file_preview_at(&self.path, &self.pattern, self.mode, &options, self.scroll)
+    }
+}
+
+// Reads and highlights previews on a worker thread, so holding an arrow key through the
+// results never waits on file IO. Requests that are overtaken before the worker gets to them
+// are skipped.
+pub struct Previewer {
+    requests: Sender<PreviewRequest>,
+    // Each preview with how long it took to read and highlight
+    ready: Receiver<(PreviewRequest, Preview, Duration)>,
+    // The preview last asked for, until it's ready
+    pending: Option<PreviewRequest>,
+    // The last preview that came back, shown while the next one for the same file loads
+    latest: Option<(PreviewRequest, Preview)>,
+    // The file hasn't changed since
+    fresh: bool,
+    // Goes up each time `latest` is replaced, so what's derived from it is worked out once
+    shown: usize,
+    // Recent previews, dropped for a file when the watcher sees it change
+    cache: ResultCache<PreviewRequest, Preview>,
+    watched: bool,
+    // The file last shown and how long its preview took, for the profiling overlay
+    #[cfg(feature = "profiling")]
+    pub load_time: Option<(PathBuf, Duration)>,
+}
+
+impl Previewer {
+    pub fn new(options: PreviewOptions) -> Self {
+        let (requests, received) = mpsc::channel::<PreviewRequest>();
+        let (sender, ready) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(mut request) = received.recv() {
+                while let Ok(newer) = received.try_recv() {
+                    request = newer;
+                }
+                let started = Instant::now();
+                let preview = request.load(&options);
+                if sender.send((request, preview, started.elapsed())).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            ready,
+            pending: None,
+            latest: None,
+            fresh: false,
+            shown: 0,
+            cache: ResultCache::new(CACHED),
+            watched: false,
+            #[cfg(feature = "profiling")]
+            load_time: None,
+        }
+    }
+
+    // The preview for a request, or whatever is worth showing while it loads
+    pub fn get(&mut self, request: PreviewRequest) -> Preview {
+        if let Some(preview) = self.cache.get(&request).cloned() {
+            if self.latest.as_ref().map(|(latest, _)| latest) != Some(&request) {
+                self.shown += 1;
+            }
+            self.latest = Some((request, preview.clone()));
+            self.fresh = true;
+            return preview;
+        }
+        let current = self.fresh
+            && self
+                .latest
+                .as_ref()
+                .is_some_and(|(latest, _)| *latest == request);
+        if !current && self.pending.as_ref() != Some(&request) {
+            let _ = self.requests.send(request.clone());
+            self.pending = Some(request.clone());
+            if let Ok(ready) = self.ready.recv_timeout(WAIT) {
+                self.receive(ready);
+            }
+        }
+        self.poll();
+        match &self.latest {
+            Some((latest, preview)) if latest.path == request.path => preview.clone(),
+            _ => (Text::raw("Loading…"), None, false),
+        }
+    }
+
+    // Takes in previews that finished, returning whether the one asked for last is among them
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        while let Ok(ready) = self.ready.try_recv() {
+            arrived |= self.receive(ready);
+        }
+        arrived
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn shown(&self) -> usize {
+        self.shown
+    }
+
+    // Whether every change to a file is reported with `invalidate`, so previews can be kept
+    // for coming back to. Without a watcher, a file moved away from is read again.
+    pub fn set_watched(&mut self, watched: bool) {
+        if !watched {
+            self.cache.clear();
+        }
+        self.watched = watched;
+    }
+
+    // Loads every file again, for changes the watcher may have missed
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.fresh = false;
+    }
+
+    // Loads a file again the next time it's shown, after it changed on disk, returning whether
+    // it's the one showing
+    pub fn invalidate(&mut self, path: &Path) -> bool {
+        self.cache.retain(|request| request.path != path);
+        let showing = self
+            .latest
+            .as_ref()
+            .is_some_and(|(latest, _)| latest.path == path);
+        if showing {
+            self.fresh = false;
+        }
+        showing
+    }
+
+    fn receive(&mut self, (request, preview, _took): (PreviewRequest, Preview, Duration)) -> bool {
+        if self.watched {
+            self.cache.insert(request.clone(), preview.clone());
+        }
+        if self.pending.as_ref() != Some(&request) {
+            return false;
+        }
+        self.pending = None;
+        #[cfg(feature = "profiling")]
+        {
+            self.load_time = Some((request.path.clone(), _took));
+        }
+        self.latest = Some((request, preview));
+        self.shown += 1;
+        self.fresh = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shows_the_previous_preview_of_a_file_while_it_reloads() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "first\n").unwrap();
+        let request = PreviewRequest {
+            path: path.clone(),
+            pattern: String::new(),
+            mode: SearchMode::Filename,
+            scroll: 0,
+            line_numbers: LineNumbers::Off,
+            replace: None,
+        };
+        let text = |preview: &Preview| preview.0.lines[0].spans[0].content.to_string();
+
+        let mut previewer = Previewer::new(PreviewOptions::default());
+        let mut preview = previewer.get(request.clone());
+        while previewer.is_loading() {
+            std::thread::sleep(Duration::from_millis(10));
+            previewer.poll();
+            preview = previewer.get(request.clone());
+        }
+        assert!(text(&preview).contains("first"));
+
+        std::fs::write(&path, "second\n").unwrap();
+        assert!(text(&previewer.get(request.clone())).contains("first"));
+        previewer.invalidate(&path);
+        while !text(&previewer.get(request.clone())).contains("second") {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_keeps_watched_previews_until_the_file_changes() {
+        let dir = TempDir::new().unwrap();
+        let request = |name: &str| PreviewRequest {
+            path: dir.path().join(name),
+            pattern: String::new(),
+            mode: SearchMode::Filename,
+            scroll: 0,
+            line_numbers: LineNumbers::Off,
+            replace: None,
+        };
+        let (a, b) = (request("a.txt"), request("b.txt"));
+        std::fs::write(&a.path, "first\n").unwrap();
+        std::fs::write(&b.path, "other\n").unwrap();
+        let text = |preview: &Preview| preview.0.lines[0].spans[0].content.to_string();
+        let mut previewer = Previewer::new(PreviewOptions::default());
+        previewer.set_watched(true);
+        let load = |previewer: &mut Previewer, request: &PreviewRequest| loop {
+            let preview = previewer.get(request.clone());
+            if !previewer.is_loading() {
+                return text(&preview);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(load(&mut previewer, &a).contains("first"));
+        assert!(load(&mut previewer, &b).contains("other"));
+
+        // Until the change is reported, coming back shows what was kept
+        std::fs::write(&a.path, "second\n").unwrap();
+        assert!(load(&mut previewer, &a).contains("first"));
+        assert!(previewer.invalidate(&a.path));
+        assert!(load(&mut previewer, &a).contains("second"));
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// How long the last walk, search, preview and frame took, for the overlay F12 shows in builds
+// with the `profiling` feature
+#[derive(Default)]
+pub struct Profile {
+    pub shown: bool,
+    // With how many files were walked
+    walk: Option<(Duration, usize)>,
+    // Matching and ranking after the walk, with how many results it found
+    search: Option<(Duration, usize)>,
+    // Reading and highlighting a preview, on the previewer's thread
+    highlight: Option<(Duration, PathBuf)>,
+    // Loading the preview and drawing the screen
+    frame: Option<Duration>,
+}
+
+impl Profile {
+    pub fn walked(&mut self, started: Instant, files: usize) {
+        self.walk = Some((started.elapsed(), files));
+    }
+
+    pub fn searched(&mut self, started: Instant, results: usize) {
+        self.search = Some((started.elapsed(), results));
+    }
+
+    pub fn highlighted(&mut self, took: Duration, path: &Path) {
+        self.highlight = Some((took, path.to_path_buf()));
+    }
+
+    pub fn drew(&mut self, started: Instant) {
+        self.frame = Some(started.elapsed());
+    }
+
+    // A line per stage, laid out for copying into a bug report
+    pub fn lines(&self) -> Vec<String> {
+        let line = |stage: &str, took: Option<Duration>, detail: String| match took {
+            Some(took) => format!("{:<10}{:>9}  {}", stage, millis(took), detail)
+                .trim_end()
+                .to_string(),
+            None => format!("{:<10}{:>9}", stage, "-"),
+        };
+        vec![
+            line(
+                "walk",
+                self.walk.map(|(took, _)| took),
+                self.walk
+                    .map(|(_, files)| format!("{} files", files))
+                    .unwrap_or_default(),
+            ),
+            line(
+                "search",
+                self.search.map(|(took, _)| took),
+                self.search
+                    .map(|(_, results)| format!("{} results", results))
+                    .unwrap_or_default(),
+            ),
+            line(
+                "highlight",
+                self.highlight.as_ref().map(|(took, _)| *took),
+                self.highlight
+                    .as_ref()
+                    .map(|(_, path)| path.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            line("frame", self.frame, String::new()),
+        ]
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let mut profile = Profile::default();
+        profile.highlighted(Duration::from_micros(12_345), Path::new("src/main.rs"));
+        assert_eq!(
+            profile.lines(),
+            vec![
+                "walk              -",
+                "search            -",
+                "highlight    12.3ms  src/main.rs",
+                "frame             -",
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::walkstats::WalkStats;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+// Found files are handed over this often, or sooner once this many have piled up
+const BATCH_EVERY: Duration = Duration::from_millis(50);
+const BATCH_FILES: usize = 2000;
+// How often the results are searched again as more files come in
+const REFRESH_EVERY: Duration = Duration::from_millis(200);
+
+enum Progress {
+    Found(Vec<PathBuf>),
+    // Everything, after the walk's own last pass like dropping duplicate links
+    Done(Vec<PathBuf>, WalkStats),
+}
+
+// A walk on a thread of its own, so a large tree or slow disk lists files as they're found
+// instead of all at once at the end
+pub struct ProgressiveWalk {
+    pub roots: Vec<PathBuf>,
+    receiver: Receiver<Progress>,
+    files: Vec<PathBuf>,
+    stats: Option<WalkStats>,
+    // How many files the last search had, and when it ran
+    shown: usize,
+    shown_at: Instant,
+}
+
+impl ProgressiveWalk {
+    // Runs `walk`, which calls back with each file it finds and returns them all at the end.
+    // The callback returns false once the walk has been dropped, for `walk` to stop early.
+    pub fn start<F>(roots: Vec<PathBuf>, walk: F) -> Self
+    where
+        F: FnOnce(&mut dyn FnMut(&Path) -> bool) -> (Vec<PathBuf>, WalkStats) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut batch = Vec::new();
+            let mut sent = Instant::now();
+            let (files, stats) = walk(&mut |path| {
+                batch.push(path.to_path_buf());
+                if batch.len() >= BATCH_FILES || sent.elapsed() >= BATCH_EVERY {
+                    // The app has moved on when nothing's receiving
+                    if sender
+                        .send(Progress::Found(std::mem::take(&mut batch)))
+                        .is_err()
+                    {
+                        return false;
+                    }
+                    sent = Instant::now();
+                }
+                true
+            });
+            let _ = sender.send(Progress::Done(files, stats));
+        });
+        Self {
+            roots,
+            receiver,
+            files: Vec::new(),
+            stats: None,
+            shown: 0,
+            shown_at: Instant::now(),
+        }
+    }
+
+    // Takes whatever has been found since, waiting up to `patience` for the walk to finish.
+    // Returns whether anything new arrived.
+    pub fn poll(&mut self, patience: Duration) -> bool {
+        let deadline = Instant::now() + patience;
+        let mut arrived = false;
+        while self.stats.is_none() {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match self.receiver.recv_timeout(wait) {
+                Ok(Progress::Found(files)) => self.files.extend(files),
+                Ok(Progress::Done(files, stats)) => {
+                    self.files = files;
+                    self.stats = Some(stats);
+                }
+                Err(RecvTimeoutError::Timeout) => return arrived,
+                Err(RecvTimeoutError::Disconnected) => self.stats = Some(WalkStats::default()),
+            }
+            arrived = true;
+        }
+        arrived
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stats.is_some()
+    }
+
+    // Whether there's more than the last search saw, which is worth searching again every so
+    // often while walking and right away once the walk is done
+    pub fn is_due(&self) -> bool {
+        self.is_done()
+            || (self.files.len() != self.shown && self.shown_at.elapsed() >= REFRESH_EVERY)
+    }
+
+    // The files so far, with the walk's statistics once it's done
+    pub fn found(&mut self) -> (Vec<PathBuf>, WalkStats) {
+        self.shown = self.files.len();
+        self.shown_at = Instant::now();
+        let stats = self.stats.clone().unwrap_or_else(|| WalkStats {
+            walked: self.files.len(),
+            ..WalkStats::default()
+        });
+        (self.files.clone(), stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Sender;
+
+    #[test]
+    fn test_hands_over_files_as_they_are_found() {
+        let (step, steps) = mpsc::channel::<()>();
+        let mut walk = ProgressiveWalk::start(vec![PathBuf::from(".")], move |found| {
+            let mut files = Vec::new();
+            for name in ["a.rs", "b.rs"] {
+                steps.recv().unwrap();
+                std::thread::sleep(BATCH_EVERY);
+                files.push(PathBuf::from(name));
+                assert!(found(Path::new(name)));
+            }
+            steps.recv().unwrap();
+            (
+                files.clone(),
+                WalkStats {
+                    walked: files.len(),
+                    ..WalkStats::default()
+                },
+            )
+        });
+        let next = |step: &Sender<()>, walk: &mut ProgressiveWalk| {
+            step.send(()).unwrap();
+            while !walk.poll(Duration::from_millis(100)) {}
+            walk.found().0
+        };
+
+        assert!(!walk.poll(Duration::ZERO));
+        assert!(!walk.is_due());
+        assert_eq!(next(&step, &mut walk), vec![PathBuf::from("a.rs")]);
+        assert!(!walk.is_done());
+        assert_eq!(next(&step, &mut walk).len(), 2);
+        assert!(!walk.is_done());
+        next(&step, &mut walk);
+        assert!(walk.is_done());
+        assert_eq!(walk.found().1.walked, 2);
+    }
+
+    #[test]
+    fn test_stops_walking_once_dropped() {
+        let (stopped, stop) = mpsc::channel();
+        let walk = ProgressiveWalk::start(Vec::new(), move |found| {
+            while found(Path::new("a.rs")) {}
+            stopped.send(()).unwrap();
+            (Vec::new(), WalkStats::default())
+        });
+        drop(walk);
+        stop.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+use crate::search;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// Parses filter tokens like `author:alice` or `ext:rs` out of the search box,
+// leaving the remaining text to be matched against file names or contents
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    pub text: String,
+    pub authors: Vec<String>,
+    pub paths: Vec<String>,
+    pub exts: Vec<String>,
+    // Matched against the language the highlighter detects, shebangs included
+    pub langs: Vec<String>,
+    pub sizes: Vec<Bound<u64>>,
+    pub mtimes: Vec<Bound<Duration>>,
+    // From a sed-style `s/pattern/replacement/` query, whose pattern is the text searched for
+    pub replace: Option<Replace>,
+}
+
+// What a content search's matches would be replaced with, shown in the preview
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replace {
+    // With `$1`, `${name}` and `$0` standing for what the pattern captured
+    pub replacement: String,
+    // Every match on a line rather than the first, for sed's `g` flag
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound<T> {
+    Below(T),
+    Above(T),
+}
+
+impl<T: PartialOrd> Bound<T> {
+    fn contains(&self, value: T) -> bool {
+        match self {
+            Bound::Below(limit) => value < *limit,
+            Bound::Above(limit) => value > *limit,
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        let mut query = Query::default();
+        let mut text = String::new();
+
+        // Keep the original spacing of the text so regexes with spaces still work
+        for piece in input.split_inclusive(char::is_whitespace) {
+            if !query.parse_token(piece.trim_end()) {
+                text.push_str(piece);
+            }
+        }
+
+        query.text = text.trim().to_string();
+        query
+    }
+
+    // Reads a sed-style query as the pattern it searches for and what would replace the
+    // matches, for regex searches of contents where that means something
+    pub fn with_substitution(mut self) -> Self {
+        if let Some((pattern, replace)) = parse_substitution(&self.text) {
+            self.text = pattern;
+            self.replace = Some(replace);
+        }
+        self
+    }
+
+    // Returns false for anything that isn't a complete, valid token so it's searched as text
+    fn parse_token(&mut self, token: &str) -> bool {
+        let Some((key, value)) = token.split_once(':') else {
+            return false;
+        };
+        if value.is_empty() {
+            return false;
+        }
+
+        match key {
+            "author" => self.authors.push(value.to_lowercase()),
+            "path" => self.paths.push(value.to_lowercase()),
+            "ext" => self.exts.extend(
+                value
+                    .split(',')
+                    .map(|ext| ext.trim_start_matches('.').to_lowercase()),
+            ),
+            "lang" => self
+                .langs
+                .extend(value.split(',').map(|lang| lang.to_lowercase())),
+            // A bare size means "at least", a bare mtime means "within the last"
+            "size" => match parse_bound(value, Bound::Above, parse_size) {
+                Some(bound) => self.sizes.push(bound),
+                None => return false,
+            },
+            "mtime" => match parse_bound(value, Bound::Below, parse_duration) {
+                Some(bound) => self.mtimes.push(bound),
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn has_path_filters(&self) -> bool {
+        !self.paths.is_empty()
+            || !self.exts.is_empty()
+            || !self.sizes.is_empty()
+            || !self.mtimes.is_empty()
+    }
+
+    pub fn needs_metadata(&self) -> bool {
+        !self.sizes.is_empty() || !self.mtimes.is_empty()
+    }
+
+    pub fn matches_path(&self, path: &Path, metadata: Option<&Metadata>) -> bool {
+        if !self.paths.is_empty() {
+            let path_str = path.to_string_lossy().to_lowercase();
+            if !self.paths.iter().any(|p| path_str.contains(p)) {
+                return false;
+            }
+        }
+
+        if !self.exts.is_empty() {
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !self.exts.contains(&ext) {
+                return false;
+            }
+        }
+
+        if !self.needs_metadata() {
+            return true;
+        }
+        let Some(metadata) = metadata else {
+            return false;
+        };
+
+        if !self
+            .sizes
+            .iter()
+            .all(|bound| bound.contains(metadata.len()))
+        {
+            return false;
+        }
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        self.mtimes.iter().all(|bound| bound.contains(age))
+    }
+}
+
+// `s/pattern/replacement/flags` as sed writes it, with any punctuation for the `/` but the
+// regex metacharacters, so a pattern like `s.len()` is still searched as is. The closing
+// delimiter can be left off while it's typed, and sed's `\1` and `&` become `${1}` and
+// `${0}`. Only the `g` and `i` flags are understood; anything else is searched as is.
+fn parse_substitution(text: &str) -> Option<(String, Replace)> {
+    let rest = text.strip_prefix('s')?;
+    let delimiter = rest
+        .chars()
+        .next()
+        .filter(|c| c.is_ascii_punctuation() && !search::is_regex_meta(*c))?;
+    let (pattern, rest) = split_unescaped(&rest[delimiter.len_utf8()..], delimiter)?;
+    let (replacement, flags) =
+        split_unescaped(rest, delimiter).unwrap_or_else(|| (rest.to_string(), ""));
+    if pattern.is_empty() || !flags.chars().all(|flag| flag == 'g' || flag == 'i') {
+        return None;
+    }
+    let pattern = if flags.contains('i') {
+        format!("(?i){}", pattern)
+    } else {
+        pattern
+    };
+    Some((
+        pattern,
+        Replace {
+            replacement: sed_replacement(&replacement),
+            global: flags.contains('g'),
+        },
+    ))
+}
+
+// The text up to the first `delimiter` that isn't escaped, with the escaped ones unescaped,
+// and what's after it
+fn split_unescaped(text: &str, delimiter: char) -> Option<(String, &str)> {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, next)) if next == delimiter => part.push(next),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            _ if c == delimiter => return Some((part, &text[i + c.len_utf8()..])),
+            _ => part.push(c),
+        }
+    }
+    None
+}
+
+fn sed_replacement(replacement: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => converted.push_str("${0}"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => converted.push_str(&format!("${{{}}}", digit)),
+                Some('n') => converted.push('\n'),
+                Some('t') => converted.push('\t'),
+                Some(other) => converted.push(other),
+                None => converted.push('\\'),
+            },
+            _ => converted.push(c),
+        }
+    }
+    converted
+}
+
+fn parse_bound<T>(
+    value: &str,
+    default: fn(T) -> Bound<T>,
+    parse: fn(&str) -> Option<T>,
+) -> Option<Bound<T>> {
+    if let Some(rest) = value.strip_prefix('<') {
+        parse(rest).map(Bound::Below)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        parse(rest).map(Bound::Above)
+    } else {
+        parse(value).map(default)
+    }
+}
+
+fn split_unit(value: &str) -> Option<(u64, String)> {
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let number = value[..digits_end].parse().ok()?;
+    Some((number, value[digits_end..].to_lowercase()))
+}
+
+// Sizes like `500`, `10k`, `2mb`
+pub fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_unit(value)?;
+    let multiplier = match unit.trim_end_matches('b') {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+// Durations like `30m`, `12h`, `7d`, `2w`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = split_unit(value)?;
+    let seconds = match unit.as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+    number.checked_mul(seconds).map(Duration::from_secs)
+}
+
+// The inverse of parse_duration, using the largest unit that fits exactly
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let units = [
+        ("w", 60 * 60 * 24 * 7),
+        ("d", 60 * 60 * 24),
+        ("h", 60 * 60),
+        ("m", 60),
+    ];
+    for (unit, size) in units {
+        if seconds > 0 && seconds.is_multiple_of(size) {
+            return format!("{}{}", seconds / size, unit);
+        }
+    }
+    format!("{}s", seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_substitution() {
+        let query = Query::parse(r"ext:rs s/fn (\w+)\/x/pub fn \1 & $1/g").with_substitution();
+        assert_eq!(query.exts, vec!["rs"]);
+        assert_eq!(query.text, r"fn (\w+)/x");
+        assert_eq!(
+            query.replace,
+            Some(Replace {
+                replacement: "pub fn ${1} ${0} $1".to_string(),
+                global: true,
+            })
+        );
+
+        // Still being typed
+        let query = Query::parse("s#foo#ba").with_substitution();
+        assert_eq!(query.text, "foo");
+        assert_eq!(query.replace.unwrap().replacement, "ba");
+        assert_eq!(
+            Query::parse("s/Foo/bar/i").with_substitution().text,
+            "(?i)Foo"
+        );
+        assert_eq!(Query::parse("s/a/b").replace, None);
+
+        for plain in [
+            "s/foo",
+            "s//bar/",
+            "s/foo/bar/x",
+            "see/foo/bar",
+            "sa",
+            "s.len().max",
+        ] {
+            let query = Query::parse(plain).with_substitution();
+            assert_eq!(query.text, plain);
+            assert_eq!(query.replace, None);
+        }
+    }
+
+    #[test]
+    fn test_parse_plain_text() {
+        let query = Query::parse("fn  main");
+        assert_eq!(query.text, "fn  main");
+        assert!(query.authors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_author_tokens() {
+        let query = Query::parse("author:Alice serde author:bob");
+        assert_eq!(query.text, "serde");
+        assert_eq!(query.authors, vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn test_parse_incomplete_token_is_text() {
+        let query = Query::parse("author:");
+        assert_eq!(query.text, "author:");
+        assert!(query.authors.is_empty());
+
+        let query = Query::parse("size:>");
+        assert_eq!(query.text, "size:>");
+        assert!(query.sizes.is_empty());
+
+        // Too large to count in bytes or seconds
+        let query = Query::parse("size:>99999999999999999g mtime:99999999999999999w");
+        assert_eq!(
+            query.text,
+            "size:>99999999999999999g mtime:99999999999999999w"
+        );
+        assert!(query.sizes.is_empty() && query.mtimes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_structured_tokens() {
+        let query = Query::parse("path:src ext:rs,.TOML size:>10k mtime:<7d serde");
+        assert_eq!(query.text, "serde");
+        assert_eq!(query.paths, vec!["src"]);
+        assert_eq!(query.exts, vec!["rs", "toml"]);
+        assert_eq!(query.sizes, vec![Bound::Above(10 * 1024)]);
+        assert_eq!(
+            query.mtimes,
+            vec![Bound::Below(Duration::from_secs(7 * 24 * 60 * 60))]
+        );
+    }
+
+    #[test]
+    fn test_parse_lang_tokens() {
+        let query = Query::parse("lang:Rust,py todo");
+        assert_eq!(query.text, "todo");
+        assert_eq!(query.langs, vec!["rust", "py"]);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_text() {
+        let query = Query::parse("(?:foo) http://example.com");
+        assert_eq!(query.text, "(?:foo) http://example.com");
+        assert!(!query.has_path_filters());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        for value in ["90s", "30m", "12h", "3d", "2w"] {
+            assert_eq!(format_duration(parse_duration(value).unwrap()), value);
+        }
+    }
+
+    #[test]
+    fn test_matches_path() {
+        let query = Query::parse("path:src ext:rs");
+        assert!(query.matches_path(Path::new("./src/main.rs"), None));
+        assert!(!query.matches_path(Path::new("./src/README.md"), None));
+        assert!(!query.matches_path(Path::new("./tests/main.rs"), None));
+    }
+
+    #[test]
+    fn test_matches_metadata() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "x".repeat(2048)).unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+
+        assert!(Query::parse("size:>1k").matches_path(&path, Some(&metadata)));
+        assert!(!Query::parse("size:<1k").matches_path(&path, Some(&metadata)));
+        assert!(Query::parse("mtime:<1h").matches_path(&path, Some(&metadata)));
+        assert!(!Query::parse("mtime:>1h").matches_path(&path, Some(&metadata)));
+        assert!(!Query::parse("size:>1k").matches_path(&path, None));
+    }
+}
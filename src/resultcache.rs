@@ -0,0 +1,65 @@
+// Results of recent searches, so deleting back to a query seen a moment ago doesn't run it
+// again. Holds the most recent `limit` entries; anything that changes what a search would
+// find (a new walk, a toggle, a file changing) clears it.
+pub struct ResultCache<K, V> {
+    entries: Vec<(K, V)>,
+    limit: usize,
+}
+
+impl<K: PartialEq, V> ResultCache<K, V> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries
+            .iter()
+            .find(|(cached, _)| cached == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.entries.retain(|(cached, _)| *cached != key);
+        if self.entries.len() >= self.limit {
+            self.entries.remove(0);
+        }
+        self.entries.push((key, value));
+    }
+
+    // Drops the entries whose keys don't pass, like those for a file that changed
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.entries.retain(|(key, _)| keep(key));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_most_recent_entries() {
+        let mut cache = ResultCache::new(2);
+        cache.insert("f", 1);
+        cache.insert("fo", 2);
+        cache.insert("f", 3);
+        cache.insert("foo", 4);
+
+        assert_eq!(cache.get(&"fo"), None);
+        assert_eq!(cache.get(&"f"), Some(&3));
+        assert_eq!(cache.get(&"foo"), Some(&4));
+
+        cache.retain(|key| key.len() > 1);
+        assert_eq!(cache.get(&"f"), None);
+        assert_eq!(cache.get(&"foo"), Some(&4));
+
+        cache.clear();
+        assert_eq!(cache.get(&"foo"), None);
+    }
+}
@@ -0,0 +1,195 @@
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::types::{Types, TypesBuilder};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+// Default search flags read from a ripgrep config file, one argument per line
+// as described in ripgrep's guide. Unsupported flags are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchDefaults {
+    pub globs: Vec<String>,
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    pub follow: bool,
+    pub case: CaseMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    Smart,
+}
+
+impl Default for SearchDefaults {
+    fn default() -> Self {
+        Self {
+            globs: Vec::new(),
+            types: Vec::new(),
+            types_not: Vec::new(),
+            // glancr has always searched hidden files
+            hidden: true,
+            no_ignore: false,
+            follow: false,
+            case: CaseMode::Sensitive,
+        }
+    }
+}
+
+impl SearchDefaults {
+    // An explicit path from the config wins over RIPGREP_CONFIG_PATH
+    pub fn load(config_path: Option<PathBuf>) -> Self {
+        let path =
+            config_path.or_else(|| std::env::var_os("RIPGREP_CONFIG_PATH").map(PathBuf::from));
+
+        match path.and_then(|path| std::fs::read_to_string(path).ok()) {
+            Some(contents) => Self::parse(&contents),
+            None => Self::default(),
+        }
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut defaults = Self::default();
+        let mut args = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        while let Some(arg) = args.next() {
+            // Values can follow as `--flag=value`, `-fvalue` or on the next line
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+                _ => match ["-g", "-t", "-T"]
+                    .into_iter()
+                    .find_map(|flag| Some((flag, arg.strip_prefix(flag)?)))
+                {
+                    Some((flag, value)) if !value.is_empty() => (flag, Some(value)),
+                    _ => (arg, None),
+                },
+            };
+            let mut value = || {
+                inline_value
+                    .map(str::to_string)
+                    .or_else(|| args.next().map(str::to_string))
+            };
+
+            match flag {
+                "-g" | "--glob" => defaults.globs.extend(value()),
+                "-t" | "--type" => defaults.types.extend(value()),
+                "-T" | "--type-not" => defaults.types_not.extend(value()),
+                "-." | "--hidden" => defaults.hidden = true,
+                "--no-hidden" => defaults.hidden = false,
+                "--no-ignore" => defaults.no_ignore = true,
+                "--ignore" => defaults.no_ignore = false,
+                "-L" | "--follow" => defaults.follow = true,
+                "--no-follow" => defaults.follow = false,
+                "-s" | "--case-sensitive" => defaults.case = CaseMode::Sensitive,
+                "-i" | "--ignore-case" => defaults.case = CaseMode::Insensitive,
+                "-S" | "--smart-case" => defaults.case = CaseMode::Smart,
+                _ => {}
+            }
+        }
+
+        defaults
+    }
+
+    // What a walk depends on, so a walk kept from elsewhere is only used with the same settings
+    pub fn walk_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (&self.globs, &self.types, &self.types_not).hash(&mut hasher);
+        (self.hidden, self.no_ignore, self.follow).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn overrides(&self, root: &Path) -> Option<Override> {
+        if self.globs.is_empty() {
+            return None;
+        }
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.globs {
+            builder.add(glob).ok()?;
+        }
+        builder.build().ok()
+    }
+
+    pub fn file_types(&self) -> Option<Types> {
+        if self.types.is_empty() && self.types_not.is_empty() {
+            return None;
+        }
+        let mut builder = TypesBuilder::new();
+        builder.add_defaults();
+        for name in &self.types {
+            builder.select(name);
+        }
+        for name in &self.types_not {
+            builder.negate(name);
+        }
+        builder.build().ok()
+    }
+
+    pub fn ignores_case(&self, query: &str) -> bool {
+        match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !query.chars().any(char::is_uppercase),
+        }
+    }
+
+    // Applies the case flags to a content search pattern
+    pub fn pattern(&self, query: &str) -> String {
+        if self.ignores_case(query) {
+            format!("(?i){}", query)
+        } else {
+            query.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ripgrep_config() {
+        let contents = "# my defaults\n\
+                        --glob=!*.min.js\n\
+                        --glob\n\
+                        !dist/*\n\
+                        -trust\n\
+                        --type-not\n\
+                        markdown\n\
+                        --smart-case\n\
+                        --no-ignore\n\
+                        --follow\n\
+                        --max-columns=150\n";
+        let defaults = SearchDefaults::parse(contents);
+
+        assert_eq!(defaults.globs, vec!["!*.min.js", "!dist/*"]);
+        assert_eq!(defaults.types, vec!["rust"]);
+        assert_eq!(defaults.types_not, vec!["markdown"]);
+        assert_eq!(defaults.case, CaseMode::Smart);
+        assert!(defaults.no_ignore);
+        assert!(defaults.follow);
+        assert!(defaults.hidden);
+
+        // Short flags that aren't ones with a value, even outside ASCII
+        let defaults = SearchDefaults::parse("-é\n-\n-gé*\n");
+        assert_eq!(defaults.globs, vec!["é*"]);
+    }
+
+    #[test]
+    fn test_pattern_case() {
+        let mut defaults = SearchDefaults::default();
+        assert_eq!(defaults.pattern("foo"), "foo");
+
+        defaults.case = CaseMode::Insensitive;
+        assert_eq!(defaults.pattern("Foo"), "(?i)Foo");
+
+        defaults.case = CaseMode::Smart;
+        assert_eq!(defaults.pattern("foo"), "(?i)foo");
+        assert_eq!(defaults.pattern("Foo"), "Foo");
+    }
+}
@@ -0,0 +1,291 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// How rows looked before they could be configured
+pub const DEFAULT_ROW_FORMAT: &str = "{mark} {path} {matches}  {captures:dim,gray}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Icon,
+    Mark,
+    Dir,
+    Name,
+    Path,
+    Matches,
+    Captures,
+    Mtime,
+    Size,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "icon" => Field::Icon,
+            "mark" => Field::Mark,
+            "dir" => Field::Dir,
+            "name" => Field::Name,
+            "path" => Field::Path,
+            "matches" => Field::Matches,
+            "captures" => Field::Captures,
+            "mtime" => Field::Mtime,
+            "size" => Field::Size,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field, Style),
+}
+
+// A result row layout like `{icon} {dir:dim}{name:bold} {matches}`, from `row_format`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowTemplate {
+    parts: Vec<Part>,
+}
+
+impl Default for RowTemplate {
+    fn default() -> Self {
+        RowTemplate::parse(DEFAULT_ROW_FORMAT).expect("default row format parses")
+    }
+}
+
+// Everything a row can show about one file
+pub struct RowValues<'a> {
+    pub path: &'a Path,
+    // The path as it's shown, relative to its root
+    pub display_path: &'a str,
+    pub marked: bool,
+    pub matches: Option<String>,
+    pub captures: Vec<&'a str>,
+    pub metadata: Option<std::fs::Metadata>,
+}
+
+impl RowTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("unclosed '{{' in row format '{}'", template))?;
+            let placeholder = &rest[start + 1..end];
+            let (name, style) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+            let field = Field::parse(name)
+                .ok_or_else(|| format!("unknown row format placeholder '{{{}}}'", name))?;
+            parts.push(Part::Field(field, parse_style(style)?));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(RowTemplate { parts })
+    }
+
+    // Only the mtime and size need a stat call, which is worth skipping for every other row
+    pub fn needs_metadata(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Field(Field::Mtime | Field::Size, _)))
+    }
+
+    // Spacing around an empty field is dropped, so rows without matches don't end in gaps
+    pub fn render(&self, values: &RowValues) -> Line<'static> {
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        let mut separator: Option<&str> = None;
+        for part in &self.parts {
+            let (text, style) = match part {
+                Part::Literal(text) if text.trim().is_empty() => {
+                    if !spans.is_empty() {
+                        separator = Some(text);
+                    }
+                    continue;
+                }
+                Part::Literal(text) => (text.clone(), Style::default()),
+                Part::Field(field, style) => (field_text(*field, values), *style),
+            };
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(separator) = separator.take() {
+                spans.push(Span::raw(separator.to_string()));
+            }
+            spans.push(Span::styled(text, style));
+        }
+        Line::from(spans)
+    }
+}
+
+fn parse_style(style: &str) -> Result<Style, String> {
+    style
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(Style::default(), |style, name| {
+            Ok(match name {
+                "bold" => style.add_modifier(Modifier::BOLD),
+                "dim" => style.add_modifier(Modifier::DIM),
+                "italic" => style.add_modifier(Modifier::ITALIC),
+                "underline" => style.add_modifier(Modifier::UNDERLINED),
+                color => style.fg(color
+                    .parse::<Color>()
+                    .map_err(|_| format!("unknown row format style '{}'", color))?),
+            })
+        })
+}
+
+fn field_text(field: Field, values: &RowValues) -> String {
+    let path = Path::new(values.display_path);
+    match field {
+        Field::Icon => icon(values.path).to_string(),
+        Field::Mark => if values.marked { "●" } else { "" }.to_string(),
+        Field::Dir => match path.parent().map(Path::to_string_lossy) {
+            Some(dir) if !dir.is_empty() => format!("{}/", dir),
+            _ => String::new(),
+        },
+        Field::Name => path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| values.display_path.to_string()),
+        Field::Path => values.display_path.to_string(),
+        Field::Matches => values
+            .matches
+            .as_ref()
+            .map(|count| format!("({})", count))
+            .unwrap_or_default(),
+        Field::Captures => values.captures.join(", "),
+        Field::Mtime => values
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map(format_age)
+            .unwrap_or_default(),
+        Field::Size => values
+            .metadata
+            .as_ref()
+            .map(|metadata| format_size(metadata.len()))
+            .unwrap_or_default(),
+    }
+}
+
+fn icon(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "rs" => "🦀",
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => "📜",
+        "py" => "🐍",
+        "md" | "txt" | "rst" => "📝",
+        "json" | "yml" | "yaml" | "toml" | "ini" | "conf" => "⚙️",
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" => "🖼️",
+        "sh" | "bash" | "zsh" | "fish" => "💲",
+        "lock" => "🔒",
+        _ => "📄",
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86_399 => format!("{}h", seconds / 3600),
+        86_400..=2_591_999 => format!("{}d", seconds / 86_400),
+        2_592_000..=31_535_999 => format!("{}mo", seconds / 2_592_000),
+        _ => format!("{}y", seconds / 31_536_000),
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || size >= 10.0 {
+        format!("{:.0}{}", size, UNITS[unit])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'a>(display_path: &'a str, matches: Option<&str>) -> RowValues<'a> {
+        RowValues {
+            path: Path::new(display_path),
+            display_path,
+            marked: false,
+            matches: matches.map(str::to_string),
+            captures: Vec::new(),
+            metadata: None,
+        }
+    }
+
+    fn text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_matches_plain_rows() {
+        let template = RowTemplate::default();
+        assert_eq!(
+            text(&template.render(&values("src/main.rs", Some("3")))),
+            "src/main.rs (3)"
+        );
+        assert_eq!(
+            text(&template.render(&values("src/main.rs", None))),
+            "src/main.rs"
+        );
+
+        let mut marked = values("a.rs", None);
+        marked.marked = true;
+        marked.captures = vec!["x", "y"];
+        assert_eq!(text(&template.render(&marked)), "● a.rs  x, y");
+    }
+
+    #[test]
+    fn test_fields_and_styles() {
+        let template = RowTemplate::parse("{icon} {dir:dim}{name:bold,green} {size}").unwrap();
+        let line = template.render(&values("src/main.rs", None));
+        assert_eq!(text(&line), "🦀 src/main.rs");
+        assert_eq!(line.spans[2].content, "src/");
+        assert!(line.spans[2].style.add_modifier.contains(Modifier::DIM));
+        assert_eq!(line.spans[3].style.fg, Some(Color::Green));
+        assert!(template.needs_metadata());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(RowTemplate::parse("{nope}").is_err());
+        assert!(RowTemplate::parse("{name").is_err());
+        assert!(RowTemplate::parse("{name:sparkly}").is_err());
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(20 * 1024 * 1024), "20M");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_age(Duration::from_secs(3 * 86_400)), "3d");
+    }
+}
@@ -0,0 +1,285 @@
+use crate::compressed;
+use grep::{
+    matcher::{Captures, Matcher},
+    regex::RegexMatcher,
+    searcher::{sinks::UTF8, Searcher},
+};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMatch {
+    pub line_number: u64,
+    // 1-based byte column of the first match, as `grep --column` reports it
+    pub column: u64,
+    pub text: String,
+    // Text captured by the pattern's groups, one entry per match in the line
+    pub captures: Vec<String>,
+}
+
+// Matching lines in a file, capped so pathological files can't blow up memory
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileMatches {
+    pub lines: Vec<LineMatch>,
+    // More lines matched than were collected
+    pub truncated: bool,
+}
+
+impl FileMatches {
+    pub fn is_match(&self) -> bool {
+        !self.lines.is_empty() || self.truncated
+    }
+
+    // Line and column of the first match, for editors that can jump straight to it
+    pub fn position(&self) -> (u64, u64) {
+        self.lines
+            .first()
+            .map(|line| (line.line_number, line.column))
+            .unwrap_or((1, 1))
+    }
+
+    pub fn count_label(&self) -> String {
+        if self.truncated {
+            format!("{}+", self.lines.len())
+        } else {
+            self.lines.len().to_string()
+        }
+    }
+}
+
+pub fn is_regex_meta(c: char) -> bool {
+    matches!(
+        c,
+        '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$'
+    )
+}
+
+pub fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if is_regex_meta(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Matches a term literally, ignoring case unless it has an uppercase letter
+pub fn literal_pattern(term: &str) -> String {
+    if term.is_empty() {
+        return String::new();
+    }
+    let pattern = escape_regex(term);
+    if term.chars().any(char::is_uppercase) {
+        pattern
+    } else {
+        format!("(?i){}", pattern)
+    }
+}
+
+// A case-insensitive pattern matching any of the literal terms in a filename query
+pub fn literal_terms_pattern(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .trim_start_matches('\'')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .filter(|term| !term.is_empty())
+        .map(escape_regex)
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(format!("(?i){}", terms.join("|")))
+    }
+}
+
+pub fn search_file(
+    searcher: &mut Searcher,
+    matcher: &RegexMatcher,
+    path: &Path,
+    limit: usize,
+) -> FileMatches {
+    // Unreadable files just have no matches
+    collect_matches(matcher, limit, |sink| {
+        let _ = searcher.search_path(matcher, path, sink);
+    })
+}
+
+// Like search_file, for the decompressed contents of a .gz, .zst, .bz2 or similar file
+pub fn search_compressed(
+    searcher: &mut Searcher,
+    matcher: &RegexMatcher,
+    path: &Path,
+    limit: usize,
+) -> FileMatches {
+    let Ok(reader) = compressed::open(path) else {
+        return FileMatches::default();
+    };
+    collect_matches(matcher, limit, |sink| {
+        let _ = searcher.search_reader(matcher, reader, sink);
+    })
+}
+
+type LineSink<'a> = UTF8<&'a mut dyn FnMut(u64, &str) -> io::Result<bool>>;
+
+// Runs a search, collecting up to `limit` matching lines
+fn collect_matches(
+    matcher: &RegexMatcher,
+    limit: usize,
+    search: impl FnOnce(LineSink),
+) -> FileMatches {
+    let mut matches = FileMatches::default();
+    let mut collect = |line_number: u64, line: &str| -> io::Result<bool> {
+        if matches.lines.len() >= limit {
+            matches.truncated = true;
+            return Ok(false);
+        }
+        let text = line.trim_end();
+        let column = matcher
+            .find(text.as_bytes())
+            .ok()
+            .flatten()
+            .map_or(1, |m| m.start() as u64 + 1);
+        matches.lines.push(LineMatch {
+            line_number,
+            column,
+            text: text.to_string(),
+            captures: line_captures(matcher, text),
+        });
+        Ok(true)
+    };
+    search(UTF8(&mut collect));
+    matches
+}
+
+// Each match's groups joined by spaces, or nothing when the pattern has no groups
+fn line_captures(matcher: &RegexMatcher, text: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    if matcher.capture_count() <= 1 {
+        return values;
+    }
+    let Ok(mut caps) = matcher.new_captures() else {
+        return values;
+    };
+    let _ = matcher.captures_iter(text.as_bytes(), &mut caps, |caps| {
+        let groups: Vec<&str> = (1..caps.len())
+            .filter_map(|group| caps.get(group))
+            .map(|m| &text[m])
+            .collect();
+        if !groups.is_empty() {
+            values.push(groups.join(" "));
+        }
+        true
+    });
+    values
+}
+
+// How many times the pattern matches in a line, which is at least once for a matching line
+pub fn count_matches(matcher: &RegexMatcher, text: &str) -> usize {
+    let mut count = 0;
+    let _ = matcher.find_iter(text.as_bytes(), |_| {
+        count += 1;
+        true
+    });
+    count.max(1)
+}
+
+// How many times each captured value occurs across the results
+pub fn capture_counts<'a>(matches: impl Iterator<Item = &'a FileMatches>) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in matches.flat_map(|file| &file.lines) {
+        for value in &line.captures {
+            *counts.entry(value).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(value, count)| (value.to_string(), count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grep::searcher::{BinaryDetection, SearcherBuilder};
+    use tempfile::TempDir;
+
+    fn search(content: &str, pattern: &str, limit: usize) -> FileMatches {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, content).unwrap();
+
+        let mut searcher = SearcherBuilder::new()
+            .binary_detection(BinaryDetection::quit(0))
+            .build();
+        let matcher = RegexMatcher::new(pattern).unwrap();
+        search_file(&mut searcher, &matcher, &path, limit)
+    }
+
+    #[test]
+    fn test_literal_terms_pattern() {
+        assert_eq!(
+            literal_terms_pattern("'ui/button.tsx"),
+            Some("(?i)ui|button\\.tsx".to_string())
+        );
+        assert_eq!(literal_terms_pattern("'"), None);
+    }
+
+    #[test]
+    fn test_literal_pattern_smart_case() {
+        assert_eq!(literal_pattern("a.b"), "(?i)a\\.b");
+        assert_eq!(literal_pattern("Needle"), "Needle");
+        assert_eq!(literal_pattern(""), "");
+    }
+
+    #[test]
+    fn test_search_file_collects_lines() {
+        let matches = search("one\ntwo match\nthree\nfour match\n", "match", 10);
+        assert_eq!(matches.lines.len(), 2);
+        assert_eq!(matches.lines[0].line_number, 2);
+        assert_eq!(matches.lines[0].column, 5);
+        assert_eq!(matches.lines[1].text, "four match");
+        assert!(!matches.truncated);
+        assert_eq!(matches.count_label(), "2");
+    }
+
+    #[test]
+    fn test_search_file_collects_captures() {
+        let matches = search(
+            "use serde::Deserialize;\nuse std::path; use std::fs;\nfn main() {}\n",
+            r"use (\w+)::",
+            10,
+        );
+        assert_eq!(matches.lines[0].captures, vec!["serde"]);
+        assert_eq!(matches.lines[1].captures, vec!["std", "std"]);
+
+        let mut counts = capture_counts(std::iter::once(&matches));
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![("serde".to_string(), 1), ("std".to_string(), 2)]
+        );
+
+        let matches = search("use serde::Deserialize;\n", "use (?:serde)", 10);
+        assert!(matches.lines[0].captures.is_empty());
+
+        let matcher = RegexMatcher::new("std").unwrap();
+        assert_eq!(count_matches(&matcher, "use std::path; use std::fs;"), 2);
+    }
+
+    #[test]
+    fn test_search_file_caps_matches() {
+        let matches = search(&"match\n".repeat(50), "match", 10);
+        assert_eq!(matches.lines.len(), 10);
+        assert!(matches.truncated);
+        assert_eq!(matches.count_label(), "10+");
+    }
+
+    #[test]
+    fn test_search_file_exactly_at_limit_is_not_truncated() {
+        let matches = search(&"match\n".repeat(3), "match", 3);
+        assert_eq!(matches.lines.len(), 3);
+        assert!(!matches.truncated);
+    }
+}
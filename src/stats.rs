@@ -0,0 +1,168 @@
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+// Files, matching lines and matches under one directory or with one extension
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Tally {
+    pub files: usize,
+    pub lines: usize,
+    pub matches: usize,
+}
+
+impl Tally {
+    fn add(&mut self, lines: usize, matches: usize) {
+        self.files += 1;
+        self.lines += lines;
+        self.matches += matches;
+    }
+}
+
+// What a query found across the tree, for `glancr stats`
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub total: Tally,
+    pub by_directory: HashMap<String, Tally>,
+    pub by_extension: HashMap<String, Tally>,
+    // Files over `max_search_file_size`, which weren't searched
+    pub skipped_large: usize,
+}
+
+impl Stats {
+    // Each result as it's displayed, with the number of lines it matched and the matches in them
+    pub fn collect<'a>(results: impl IntoIterator<Item = (&'a Path, usize, usize)>) -> Self {
+        let mut stats = Stats::default();
+        for (path, lines, matches) in results {
+            stats.total.add(lines, matches);
+            let directory = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => {
+                    parent.to_string_lossy().into_owned()
+                }
+                _ => ".".to_string(),
+            };
+            stats
+                .by_directory
+                .entry(directory)
+                .or_default()
+                .add(lines, matches);
+            let extension = path.extension().map_or("(none)".to_string(), |ext| {
+                ext.to_string_lossy().into_owned()
+            });
+            stats
+                .by_extension
+                .entry(extension)
+                .or_default()
+                .add(lines, matches);
+        }
+        stats
+    }
+
+    // Totals, then each breakdown with the most matches first
+    pub fn render(&self, lines: bool) -> String {
+        let mut output = String::new();
+        let _ = writeln!(output, "{} files", self.total.files);
+        if lines {
+            let _ = writeln!(output, "{} matching lines", self.total.lines);
+            let _ = writeln!(output, "{} matches", self.total.matches);
+        }
+        if self.skipped_large > 0 {
+            let _ = writeln!(
+                output,
+                "{} files too large to search (max_search_file_size)",
+                self.skipped_large
+            );
+        }
+        if self.total.files == 0 {
+            return output;
+        }
+        for (title, tallies) in [
+            ("By directory", &self.by_directory),
+            ("By extension", &self.by_extension),
+        ] {
+            let _ = writeln!(output, "\n{}:", title);
+            let rows = sorted(tallies);
+            let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            for (name, tally) in rows {
+                let _ = write!(output, "  {:width$}  {:>6} files", name, tally.files);
+                if lines {
+                    let _ = write!(output, "  {:>8} lines", tally.lines);
+                    let _ = write!(output, "  {:>8} matches", tally.matches);
+                }
+                output.push('\n');
+            }
+        }
+        output
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let breakdown = |tallies: &HashMap<String, Tally>| {
+            sorted(tallies)
+                .into_iter()
+                .map(|(name, tally)| {
+                    json!({
+                        "name": name,
+                        "files": tally.files,
+                        "lines": tally.lines,
+                        "matches": tally.matches,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "files": self.total.files,
+            "lines": self.total.lines,
+            "matches": self.total.matches,
+            "skipped_large": self.skipped_large,
+            "by_directory": breakdown(&self.by_directory),
+            "by_extension": breakdown(&self.by_extension),
+        })
+    }
+}
+
+fn sorted(tallies: &HashMap<String, Tally>) -> Vec<(&str, Tally)> {
+    let mut rows: Vec<(&str, Tally)> = tallies
+        .iter()
+        .map(|(name, tally)| (name.as_str(), *tally))
+        .collect();
+    rows.sort_by(|a, b| {
+        (b.1.matches, b.1.lines, b.1.files)
+            .cmp(&(a.1.matches, a.1.lines, a.1.files))
+            .then(a.0.cmp(b.0))
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_by_directory_and_extension() {
+        let results = [
+            (Path::new("src/main.rs"), 3, 5),
+            (Path::new("src/lib.rs"), 1, 1),
+            (Path::new("README.md"), 2, 2),
+            (Path::new("Makefile"), 0, 0),
+        ];
+        let mut stats = Stats::collect(results);
+        let tally = |files, lines, matches| Tally {
+            files,
+            lines,
+            matches,
+        };
+        assert_eq!(stats.total, tally(4, 6, 8));
+        assert_eq!(stats.by_directory["src"], tally(2, 4, 6));
+        assert_eq!(stats.by_directory["."], tally(2, 2, 2));
+        assert_eq!(stats.by_extension["(none)"], tally(1, 0, 0));
+
+        stats.skipped_large = 1;
+        let rendered = stats.render(true);
+        assert!(rendered.starts_with(
+            "4 files\n6 matching lines\n8 matches\n1 files too large to search (max_search_file_size)\n"
+        ));
+        assert!(rendered
+            .contains("\nBy directory:\n  src       2 files         4 lines         6 matches\n"));
+        assert_eq!(stats.to_json()["by_extension"][0]["name"], "rs");
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use crossterm::{
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use std::io::stdout;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Holds the terminal in raw mode on the alternate screen, restoring it when dropped
+// so early returns and errors never leave the shell unusable
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter() -> Result<Self> {
+        stdout().execute(EnterAlternateScreen)?;
+        enable_raw_mode()?;
+        Ok(TerminalGuard)
+    }
+
+    // Runs a program that needs the terminal, like a pager, then takes it back
+    pub fn run_foreground(&self, command: &mut Command) -> Result<ExitStatus> {
+        restore();
+        let status = command.status();
+        stdout().execute(EnterAlternateScreen)?;
+        enable_raw_mode()?;
+        Ok(status?)
+    }
+
+    // Hands the terminal back to the shell while stopped, taking it over again on resume
+    pub fn suspend(&self) -> Result<()> {
+        restore();
+        #[cfg(unix)]
+        signal_hook::low_level::raise(signal_hook::consts::SIGSTOP)?;
+        stdout().execute(EnterAlternateScreen)?;
+        enable_raw_mode()?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// Best effort, since this also runs while panicking
+pub fn restore() {
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+}
+
+// Restores the terminal before the panic message prints so it's readable
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+    }));
+}
+
+// Termination and stop signals, noted so the main loop can restore the terminal first
+#[derive(Default)]
+pub struct Signals {
+    terminate: Arc<AtomicBool>,
+    suspend: Arc<AtomicBool>,
+}
+
+impl Signals {
+    pub fn register() -> Result<Self> {
+        let signals = Self::default();
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGHUP, SIGTERM, SIGTSTP};
+            use signal_hook::flag::register;
+            register(SIGTERM, Arc::clone(&signals.terminate))?;
+            register(SIGHUP, Arc::clone(&signals.terminate))?;
+            register(SIGTSTP, Arc::clone(&signals.suspend))?;
+        }
+        Ok(signals)
+    }
+
+    pub fn terminated(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+
+    pub fn take_suspend(&self) -> bool {
+        self.suspend.swap(false, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,110 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// What decides between results that rank the same, in the order `tiebreaks` lists them
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tiebreak {
+    // A to Z
+    Path,
+    // Most recently modified first
+    Mtime,
+    // Fewest directories deep first
+    Depth,
+}
+
+// Puts walked files in tiebreak order instead of the order the filesystem lists them in, which
+// differs between machines. Every ranking after is stable, so it only reorders files it can
+// tell apart.
+pub fn sort(files: Vec<PathBuf>, tiebreaks: &[Tiebreak]) -> Vec<PathBuf> {
+    // No two files have the same path, so nothing after it is ever looked at
+    let deciding = match tiebreaks
+        .iter()
+        .position(|tiebreak| *tiebreak == Tiebreak::Path)
+    {
+        Some(path) => &tiebreaks[..=path],
+        None => tiebreaks,
+    };
+    let stat = deciding.contains(&Tiebreak::Mtime);
+    let mut keyed: Vec<(Option<SystemTime>, PathBuf)> = files
+        .into_iter()
+        .map(|path| {
+            let modified = stat.then(|| {
+                std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH)
+            });
+            (modified, path)
+        })
+        .collect();
+    keyed.sort_by(|(a_modified, a), (b_modified, b)| {
+        deciding.iter().fold(Ordering::Equal, |order, tiebreak| {
+            order.then_with(|| match tiebreak {
+                Tiebreak::Path => a.cmp(b),
+                Tiebreak::Mtime => b_modified.cmp(a_modified),
+                Tiebreak::Depth => a.components().count().cmp(&b.components().count()),
+            })
+        })
+    });
+    keyed.into_iter().map(|(_, path)| path).collect()
+}
+
+// For the title bar, when the tiebreaks are all there is to the order
+pub fn describe(tiebreaks: &[Tiebreak]) -> &'static str {
+    match tiebreaks.first() {
+        Some(Tiebreak::Path) => "by path",
+        Some(Tiebreak::Mtime) => "newest first",
+        Some(Tiebreak::Depth) => "shallowest first",
+        None => "walk order",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sort() {
+        let dir = TempDir::new().unwrap();
+        let now = SystemTime::now();
+        let paths: Vec<PathBuf> = ["b.rs", "a/z.rs", "c.rs", "a/b/c.rs"]
+            .iter()
+            .enumerate()
+            .map(|(age, name)| {
+                let path = dir.path().join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                let file = std::fs::File::create(&path).unwrap();
+                file.set_modified(now - Duration::from_secs(60 * age as u64))
+                    .unwrap();
+                path
+            })
+            .collect();
+        let sorted = |tiebreaks: &[Tiebreak]| -> Vec<String> {
+            sort(paths.clone(), tiebreaks)
+                .iter()
+                .map(|path| {
+                    let relative = path.strip_prefix(dir.path()).unwrap();
+                    relative.to_string_lossy().into_owned()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            sorted(&[Tiebreak::Path, Tiebreak::Mtime]),
+            vec!["a/b/c.rs", "a/z.rs", "b.rs", "c.rs"]
+        );
+        assert_eq!(
+            sorted(&[Tiebreak::Mtime]),
+            vec!["b.rs", "a/z.rs", "c.rs", "a/b/c.rs"]
+        );
+        assert_eq!(
+            sorted(&[Tiebreak::Depth, Tiebreak::Path]),
+            vec!["b.rs", "c.rs", "a/z.rs", "a/b/c.rs"]
+        );
+        assert_eq!(sorted(&[]), vec!["b.rs", "a/z.rs", "c.rs", "a/b/c.rs"]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+// A line of the tree view, either a directory or one of the results
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeRow {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+    // The result this row shows, or the first one beneath a directory
+    pub file_index: usize,
+}
+
+#[derive(Default)]
+struct Node {
+    dirs: BTreeMap<String, (PathBuf, Node)>,
+    files: Vec<PathBuf>,
+}
+
+// Nests the results under their directories, directories first and each level sorted by
+// name. Returns the results in tree order along with the visible rows; the children of
+// collapsed directories are left out. With several roots, each is a top-level directory.
+pub fn build(
+    files: &[PathBuf],
+    roots: &[&Path],
+    collapsed: &HashSet<PathBuf>,
+) -> (Vec<PathBuf>, Vec<TreeRow>) {
+    let mut tree = Node::default();
+    for path in files {
+        let root = roots.iter().find(|root| path.starts_with(root));
+        let mut node = &mut tree;
+        if let (Some(root), true) = (root, roots.len() > 1) {
+            // Named like display paths in a workspace
+            let name = root
+                .file_name()
+                .unwrap_or(root.as_os_str())
+                .to_string_lossy()
+                .into_owned();
+            node = &mut node
+                .dirs
+                .entry(name)
+                .or_insert_with(|| (root.to_path_buf(), Node::default()))
+                .1;
+        }
+
+        // Directories between the root and the file, outermost first
+        let mut dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| {
+                root.is_none_or(|root| dir != root)
+                    && !dir.as_os_str().is_empty()
+                    && *dir != Path::new(".")
+            })
+            .collect();
+        dirs.reverse();
+        for dir in dirs {
+            let name = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| dir.to_string_lossy().into_owned());
+            node = &mut node
+                .dirs
+                .entry(name)
+                .or_insert_with(|| (dir.to_path_buf(), Node::default()))
+                .1;
+        }
+        node.files.push(path.clone());
+    }
+
+    let mut ordered = Vec::new();
+    let mut rows = Vec::new();
+    flatten(tree, 0, true, collapsed, &mut ordered, &mut rows);
+    (ordered, rows)
+}
+
+fn flatten(
+    mut node: Node,
+    depth: usize,
+    visible: bool,
+    collapsed: &HashSet<PathBuf>,
+    ordered: &mut Vec<PathBuf>,
+    rows: &mut Vec<TreeRow>,
+) {
+    for (name, (path, child)) in std::mem::take(&mut node.dirs) {
+        let expanded = !collapsed.contains(&path);
+        if visible {
+            rows.push(TreeRow {
+                path,
+                name,
+                depth,
+                is_dir: true,
+                expanded,
+                file_index: ordered.len(),
+            });
+        }
+        flatten(
+            child,
+            depth + 1,
+            visible && expanded,
+            collapsed,
+            ordered,
+            rows,
+        );
+    }
+
+    node.files.sort();
+    for path in node.files {
+        if visible {
+            rows.push(TreeRow {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path: path.clone(),
+                depth,
+                is_dir: false,
+                expanded: false,
+                file_index: ordered.len(),
+            });
+        }
+        ordered.push(path);
+    }
+}
+
+// A directory's README, previewed when the directory is selected. Markdown first, then
+// whatever other README there is, whatever its case.
+pub fn readme(dir: &Path) -> Option<PathBuf> {
+    let mut readmes: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_stem()
+                .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"))
+        })
+        .collect();
+    readmes.sort_by_key(|path| {
+        let extension = path.extension().map(|ext| ext.to_ascii_lowercase());
+        let markdown = matches!(
+            extension.as_ref().and_then(|ext| ext.to_str()),
+            Some("md" | "markdown")
+        );
+        (!markdown, path.clone())
+    });
+    readmes.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn outline(rows: &[TreeRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| {
+                let suffix = if row.is_dir { "/" } else { "" };
+                format!("{}{}{}", "  ".repeat(row.depth), row.name, suffix)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build_nests_directories_first() {
+        let files = paths(&[
+            "./README.md",
+            "./src/main.rs",
+            "./src/ui/list.rs",
+            "./Cargo.toml",
+        ]);
+        let (ordered, rows) = build(&files, &[Path::new(".")], &HashSet::new());
+        assert_eq!(
+            outline(&rows),
+            vec![
+                "src/",
+                "  ui/",
+                "    list.rs",
+                "  main.rs",
+                "Cargo.toml",
+                "README.md"
+            ]
+        );
+        assert_eq!(
+            ordered,
+            paths(&[
+                "./src/ui/list.rs",
+                "./src/main.rs",
+                "./Cargo.toml",
+                "./README.md"
+            ])
+        );
+        assert_eq!(rows[0].file_index, 0);
+        assert_eq!(rows[3].file_index, 1);
+    }
+
+    #[test]
+    fn test_build_hides_collapsed_children() {
+        let files = paths(&["./src/main.rs", "./src/ui/list.rs", "./README.md"]);
+        let collapsed = HashSet::from([PathBuf::from("./src")]);
+        let (ordered, rows) = build(&files, &[Path::new(".")], &collapsed);
+        assert_eq!(outline(&rows), vec!["src/", "README.md"]);
+        assert!(!rows[0].expanded);
+        assert_eq!(rows[1].file_index, 2);
+        assert_eq!(ordered.len(), 3);
+    }
+
+    #[test]
+    fn test_build_with_several_roots() {
+        let files = paths(&["/work/api/lib.rs", "/work/web/app.ts"]);
+        let roots = [Path::new("/work/api"), Path::new("/work/web")];
+        let (_, rows) = build(&files, &roots, &HashSet::new());
+        assert_eq!(outline(&rows), vec!["api/", "  lib.rs", "web/", "  app.ts"]);
+    }
+
+    #[test]
+    fn test_readme_prefers_markdown() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(readme(dir.path()), None);
+        std::fs::write(dir.path().join("README.txt"), "").unwrap();
+        std::fs::write(dir.path().join("readme.md"), "").unwrap();
+        std::fs::create_dir(dir.path().join("README")).unwrap();
+        assert_eq!(readme(dir.path()), Some(dir.path().join("readme.md")));
+    }
+}
@@ -0,0 +1,205 @@
+use ratatui::style::{Color, Style};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+// Capture names the grammars' queries use, most specific first within a family
+const HIGHLIGHT_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "embedded",
+    "function",
+    "function.builtin",
+    "keyword",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "string",
+    "string.special",
+    "tag",
+    "type",
+    "type.builtin",
+    "variable.builtin",
+    "variable.parameter",
+];
+
+fn color(name: &str) -> Option<Color> {
+    let family = name.split('.').next().unwrap_or(name);
+    match (name, family) {
+        ("variable.builtin", _) | (_, "tag") => Some(Color::Red),
+        (_, "comment") => Some(Color::DarkGray),
+        (_, "keyword") => Some(Color::Magenta),
+        (_, "string") => Some(Color::Green),
+        (_, "function") | (_, "constructor") => Some(Color::Blue),
+        (_, "type") | (_, "attribute") => Some(Color::Yellow),
+        (_, "constant") | (_, "number") => Some(Color::Cyan),
+        (_, "property") => Some(Color::LightBlue),
+        _ => None,
+    }
+}
+
+// The grammar for a file, if its language is one the config switched to tree-sitter
+pub fn language_for(path: &Path, enabled: &[String]) -> Option<&'static str> {
+    let language = match path.extension()?.to_str()? {
+        "js" | "jsx" | "mjs" | "cjs" => "javascript",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "rs" => "rust",
+        _ => return None,
+    };
+    enabled
+        .iter()
+        .any(|name| name == language)
+        .then_some(language)
+}
+
+fn configurations() -> &'static HashMap<&'static str, HighlightConfiguration> {
+    static CONFIGURATIONS: OnceLock<HashMap<&'static str, HighlightConfiguration>> =
+        OnceLock::new();
+    CONFIGURATIONS.get_or_init(|| {
+        // TypeScript's queries only add to JavaScript's, so they're combined the way the
+        // grammars' own configs do, base queries first
+        let javascript_highlights = format!(
+            "{}\n{}",
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+            tree_sitter_javascript::JSX_HIGHLIGHT_QUERY
+        );
+        let typescript_highlights = format!(
+            "{}\n{}",
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+            tree_sitter_typescript::HIGHLIGHTS_QUERY
+        );
+        let tsx_highlights = format!(
+            "{}\n{}",
+            javascript_highlights,
+            tree_sitter_typescript::HIGHLIGHTS_QUERY
+        );
+        let typescript_locals = format!(
+            "{}\n{}",
+            tree_sitter_javascript::LOCALS_QUERY,
+            tree_sitter_typescript::LOCALS_QUERY
+        );
+
+        let grammars = [
+            (
+                "javascript",
+                tree_sitter_javascript::LANGUAGE.into(),
+                javascript_highlights.as_str(),
+                tree_sitter_javascript::INJECTIONS_QUERY,
+                tree_sitter_javascript::LOCALS_QUERY,
+            ),
+            (
+                "typescript",
+                tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+                typescript_highlights.as_str(),
+                tree_sitter_javascript::INJECTIONS_QUERY,
+                typescript_locals.as_str(),
+            ),
+            (
+                "tsx",
+                tree_sitter_typescript::LANGUAGE_TSX.into(),
+                tsx_highlights.as_str(),
+                tree_sitter_javascript::INJECTIONS_QUERY,
+                typescript_locals.as_str(),
+            ),
+            (
+                "rust",
+                tree_sitter_rust::LANGUAGE.into(),
+                tree_sitter_rust::HIGHLIGHTS_QUERY,
+                tree_sitter_rust::INJECTIONS_QUERY,
+                "",
+            ),
+        ];
+        grammars
+            .into_iter()
+            .filter_map(|(name, language, highlights, injections, locals)| {
+                let mut config =
+                    HighlightConfiguration::new(language, name, highlights, injections, locals)
+                        .ok()?;
+                config.configure(HIGHLIGHT_NAMES);
+                Some((name, config))
+            })
+            .collect()
+    })
+}
+
+// Styled pieces of each line, or None if the source couldn't be parsed
+pub fn highlight(language: &str, source: &str) -> Option<Vec<Vec<(Style, String)>>> {
+    let configurations = configurations();
+    let config = configurations.get(language)?;
+    let mut highlighter = Highlighter::new();
+    // Embedded code, like a regex or another language in a template, uses its own grammar
+    let events = highlighter
+        .highlight(config, source.as_bytes(), None, |name| {
+            configurations.get(name)
+        })
+        .ok()?;
+
+    let mut lines = vec![Vec::new()];
+    let mut styles = vec![Style::default()];
+    for event in events {
+        match event.ok()? {
+            HighlightEvent::HighlightStart(highlight) => {
+                let current = *styles.last().unwrap_or(&Style::default());
+                let style = match color(HIGHLIGHT_NAMES[highlight.0]) {
+                    Some(color) => current.fg(color),
+                    None => current,
+                };
+                styles.push(style);
+            }
+            HighlightEvent::HighlightEnd => {
+                styles.pop();
+            }
+            HighlightEvent::Source { start, end } => {
+                let style = *styles.last().unwrap_or(&Style::default());
+                for (index, piece) in source[start..end].split('\n').enumerate() {
+                    if index > 0 {
+                        lines.push(Vec::new());
+                    }
+                    let piece = piece.trim_end_matches('\r');
+                    if let (false, Some(line)) = (piece.is_empty(), lines.last_mut()) {
+                        line.push((style, piece.to_string()));
+                    }
+                }
+            }
+        }
+    }
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_for_needs_opt_in() {
+        let enabled = vec!["tsx".to_string()];
+        assert_eq!(language_for(Path::new("App.tsx"), &enabled), Some("tsx"));
+        assert_eq!(language_for(Path::new("app.ts"), &enabled), None);
+        assert_eq!(language_for(Path::new("README"), &enabled), None);
+    }
+
+    #[test]
+    fn test_highlight_jsx() {
+        for language in ["javascript", "tsx"] {
+            let lines = highlight(language, "const a = <div>{'hi'}</div>;\n// done\n").unwrap();
+            let text: String = lines[0].iter().map(|(_, text)| text.as_str()).collect();
+            assert_eq!(text, "const a = <div>{'hi'}</div>;");
+
+            let style_of = |wanted: &str| {
+                lines[0]
+                    .iter()
+                    .find(|(_, text)| text == wanted)
+                    .map(|(style, _)| style.fg)
+            };
+            assert_eq!(style_of("const"), Some(Some(Color::Magenta)));
+            assert_eq!(style_of("div"), Some(Some(Color::Red)));
+            assert_eq!(lines[1][0].0.fg, Some(Color::DarkGray));
+        }
+    }
+}
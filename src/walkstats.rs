@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+// Error messages past this many are only counted
+const MAX_ERRORS: usize = 20;
+
+// What happened to every entry the last walk came across, so missing files can be explained
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WalkStats {
+    // Files the walk reached, before any were skipped
+    pub walked: usize,
+    // Matched `ignored_dirs` or `ignored_patterns` from the config
+    pub excluded: usize,
+    // Didn't match a structured filter in the query, like `ext:` or `size:`
+    pub filtered: usize,
+    pub binary: usize,
+    // Looked minified or generated, with `generated.action` set to exclude
+    pub generated: usize,
+    pub permission_denied: usize,
+    // Listed, but can't be opened for lack of permission
+    pub unreadable: usize,
+    // The other paths a file was reached by through links, by the path it's listed as
+    pub linked: HashMap<PathBuf, Vec<PathBuf>>,
+    pub errors: usize,
+    pub error_messages: Vec<String>,
+    // Every directory walked into and the ignore files in them, with when each was modified,
+    // for telling next session whether the list still holds without walking again
+    pub seen: Vec<(PathBuf, u64)>,
+}
+
+impl WalkStats {
+    pub fn skipped(&self) -> usize {
+        self.excluded
+            + self.filtered
+            + self.binary
+            + self.generated
+            + self.permission_denied
+            + self.linked_copies()
+            + self.errors
+    }
+
+    // Paths left out for leading to a file that's already listed
+    pub fn linked_copies(&self) -> usize {
+        self.linked.values().map(Vec::len).sum()
+    }
+
+    pub fn record_error(&mut self, error: &ignore::Error) {
+        let denied = error
+            .io_error()
+            .is_some_and(|err| err.kind() == ErrorKind::PermissionDenied);
+        if denied {
+            self.permission_denied += 1;
+        } else {
+            self.errors += 1;
+        }
+        if self.error_messages.len() < MAX_ERRORS {
+            self.error_messages.push(error.to_string());
+        }
+    }
+
+    // Adds what another walk came across, like one of several roots walked on its own
+    pub fn absorb(&mut self, other: WalkStats) {
+        self.walked += other.walked;
+        self.permission_denied += other.permission_denied;
+        self.errors += other.errors;
+        self.linked.extend(other.linked);
+        self.seen.extend(other.seen);
+        let room = MAX_ERRORS.saturating_sub(self.error_messages.len());
+        self.error_messages
+            .extend(other.error_messages.into_iter().take(room));
+    }
+
+    // The details popup; `gitignored` is counted separately since ignored paths are never walked
+    pub fn lines(&self, too_large: usize, gitignored: Option<usize>) -> Vec<String> {
+        let mut lines = vec![
+            format!("{:>8}  files walked", self.walked),
+            format!(
+                "{:>8}  excluded by ignored_dirs / ignored_patterns",
+                self.excluded
+            ),
+            format!("{:>8}  filtered out by the query's filters", self.filtered),
+            format!("{:>8}  skipped as binary", self.binary),
+            format!("{:>8}  excluded as generated or minified", self.generated),
+            format!(
+                "{:>8}  too large to search (max_search_file_size)",
+                too_large
+            ),
+            format!("{:>8}  permission denied", self.permission_denied),
+            format!("{:>8}  listed, but unreadable", self.unreadable),
+            format!(
+                "{:>8}  reached again through a link, listed once",
+                self.linked_copies()
+            ),
+            format!("{:>8}  other errors", self.errors),
+        ];
+        if let Some(gitignored) = gitignored {
+            lines.push(format!(
+                "{:>8}  ignored by .gitignore and friends (directories count once)",
+                gitignored
+            ));
+        }
+        if !self.error_messages.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.error_messages.iter().cloned());
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_error_counts_permission_denied() {
+        let mut stats = WalkStats::default();
+        let denied = std::io::Error::new(ErrorKind::PermissionDenied, "denied");
+        stats.record_error(&ignore::Error::from(denied));
+        let other = std::io::Error::other("broken");
+        stats.record_error(&ignore::Error::from(other));
+
+        assert_eq!(stats.permission_denied, 1);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.skipped(), 2);
+        assert_eq!(stats.error_messages, vec!["denied", "broken"]);
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wasmtime::{Config, Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+// Bounds each call so a looping module can't hang the UI
+const FUEL_PER_CALL: u64 = 2_000_000_000;
+// Larger files are left to the built-in preview rather than copied into a module
+const MAX_PREVIEW_INPUT: u64 = 16 * 1024 * 1024;
+
+// A module from the `wasm_plugins` directory. Every module exports `memory` and
+// `alloc(len) -> ptr` for handing it strings, plus any of:
+//   filter(path_ptr, path_len) -> i32     0 drops the file from the results
+//   previews(path_ptr, path_len) -> i32   non-zero if `preview` renders this file
+//   preview(data_ptr, data_len) -> i64    the file's preview text as `ptr << 32 | len`
+struct Plugin {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    filter: Option<TypedFunc<(i32, i32), i32>>,
+    previews: Option<TypedFunc<(i32, i32), i32>>,
+    preview: Option<TypedFunc<(i32, i32), i64>>,
+}
+
+impl Plugin {
+    fn load(engine: &Engine, path: &Path) -> Result<Self> {
+        let module = Module::from_file(engine, path)?;
+        let mut store = Store::new(engine, ());
+        store.set_fuel(FUEL_PER_CALL)?;
+        // No imports, so plugins can't touch anything but what they're handed
+        let instance: Instance = Linker::new(engine).instantiate(&mut store, &module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("doesn't export `memory`"))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        let filter = instance.get_typed_func(&mut store, "filter").ok();
+        let (previews, preview) = (
+            instance.get_typed_func(&mut store, "previews").ok(),
+            instance.get_typed_func(&mut store, "preview").ok(),
+        );
+        Ok(Plugin {
+            store,
+            memory,
+            alloc,
+            filter,
+            previews: previews.filter(|_| preview.is_some()),
+            preview,
+        })
+    }
+
+    fn pass(&mut self, bytes: &[u8]) -> Result<(i32, i32)> {
+        self.store.set_fuel(FUEL_PER_CALL)?;
+        let len = i32::try_from(bytes.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)?;
+        Ok((ptr, len))
+    }
+
+    fn keeps(&mut self, path: &Path) -> Result<bool> {
+        let Some(filter) = self.filter.clone() else {
+            return Ok(true);
+        };
+        let args = self.pass(path.to_string_lossy().as_bytes())?;
+        Ok(filter.call(&mut self.store, args)? != 0)
+    }
+
+    fn renders(&mut self, path: &Path) -> Result<bool> {
+        let Some(previews) = self.previews.clone() else {
+            return Ok(false);
+        };
+        let args = self.pass(path.to_string_lossy().as_bytes())?;
+        Ok(previews.call(&mut self.store, args)? != 0)
+    }
+
+    fn render(&mut self, data: &[u8]) -> Result<String> {
+        let preview = self.preview.clone().context("doesn't export `preview`")?;
+        let args = self.pass(data)?;
+        let packed = preview.call(&mut self.store, args)? as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut text = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut text)?;
+        Ok(String::from_utf8_lossy(&text).into_owned())
+    }
+}
+
+// Custom result filters and previewers compiled to WebAssembly
+#[derive(Default)]
+pub struct WasmPlugins {
+    // Each with the file it came from, for error messages
+    plugins: Vec<(PathBuf, Mutex<Plugin>)>,
+}
+
+impl WasmPlugins {
+    // Every `.wasm` (or `.wat`) module in `dir`, with a description of each one that failed
+    pub fn load(dir: &Path) -> (Self, Vec<String>) {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "wasm" || ext == "wat")
+            })
+            .collect();
+        paths.sort();
+
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = match Engine::new(&config) {
+            Ok(engine) => engine,
+            Err(err) => return (WasmPlugins::default(), vec![err.to_string()]),
+        };
+        let mut problems = Vec::new();
+        let mut plugins = Vec::new();
+        for path in paths {
+            match Plugin::load(&engine, &path) {
+                Ok(plugin) => plugins.push((path, Mutex::new(plugin))),
+                Err(err) => problems.push(format!("WASM plugin {}: {:#}", path.display(), err)),
+            }
+        }
+        (WasmPlugins { plugins }, problems)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    // Files every plugin's `filter` keeps
+    pub fn filter(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let mut filters: Vec<_> = self
+            .plugins
+            .iter()
+            .filter_map(|(source, plugin)| Some((source, plugin.lock().ok()?)))
+            .filter(|(_, plugin)| plugin.filter.is_some())
+            .collect();
+        if filters.is_empty() {
+            return Ok(paths);
+        }
+        let mut kept = Vec::with_capacity(paths.len());
+        'paths: for path in paths {
+            for (source, plugin) in filters.iter_mut() {
+                if !plugin.keeps(&path).with_context(|| name(source))? {
+                    continue 'paths;
+                }
+            }
+            kept.push(path);
+        }
+        Ok(kept)
+    }
+
+    // The first plugin that renders this file, or None to fall back to the normal preview
+    pub fn preview(&self, path: &Path) -> Option<Result<String>> {
+        let small = std::fs::metadata(path).is_ok_and(|meta| meta.len() <= MAX_PREVIEW_INPUT);
+        if !small {
+            return None;
+        }
+        for (source, plugin) in &self.plugins {
+            let Ok(mut plugin) = plugin.lock() else {
+                continue;
+            };
+            match plugin.renders(path) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(err) => return Some(Err(err.context(name(source)))),
+            }
+            let rendered = std::fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| plugin.render(&data));
+            return Some(rendered.with_context(|| name(source)));
+        }
+        None
+    }
+}
+
+fn name(source: &Path) -> String {
+    format!(
+        "WASM plugin {}",
+        source.file_name().unwrap_or_default().to_string_lossy()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // Keeps paths of at most 8 bytes
+    const SHORT_PATHS: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "filter") (param i32 i32) (result i32)
+            local.get 1
+            i32.const 8
+            i32.le_u))"#;
+
+    // Renders `.db` files as a fixed line
+    const DB_PREVIEW: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 16) "decoded")
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "previews") (param $ptr i32) (param $len i32) (result i32)
+            (i32.eq
+                (i32.load8_u (i32.sub (i32.add (local.get $ptr) (local.get $len)) (i32.const 1)))
+                (i32.const 98)))
+        (func (export "preview") (param i32 i32) (result i64)
+            i64.const 68719476743))"#;
+
+    const LOOPS: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "filter") (param i32 i32) (result i32)
+            (loop $forever (br $forever))
+            i32.const 1))"#;
+
+    fn plugins(modules: &[(&str, &str)]) -> (TempDir, WasmPlugins) {
+        let dir = TempDir::new().unwrap();
+        for (name, source) in modules {
+            std::fs::write(dir.path().join(name), source).unwrap();
+        }
+        let (plugins, problems) = WasmPlugins::load(dir.path());
+        assert_eq!(problems, Vec::<String>::new());
+        (dir, plugins)
+    }
+
+    #[test]
+    fn test_filter() {
+        let (_dir, plugins) = plugins(&[("short.wat", SHORT_PATHS)]);
+        let paths = vec![PathBuf::from("a.rs"), PathBuf::from("src/long.rs")];
+        assert_eq!(plugins.filter(paths).unwrap(), vec![PathBuf::from("a.rs")]);
+    }
+
+    #[test]
+    fn test_preview() {
+        let (dir, plugins) = plugins(&[("db.wat", DB_PREVIEW)]);
+        let db = dir.path().join("x.db");
+        std::fs::write(&db, [0u8, 159, 146, 150]).unwrap();
+        assert_eq!(plugins.preview(&db).unwrap().unwrap(), "decoded");
+        assert!(plugins.preview(&dir.path().join("db.wat")).is_none());
+    }
+
+    #[test]
+    fn test_problems_and_runaway_modules() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("broken.wasm"), "not a module").unwrap();
+        let (plugins, problems) = WasmPlugins::load(dir.path());
+        assert!(plugins.is_empty());
+        assert!(problems[0].contains("broken.wasm"));
+
+        let (_dir, plugins) = self::plugins(&[("loops.wat", LOOPS)]);
+        let err = plugins.filter(vec![PathBuf::from("a")]).unwrap_err();
+        assert!(format!("{:#}", err).contains("loops.wat"));
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher as _};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+// Changes are handed over once things have been quiet this long, so a checkout or a build
+// writing thousands of files causes one refresh rather than thousands
+const SETTLE: Duration = Duration::from_millis(200);
+
+// What happened to a path, as the walk would name it
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Modified(PathBuf),
+    Created(PathBuf),
+    Removed(PathBuf),
+    // Notifications were lost, or just started, so anything could have changed
+    Rescan,
+}
+
+// Change notifications for everything under the roots from the platform (inotify, FSEvents or
+// ReadDirectoryChangesW), so results can follow edits without walking again
+pub struct Watcher {
+    events: Receiver<notify::Result<Event>>,
+    // How setting up the watches went, which takes a walk's worth of time in a big tree
+    setup: Receiver<Result<(), String>>,
+    ready: bool,
+    // Dropped with the watcher, which tells the thread holding the watches to stop
+    _stop: Sender<()>,
+    // Each root as the OS reports paths under it, and as it was given
+    roots: Vec<(PathBuf, PathBuf)>,
+    pending: Vec<Change>,
+    last_event: Option<Instant>,
+}
+
+impl Watcher {
+    pub fn new(roots: &[PathBuf]) -> Result<Self> {
+        let roots: Vec<(PathBuf, PathBuf)> = roots
+            .iter()
+            .map(|root| Ok((root.canonicalize()?, root.clone())))
+            .collect::<Result<_>>()?;
+        let (sender, events) = mpsc::channel();
+        let (setup_sender, setup) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let canonical: Vec<PathBuf> = roots
+            .iter()
+            .map(|(canonical, _)| canonical.clone())
+            .collect();
+        std::thread::spawn(move || {
+            let watcher = notify::recommended_watcher(sender).and_then(|mut watcher| {
+                for root in &canonical {
+                    watcher.watch(root, RecursiveMode::Recursive)?;
+                }
+                Ok(watcher)
+            });
+            let _ = setup_sender.send(watcher.as_ref().map(|_| ()).map_err(|err| err.to_string()));
+            let _ = stopped.recv();
+        });
+        Ok(Watcher {
+            events,
+            setup,
+            ready: false,
+            _stop: stop,
+            roots,
+            pending: Vec::new(),
+            last_event: None,
+        })
+    }
+
+    // Once every directory is watched; until then nothing that changes is reported
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    // Whatever changed, once the changes have settled, or why watching isn't possible
+    pub fn changes(&mut self) -> Result<Vec<Change>, String> {
+        if !self.ready {
+            match self.setup.try_recv() {
+                Ok(Ok(())) => {
+                    self.ready = true;
+                    return Ok(vec![Change::Rescan]);
+                }
+                Ok(Err(err)) => return Err(err),
+                Err(_) => return Ok(Vec::new()),
+            }
+        }
+        for event in self.events.try_iter().flatten() {
+            self.last_event = Some(Instant::now());
+            if event.need_rescan() {
+                self.pending.push(Change::Rescan);
+            }
+            let changes = event.paths.iter().filter_map(|path| {
+                let path = self.walked_path(path)?;
+                Some(match event.kind {
+                    EventKind::Create(_) => Change::Created(path),
+                    EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                        Change::Removed(path)
+                    }
+                    EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Change::Created(path),
+                    // Either end of a rename, told apart by what's there now
+                    EventKind::Modify(ModifyKind::Name(_)) if path.exists() => {
+                        Change::Created(path)
+                    }
+                    EventKind::Modify(ModifyKind::Name(_)) => Change::Removed(path),
+                    EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => {
+                        Change::Modified(path)
+                    }
+                    _ => return None,
+                })
+            });
+            let changes: Vec<Change> = changes.collect();
+            for change in changes {
+                if !self.pending.contains(&change) {
+                    self.pending.push(change);
+                }
+            }
+        }
+        Ok(match self.last_event {
+            Some(last) if last.elapsed() >= SETTLE => {
+                self.last_event = None;
+                std::mem::take(&mut self.pending)
+            }
+            _ => Vec::new(),
+        })
+    }
+
+    fn walked_path(&self, path: &Path) -> Option<PathBuf> {
+        // Every git command touches .git, none of which is ever walked
+        if path
+            .components()
+            .any(|part| part == Component::Normal(".git".as_ref()))
+        {
+            return None;
+        }
+        self.roots.iter().find_map(|(canonical, given)| {
+            let relative = path.strip_prefix(canonical).ok()?;
+            Some(given.join(relative))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reports_changes_by_walked_path() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "").unwrap();
+        let mut watcher = Watcher::new(&[dir.path().to_path_buf()]).unwrap();
+        while watcher.changes().unwrap() != [Change::Rescan] {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        std::fs::write(dir.path().join("a.rs"), "changed").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "").unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".git").join("index"), "").unwrap();
+
+        let mut changes = Vec::new();
+        let started = Instant::now();
+        while changes.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(50));
+            changes = watcher.changes().unwrap();
+        }
+        assert!(changes.contains(&Change::Modified(dir.path().join("a.rs"))));
+        assert!(changes.contains(&Change::Created(dir.path().join("b.rs"))));
+        assert!(changes.iter().all(|change| {
+            let (Change::Modified(path) | Change::Created(path) | Change::Removed(path)) = change
+            else {
+                return true;
+            };
+            !path.starts_with(dir.path().join(".git"))
+        }));
+    }
+}